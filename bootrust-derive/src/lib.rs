@@ -0,0 +1,316 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, LitStr, Type};
+
+/// 为结构体生成 `bootrust::entity::Entity` 实现
+///
+/// - `#[entity(table = "...")]` 指定表名, 缺省为结构体名的 snake_case
+/// - `#[entity(timestamped)]` 由 DataAccessory 维护时间列, 实体需实现 `Timestamped`
+/// - `#[entity(primary_key)]` 标记主键字段
+/// - `#[entity(column = "...")]` 指定字段对应的列名
+#[proc_macro_derive(Entity, attributes(entity))]
+pub fn derive_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut table = None;
+    let mut timestamped = false;
+    for attr in entity_attrs(&input.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else if meta.path.is_ident("timestamped") {
+                timestamped = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported entity attribute, expected `table` or `timestamped`"))
+            }
+        })?;
+    }
+    let table = table.unwrap_or_else(|| snake_case(&name.to_string()));
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "Entity can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Entity can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut primary_key = None;
+    let mut renames = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        // 序列化后的键名, 需要跟随 serde(rename)
+        let key = serde_rename(&field.attrs).unwrap_or_else(|| ident.to_string());
+
+        let mut column = None;
+        let mut is_primary = false;
+        for attr in entity_attrs(&field.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("primary_key") {
+                    is_primary = true;
+                    Ok(())
+                } else if meta.path.is_ident("column") {
+                    column = Some(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else {
+                    Err(meta
+                        .error("unsupported entity attribute, expected `primary_key` or `column`"))
+                }
+            })?;
+        }
+
+        let column_name = column.clone().unwrap_or_else(|| key.clone());
+        if is_primary {
+            if primary_key.is_some() {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "only one field can be marked #[entity(primary_key)]",
+                ));
+            }
+            primary_key = Some(column_name.clone());
+        }
+        if column.is_some() && column_name != key {
+            renames.push((key, column_name));
+        }
+    }
+
+    let primary_key = primary_key.ok_or_else(|| {
+        syn::Error::new_spanned(name, "missing #[entity(primary_key)] on one of the fields")
+    })?;
+
+    let columns = if renames.is_empty() {
+        quote! {}
+    } else {
+        let (keys, columns): (Vec<String>, Vec<String>) = renames.into_iter().unzip();
+        quote! {
+            fn columns() -> Vec<(String, String)> {
+                vec![#((#keys.to_string(), #columns.to_string())),*]
+            }
+        }
+    };
+
+    let timestamps = timestamps(timestamped, &quote! { Self });
+
+    Ok(quote! {
+        impl #impl_generics ::bootrust::entity::Entity for #name #ty_generics #where_clause {
+            fn table() -> String {
+                #table.to_string()
+            }
+
+            fn primary_key() -> String {
+                #primary_key.to_string()
+            }
+
+            #columns
+
+            #timestamps
+        }
+    })
+}
+
+/// 为 DAO 结构体生成 `bootrust::asyncdao::Dao<T>` 实现
+///
+/// - `#[dao(entity = User)]` 指定实体类型, 必填
+/// - `#[dao(table = "...")]` 指定表名, 缺省为实体名的 snake_case
+/// - `#[dao(primary_key = "...")]` 指定主键列名, 缺省为 `id`
+/// - `#[dao(timestamped)]` 由 create/update 维护时间列, 实体需实现 `Timestamped`
+/// - `#[dao(sync)]` 改为实现同步的 `bootrust::dao::Dao<T>`
+///
+/// 结构体需要一个名为 `database` 的字段, 其余字段用 `Default::default()` 初始化
+#[proc_macro_derive(Dao, attributes(dao))]
+pub fn derive_dao(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_dao(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_dao(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut entity = None;
+    let mut table = None;
+    let mut primary_key = None;
+    let mut sync = false;
+    let mut timestamped = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("dao"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("entity") {
+                entity = Some(meta.value()?.parse::<Type>()?);
+            } else if meta.path.is_ident("table") {
+                table = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("primary_key") {
+                primary_key = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("sync") {
+                sync = true;
+            } else if meta.path.is_ident("timestamped") {
+                timestamped = true;
+            } else {
+                return Err(meta.error(
+                    "unsupported dao attribute, expected `entity`, `table`, `primary_key`, `timestamped` or `sync`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+    let entity = entity
+        .ok_or_else(|| syn::Error::new_spanned(name, "missing #[dao(entity = ...)] attribute"))?;
+    let table = match table {
+        Some(table) => table,
+        None => match &entity {
+            Type::Path(path) => snake_case(&path.path.segments.last().unwrap().ident.to_string()),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &entity,
+                    "cannot infer table name, add #[dao(table = \"...\")]",
+                ))
+            }
+        },
+    };
+    let primary_key = primary_key.unwrap_or_else(|| "id".to_string());
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "Dao can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Dao can only be derived for structs",
+            ))
+        }
+    };
+    let database = fields
+        .iter()
+        .find(|field| field.ident.as_ref().is_some_and(|i| i == "database"))
+        .ok_or_else(|| syn::Error::new_spanned(name, "Dao requires a `database` field"))?;
+    let database_type = &database.ty;
+    let others = fields
+        .iter()
+        .filter_map(|field| field.ident.as_ref())
+        .filter(|ident| *ident != "database");
+
+    let timestamps = timestamps(timestamped, &quote! { #entity });
+    let dao = if sync {
+        quote! { ::bootrust::dao::Dao }
+    } else {
+        quote! { ::bootrust::asyncdao::Dao }
+    };
+
+    Ok(quote! {
+        impl #impl_generics #dao<#entity> for #name #ty_generics #where_clause {
+            type Database = #database_type;
+
+            fn new(database: Self::Database) -> Self {
+                Self {
+                    database,
+                    #(#others: ::std::default::Default::default(),)*
+                }
+            }
+
+            fn database(&self) -> &Self::Database {
+                &self.database
+            }
+
+            fn table_name() -> String {
+                #table.to_string()
+            }
+
+            fn primary_key_column() -> String {
+                #primary_key.to_string()
+            }
+
+            #timestamps
+        }
+    })
+}
+
+// 实体实现了 Timestamped 时转发其 timestamps, 否则沿用默认值
+fn timestamps(timestamped: bool, entity: &TokenStream2) -> TokenStream2 {
+    if !timestamped {
+        return quote! {};
+    }
+    quote! {
+        fn timestamps() -> Option<(String, String)> {
+            <#entity as ::bootrust::dao::Timestamped>::timestamps()
+        }
+    }
+}
+
+fn entity_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("entity"))
+}
+
+// 只识别 `#[serde(rename = "...")]`, 其余 serde 参数忽略
+fn serde_rename(attrs: &[Attribute]) -> Option<String> {
+    let mut rename = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") && meta.input.peek(syn::Token![=]) {
+                rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<syn::Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|nested| {
+                    if nested.input.peek(syn::Token![=]) {
+                        nested.value()?.parse::<syn::Expr>()?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        });
+    }
+    rename
+}
+
+// 与 RenamePolicy::SnakeCase 相同的规则, 连续大写视为一个缩写: HTTPLog -> http_log
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev_lower = i > 0 && !chars[i - 1].is_uppercase() && chars[i - 1] != '_';
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if i > 0 && (prev_lower || (next_lower && chars[i - 1] != '_')) {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(*c);
+        }
+    }
+    snake
+}
@@ -6,13 +6,23 @@
         feature = "postgresql_async"
     )
 ))]
-use bootrust::{
-    asyncdatabase::{auto_config, RelationalDatabase},
-    entity::Entity,
+use {
+    bootrust::{
+        asyncdatabase::{auto_config, RelationalDatabase},
+        entity::Entity,
+    },
+    chrono::{DateTime, Utc},
+    serde::{Deserialize, Serialize},
 };
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
 
+#[cfg(all(
+    not(feature = "full"),
+    any(
+        feature = "sqlite_async",
+        feature = "mysql_async",
+        feature = "postgresql_async"
+    )
+))]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct User {
     id: i64,
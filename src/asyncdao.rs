@@ -1,10 +1,22 @@
-use crate::asyncdatabase::{DbError, RelationalDatabase, Row, Value};
-use crate::serde::{EntityConvertor, EntityDeserializer};
-use crate::sql_builder::SqlExecutor;
+use crate::asyncdatabase::{DbError, QueryErrorKind, RelationalDatabase, Row, Value};
+use crate::dao::stamp_timestamps;
+pub use crate::dao::Timestamped;
+use crate::entity::{Entity, EntityData};
+use crate::serde::{EntityConvertor, EntityDeserializer, RenamePolicy};
+use crate::sql_builder::{is_identifier, SortDir, SqlExecutor, LIKE_ESCAPE};
+use chrono::Utc;
 use serde::{de::Deserialize, ser::Serialize};
 use std::io::Cursor;
 use std::marker::PhantomData;
 
+#[cfg(feature = "derive")]
+pub use bootrust_derive::Dao;
+
+// delete_by_ids 每条语句最多携带的主键个数
+const DELETE_BATCH: usize = 500;
+// for_each 和 fold 每次读取的行数
+const SCAN_BATCH: usize = 1000;
+
 #[async_trait::async_trait]
 pub trait Dao<T>: Sized
 where
@@ -24,7 +36,8 @@ where
     fn new(database: Self::Database) -> Self;
 
     fn row_to_entity(row: Row) -> Result<T, DbError> {
-        let de = EntityDeserializer::from_value(row.to_table());
+        let de = EntityDeserializer::from_value(row.to_table())
+            .with_rename_policy(Self::rename_policy());
         T::deserialize(de).map_err(|e| DbError::ConversionError(e.to_string()))
     }
 
@@ -40,7 +53,7 @@ where
 
     fn entity_to_map(entity: &T) -> Vec<(String, Value)> {
         let cursor = Cursor::new(Vec::new());
-        let mut convertor = EntityConvertor::new(cursor);
+        let mut convertor = EntityConvertor::new(cursor).with_rename_policy(Self::rename_policy());
         let result = entity.serialize(&mut convertor);
         match result {
             Ok(Value::Table(table)) => table,
@@ -74,15 +87,124 @@ where
     /// 获取主键列名
     fn primary_key_column() -> String;
 
+    /// 字段名到列名的转换规则, 默认与字段同名
+    fn rename_policy() -> RenamePolicy {
+        RenamePolicy::Verbatim
+    }
+
+    /// 自动维护的 (created_at, updated_at) 列, 默认不维护
+    ///
+    /// 实体实现了 Timestamped 时重写为 `T::timestamps()`, 派生时用 `#[dao(timestamped)]`
+    fn timestamps() -> Option<(String, String)> {
+        None
+    }
+
+    /// 软删除标记列, 如 `deleted_at`, 默认不启用
+    ///
+    /// 启用后 delete 只把该列设为当前时间, find_* 和 exists_by_id 跳过已删除的记录;
+    /// prepare() 生成的查询不受影响
+    fn soft_delete_column() -> Option<String> {
+        None
+    }
+
+    /// 获取全部主键列名, 复合主键时重写
+    fn primary_key_columns() -> Vec<String> {
+        vec![Self::primary_key_column()]
+    }
+
+    /// 实例操作的表名, 生成的 SQL 都以此为准, 默认为 table_name()
+    fn table(&self) -> String {
+        Self::table_name()
+    }
+
+    /// 实例操作的主键列名, 默认为 primary_key_column()
+    fn primary_key(&self) -> String {
+        Self::primary_key_column()
+    }
+
+    /// 实例操作的全部主键列名, 默认为 primary_key_columns()
+    fn primary_keys(&self) -> Vec<String> {
+        Self::primary_key_columns()
+    }
+
+    /// 生成主键条件, 占位符从第 offset 个参数开始
+    fn primary_key_condition(&self, offset: usize) -> String {
+        let columns = self.primary_keys();
+        let placeholders = self.placeholders(&vec![String::new(); offset + columns.len()]);
+        columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} = {}", c, placeholders[offset + i]))
+            .collect::<Vec<String>>()
+            .join(" AND ")
+    }
+
+    /// 生成 `a = ? AND b > ?` 形式的条件, 每个条件后接一个占位符
+    fn where_condition(&self, condition: &[&str]) -> String {
+        let conditions: Vec<String> = condition.iter().map(|s| s.to_string()).collect();
+        let placeholders = self.placeholders(&conditions);
+        conditions
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} {}", c, placeholders[i]))
+            .collect::<Vec<String>>()
+            .join(" AND ")
+    }
+
+    /// 生成 WHERE 子句, 启用软删除时追加 `deleted_at IS NULL`, 没有条件时为空
+    fn live_filter(condition: Option<String>) -> String {
+        let conditions: Vec<String> = condition
+            .into_iter()
+            .chain(Self::soft_delete_column().map(|c| format!("{} IS NULL", c)))
+            .collect();
+        if conditions.is_empty() {
+            return String::new();
+        }
+        format!(" WHERE {}", conditions.join(" AND "))
+    }
+
+    fn check_primary_key(&self, key: &[Value]) -> Result<(), DbError> {
+        let expected = self.primary_keys().len();
+        if key.len() != expected {
+            return Err(DbError::QueryError(QueryErrorKind::Other(
+                format!(
+                    "expected {} primary key values, got {}",
+                    expected,
+                    key.len()
+                )
+                .into(),
+            )));
+        }
+        Ok(())
+    }
+
+    // 按主键操作应当恰好影响一行
+    fn check_one_affected(&self, affected: u64) -> Result<(), DbError> {
+        match affected {
+            0 => Err(DbError::NotFound(format!(
+                "no row in {} matches the primary key",
+                self.table()
+            ))),
+            1 => Ok(()),
+            n => Err(DbError::Conflict(format!(
+                "{} rows in {} match the primary key",
+                n,
+                self.table()
+            ))),
+        }
+    }
+
     /// 创建新记录
     async fn create(&self, entity: &T) -> Result<u64, DbError> {
-        let values = self.entity_to_values(entity);
-        let keys = self.entity_to_keys(entity);
+        let mut map = Self::entity_to_map(entity);
+        stamp_timestamps(&mut map, Self::timestamps(), true);
+        let (keys, values): (Vec<String>, Vec<Value>) = map.into_iter().unzip();
         let placeholders: Vec<String> = self.placeholders(&keys);
 
         let query = format!(
-            "INSERT INTO {} VALUES ({})",
-            Self::table_name(),
+            "INSERT INTO {} ({}) VALUES ({})",
+            self.table(),
+            keys.join(", "),
             placeholders.join(", ")
         );
 
@@ -91,24 +213,59 @@ where
 
     /// 根据ID查找记录
     async fn find_by_id(&self, id: Value) -> Result<Option<T>, DbError> {
-        let placeholder = self.placeholders(&[Self::primary_key_column()])[0].clone();
+        self.find_by_key(vec![id]).await
+    }
+
+    /// 根据主键查找记录, 值按 primary_key_columns 的顺序对应
+    async fn find_by_key(&self, key: Vec<Value>) -> Result<Option<T>, DbError> {
+        self.check_primary_key(&key)?;
         let query = format!(
-            "SELECT * FROM {} WHERE {} = {}",
-            Self::table_name(),
-            Self::primary_key_column(),
-            placeholder
+            "SELECT * FROM {}{}",
+            self.table(),
+            Self::live_filter(Some(self.primary_key_condition(0)))
         );
 
-        let result = self.database().query_one(&query, vec![id]).await?;
+        let result = self.database().query_one(&query, key).await?;
         match result {
             Some(row) => Ok(Some(Self::row_to_entity(row)?)),
             None => Ok(None),
         }
     }
 
+    /// 按实体的主键重新读取记录, 取得触发器, 默认值等由数据库写入的列
+    async fn refresh(&self, entity: &T) -> Result<Option<T>, DbError> {
+        let map = Self::entity_to_map(entity);
+        let key = self
+            .primary_keys()
+            .iter()
+            .map(|column| {
+                map.iter()
+                    .find(|(k, _)| k == column)
+                    .map(|(_, v)| v.clone())
+                    .ok_or_else(|| {
+                        DbError::ConversionError(format!("missing primary key column {}", column))
+                    })
+            })
+            .collect::<Result<Vec<Value>, DbError>>()?;
+        self.find_by_key(key).await
+    }
+
+    /// 是否存在该主键的记录, 只查询 `SELECT 1`, 不构造实体
+    async fn exists_by_id(&self, id: Value) -> Result<bool, DbError> {
+        let key = vec![id];
+        self.check_primary_key(&key)?;
+        let query = format!(
+            "SELECT 1 FROM {}{} LIMIT 1",
+            self.table(),
+            Self::live_filter(Some(self.primary_key_condition(0)))
+        );
+
+        Ok(self.database().query_one(&query, key).await?.is_some())
+    }
+
     /// 查找所有记录
     async fn find_all(&self) -> Result<Vec<T>, DbError> {
-        let query = format!("SELECT * FROM {}", Self::table_name());
+        let query = format!("SELECT * FROM {}{}", self.table(), Self::live_filter(None));
         let rows = self.database().query(&query, vec![]).await?;
 
         let mut entities = Vec::with_capacity(rows.len());
@@ -118,20 +275,126 @@ where
         Ok(entities)
     }
 
+    /// 查找所有记录, 包括已软删除的
+    async fn find_with_deleted(&self) -> Result<Vec<T>, DbError> {
+        let query = format!("SELECT * FROM {}", self.table());
+        let rows = self.database().query(&query, vec![]).await?;
+
+        self.convert_rows_to_entitys(rows)
+    }
+
+    /// 分页查找记录, 按主键排序
+    async fn find_all_limited(&self, limit: u64, offset: u64) -> Result<Vec<T>, DbError> {
+        let to_bigint = |v: u64| {
+            i64::try_from(v)
+                .map(Value::Bigint)
+                .map_err(|_| DbError::ConversionError(format!("{} out of range for i64", v)))
+        };
+        let params = vec![to_bigint(limit)?, to_bigint(offset)?];
+        let placeholders = self.placeholders(&["limit".to_string(), "offset".to_string()]);
+        let query = format!(
+            "SELECT * FROM {}{} ORDER BY {} LIMIT {} OFFSET {}",
+            self.table(),
+            Self::live_filter(None),
+            self.primary_key(),
+            placeholders[0],
+            placeholders[1]
+        );
+        let rows = self.database().query(&query, params).await?;
+
+        self.convert_rows_to_entitys(rows)
+    }
+
+    /// 按多列排序查询全部记录, 生成 `ORDER BY a ASC, b DESC`
+    ///
+    /// 列名直接拼入 SQL, 只接受字母, 数字, 下划线和点组成的列名
+    async fn find_all_ordered(&self, order: &[(&str, SortDir)]) -> Result<Vec<T>, DbError> {
+        let mut query = format!("SELECT * FROM {}{}", self.table(), Self::live_filter(None));
+        if !order.is_empty() {
+            let mut columns = Vec::with_capacity(order.len());
+            for (column, dir) in order {
+                if !is_identifier(column) {
+                    return Err(DbError::InvalidIdentifier(column.to_string()));
+                }
+                columns.push(format!("{} {}", column, dir));
+            }
+            query.push_str(" ORDER BY ");
+            query.push_str(&columns.join(", "));
+        }
+        let rows = self.database().query(&query, vec![]).await?;
+
+        self.convert_rows_to_entitys(rows)
+    }
+
+    /// 按主键顺序逐条处理全部记录, 见 fold
+    async fn for_each<F>(&self, mut f: F) -> Result<(), DbError>
+    where
+        F: FnMut(T) + Send,
+    {
+        self.fold((), |(), entity| f(entity)).await
+    }
+
+    /// 按主键顺序把全部记录累积为一个结果
+    ///
+    /// 以主键为游标每次读取 SCAN_BATCH 行, 内存中只保留当前一批, 不会一次载入整张表
+    async fn fold<B, F>(&self, init: B, mut f: F) -> Result<B, DbError>
+    where
+        B: Send,
+        F: FnMut(B, T) -> B + Send,
+    {
+        let columns = self.primary_keys();
+        let placeholders = self.placeholders(&columns);
+        let after = if columns.len() == 1 {
+            format!("{} > {}", columns[0], placeholders[0])
+        } else {
+            format!("({}) > ({})", columns.join(", "), placeholders.join(", "))
+        };
+
+        let mut acc = init;
+        let mut last: Option<Vec<Value>> = None;
+        loop {
+            let query = format!(
+                "SELECT * FROM {}{} ORDER BY {} LIMIT {}",
+                self.table(),
+                Self::live_filter(last.as_ref().map(|_| after.clone())),
+                columns.join(", "),
+                SCAN_BATCH
+            );
+            let rows = self
+                .database()
+                .query(&query, last.take().unwrap_or_default())
+                .await?;
+            let full = rows.len() == SCAN_BATCH;
+            if let Some(row) = rows.last() {
+                let key = columns
+                    .iter()
+                    .map(|c| {
+                        row.get_by_name(c).cloned().ok_or_else(|| {
+                            DbError::ConversionError(format!("column {} not found", c))
+                        })
+                    })
+                    .collect::<Result<Vec<Value>, DbError>>()?;
+                last = Some(key);
+            }
+            for row in rows {
+                acc = f(acc, Self::row_to_entity(row)?);
+            }
+            if !full {
+                return Ok(acc);
+            }
+        }
+    }
+
     /// 更新记录
     async fn update(&self, entity: &T) -> Result<u64, DbError> {
-        let map = Self::entity_to_map(entity);
+        let mut map = Self::entity_to_map(entity);
+        stamp_timestamps(&mut map, Self::timestamps(), false);
         let mut values: Vec<Value> = Vec::new();
 
-        let mut primary_value = None;
+        let primary_columns = self.primary_keys();
         let update_columns: Vec<String> = map
             .iter()
-            .inspect(|kv| {
-                if kv.0 == Self::primary_key_column() {
-                    primary_value = Some(kv.1.clone());
-                }
-            })
-            .filter(|kv| kv.0 != Self::primary_key_column())
+            .filter(|kv| !primary_columns.contains(&kv.0))
             .enumerate()
             .map(|(i, kv)| {
                 let placeholder = self.placeholders(&vec![kv.0.clone(); i + 1])[i].clone();
@@ -141,53 +404,265 @@ where
             })
             .collect();
 
-        if let Some(id_value) = primary_value {
-            values.push(id_value.clone());
+        let offset = values.len();
+        for column in &primary_columns {
+            if let Some(kv) = map.iter().find(|kv| &kv.0 == column) {
+                values.push(kv.1.clone());
+            }
         }
 
         let query = format!(
-            "UPDATE {} SET {} WHERE {} = {}",
-            Self::table_name(),
+            "UPDATE {} SET {} WHERE {}",
+            self.table(),
             update_columns.join(", "),
-            Self::primary_key_column(),
-            self.placeholders(&vec![Self::primary_key_column(); values.len()])[values.len() - 1]
-                .clone(),
+            self.primary_key_condition(offset),
+        );
+
+        self.database().execute(&query, values).await
+    }
+
+    /// 更新记录, 没有命中时返回 DbError::NotFound
+    async fn update_expect_one(&self, entity: &T) -> Result<(), DbError> {
+        self.check_one_affected(self.update(entity).await?)
+    }
+
+    /// 只更新给定的列, 其余列保持不变
+    async fn update_fields(&self, id: Value, fields: Vec<(&str, Value)>) -> Result<u64, DbError> {
+        if fields.is_empty() {
+            return Err(DbError::QueryError(QueryErrorKind::Other(
+                "update_fields requires at least one field".into(),
+            )));
+        }
+        let key = vec![id];
+        self.check_primary_key(&key)?;
+
+        let placeholders = self.placeholders(&vec![String::new(); fields.len()]);
+        let (columns, mut values): (Vec<&str>, Vec<Value>) = fields.into_iter().unzip();
+        let assignments: Vec<String> = columns
+            .iter()
+            .zip(&placeholders)
+            .map(|(column, placeholder)| format!("{} = {}", column, placeholder))
+            .collect();
+
+        let query = format!(
+            "UPDATE {} SET {} WHERE {}",
+            self.table(),
+            assignments.join(", "),
+            self.primary_key_condition(values.len()),
         );
+        values.extend(key);
 
         self.database().execute(&query, values).await
     }
 
     /// 删除记录
     async fn delete(&self, id: Value) -> Result<u64, DbError> {
-        let placeholder = self.placeholders(&[Self::primary_key_column()])[0].clone();
+        self.delete_by_key(vec![id]).await
+    }
+
+    /// 删除记录, 没有命中时返回 DbError::NotFound
+    async fn delete_expect_one(&self, id: Value) -> Result<(), DbError> {
+        self.check_one_affected(self.delete(id).await?)
+    }
+
+    /// 根据主键删除记录, 值按 primary_key_columns 的顺序对应
+    ///
+    /// 启用软删除时只标记删除时间, 已删除的记录不计入影响行数
+    async fn delete_by_key(&self, key: Vec<Value>) -> Result<u64, DbError> {
+        let Some(column) = Self::soft_delete_column() else {
+            return self.hard_delete_by_key(key).await;
+        };
+        self.check_primary_key(&key)?;
         let query = format!(
-            "DELETE FROM {} WHERE {} = {}",
-            Self::table_name(),
-            Self::primary_key_column(),
-            placeholder
+            "UPDATE {} SET {} = {}{}",
+            self.table(),
+            column,
+            self.placeholders(&[String::new()])[0],
+            Self::live_filter(Some(self.primary_key_condition(1)))
         );
+        let mut values = vec![Value::DateTime(Utc::now())];
+        values.extend(key);
 
-        self.database().execute(&query, vec![id]).await
+        self.database().execute(&query, values).await
+    }
+
+    /// 物理删除记录, 不受软删除影响
+    async fn hard_delete(&self, id: Value) -> Result<u64, DbError> {
+        self.hard_delete_by_key(vec![id]).await
+    }
+
+    /// 根据主键物理删除记录
+    async fn hard_delete_by_key(&self, key: Vec<Value>) -> Result<u64, DbError> {
+        self.check_primary_key(&key)?;
+        let query = format!(
+            "DELETE FROM {} WHERE {}",
+            self.table(),
+            self.primary_key_condition(0)
+        );
+
+        self.database().execute(&query, key).await
+    }
+
+    /// 按主键批量删除, 生成 `DELETE ... WHERE pk IN (...)`, 返回删除的总行数
+    ///
+    /// 超过 DELETE_BATCH 个主键时分多条语句执行, 仅支持单列主键; 启用软删除时只标记删除时间
+    async fn delete_by_ids(&self, ids: Vec<Value>) -> Result<u64, DbError> {
+        // 复合主键时只按第一列匹配会删掉多余的行
+        if self.primary_keys().len() != 1 {
+            return Err(DbError::QueryError(QueryErrorKind::Other(
+                format!(
+                    "delete_by_ids requires a single-column primary key, {} has {}",
+                    self.table(),
+                    self.primary_keys().len()
+                )
+                .into(),
+            )));
+        }
+        let soft_delete = Self::soft_delete_column();
+        let mut affected = 0;
+        for chunk in ids.chunks(DELETE_BATCH) {
+            let offset = usize::from(soft_delete.is_some());
+            let placeholders = self.placeholders(&vec![String::new(); offset + chunk.len()]);
+            let condition = format!(
+                "{} IN ({})",
+                self.primary_key(),
+                placeholders[offset..].join(", ")
+            );
+            let mut values = chunk.to_vec();
+            let query = match &soft_delete {
+                Some(column) => {
+                    values.insert(0, Value::DateTime(Utc::now()));
+                    format!(
+                        "UPDATE {} SET {} = {}{}",
+                        self.table(),
+                        column,
+                        placeholders[0],
+                        Self::live_filter(Some(condition))
+                    )
+                }
+                None => format!("DELETE FROM {} WHERE {}", self.table(), condition),
+            };
+            affected += self.database().execute(&query, values).await?;
+        }
+        Ok(affected)
+    }
+
+    /// 用一条语句删除满足条件的记录, 返回删除的行数; 启用软删除时只标记删除时间
+    ///
+    /// 条件按原样拼入 WHERE, 占位符写作 `?` 或 `$n`, 如 `user_id = ? AND quantity < ?`
+    async fn delete_by_condition(
+        &self,
+        condition: &str,
+        params: Vec<Value>,
+    ) -> Result<u64, DbError> {
+        let (query, values) = match Self::soft_delete_column() {
+            Some(column) => self
+                .prepare()
+                .update(&[&column])
+                .values(vec![Value::DateTime(Utc::now())])
+                .raw_where(condition, params)
+                .raw_where(&format!("{} IS NULL", column), vec![])
+                .build()?,
+            None => self
+                .prepare()
+                .delete()
+                .raw_where(condition, params)
+                .build()?,
+        };
+        self.database().execute(&query, values).await
+    }
+
+    /// 用一条语句更新满足条件的记录, 返回更新的行数, 条件的写法与 delete_by_condition 相同
+    async fn update_by_condition(
+        &self,
+        set: Vec<(&str, Value)>,
+        condition: &str,
+        params: Vec<Value>,
+    ) -> Result<u64, DbError> {
+        if set.is_empty() {
+            return Err(DbError::QueryError(QueryErrorKind::Other(
+                "update_by_condition requires at least one field".into(),
+            )));
+        }
+        let (columns, values): (Vec<&str>, Vec<Value>) = set.into_iter().unzip();
+        let (query, values) = self
+            .prepare()
+            .update(&columns)
+            .values(values)
+            .raw_where(condition, params)
+            .build()?;
+        self.database().execute(&query, values).await
+    }
+
+    /// 删除表中所有记录, 返回删除的行数; 启用软删除时只标记删除时间
+    async fn delete_all(&self) -> Result<u64, DbError> {
+        let (query, values) = match Self::soft_delete_column() {
+            Some(column) => (
+                format!(
+                    "UPDATE {} SET {} = {}{}",
+                    self.table(),
+                    column,
+                    self.placeholders(&[String::new()])[0],
+                    Self::live_filter(None)
+                ),
+                vec![Value::DateTime(Utc::now())],
+            ),
+            None => (format!("DELETE FROM {}", self.table()), vec![]),
+        };
+
+        self.database().execute(&query, values).await
+    }
+
+    /// 清空表, 不受软删除影响; SQLite 没有 TRUNCATE, 改用 DELETE
+    async fn truncate(&self) -> Result<(), DbError> {
+        let query = self.database().truncate_statement(&self.table());
+        self.database().execute(&query, vec![]).await?;
+        Ok(())
+    }
+
+    /// 删除表, if_exists 为 true 时表不存在也不报错
+    async fn drop_table(&self, if_exists: bool) -> Result<(), DbError> {
+        let query = format!(
+            "DROP TABLE {}{}",
+            if if_exists { "IF EXISTS " } else { "" },
+            self.table()
+        );
+        self.database().execute(&query, vec![]).await?;
+        Ok(())
+    }
+
+    /// 记录总数, 启用软删除时不计已删除的记录
+    async fn count(&self) -> Result<i64, DbError> {
+        let query = format!(
+            "SELECT COUNT(*) FROM {}{}",
+            self.table(),
+            Self::live_filter(None)
+        );
+        match self.database().query_one(&query, vec![]).await? {
+            Some(row) => row.get_by_index(0),
+            None => Ok(0),
+        }
     }
 
     /// 自定义条件查询
+    ///
+    /// 多个条件用 AND 连接, 写法与 Entity::find_by_conditions 相同, 如 `vec!["id =", "amount <"]`,
+    /// 条件个数与参数个数不一致时返回 ParameterCountMismatch
     async fn find_by_condition(
         &self,
         condition: Vec<&str>,
         params: Vec<Value>,
     ) -> Result<Vec<T>, DbError> {
-        let conditions: Vec<String> = condition.iter().map(|s| s.to_string()).collect();
-        let placeholders = self.placeholders(&conditions);
-        let where_condition: String = conditions
-            .iter()
-            .enumerate()
-            .map(|(i, c)| format!("{} {}", c, placeholders[i]))
-            .collect::<Vec<String>>()
-            .join(" AND ");
+        if condition.len() != params.len() {
+            return Err(DbError::ParameterCountMismatch {
+                expected: condition.len(),
+                actual: params.len(),
+            });
+        }
         let query = format!(
-            "SELECT * FROM {} WHERE {}",
-            Self::table_name(),
-            where_condition
+            "SELECT * FROM {}{}",
+            self.table(),
+            Self::live_filter(Some(self.where_condition(&condition)))
         );
 
         let rows = self.database().query(&query, params).await?;
@@ -198,6 +673,94 @@ where
         Ok(entities)
     }
 
+    /// 条件按原样拼入 WHERE, 占位符由调用方按数据库的写法给出, 如 `name = $1 OR email = $2`
+    async fn find_by_raw_condition(
+        &self,
+        condition: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<T>, DbError> {
+        // 加括号, 避免 OR 条件绕过软删除过滤
+        let query = format!(
+            "SELECT * FROM {}{}",
+            self.table(),
+            Self::live_filter(Some(format!("({})", condition)))
+        );
+
+        let rows = self.database().query(&query, params).await?;
+        self.convert_rows_to_entitys(rows)
+    }
+
+    /// 按 `column LIKE pattern` 查找, pattern 作为参数绑定
+    ///
+    /// `!` 为转义字符; 按字面匹配时用 like_contains / like_starts_with 构造 pattern
+    async fn find_by_like(&self, column: &str, pattern: &str) -> Result<Vec<T>, DbError> {
+        self.find_by_like_clause(column, format!("{} LIKE ?", column), pattern)
+            .await
+    }
+
+    /// 不区分大小写的 find_by_like, Postgres 生成 `ILIKE`, 其他数据库比较 LOWER() 之后的值
+    async fn find_by_ilike(&self, column: &str, pattern: &str) -> Result<Vec<T>, DbError> {
+        let clause = self.database().ilike_clause(column);
+        self.find_by_like_clause(column, clause, pattern).await
+    }
+
+    // clause 中的 `?` 换成数据库的占位符
+    async fn find_by_like_clause(
+        &self,
+        column: &str,
+        clause: String,
+        pattern: &str,
+    ) -> Result<Vec<T>, DbError> {
+        if !is_identifier(column) {
+            return Err(DbError::InvalidIdentifier(column.to_string()));
+        }
+        let condition = format!(
+            "{} ESCAPE '{}'",
+            clause.replace('?', &self.placeholders(&[column.to_string()])[0]),
+            LIKE_ESCAPE
+        );
+        let query = format!(
+            "SELECT * FROM {}{}",
+            self.table(),
+            Self::live_filter(Some(condition))
+        );
+
+        let rows = self.database().query(&query, vec![pattern.into()]).await?;
+        self.convert_rows_to_entitys(rows)
+    }
+
+    /// 按条件查找唯一记录, 没有时返回 None, 多于一条时返回 DbError::Conflict
+    ///
+    /// 条件的写法与 find_by_condition 相同, 个数与参数不一致时返回 ParameterCountMismatch
+    async fn find_one_by_condition(
+        &self,
+        condition: Vec<&str>,
+        params: Vec<Value>,
+    ) -> Result<Option<T>, DbError> {
+        if condition.len() != params.len() {
+            return Err(DbError::ParameterCountMismatch {
+                expected: condition.len(),
+                actual: params.len(),
+            });
+        }
+        // 取两条即可判断是否唯一
+        let query = format!(
+            "SELECT * FROM {}{} LIMIT 2",
+            self.table(),
+            Self::live_filter(Some(self.where_condition(&condition)))
+        );
+
+        let mut rows = self.database().query(&query, params).await?;
+        if rows.len() > 1 {
+            return Err(DbError::Conflict(format!(
+                "more than one row in {} matches {}",
+                self.table(),
+                condition.join(" AND ")
+            )));
+        }
+        rows.pop().map(Self::row_to_entity).transpose()
+    }
+
     async fn begin_transaction(&self) -> Result<(), DbError> {
         self.database().begin_transaction().await
     }
@@ -210,18 +773,114 @@ where
         self.database().rollback().await
     }
 
-    fn prepare(&self) -> SqlExecutor<Self::Database, T> {
-        SqlExecutor::new(self.database(), Self::table_name())
+    fn prepare(&self) -> SqlExecutor<'_, Self::Database, T> {
+        SqlExecutor::new(self.database(), self.table()).rename_policy(Self::rename_policy())
+    }
+}
+
+/// 乐观锁: 通过版本列检测并发修改
+#[async_trait::async_trait]
+pub trait VersionedDao<T>: Dao<T>
+where
+    T: Sized + Sync + Serialize + for<'de> Deserialize<'de>,
+{
+    fn version_column() -> String {
+        "version".to_string()
+    }
+
+    /// 带版本号检查的更新, 成功后数据库中的版本号加一
+    ///
+    /// 实体中的版本号需是读取时的值, 不匹配时返回 DbError::Conflict
+    async fn update_versioned(&self, entity: &T) -> Result<u64, DbError> {
+        let map = Self::entity_to_map(entity);
+        let version_column = Self::version_column();
+        let primary_columns = self.primary_keys();
+        let version = map
+            .iter()
+            .find(|kv| kv.0 == version_column)
+            .map(|kv| kv.1.clone())
+            .ok_or_else(|| {
+                DbError::ConversionError(format!("missing version column {}", version_column))
+            })?;
+
+        let fields: Vec<&(String, Value)> = map
+            .iter()
+            .filter(|kv| !primary_columns.contains(&kv.0) && kv.0 != version_column)
+            .collect();
+        let placeholders = self.placeholders(&vec![
+            String::new();
+            fields.len() + primary_columns.len() + 1
+        ]);
+        let mut values: Vec<Value> = Vec::new();
+        let mut update_columns: Vec<String> = Vec::new();
+        for (i, kv) in fields.iter().enumerate() {
+            update_columns.push(format!("{} = {}", kv.0, placeholders[i]));
+            values.push(kv.1.clone());
+        }
+        update_columns.push(format!("{0} = {0} + 1", version_column));
+
+        let offset = values.len();
+        for column in &primary_columns {
+            if let Some(kv) = map.iter().find(|kv| &kv.0 == column) {
+                values.push(kv.1.clone());
+            }
+        }
+        values.push(version);
+
+        let query = format!(
+            "UPDATE {} SET {} WHERE {} AND {} = {}",
+            self.table(),
+            update_columns.join(", "),
+            self.primary_key_condition(offset),
+            version_column,
+            placeholders[values.len() - 1],
+        );
+
+        let affected = self.database().execute(&query, values).await?;
+        if affected == 0 {
+            return Err(DbError::Conflict(format!(
+                "{} was modified or deleted concurrently",
+                self.table()
+            )));
+        }
+        Ok(affected)
     }
 }
 
+/// 通用的 Dao, 表名, 主键和列名映射取自实体的 Entity 实现, 不需要为每个实体单独实现 Dao
+///
+/// `DataAccessory::<Product, _>::new(db)`, 表名不同时用 `DataAccessory::new_for(db, "products", "id")`
 pub struct DataAccessory<T: Sized, D: RelationalDatabase> {
     database: D,
+    table: Option<String>,
+    primary_key: Option<String>,
     _table: PhantomData<T>,
 }
+
+impl<T: Sized, D: RelationalDatabase> DataAccessory<T, D> {
+    pub(crate) fn from_database(database: D) -> Self {
+        Self {
+            database,
+            table: None,
+            primary_key: None,
+            _table: PhantomData,
+        }
+    }
+
+    /// 指定表名和主键, 同一实体类型可以操作多张结构相同的表
+    pub fn new_for(database: D, table: &str, primary_key: &str) -> Self {
+        Self {
+            database,
+            table: Some(table.to_string()),
+            primary_key: Some(primary_key.to_string()),
+            _table: PhantomData,
+        }
+    }
+}
+
 impl<T, D> Dao<T> for DataAccessory<T, D>
 where
-    T: Sized + Sync + Serialize + for<'de> Deserialize<'de>,
+    T: Entity + EntityData,
     D: RelationalDatabase,
 {
     type Database = D;
@@ -230,25 +889,42 @@ where
     }
 
     fn new(database: Self::Database) -> Self {
-        Self {
-            database,
-            _table: PhantomData,
-        }
+        Self::from_database(database)
+    }
+
+    fn row_to_entity(row: Row) -> Result<T, DbError> {
+        T::row_to_entity(row)
     }
 
     fn entity_to_map(entity: &T) -> Vec<(String, Value)> {
-        let cursor = Cursor::new(Vec::new());
-        let mut convertor = EntityConvertor::new(cursor);
-        let result = entity.serialize(&mut convertor);
-        match result {
-            Ok(Value::Table(table)) => table,
-            _ => vec![("".to_string(), Value::Null)],
-        }
+        T::entity_to_map(entity)
     }
+
     fn table_name() -> String {
-        "user".to_string()
+        T::table()
     }
+
     fn primary_key_column() -> String {
-        "id".to_string()
+        T::primary_key()
+    }
+
+    fn table(&self) -> String {
+        self.table.clone().unwrap_or_else(T::table)
+    }
+
+    fn primary_key(&self) -> String {
+        self.primary_key.clone().unwrap_or_else(T::primary_key)
+    }
+
+    fn primary_keys(&self) -> Vec<String> {
+        vec![self.primary_key()]
+    }
+
+    fn rename_policy() -> RenamePolicy {
+        T::rename_policy()
+    }
+
+    fn timestamps() -> Option<(String, String)> {
+        T::timestamps()
     }
 }
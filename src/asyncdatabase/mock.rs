@@ -0,0 +1,81 @@
+use crate::asyncdatabase::{DatabaseConfig, DbError, RelationalDatabase, Row, Value};
+use crate::database::mock::MockDatabase as SyncMockDatabase;
+use crate::database::RelationalDatabase as _;
+use async_trait::async_trait;
+use std::ops::Deref;
+use std::time::Duration;
+
+/// 内存中的模拟数据库, 支持的语句与 `database::mock::MockDatabase` 相同
+///
+/// 通过 Deref 调用 respond_query, rows 等辅助方法
+#[derive(Debug, Clone, Default)]
+pub struct MockDatabase {
+    inner: SyncMockDatabase,
+}
+
+impl MockDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Deref for MockDatabase {
+    type Target = SyncMockDatabase;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl RelationalDatabase for MockDatabase {
+    fn placeholders(&self, keys: &[String]) -> Vec<String> {
+        self.inner.placeholders(keys)
+    }
+
+    async fn connect(_config: DatabaseConfig) -> Result<Self, DbError> {
+        Ok(Self::new())
+    }
+
+    async fn close(&self) -> Result<(), DbError> {
+        self.inner.close()
+    }
+
+    async fn ping(&self) -> Result<(), DbError> {
+        self.inner.ping()
+    }
+
+    async fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
+        self.inner.ping_timeout(timeout)
+    }
+
+    async fn begin_transaction(&self) -> Result<(), DbError> {
+        self.inner.begin_transaction()
+    }
+
+    async fn commit(&self) -> Result<(), DbError> {
+        self.inner.commit()
+    }
+
+    async fn rollback(&self) -> Result<(), DbError> {
+        self.inner.rollback()
+    }
+
+    fn session(&self) -> Self {
+        Self {
+            inner: self.inner.session(),
+        }
+    }
+
+    async fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
+        self.inner.execute(query, params)
+    }
+
+    async fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
+        self.inner.query(query, params)
+    }
+
+    async fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
+        self.inner.query_one(query, params)
+    }
+}
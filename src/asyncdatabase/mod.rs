@@ -1,54 +1,275 @@
+#[cfg(feature = "mock")]
+pub mod mock;
 #[cfg(feature = "mysql_async")]
 pub mod mysql;
 #[cfg(feature = "postgresql_async")]
 pub mod postgres;
+pub mod replicated;
 #[cfg(feature = "sqlite_async")]
 pub mod sqlite;
 
-pub use crate::common::{Connection, DatabaseConfig, DbError, QueryErrorKind, Row, Value};
+pub use crate::common::{
+    Connection, DatabaseConfig, DatabaseConfigBuilder, DbError, ErrorDetail, PoolStatus,
+    QueryErrorKind, QueryObserver, ReadPolicy, Row, Value,
+};
+pub use crate::sql_builder::ConflictAction;
+use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[async_trait::async_trait]
 pub trait RelationalDatabase: Sync + Send + Clone {
     fn placeholders(&self, keys: &[String]) -> Vec<String>;
+    // 建表时 Value 对应的列类型
+    fn column_type(&self, value: &Value) -> &'static str {
+        match value {
+            Value::Int(_) => "INTEGER",
+            Value::Bigint(_) => "BIGINT",
+            Value::UBigint(_) => "BIGINT UNSIGNED",
+            Value::Float(_) => "REAL",
+            Value::Double(_) => "DOUBLE",
+            Value::Varchar(_) => "VARCHAR(255)",
+            Value::Boolean(_) => "BOOLEAN",
+            Value::Byte(_) | Value::Smallint(_) => "SMALLINT",
+            Value::TinyInt(_) => "TINYINT",
+            Value::Bytes(_) | Value::Array(_) => "BLOB",
+            Value::DateTime(_) => "DATETIME",
+            Value::Interval(_) => "TIME",
+            Value::Null | Value::Text(_) | Value::Table(_) => "TEXT",
+        }
+    }
+    /// INSERT 冲突处理子句, 默认为 Postgres 和 SQLite 的 `ON CONFLICT (...) DO ...`
+    fn on_conflict_clause(&self, columns: &[&str], action: &ConflictAction<'_>) -> String {
+        let action = match action {
+            ConflictAction::DoNothing => "DO NOTHING".to_string(),
+            ConflictAction::DoUpdate(assignments) => format!(
+                "DO UPDATE SET {}",
+                assignments
+                    .iter()
+                    .map(|(column, expr)| format!("{} = {}", column, expr))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
+        format!("ON CONFLICT ({}) {}", columns.join(", "), action)
+    }
+    /// 不区分大小写的 LIKE 条件, 占位符写作 `?`, 默认两边都转为小写再比较
+    fn ilike_clause(&self, column: &str) -> String {
+        format!("LOWER({}) LIKE LOWER(?)", column)
+    }
+    /// 清空表的语句, 默认为 `TRUNCATE TABLE`
+    fn truncate_statement(&self, table: &str) -> String {
+        format!("TRUNCATE TABLE {}", table)
+    }
+    /// 给标识符加引号, 默认为标准 SQL 的双引号, MySQL 使用反引号
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
     // 连接相关
     async fn connect(config: DatabaseConfig) -> Result<Self, DbError>
     where
         Self: Sized;
+    /// 连接后 ping 确认数据库可用, 连接失败 (连接错误, 连接池错误, 超时) 时等待 delay 后重试,
+    /// 每次等待时间翻倍, 每次 ping 最多等待 5 秒
+    ///
+    /// 最多尝试 attempts 次, 仍失败时返回最后一次的错误; 其他错误直接返回
+    async fn connect_with_retry(
+        config: DatabaseConfig,
+        attempts: u32,
+        delay: Duration,
+    ) -> Result<Self, DbError>
+    where
+        Self: Sized,
+    {
+        Self::connect_with_retry_timeout(config, attempts, delay, DEFAULT_PING_TIMEOUT).await
+    }
+    /// 同 connect_with_retry, 每次 ping 最多等待 timeout
+    async fn connect_with_retry_timeout(
+        config: DatabaseConfig,
+        attempts: u32,
+        delay: Duration,
+        timeout: Duration,
+    ) -> Result<Self, DbError>
+    where
+        Self: Sized,
+    {
+        let mut delay = delay;
+        let mut attempt = 1;
+        loop {
+            let result = match Self::connect(config.clone()).await {
+                Ok(db) => db.ping_timeout(timeout).await.map(|_| db),
+                Err(e) => Err(e),
+            };
+            match result {
+                Err(e) if is_unavailable(&e) && attempt < attempts => {
+                    log::warn!("connect attempt {} failed: {}", attempt, e);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
     async fn close(&self) -> Result<(), DbError>;
     async fn ping(&self) -> Result<(), DbError>;
+    // 超时未响应返回 DbError::Timeout
+    async fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
+        tokio::time::timeout(timeout, self.ping())
+            .await
+            .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?
+    }
 
     // 事务相关
+    /// 事务状态由所有克隆共享, 在一个克隆上开始事务后, 其他克隆的语句也在该事务中执行,
+    /// 直到 commit 或 rollback. 优先使用 start_transaction, 由守卫负责结束事务
     async fn begin_transaction(&self) -> Result<(), DbError>;
     async fn commit(&self) -> Result<(), DbError>;
     async fn rollback(&self) -> Result<(), DbError>;
 
+    /// 共享连接池, 但事务状态独立的句柄, 在其上开始的事务不影响其他克隆
+    fn session(&self) -> Self
+    where
+        Self: Sized;
+
+    /// 在新的 session 上开始事务并返回守卫, 事务独占一个连接, 守卫被丢弃前没有 commit 则回滚
+    async fn start_transaction(&self) -> Result<Transaction<Self>, DbError>
+    where
+        Self: Sized + 'static,
+    {
+        let db = self.session();
+        db.begin_transaction().await?;
+        Ok(Transaction {
+            db,
+            finished: false,
+        })
+    }
+
     // 查询相关
     async fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError>;
     async fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError>;
     async fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError>;
 
+    /// 不带参数, 不预编译直接执行
+    async fn execute_raw(&self, sql: &str) -> Result<u64, DbError> {
+        self.execute(sql, vec![]).await
+    }
+
+    /// 不带参数的查询, 各列均以 Value::Text 返回, NULL 仍为 Value::Null
+    ///
+    /// 用于 Value 无法表示的列类型. Postgres 使用文本协议, 其他数据库转换普通查询的结果
+    async fn query_raw(&self, sql: &str) -> Result<Vec<Row>, DbError> {
+        let rows = self.query(sql, vec![]).await?;
+        Ok(rows.into_iter().map(Row::into_text).collect())
+    }
+
+    /// 执行分号分隔的多条语句, 不带参数, 用于建表等脚本
+    ///
+    /// 各数据库一次发送整个脚本; 默认实现按分号拆开逐条执行, 语句本身不能包含分号
+    async fn batch_execute(&self, sql: &str) -> Result<(), DbError> {
+        for statement in sql.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            self.execute(statement, vec![]).await?;
+        }
+        Ok(())
+    }
+
     // 连接池相关
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus::default()
+    }
     // async fn get_connection(&self) -> Result<Connection, DbError>;
     // async fn release_connection(&self, conn: Connection) -> Result<(), DbError>;
 }
 
+/// 事务守卫, 由 `start_transaction` 返回, 可以像数据库本身一样执行语句
+///
+/// 调用 commit 提交, rollback 回滚; 两者都没有调用就被丢弃时在后台任务中回滚,
+/// 不在 tokio 运行时中丢弃时只记录警告
+pub struct Transaction<D: RelationalDatabase + 'static> {
+    db: D,
+    finished: bool,
+}
+
+impl<D: RelationalDatabase + 'static> Transaction<D> {
+    pub async fn commit(mut self) -> Result<(), DbError> {
+        self.finished = true;
+        self.db.commit().await
+    }
+
+    pub async fn rollback(mut self) -> Result<(), DbError> {
+        self.finished = true;
+        self.db.rollback().await
+    }
+}
+
+impl<D: RelationalDatabase + 'static> Deref for Transaction<D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        &self.db
+    }
+}
+
+impl<D: RelationalDatabase + 'static> Drop for Transaction<D> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            log::warn!("transaction dropped outside a tokio runtime, not rolled back");
+            return;
+        };
+        let db = self.db.clone();
+        handle.spawn(async move {
+            if let Err(e) = db.rollback().await {
+                log::warn!("rollback of dropped transaction failed: {}", e);
+            }
+        });
+    }
+}
+
+// connect_with_retry 每次 ping 的等待时间
+const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 连接层面的错误, 重试或换一个库可能成功
+pub(crate) fn is_unavailable(e: &DbError) -> bool {
+    matches!(
+        e,
+        DbError::ConnectionError(_) | DbError::PoolError(_) | DbError::Timeout(_)
+    )
+}
+
 #[cfg(all(not(feature = "full"), feature = "postgresql_async"))]
 pub async fn auto_config() -> postgres::PostgresDatabase {
-    let config = DatabaseConfig::default();
-    postgres::PostgresDatabase::connect(config).await.unwrap()
+    try_auto_config().await.unwrap()
+}
+
+#[cfg(all(not(feature = "full"), feature = "postgresql_async"))]
+pub async fn try_auto_config() -> Result<postgres::PostgresDatabase, DbError> {
+    let config = DatabaseConfig::from_env()?;
+    postgres::PostgresDatabase::connect(config).await
 }
 
 #[cfg(all(not(feature = "full"), feature = "mysql_async"))]
 pub async fn auto_config() -> mysql::MySqlDatabase {
-    let config = DatabaseConfig::default();
-    mysql::MySqlDatabase::connect(config).await.unwrap()
+    try_auto_config().await.unwrap()
+}
+
+#[cfg(all(not(feature = "full"), feature = "mysql_async"))]
+pub async fn try_auto_config() -> Result<mysql::MySqlDatabase, DbError> {
+    let config = DatabaseConfig::from_env()?;
+    mysql::MySqlDatabase::connect(config).await
 }
 
 #[cfg(all(not(feature = "full"), feature = "sqlite_async"))]
 pub async fn auto_config() -> sqlite::SqliteDatabase {
-    let config = DatabaseConfig::default();
-    sqlite::SqliteDatabase::connect(config).await.unwrap()
+    try_auto_config().await.unwrap()
+}
+
+#[cfg(all(not(feature = "full"), feature = "sqlite_async"))]
+pub async fn try_auto_config() -> Result<sqlite::SqliteDatabase, DbError> {
+    let config = DatabaseConfig::from_env()?;
+    sqlite::SqliteDatabase::connect(config).await
 }
 
 #[async_trait::async_trait]
@@ -56,6 +277,21 @@ impl<T: RelationalDatabase> RelationalDatabase for Arc<T> {
     fn placeholders(&self, keys: &[String]) -> Vec<String> {
         (**self).placeholders(keys)
     }
+    fn column_type(&self, value: &Value) -> &'static str {
+        (**self).column_type(value)
+    }
+    fn on_conflict_clause(&self, columns: &[&str], action: &ConflictAction<'_>) -> String {
+        (**self).on_conflict_clause(columns, action)
+    }
+    fn ilike_clause(&self, column: &str) -> String {
+        (**self).ilike_clause(column)
+    }
+    fn truncate_statement(&self, table: &str) -> String {
+        (**self).truncate_statement(table)
+    }
+    fn quote_identifier(&self, name: &str) -> String {
+        (**self).quote_identifier(name)
+    }
     // 连接相关
     async fn connect(config: DatabaseConfig) -> Result<Self, DbError>
     where
@@ -70,6 +306,9 @@ impl<T: RelationalDatabase> RelationalDatabase for Arc<T> {
     async fn ping(&self) -> Result<(), DbError> {
         (**self).ping().await
     }
+    async fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
+        (**self).ping_timeout(timeout).await
+    }
 
     // 事务相关
     async fn begin_transaction(&self) -> Result<(), DbError> {
@@ -81,6 +320,9 @@ impl<T: RelationalDatabase> RelationalDatabase for Arc<T> {
     async fn rollback(&self) -> Result<(), DbError> {
         (**self).rollback().await
     }
+    fn session(&self) -> Self {
+        Arc::new((**self).session())
+    }
 
     // 查询相关
     async fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
@@ -92,7 +334,19 @@ impl<T: RelationalDatabase> RelationalDatabase for Arc<T> {
     async fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
         (**self).query_one(query, params).await
     }
+    async fn execute_raw(&self, sql: &str) -> Result<u64, DbError> {
+        (**self).execute_raw(sql).await
+    }
+    async fn query_raw(&self, sql: &str) -> Result<Vec<Row>, DbError> {
+        (**self).query_raw(sql).await
+    }
+    async fn batch_execute(&self, sql: &str) -> Result<(), DbError> {
+        (**self).batch_execute(sql).await
+    }
     // 连接池相关
+    fn pool_status(&self) -> PoolStatus {
+        (**self).pool_status()
+    }
     // async fn get_connection(&self) -> Result<Connection, DbError>{self.get_connection().await}
     // async fn release_connection(&self, conn: Connection) -> Result<(), DbError>{self.release_connection().await}
 }
@@ -1,18 +1,26 @@
 use crate::asyncdatabase::{
-    Connection, DatabaseConfig, DbError, QueryErrorKind, RelationalDatabase, Row, Value,
+    ConflictAction, Connection, DatabaseConfig, DbError, ErrorDetail, PoolStatus, QueryErrorKind,
+    QueryObserver, RelationalDatabase, Row, Value,
+};
+use crate::common::{
+    check_tls, encode_array, interval_to_mysql_time, mysql_time_to_interval, observe_query,
+    ClosablePool,
 };
 use async_trait::async_trait;
 use chrono::{Datelike, NaiveDateTime, TimeZone, Timelike, Utc};
-use mysql::OptsBuilder;
+use mysql::{OptsBuilder, SslOpts};
 use r2d2::{Pool, PooledConnection};
 use r2d2_mysql::mysql::{prelude::*, Value as MySqlValue};
 use r2d2_mysql::MySqlConnectionManager;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct MySqlDatabase {
-    pool: Arc<Pool<MySqlConnectionManager>>,
+    pool: ClosablePool<Pool<MySqlConnectionManager>>,
     current_transaction: Arc<Mutex<Option<PooledConnection<MySqlConnectionManager>>>>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    slow_query_threshold: Option<Duration>,
 }
 
 impl MySqlDatabase {
@@ -24,22 +32,37 @@ impl MySqlDatabase {
             .tcp_port(config.port)
             .user(Some(&config.username))
             .pass(Some(&config.password))
-            .db_name(Some(&config.database_name));
+            .db_name(Some(&config.database_name))
+            .ssl_opts(config.tls.then(SslOpts::default))
+            // mysql 自带按连接的语句缓存, prep 时按 SQL 文本复用
+            .stmt_cache_size(config.statement_cache_size);
 
         let manager = MySqlConnectionManager::new(opts);
-        Pool::builder().max_size(config.max_size).build(manager)
+        Pool::builder()
+            .max_size(config.max_size)
+            .test_on_check_out(config.test_on_check_out)
+            .min_idle(config.min_idle)
+            .build(manager)
     }
 
     fn value_to_mysql(value: &Value) -> MySqlValue {
         match value {
             Value::Null => MySqlValue::NULL,
             Value::Bigint(i) => MySqlValue::Int(*i),
+            Value::Smallint(i) => MySqlValue::Int((*i).into()),
+            Value::TinyInt(i) => MySqlValue::Int((*i).into()),
+            Value::UBigint(i) => MySqlValue::UInt(*i),
             Value::Float(f) => MySqlValue::Float(*f as f32),
             Value::Double(f) => MySqlValue::Double(*f),
             // Value::Text(s) => MySqlValue::Bytes(s.clone().into_bytes()),
             Value::Text(s) => MySqlValue::from(s),
             Value::Boolean(b) => MySqlValue::Int(if *b { 1 } else { 0 }),
             Value::Bytes(b) => MySqlValue::from(b),
+            Value::Array(values) => MySqlValue::from(encode_array(values)),
+            Value::Interval(d) => {
+                let (negative, days, hours, minutes, seconds, micros) = interval_to_mysql_time(d);
+                MySqlValue::Time(negative, days, hours, minutes, seconds, micros)
+            }
             Value::DateTime(dt) => MySqlValue::Date(
                 dt.year() as u16,
                 dt.month() as u8,
@@ -53,13 +76,34 @@ impl MySqlDatabase {
         }
     }
 
+    // TINYINT 和 SMALLINT 列按列类型还原为窄整数, 无符号列放宽一级
+    fn narrow_integer(value: Value, column: &mysql::Column) -> Value {
+        use mysql::consts::{ColumnFlags, ColumnType};
+
+        let Value::Bigint(i) = value else {
+            return value;
+        };
+        let unsigned = column.flags().contains(ColumnFlags::UNSIGNED_FLAG);
+        match (column.column_type(), unsigned) {
+            (ColumnType::MYSQL_TYPE_TINY, false) => Value::TinyInt(i as i8),
+            (ColumnType::MYSQL_TYPE_TINY, true) | (ColumnType::MYSQL_TYPE_SHORT, false) => {
+                Value::Smallint(i as i16)
+            }
+            _ => Value::Bigint(i),
+        }
+    }
+
     fn convert_mysql_to_value(value: MySqlValue) -> Result<Value, DbError> {
         match value {
             MySqlValue::NULL => Ok(Value::Null),
             MySqlValue::Int(i) => Ok(Value::Bigint(i)),
+            MySqlValue::UInt(i) => Ok(Value::UBigint(i)),
             MySqlValue::Float(f) => Ok(Value::Float(f)),
             MySqlValue::Double(f) => Ok(Value::Double(f)),
             MySqlValue::Bytes(bytes) => Ok(Value::Bytes(bytes)),
+            MySqlValue::Time(negative, days, hours, minutes, seconds, micros) => Ok(
+                mysql_time_to_interval(negative, days, hours, minutes, seconds, micros),
+            ),
             MySqlValue::Date(year, month, day, hour, minute, second, micros) => {
                 let naive = NaiveDateTime::new(
                     chrono::NaiveDate::from_ymd_opt(year as i32, month as u32, day as u32)
@@ -74,9 +118,6 @@ impl MySqlDatabase {
                 );
                 Ok(Value::DateTime(Utc.from_utc_datetime(&naive)))
             }
-            _ => Err(DbError::ConversionError(
-                "Unsupported MySQL type".to_string(),
-            )),
         }
     }
 
@@ -87,15 +128,12 @@ impl MySqlDatabase {
         let mut transaction_guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         let mut conn = if let Some(conn) = &mut *transaction_guard {
             conn
         } else {
-            &mut self
-                .pool
-                .get()
-                .map_err(|e| DbError::ConnectionError(e.to_string()))?
+            &mut self.pool.get(DbError::ConnectionError)?
         };
 
         // f(conn)
@@ -103,10 +141,7 @@ impl MySqlDatabase {
     }
 
     pub async fn get_connection(&self) -> Result<Connection, DbError> {
-        let _conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let _conn = self.pool.get(DbError::PoolError)?;
         Ok(Connection {})
     }
 
@@ -120,44 +155,85 @@ impl RelationalDatabase for MySqlDatabase {
     fn placeholders(&self, keys: &[String]) -> Vec<String> {
         vec!["?".to_string(); keys.len()]
     }
+
+    // MySQL 按任意唯一键冲突, 不指定列; DoNothing 用 `列 = 列` 保持原值
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("`{}`", name.replace('`', "``"))
+    }
+
+    fn on_conflict_clause(&self, columns: &[&str], action: &ConflictAction<'_>) -> String {
+        let assignments: Vec<String> = match action {
+            ConflictAction::DoNothing => columns
+                .first()
+                .map(|column| format!("{} = {}", column, column))
+                .into_iter()
+                .collect(),
+            ConflictAction::DoUpdate(assignments) => assignments
+                .iter()
+                .map(|(column, expr)| format!("{} = {}", column, expr))
+                .collect(),
+        };
+        format!("ON DUPLICATE KEY UPDATE {}", assignments.join(", "))
+    }
+
     async fn connect(config: DatabaseConfig) -> Result<Self, DbError> {
+        check_tls(&config)?;
         let pool = Self::new_pool(&config)
             .await
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
 
         Ok(MySqlDatabase {
-            pool: Arc::new(pool),
+            pool: ClosablePool::new(pool, &config),
             current_transaction: Arc::new(Mutex::new(None)),
+            observer: config.observer,
+            slow_query_threshold: config.slow_query_threshold,
         })
     }
 
+    // 之后的操作返回 DbError::Closed, 空闲连接随池一起释放, 进行中的事务被丢弃
     async fn close(&self) -> Result<(), DbError> {
+        self.pool.close();
+        self.current_transaction
+            .lock()
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?
+            .take();
         Ok(())
     }
 
     async fn ping(&self) -> Result<(), DbError> {
-        let mut conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+        let mut conn = self.pool.get(DbError::ConnectionError)?;
         conn.query_drop("SELECT 1")
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
         Ok(())
     }
 
+    // r2d2 取连接和查询都会阻塞线程, 放到阻塞线程池中执行, 整体受 timeout 限制
+    async fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
+        let pool = self.pool.current()?;
+        let ping = tokio::task::spawn_blocking(move || {
+            let mut conn = pool
+                .get_timeout(timeout)
+                .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?;
+            conn.query_drop("SELECT 1")
+                .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
+            Ok(())
+        });
+        tokio::time::timeout(timeout, ping)
+            .await
+            .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?
+    }
+
     async fn begin_transaction(&self) -> Result<(), DbError> {
-        let mut conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+        let mut conn = self.pool.get(DbError::TransactionError)?;
 
         conn.query_drop("START TRANSACTION")
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
 
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
         *guard = Some(conn);
 
         Ok(())
@@ -167,11 +243,11 @@ impl RelationalDatabase for MySqlDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(mut conn) = guard.take() {
             conn.query_drop("COMMIT")
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
@@ -180,103 +256,173 @@ impl RelationalDatabase for MySqlDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(mut conn) = guard.take() {
             conn.query_drop("ROLLBACK")
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
 
+    fn session(&self) -> Self {
+        Self {
+            current_transaction: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
     async fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
-        self.execute_with_connection(|conn| {
-            let params: Vec<mysql::Value> =
-                params.iter().map(MySqlDatabase::value_to_mysql).collect();
-
-            let stmt = conn
-                .prep(query)
-                .map_err(|e| DbError::ConversionError(e.to_string()))?;
-
-            conn.exec_drop(&stmt, &params).map_err(|e| {
-                match e {
-                    mysql::Error::MySqlError(ref mysql_err) => {
-                        // 获取 MySQL 错误码
-                        match mysql_err.code {
-                            1451 | 1452 => {
-                                // 外键约束错误
-                                DbError::QueryError(QueryErrorKind::ForeignKeyViolation(
-                                    mysql_err.message.clone(),
-                                ))
-                            }
-                            1062 => {
-                                // 唯一约束错误
-                                DbError::QueryError(QueryErrorKind::UniqueViolation(
-                                    mysql_err.message.clone(),
-                                ))
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self
+            .execute_with_connection(|conn| {
+                let params: Vec<mysql::Value> =
+                    params.iter().map(MySqlDatabase::value_to_mysql).collect();
+
+                let stmt = conn
+                    .prep(query)
+                    .map_err(|e| DbError::ConversionError(e.to_string()))?;
+
+                conn.exec_drop(&stmt, &params).map_err(|e| {
+                    match e {
+                        mysql::Error::MySqlError(ref mysql_err) => {
+                            // 获取 MySQL 错误码
+                            match mysql_err.code {
+                                1451 | 1452 => {
+                                    // 外键约束错误
+                                    DbError::QueryError(QueryErrorKind::ForeignKeyViolation(
+                                        ErrorDetail::with_source(mysql_err.message.clone(), e),
+                                    ))
+                                }
+                                1062 => {
+                                    // 唯一约束错误
+                                    DbError::QueryError(QueryErrorKind::UniqueViolation(
+                                        ErrorDetail::with_source(mysql_err.message.clone(), e),
+                                    ))
+                                }
+                                1048 => {
+                                    // 非空约束错误
+                                    DbError::QueryError(QueryErrorKind::NotNullViolation(
+                                        ErrorDetail::with_source(mysql_err.message.clone(), e),
+                                    ))
+                                }
+                                3819 => {
+                                    // 检查约束错误
+                                    DbError::QueryError(QueryErrorKind::CheckViolation(
+                                        ErrorDetail::with_source(mysql_err.message.clone(), e),
+                                    ))
+                                }
+                                // 其他错误
+                                other_code => DbError::QueryError(QueryErrorKind::Other(
+                                    ErrorDetail::with_source(
+                                        format!(
+                                            "code: {}, message: {}",
+                                            other_code, mysql_err.message
+                                        ),
+                                        e,
+                                    ),
+                                )),
                             }
-                            1048 => {
-                                // 非空约束错误
-                                DbError::QueryError(QueryErrorKind::NotNullViolation(
-                                    mysql_err.message.clone(),
-                                ))
-                            }
-                            // 其他错误
-                            other_code => DbError::QueryError(QueryErrorKind::Other(format!(
-                                "code: {}, message: {}",
-                                other_code, mysql_err.message
-                            ))),
                         }
+                        // 其他类型的错误（比如连接错误、IO错误等）
+                        _ => DbError::QueryError(QueryErrorKind::Other(ErrorDetail::with_source(
+                            format!("message: {}", e),
+                            e,
+                        ))),
                     }
-                    // 其他类型的错误（比如连接错误、IO错误等）
-                    _ => DbError::QueryError(QueryErrorKind::Other(format!("message: {}", e))),
-                }
-            })?;
-            Ok(conn.affected_rows() as u64)
-        })
-        .await
+                })?;
+                Ok(conn.affected_rows() as u64)
+            })
+            .await;
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     async fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
-        self.execute_with_connection(|conn| {
-            let params: Vec<mysql::Value> =
-                params.iter().map(MySqlDatabase::value_to_mysql).collect();
-            let stmt = conn
-                .prep(query)
-                .map_err(|e| DbError::ConversionError(e.to_string()))?;
-
-            let result = conn
-                .exec_map(&stmt, params, |row: mysql::Row| {
-                    let mut values = Vec::new();
-                    let columns = row.columns();
-
-                    for (i, _column) in columns.iter().enumerate() {
-                        let value = row.get(i).ok_or_else(|| {
-                            DbError::QueryError("Missing column value".to_string().into())
-                        })?;
-                        values.push(Self::convert_mysql_to_value(value)?);
-                    }
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self
+            .execute_with_connection(|conn| {
+                let params: Vec<mysql::Value> =
+                    params.iter().map(MySqlDatabase::value_to_mysql).collect();
+                let stmt = conn
+                    .prep(query)
+                    .map_err(|e| DbError::ConversionError(e.to_string()))?;
+
+                let result = conn
+                    .exec_map(&stmt, params, |row: mysql::Row| {
+                        let mut values = Vec::new();
+                        let columns = row.columns();
+
+                        for (i, column) in columns.iter().enumerate() {
+                            let value = row.get(i).ok_or_else(|| {
+                                DbError::QueryError("Missing column value".to_string().into())
+                            })?;
+                            values.push(Self::narrow_integer(
+                                Self::convert_mysql_to_value(value)?,
+                                column,
+                            ));
+                        }
 
-                    Ok::<Row, DbError>(Row {
-                        columns: columns.iter().map(|c| c.name_str().to_string()).collect(),
-                        values,
+                        Ok::<Row, DbError>(Row {
+                            columns: columns.iter().map(|c| c.name_str().to_string()).collect(),
+                            values,
+                        })
                     })
-                })
-                .map_err(|e| DbError::QueryError(e.to_string().into()))?;
+                    .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))?;
 
-            let mut rows = Vec::new();
-            for row_result in result {
-                rows.push(row_result?);
-            }
-            Ok(rows)
-        })
-        .await
+                let mut rows = Vec::new();
+                for row_result in result {
+                    rows.push(row_result?);
+                }
+                Ok(rows)
+            })
+            .await;
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     async fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
         let mut rows = self.query(query, params).await?;
         Ok(rows.pop())
     }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), DbError> {
+        let start = Instant::now();
+        let result = self
+            .execute_with_connection(|conn| {
+                conn.query_drop(sql)
+                    .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))
+            })
+            .await;
+        observe_query(&self.observer, self.slow_query_threshold, sql, 0, start);
+        result
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        let Ok(pool) = self.pool.current() else {
+            return PoolStatus::default();
+        };
+        let state = pool.state();
+        PoolStatus {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: pool.max_size(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -293,6 +439,7 @@ mod tests {
             password: "root".to_string(),
             database_name: "test".to_string(),
             max_size: 10,
+            ..Default::default()
         };
         MySqlDatabase::connect(config).await.unwrap()
     }
@@ -304,6 +451,27 @@ mod tests {
         assert!(db.ping().await.is_ok());
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_pool_status() {
+        let db = setup_test_db().await;
+        assert!(db.ping_timeout(Duration::from_secs(5)).await.is_ok());
+
+        let status = db.pool_status();
+        assert!(status.connections <= 10);
+        assert!(status.idle_connections <= status.connections);
+        assert!(status.connections <= status.max_size);
+
+        // 取出两个连接后空闲数相应减少
+        let held = vec![
+            db.pool.current().unwrap().get().unwrap(),
+            db.pool.current().unwrap().get().unwrap(),
+        ];
+        let status = db.pool_status();
+        assert_eq!(status.connections - status.idle_connections, 2);
+        drop(held);
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_execute() {
@@ -505,4 +673,33 @@ mod tests {
             panic!("Expected DateTime");
         }
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_unsigned_bigint() {
+        let db = setup_test_db().await;
+        db.execute("DROP TABLE IF EXISTS counters", vec![])
+            .await
+            .unwrap();
+        db.execute(
+            "CREATE TABLE counters (id BIGINT UNSIGNED PRIMARY KEY)",
+            vec![],
+        )
+        .await
+        .unwrap();
+
+        let id = u64::MAX - 1;
+        db.execute("INSERT INTO counters (id) VALUES (?)", vec![id.into()])
+            .await
+            .unwrap();
+        let row = db
+            .query_one("SELECT id FROM counters", vec![])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.values[0], Value::UBigint(id));
+        assert_eq!(row.get::<u64>("id").unwrap(), id);
+
+        db.execute("DROP TABLE counters", vec![]).await.unwrap();
+    }
 }
@@ -1,19 +1,113 @@
 use crate::asyncdatabase::{
-    DatabaseConfig, DbError, QueryErrorKind, RelationalDatabase, Row, Value,
+    DatabaseConfig, DbError, ErrorDetail, PoolStatus, QueryErrorKind, QueryObserver,
+    RelationalDatabase, Row, Value,
 };
+use crate::common::{observe_query, pg_tls, ClosablePool, PgTls, StatementCache};
 use async_trait::async_trait;
-use bb8::Pool;
+use bb8::{ManageConnection, Pool, PooledConnection};
 use bb8_postgres::PostgresConnectionManager;
-use tokio_postgres::{NoTls, Row as TokioRow};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_postgres::{
+    types::{Kind, Type},
+    Client, Row as TokioRow, SimpleQueryMessage, Statement,
+};
+
+// 连接与它自己的预编译语句缓存, 语句只能在创建它的连接上使用
+#[derive(Debug)]
+struct CachedClient {
+    client: Client,
+    statements: StatementCache<Statement>,
+}
+
+impl CachedClient {
+    async fn prepare_cached(&mut self, query: &str) -> Result<Statement, tokio_postgres::Error> {
+        if let Some(stmt) = self.statements.get(query) {
+            return Ok(stmt);
+        }
+        let stmt = self.client.prepare(query).await?;
+        self.statements.insert(query, stmt.clone());
+        Ok(stmt)
+    }
+}
+
+impl Deref for CachedClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+#[derive(Debug)]
+struct CachedConnectionManager {
+    inner: PostgresConnectionManager<PgTls>,
+    statement_cache_size: usize,
+}
+
+impl ManageConnection for CachedConnectionManager {
+    type Connection = CachedClient;
+    type Error = tokio_postgres::Error;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(CachedClient {
+            client: self.inner.connect().await?,
+            statements: StatementCache::new(self.statement_cache_size),
+        })
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        self.inner.is_valid(&mut conn.client).await
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        self.inner.has_broken(&mut conn.client)
+    }
+}
+
+type PooledClient = PooledConnection<'static, CachedConnectionManager>;
+
+// 执行语句的连接: 事务中为事务连接, 否则从连接池取出
+enum Connection<'a> {
+    Transaction(MutexGuard<'a, Option<PooledClient>>),
+    Pooled(Box<PooledClient>),
+}
+
+impl Deref for Connection<'_> {
+    type Target = CachedClient;
+
+    fn deref(&self) -> &CachedClient {
+        match self {
+            Connection::Transaction(guard) => guard.as_ref().expect("transaction connection"),
+            Connection::Pooled(conn) => conn,
+        }
+    }
+}
+
+impl DerefMut for Connection<'_> {
+    fn deref_mut(&mut self) -> &mut CachedClient {
+        match self {
+            Connection::Transaction(guard) => guard.as_mut().expect("transaction connection"),
+            Connection::Pooled(conn) => conn,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct PostgresDatabase {
-    pool: Pool<PostgresConnectionManager<NoTls>>,
+    pool: ClosablePool<Pool<CachedConnectionManager>>,
+    // 事务期间所有语句都在这个连接上执行, 由所有克隆共享, session 另建一个
+    current_transaction: Arc<Mutex<Option<PooledClient>>>,
+    max_size: u32,
+    observer: Option<Arc<dyn QueryObserver>>,
+    slow_query_threshold: Option<Duration>,
 }
 
 impl From<tokio_postgres::Error> for DbError {
     fn from(e: tokio_postgres::Error) -> Self {
-        DbError::ConnectionError(e.to_string())
+        DbError::ConnectionError(ErrorDetail::new(e))
     }
 }
 
@@ -26,170 +120,319 @@ impl RelationalDatabase for PostgresDatabase {
             .collect()
     }
 
+    fn column_type(&self, value: &Value) -> &'static str {
+        match value {
+            Value::Double(_) => "FLOAT8",
+            Value::Bytes(_) => "BYTEA",
+            Value::DateTime(_) => "TIMESTAMPTZ",
+            Value::Interval(_) => "INTERVAL",
+            Value::Int(_) => "INTEGER",
+            Value::Bigint(_) | Value::UBigint(_) => "BIGINT",
+            Value::Float(_) => "REAL",
+            Value::Varchar(_) => "VARCHAR(255)",
+            Value::Boolean(_) => "BOOLEAN",
+            Value::Byte(_) | Value::Smallint(_) | Value::TinyInt(_) => "SMALLINT",
+            Value::Array(values) => match values.first() {
+                Some(Value::Int(_)) => "INTEGER[]",
+                Some(Value::Bigint(_)) => "BIGINT[]",
+                Some(Value::Float(_)) => "REAL[]",
+                Some(Value::Double(_)) => "FLOAT8[]",
+                Some(Value::Boolean(_)) => "BOOLEAN[]",
+                Some(Value::DateTime(_)) => "TIMESTAMPTZ[]",
+                _ => "TEXT[]",
+            },
+            Value::Null | Value::Text(_) | Value::Table(_) => "TEXT",
+        }
+    }
+
+    fn ilike_clause(&self, column: &str) -> String {
+        format!("{} ILIKE ?", column)
+    }
+
     async fn connect(config: DatabaseConfig) -> Result<Self, DbError> {
         let manager = PostgresConnectionManager::new_from_stringlike(
             format!(
-                "host={} port={} user={} password={} dbname={}",
-                config.host, config.port, config.username, config.password, config.database_name
+                "host={} port={} user={} password={} dbname={} sslmode={}",
+                config.host,
+                config.port,
+                config.username,
+                config.password,
+                config.database_name,
+                if config.tls { "require" } else { "disable" }
             ),
-            NoTls,
+            pg_tls(&config)?,
         )?;
+        let manager = CachedConnectionManager {
+            inner: manager,
+            statement_cache_size: config.statement_cache_size,
+        };
 
         let pool = Pool::builder()
             .max_size(config.max_size) // 使用配置中的 max_size
+            .test_on_check_out(config.test_on_check_out)
+            .min_idle(config.min_idle)
             .build(manager)
             .await
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+            .map_err(|e| DbError::PoolError(ErrorDetail::new(e)))?;
 
-        Ok(PostgresDatabase { pool })
+        Ok(PostgresDatabase {
+            pool: ClosablePool::new(pool, &config),
+            current_transaction: Arc::new(Mutex::new(None)),
+            max_size: config.max_size,
+            observer: config.observer,
+            slow_query_threshold: config.slow_query_threshold,
+        })
     }
 
+    // 之后的操作返回 DbError::Closed, 空闲连接随池一起释放
     async fn close(&self) -> Result<(), DbError> {
-        // bb8 的 Pool 会在 Drop 时自动关闭连接，无需手动关闭
+        self.pool.close();
+        self.current_transaction.lock().await.take();
         Ok(())
     }
 
     async fn ping(&self) -> Result<(), DbError> {
-        let conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let conn = self.pool.get_owned(DbError::PoolError).await?;
         conn.simple_query("")
             .await
             .map(|_| ())
-            .map_err(|e| DbError::ConnectionError(e.to_string()))
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))
     }
 
     async fn begin_transaction(&self) -> Result<(), DbError> {
-        let conn = self
-            .pool
-            .get()
+        let conn = self.pool.get_owned(DbError::PoolError).await?;
+        conn.batch_execute("BEGIN")
             .await
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
-        conn.execute("BEGIN", &[])
-            .await
-            .map(|_| ())
-            .map_err(|e| DbError::TransactionError(e.to_string()))
+            .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
+        *self.current_transaction.lock().await = Some(conn);
+        Ok(())
     }
 
     async fn commit(&self) -> Result<(), DbError> {
-        let conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
-        conn.execute("COMMIT", &[])
-            .await
-            .map(|_| ())
-            .map_err(|e| DbError::TransactionError(e.to_string()))
+        if let Some(conn) = self.current_transaction.lock().await.take() {
+            conn.batch_execute("COMMIT")
+                .await
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
+        }
+        Ok(())
     }
 
     async fn rollback(&self) -> Result<(), DbError> {
-        let conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
-        conn.execute("ROLLBACK", &[])
-            .await
-            .map(|_| ())
-            .map_err(|e| DbError::TransactionError(e.to_string()))
+        if let Some(conn) = self.current_transaction.lock().await.take() {
+            conn.batch_execute("ROLLBACK")
+                .await
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
+        }
+        Ok(())
+    }
+
+    fn session(&self) -> Self {
+        Self {
+            current_transaction: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
     }
 
     async fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
-        let conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let start = Instant::now();
+        let mut conn = self.connection().await?;
 
+        let stmt = conn.prepare_cached(query).await?;
         let params = Self::params_to_postgres(&params);
-
-        let stmt = conn.prepare(&query).await?;
-        conn.execute(&stmt, &params).await.map_err(|e| {
+        let result = conn.execute(&stmt, &params).await.map_err(|e| {
             if let Some(db_err) = e.as_db_error() {
                 match db_err.code().code() {
                     "23503" => {
                         // 外键约束错误
                         DbError::QueryError(QueryErrorKind::ForeignKeyViolation(
-                            db_err.message().to_string(),
+                            ErrorDetail::with_source(db_err.message().to_string(), e),
                         ))
                     }
                     "23505" => {
                         // 唯一约束错误（包括主键冲突）
                         DbError::QueryError(QueryErrorKind::UniqueViolation(
-                            db_err.message().to_string(),
+                            ErrorDetail::with_source(db_err.message().to_string(), e),
                         ))
                     }
                     "23502" => {
                         // 非空约束错误
                         DbError::QueryError(QueryErrorKind::NotNullViolation(
-                            db_err.message().to_string(),
+                            ErrorDetail::with_source(db_err.message().to_string(), e),
                         ))
                     }
                     "23514" => {
                         // 检查约束错误
                         DbError::QueryError(QueryErrorKind::CheckViolation(
-                            db_err.message().to_string(),
+                            ErrorDetail::with_source(db_err.message().to_string(), e),
                         ))
                     }
                     "23P01" => {
                         // 排他约束错误
                         DbError::QueryError(QueryErrorKind::ExclusionViolation(
-                            db_err.message().to_string(),
+                            ErrorDetail::with_source(db_err.message().to_string(), e),
                         ))
                     }
                     _ => {
                         // 其他数据库错误
-                        DbError::QueryError(QueryErrorKind::Other(format!(
-                            "code: {}, message: {}",
-                            db_err.code().code(),
-                            db_err.message().to_string()
+                        DbError::QueryError(QueryErrorKind::Other(ErrorDetail::with_source(
+                            format!(
+                                "code: {}, message: {}",
+                                db_err.code().code(),
+                                db_err.message().to_string()
+                            ),
+                            e,
                         )))
                     }
                 }
             } else {
                 // 如果不是数据库错误，比如 IO 错误等
-                DbError::QueryError(QueryErrorKind::Other(format!("message: {}", e.to_string())))
+                DbError::QueryError(QueryErrorKind::Other(ErrorDetail::with_source(
+                    format!("message: {}", e.to_string()),
+                    e,
+                )))
             }
-        })
+        });
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            params.len(),
+            start,
+        );
+        if result.is_err() {
+            conn.statements.remove(query);
+        }
+        result
     }
 
     async fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
-        let conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let start = Instant::now();
+        let mut conn = self.connection().await?;
+        let stmt = conn.prepare_cached(query).await?;
         let params = Self::params_to_postgres(&params);
-        let stmt = conn.prepare(&query).await?;
         let rows = conn
             .query(&stmt, &params[..])
             .await
-            .map_err(|e| DbError::QueryError(e.to_string().into()))?;
-        Ok(Self::convert_rows(rows))
+            .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()));
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            params.len(),
+            start,
+        );
+        if rows.is_err() {
+            conn.statements.remove(query);
+        }
+        Ok(Self::convert_rows(rows?))
     }
     async fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
-        let conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let start = Instant::now();
+        let mut conn = self.connection().await?;
+        let stmt = conn.prepare_cached(query).await?;
         let params = Self::params_to_postgres(&params);
-        let stmt = conn.prepare(&query).await?;
 
         let row = conn
             .query_opt(&stmt, &params[..])
             .await
-            .map_err(|e| DbError::QueryError(e.to_string().into()))?;
-        Ok(row
+            .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()));
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            params.len(),
+            start,
+        );
+        if row.is_err() {
+            conn.statements.remove(query);
+        }
+        Ok(row?
             .map(|r| Self::convert_rows(vec![r]))
             .and_then(|mut v| v.pop()))
     }
+
+    async fn execute_raw(&self, sql: &str) -> Result<u64, DbError> {
+        let start = Instant::now();
+        let conn = self.connection().await?;
+        let messages = conn
+            .simple_query(sql)
+            .await
+            .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()));
+        observe_query(&self.observer, self.slow_query_threshold, sql, 0, start);
+        Ok(Self::simple_rows(messages?).1)
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), DbError> {
+        let start = Instant::now();
+        let conn = self.connection().await?;
+        let result = conn
+            .batch_execute(sql)
+            .await
+            .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()));
+        observe_query(&self.observer, self.slow_query_threshold, sql, 0, start);
+        result
+    }
+
+    async fn query_raw(&self, sql: &str) -> Result<Vec<Row>, DbError> {
+        let start = Instant::now();
+        let conn = self.connection().await?;
+        let messages = conn
+            .simple_query(sql)
+            .await
+            .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()));
+        observe_query(&self.observer, self.slow_query_threshold, sql, 0, start);
+        Ok(Self::simple_rows(messages?).0)
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        let Ok(pool) = self.pool.current() else {
+            return PoolStatus::default();
+        };
+        let state = pool.state();
+        PoolStatus {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: self.max_size,
+        }
+    }
 }
 
 impl PostgresDatabase {
+    // 事务中返回事务连接, 其他语句在事务结束前等待; 否则从连接池取一个连接
+    async fn connection(&self) -> Result<Connection<'_>, DbError> {
+        let guard = self.current_transaction.lock().await;
+        if guard.is_some() {
+            return Ok(Connection::Transaction(guard));
+        }
+        drop(guard);
+        Ok(Connection::Pooled(Box::new(
+            self.pool.get_owned(DbError::PoolError).await?,
+        )))
+    }
+
+    // 文本协议的结果, 返回 (行, 最后一条语句影响的行数)
+    fn simple_rows(messages: Vec<SimpleQueryMessage>) -> (Vec<Row>, u64) {
+        let mut rows = Vec::new();
+        let mut affected = 0;
+        for message in messages {
+            match message {
+                SimpleQueryMessage::Row(row) => rows.push(Row {
+                    columns: row.columns().iter().map(|c| c.name().to_string()).collect(),
+                    values: (0..row.len())
+                        .map(|i| {
+                            row.get(i)
+                                .map_or(Value::Null, |v| Value::Text(v.to_string()))
+                        })
+                        .collect(),
+                }),
+                SimpleQueryMessage::CommandComplete(n) => affected = n,
+                _ => {}
+            }
+        }
+        (rows, affected)
+    }
+
+    // NULL 列统一转换为 Value::Null
     fn convert_rows(rows: Vec<TokioRow>) -> Vec<Row> {
         let mut result_rows = Vec::new();
         for row in rows {
@@ -199,56 +442,42 @@ impl PostgresDatabase {
                 columns.push(column.name().to_string());
                 // 根据列的类型进行值的转换
                 let value = match column.type_() {
-                    &tokio_postgres::types::Type::INT4 => Value::Int(row.get(i)),
-                    &tokio_postgres::types::Type::INT8 => {
-                        let v: Option<i64> = row.get(i);
-
-                        Value::Bigint(v.unwrap_or(0))
-                    }
-                    &tokio_postgres::types::Type::TEXT => {
-                        let v: Option<String> = row.get(i);
-                        // Value::Text(v.unwrap_or("1900-01-01T00:00:00.000000000Z".to_string()))
-                        Value::Text(v.unwrap_or("".to_string()))
+                    &Type::INT2 => row.get::<_, Option<i16>>(i).map(Value::Smallint),
+                    &Type::INT4 => row.get::<_, Option<i32>>(i).map(Value::Int),
+                    &Type::INT8 => row.get::<_, Option<i64>>(i).map(Value::Bigint),
+                    &Type::TEXT | &Type::VARCHAR | &Type::BPCHAR => {
+                        row.get::<_, Option<String>>(i).map(Value::Text)
                     }
-                    &tokio_postgres::types::Type::VARCHAR => Value::Text(row.get(i)),
-                    &tokio_postgres::types::Type::BPCHAR => Value::Text(row.get(i)),
-                    &tokio_postgres::types::Type::FLOAT4 => Value::Float(row.get(i)),
-                    &tokio_postgres::types::Type::FLOAT8 => Value::Double(row.get(i)),
-                    &tokio_postgres::types::Type::BOOL => Value::Boolean(row.get(i)),
-                    &tokio_postgres::types::Type::BYTEA => Value::Bytes(row.get(i)),
-                    &tokio_postgres::types::Type::TIMESTAMPTZ => {
-                        Value::DateTime(row.get(i)) // 对应 Rust 中的 chrono::DateTime<chrono::Utc>
+                    &Type::FLOAT4 => row.get::<_, Option<f32>>(i).map(Value::Float),
+                    &Type::FLOAT8 => row.get::<_, Option<f64>>(i).map(Value::Double),
+                    &Type::BOOL => row.get::<_, Option<bool>>(i).map(Value::Boolean),
+                    &Type::BYTEA => row.get::<_, Option<Vec<u8>>>(i).map(Value::Bytes),
+                    &Type::TIMESTAMPTZ => {
+                        // 对应 Rust 中的 chrono::DateTime<chrono::Utc>
+                        row.get::<_, Option<chrono::DateTime<chrono::Utc>>>(i)
+                            .map(Value::DateTime)
                     }
-                    &tokio_postgres::types::Type::VOID => Value::Null,
+                    &Type::INTERVAL => row.get::<_, Option<Value>>(i),
+                    &Type::VOID => None,
+                    // 数组元素按数组列的元素类型转换
+                    ty if matches!(ty.kind(), Kind::Array(_)) => row.get::<_, Option<Value>>(i),
                     // ... 其他类型的处理
                     _ => {
                         unimplemented!()
                     }
                 };
-                values.push(value);
+                values.push(value.unwrap_or(Value::Null));
             }
             result_rows.push(Row { columns, values });
         }
         result_rows
     }
 
-    fn params_to_postgres(params: &Vec<Value>) -> Vec<&(dyn tokio_postgres::types::ToSql + Sync)> {
+    fn params_to_postgres(params: &[Value]) -> Vec<&(dyn tokio_postgres::types::ToSql + Sync)> {
+        // 统一按 Value 的 ToSql 编码, 类型不匹配时返回错误而不是 panic
         params
             .iter()
-            .map(|v| match v {
-                Value::Int(i) => i as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::Bigint(i) => i as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::Text(s) => s as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::Varchar(s) => s as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::Float(f) => f as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::Double(d) => d as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::Boolean(b) => b as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::Bytes(by) => by as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::DateTime(dt) => dt as &(dyn tokio_postgres::types::ToSql + Sync),
-                Value::Null => &None::<&str> as &(dyn tokio_postgres::types::ToSql + Sync),
-                // ... 其他 Value 类型的处理
-                _ => unimplemented!(),
-            })
+            .map(|v| v as &(dyn tokio_postgres::types::ToSql + Sync))
             .collect::<Vec<_>>()
     }
 }
@@ -258,6 +487,7 @@ mod tests {
     use super::*;
     use chrono::Utc;
     use serial_test::serial;
+    use std::time::Duration;
 
     async fn setup_test_db() -> PostgresDatabase {
         let config = DatabaseConfig {
@@ -267,6 +497,7 @@ mod tests {
             password: "root".to_string(),
             database_name: "test".to_string(),
             max_size: 10,
+            ..Default::default()
         };
         PostgresDatabase::connect(config).await.unwrap()
     }
@@ -278,6 +509,322 @@ mod tests {
         assert!(db.ping().await.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_ping_timeout_dead_port() {
+        // 端口 1 上没有服务, 连接会一直重试直到超时
+        let config = DatabaseConfig {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+            username: "root".to_string(),
+            password: "root".to_string(),
+            database_name: "test".to_string(),
+            max_size: 1,
+            ..Default::default()
+        };
+        let db = PostgresDatabase::connect(config).await.unwrap();
+
+        let start = std::time::Instant::now();
+        let result = db.ping_timeout(Duration::from_millis(200)).await;
+        assert!(matches!(result, Err(DbError::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    fn config_for_port(port: u16) -> DatabaseConfig {
+        DatabaseConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: "root".to_string(),
+            password: "root".to_string(),
+            database_name: "test".to_string(),
+            max_size: 1,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_connect_with_retry() {
+        let db = PostgresDatabase::connect_with_retry(
+            config_for_port(5432),
+            3,
+            Duration::from_millis(50),
+        )
+        .await
+        .unwrap();
+        let row = db
+            .query_one("SELECT 1 AS one", vec![])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.get::<i32>("one").unwrap(), 1);
+
+        // 不可路由的地址一直不可用, 等待 50ms, 100ms 后返回最后一次的错误
+        let config = DatabaseConfig {
+            host: "10.255.255.1".to_string(),
+            ..config_for_port(5432)
+        };
+        let start = std::time::Instant::now();
+        let result = PostgresDatabase::connect_with_retry_timeout(
+            config,
+            3,
+            Duration::from_millis(50),
+            Duration::from_millis(200),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(DbError::Timeout(_) | DbError::ConnectionError(_) | DbError::PoolError(_))
+        ));
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reconnect_after_server_kill() {
+        let db = PostgresDatabase::connect(config_for_port(5432))
+            .await
+            .unwrap();
+        let backend_pid = |row: Option<Row>| row.unwrap().get::<i32>("pid").unwrap();
+        let pid = backend_pid(
+            db.query_one("SELECT pg_backend_pid() AS pid", vec![])
+                .await
+                .unwrap(),
+        );
+
+        // 模拟服务端断开空闲连接
+        let admin = setup_test_db().await;
+        admin
+            .query("SELECT pg_terminate_backend($1)", vec![Value::Int(pid)])
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // 失效的连接在取出时被丢弃, 语句在新连接上执行
+        let row = db
+            .query_one("SELECT pg_backend_pid() AS pid", vec![])
+            .await
+            .unwrap();
+        assert_ne!(backend_pid(row), pid);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_close() {
+        let db = setup_test_db().await;
+        let clone = db.clone();
+        db.ping().await.unwrap();
+
+        db.close().await.unwrap();
+        // 克隆共享同一个池, 关闭后不会重新建立连接
+        let result = clone.query("SELECT 1", vec![]).await;
+        assert!(matches!(result, Err(DbError::Closed)));
+        assert!(matches!(
+            db.execute("SELECT 1", vec![]).await,
+            Err(DbError::Closed)
+        ));
+        assert_eq!(db.pool_status(), PoolStatus::default());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_pool_status() {
+        let db = setup_test_db().await;
+        assert!(db.ping_timeout(Duration::from_secs(5)).await.is_ok());
+
+        let status = db.pool_status();
+        assert!(status.connections <= 10);
+        assert!(status.idle_connections <= status.connections);
+        assert_eq!(status.max_size, 10);
+
+        // 取出两个连接后空闲数相应减少
+        let first = db.pool.current().unwrap().get_owned().await.unwrap();
+        let second = db.pool.current().unwrap().get_owned().await.unwrap();
+        let status = db.pool_status();
+        assert_eq!(status.connections - status.idle_connections, 2);
+        drop((first, second));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_min_idle_warmup() {
+        let mut config = config_for_port(5432);
+        config.max_size = 5;
+        config.min_idle = Some(3);
+        let db = PostgresDatabase::connect(config).await.unwrap();
+
+        // connect 返回时连接已经建立, 不需要先执行语句
+        let status = db.pool_status();
+        assert!(status.idle_connections >= 3);
+        assert_eq!(status.connections, status.idle_connections);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_statement_cache() {
+        // 单连接, 方便检查同一个连接上的缓存
+        let db = PostgresDatabase::connect(DatabaseConfig {
+            host: "localhost".to_string(),
+            port: 5432,
+            username: "root".to_string(),
+            password: "root".to_string(),
+            database_name: "test".to_string(),
+            max_size: 1,
+            statement_cache_size: 2,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        for i in 0..5 {
+            let row = db
+                .query_one("SELECT $1::INT8 AS n", vec![Value::Bigint(i)])
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(row.values[0], Value::Bigint(i));
+        }
+        assert_eq!(
+            db.pool
+                .current()
+                .unwrap()
+                .get_owned()
+                .await
+                .unwrap()
+                .statements
+                .prepared,
+            1
+        );
+
+        // 超出容量后最久未使用的语句被淘汰, 再次执行时重新预编译
+        db.query("SELECT 1", vec![]).await.unwrap();
+        db.query("SELECT 2", vec![]).await.unwrap();
+        db.query("SELECT $1::INT8 AS n", vec![Value::Bigint(1)])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.pool
+                .current()
+                .unwrap()
+                .get_owned()
+                .await
+                .unwrap()
+                .statements
+                .prepared,
+            4
+        );
+
+        // 表结构变化后缓存的语句失效, 失败一次后重新预编译
+        db.execute("DROP TABLE IF EXISTS cache_items", vec![])
+            .await
+            .unwrap();
+        db.execute("CREATE TABLE cache_items (id INT8)", vec![])
+            .await
+            .unwrap();
+        db.query("SELECT * FROM cache_items", vec![]).await.unwrap();
+        db.execute("ALTER TABLE cache_items ADD COLUMN name TEXT", vec![])
+            .await
+            .unwrap();
+        if db.query("SELECT * FROM cache_items", vec![]).await.is_err() {
+            db.query("SELECT * FROM cache_items", vec![]).await.unwrap();
+        }
+        db.execute("DROP TABLE cache_items", vec![]).await.unwrap();
+    }
+
+    // 记录被判定为慢查询的语句
+    #[derive(Default)]
+    struct SlowQueries(std::sync::Mutex<Vec<String>>);
+
+    impl QueryObserver for SlowQueries {
+        fn on_query(&self, _sql: &str, _param_count: usize, _elapsed: Duration) {}
+
+        fn on_slow_query(&self, sql: &str, _param_count: usize, _elapsed: Duration) {
+            self.0.lock().unwrap().push(sql.to_string());
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_slow_query_threshold() {
+        let slow_queries = Arc::new(SlowQueries::default());
+        let db = PostgresDatabase::connect(DatabaseConfig {
+            host: "localhost".to_string(),
+            port: 5432,
+            username: "root".to_string(),
+            password: "root".to_string(),
+            database_name: "test".to_string(),
+            max_size: 1,
+            observer: Some(slow_queries.clone()),
+            slow_query_threshold: Some(Duration::from_millis(100)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        db.query("SELECT 1", vec![]).await.unwrap();
+        assert!(slow_queries.0.lock().unwrap().is_empty());
+
+        db.query("SELECT pg_sleep(0.2)", vec![]).await.unwrap();
+        assert_eq!(
+            *slow_queries.0.lock().unwrap(),
+            vec!["SELECT pg_sleep(0.2)".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_query_raw() {
+        let db = setup_test_db().await;
+        let rows = db
+            .query_raw("SELECT generate_series(1, 3) AS n, NULL::INTERVAL AS gap")
+            .await
+            .unwrap();
+        let values: Vec<&Value> = rows.iter().map(|row| &row.values[0]).collect();
+        assert_eq!(
+            values,
+            vec![
+                &Value::Text("1".to_string()),
+                &Value::Text("2".to_string()),
+                &Value::Text("3".to_string())
+            ]
+        );
+        assert_eq!(rows[0].columns, vec!["n", "gap"]);
+        assert_eq!(rows[0].values[1], Value::Null);
+
+        // 多条语句一起执行, 返回最后一条影响的行数
+        let affected = db
+            .execute_raw(
+                "DROP TABLE IF EXISTS raw_items;
+                 CREATE TABLE raw_items (id INT8);
+                 INSERT INTO raw_items SELECT generate_series(1, 4)",
+            )
+            .await
+            .unwrap();
+        assert_eq!(affected, 4);
+        db.execute_raw("DROP TABLE raw_items").await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_batch_execute() {
+        let db = setup_test_db().await;
+        db.batch_execute(
+            "DROP TABLE IF EXISTS batch_items;
+             CREATE TABLE batch_items (id INT8 PRIMARY KEY, name TEXT);
+             INSERT INTO batch_items VALUES (1, 'a;b')",
+        )
+        .await
+        .unwrap();
+
+        let rows = db
+            .query("SELECT name FROM batch_items", vec![])
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<String>("name").unwrap(), "a;b");
+        db.batch_execute("DROP TABLE batch_items").await.unwrap();
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_execute() {
@@ -480,6 +1027,124 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_null_params_of_any_type() {
+        let db = setup_test_db().await;
+        db.execute("DROP TABLE IF EXISTS typed_nulls", vec![])
+            .await
+            .unwrap();
+        db.execute(
+            "CREATE TABLE typed_nulls (id INT4, due DATE, price NUMERIC, elapsed INTERVAL, extra JSONB)",
+            vec![],
+        )
+        .await
+        .unwrap();
+        db.execute(
+            "INSERT INTO typed_nulls (id, due, price, elapsed, extra) VALUES ($1, $2, $3, $4, $5)",
+            vec![Value::Null; 5],
+        )
+        .await
+        .unwrap();
+
+        let row = db
+            .query_one(
+                "SELECT COUNT(*) FROM typed_nulls WHERE id IS NULL AND due IS NULL \
+                 AND price IS NULL AND elapsed IS NULL AND extra IS NULL",
+                vec![],
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.get_by_index::<i64>(0).unwrap(), 1);
+        db.execute("DROP TABLE typed_nulls", vec![]).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_params_of_wrong_type_return_error() {
+        let db = setup_test_db().await;
+        db.execute("DROP TABLE IF EXISTS typed_params", vec![])
+            .await
+            .unwrap();
+        db.execute("CREATE TABLE typed_params (small INT2, n INT4)", vec![])
+            .await
+            .unwrap();
+        db.execute(
+            "INSERT INTO typed_params (small, n) VALUES ($1, $2)",
+            vec![Value::Byte(7), Value::Int(1)],
+        )
+        .await
+        .unwrap();
+
+        // 类型不匹配与无法绑定的值都返回错误, 不会 panic
+        let result = db
+            .execute(
+                "INSERT INTO typed_params (small, n) VALUES ($1, $2)",
+                vec![Value::Byte(1), Value::Text("one".to_string())],
+            )
+            .await;
+        assert!(matches!(result, Err(DbError::QueryError(_))));
+        let result = db
+            .execute(
+                "INSERT INTO typed_params (small, n) VALUES ($1, $2)",
+                vec![Value::Table(Vec::new()), Value::Int(2)],
+            )
+            .await;
+        assert!(matches!(result, Err(DbError::QueryError(_))));
+
+        let rows = db
+            .query("SELECT small FROM typed_params", vec![])
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].values, vec![Value::Smallint(7)]);
+        db.execute("DROP TABLE typed_params", vec![]).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_null_columns() {
+        let db = setup_test_db().await;
+        db.execute("DROP TABLE IF EXISTS nullable_items", vec![])
+            .await
+            .unwrap();
+        db.execute(
+            "CREATE TABLE nullable_items (id INT8 PRIMARY KEY, note TEXT, qty INT8)",
+            vec![],
+        )
+        .await
+        .unwrap();
+        db.execute(
+            "INSERT INTO nullable_items (id, note, qty) VALUES ($1, $2, $3), ($4, $5, $6)",
+            vec![
+                Value::Bigint(1),
+                Value::Null,
+                Value::Null,
+                Value::Bigint(2),
+                Value::Text("".to_string()),
+                Value::Bigint(0),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let rows = db
+            .query("SELECT note, qty FROM nullable_items ORDER BY id", vec![])
+            .await
+            .unwrap();
+        // NULL 与空字符串, 0 区分开
+        assert_eq!(rows[0].values, vec![Value::Null, Value::Null]);
+        assert_eq!(
+            rows[1].values,
+            vec![Value::Text("".to_string()), Value::Bigint(0)]
+        );
+
+        db.execute("DROP TABLE nullable_items", vec![])
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_execute_foreign_key_violation() {
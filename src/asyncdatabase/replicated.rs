@@ -0,0 +1,241 @@
+use crate::asyncdatabase::{
+    is_unavailable, ConflictAction, DatabaseConfig, DbError, PoolStatus, ReadPolicy,
+    RelationalDatabase, Row, Value,
+};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 读写分离: `query`/`query_one` 走从库, `execute` 和事务走主库
+///
+/// 多个从库按 ReadPolicy 轮询. 没有配置从库, 策略为 PrimaryOnly 或者正处于事务中时,
+/// 读请求也走主库
+///
+/// 从库连接失败 (连接错误, 连接池错误, 超时) 时, 本次读请求改走主库;
+/// SQL 本身的错误不会重试, 直接返回. connect 时连不上的从库被跳过, ping 只要求主库可用
+#[derive(Debug, Clone)]
+pub struct ReplicatedDatabase<D: RelationalDatabase> {
+    primary: D,
+    replicas: Vec<D>,
+    read_policy: ReadPolicy,
+    next_replica: Arc<AtomicUsize>,
+    in_transaction: Arc<AtomicBool>,
+}
+
+impl<D: RelationalDatabase> ReplicatedDatabase<D> {
+    pub fn new(primary: D, replica: Option<D>) -> Self {
+        Self::with_replicas(
+            primary,
+            replica.into_iter().collect(),
+            ReadPolicy::default(),
+        )
+    }
+
+    pub fn with_replicas(primary: D, replicas: Vec<D>, read_policy: ReadPolicy) -> Self {
+        Self {
+            primary,
+            replicas,
+            read_policy,
+            next_replica: Arc::new(AtomicUsize::new(0)),
+            in_transaction: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn primary(&self) -> &D {
+        &self.primary
+    }
+
+    pub fn replica(&self) -> Option<&D> {
+        self.replicas.first()
+    }
+
+    pub fn replicas(&self) -> &[D] {
+        &self.replicas
+    }
+
+    fn next_replica(&self) -> Option<&D> {
+        if self.replicas.is_empty() {
+            return None;
+        }
+        let index = self.next_replica.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        Some(&self.replicas[index])
+    }
+
+    fn reader(&self) -> Option<&D> {
+        if self.read_policy == ReadPolicy::PrimaryOnly || self.in_transaction.load(Ordering::SeqCst)
+        {
+            return None;
+        }
+        self.next_replica()
+    }
+
+    async fn query_on(
+        &self,
+        replica: Option<&D>,
+        query: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<Row>, DbError> {
+        let Some(replica) = replica else {
+            return self.primary.query(query, params).await;
+        };
+        match replica.query(query, params.clone()).await {
+            Err(e) if is_unavailable(&e) => self.primary.query(query, params).await,
+            result => result,
+        }
+    }
+
+    /// 明确要求从库读取, 不受 ReadPolicy 和事务影响; 没有从库时走主库
+    pub async fn query_replica(
+        &self,
+        query: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<Row>, DbError> {
+        self.query_on(self.next_replica(), query, params).await
+    }
+}
+
+#[async_trait]
+impl<D: RelationalDatabase> RelationalDatabase for ReplicatedDatabase<D> {
+    fn placeholders(&self, keys: &[String]) -> Vec<String> {
+        self.primary.placeholders(keys)
+    }
+
+    fn column_type(&self, value: &Value) -> &'static str {
+        self.primary.column_type(value)
+    }
+
+    fn on_conflict_clause(&self, columns: &[&str], action: &ConflictAction<'_>) -> String {
+        self.primary.on_conflict_clause(columns, action)
+    }
+
+    fn ilike_clause(&self, column: &str) -> String {
+        self.primary.ilike_clause(column)
+    }
+
+    fn truncate_statement(&self, table: &str) -> String {
+        self.primary.truncate_statement(table)
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        self.primary.quote_identifier(name)
+    }
+
+    async fn connect(mut config: DatabaseConfig) -> Result<Self, DbError> {
+        let mut replica_configs = Vec::new();
+        if let Some(replica_config) = config.replica_config.take() {
+            replica_configs.push(*replica_config);
+        }
+        for (host, port) in std::mem::take(&mut config.replica_hosts) {
+            replica_configs.push(config.with_host(&host, port));
+        }
+        let read_policy = config.read_policy;
+        let primary = D::connect(config).await?;
+
+        // 从库不可用时跳过, 读请求由其余从库或主库承担
+        let mut replicas = Vec::new();
+        for replica_config in replica_configs {
+            let host = format!("{}:{}", replica_config.host, replica_config.port);
+            match D::connect(replica_config).await {
+                Ok(replica) => replicas.push(replica),
+                Err(e) if is_unavailable(&e) => {
+                    log::warn!("skipping unavailable replica {}: {}", host, e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Self::with_replicas(primary, replicas, read_policy))
+    }
+
+    async fn close(&self) -> Result<(), DbError> {
+        for replica in &self.replicas {
+            replica.close().await?;
+        }
+        self.primary.close().await
+    }
+
+    // 从库失败只记录日志, 读请求会自动改走主库
+    async fn ping(&self) -> Result<(), DbError> {
+        self.primary.ping().await?;
+        for (index, replica) in self.replicas.iter().enumerate() {
+            if let Err(e) = replica.ping().await {
+                log::warn!("replica {} is unhealthy: {}", index, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
+        self.primary.ping_timeout(timeout).await?;
+        for (index, replica) in self.replicas.iter().enumerate() {
+            if let Err(e) = replica.ping_timeout(timeout).await {
+                log::warn!("replica {} is unhealthy: {}", index, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn begin_transaction(&self) -> Result<(), DbError> {
+        self.primary.begin_transaction().await?;
+        self.in_transaction.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<(), DbError> {
+        self.in_transaction.store(false, Ordering::SeqCst);
+        self.primary.commit().await
+    }
+
+    async fn rollback(&self) -> Result<(), DbError> {
+        self.in_transaction.store(false, Ordering::SeqCst);
+        self.primary.rollback().await
+    }
+
+    fn session(&self) -> Self {
+        Self {
+            primary: self.primary.session(),
+            in_transaction: Arc::new(AtomicBool::new(false)),
+            ..self.clone()
+        }
+    }
+
+    async fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
+        self.primary.execute(query, params).await
+    }
+
+    async fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
+        self.query_on(self.reader(), query, params).await
+    }
+
+    async fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
+        let Some(replica) = self.reader() else {
+            return self.primary.query_one(query, params).await;
+        };
+        match replica.query_one(query, params.clone()).await {
+            Err(e) if is_unavailable(&e) => self.primary.query_one(query, params).await,
+            result => result,
+        }
+    }
+
+    async fn execute_raw(&self, sql: &str) -> Result<u64, DbError> {
+        self.primary.execute_raw(sql).await
+    }
+
+    async fn query_raw(&self, sql: &str) -> Result<Vec<Row>, DbError> {
+        let Some(replica) = self.reader() else {
+            return self.primary.query_raw(sql).await;
+        };
+        match replica.query_raw(sql).await {
+            Err(e) if is_unavailable(&e) => self.primary.query_raw(sql).await,
+            result => result,
+        }
+    }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), DbError> {
+        self.primary.batch_execute(sql).await
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        self.primary.pool_status()
+    }
+}
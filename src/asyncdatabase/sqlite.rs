@@ -1,23 +1,46 @@
-use crate::asyncdatabase::{Connection, DatabaseConfig, DbError, RelationalDatabase, Row, Value};
+use crate::asyncdatabase::{
+    Connection, DatabaseConfig, DbError, ErrorDetail, PoolStatus, QueryObserver,
+    RelationalDatabase, Row, Value,
+};
 
+use crate::common::{encode_array, interval_seconds, observe_query, sqlite_path, ClosablePool};
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::ToSql;
+use rusqlite::{OpenFlags, ToSql};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct SqliteDatabase {
-    pool: Arc<Pool<SqliteConnectionManager>>,
+    pool: ClosablePool<Pool<SqliteConnectionManager>>,
     current_transaction: Arc<Mutex<Option<PooledConnection<SqliteConnectionManager>>>>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    slow_query_threshold: Option<Duration>,
 }
 
 impl SqliteDatabase {
     async fn new_pool(
-        path: &str,
-        max_size: u32,
+        config: &DatabaseConfig,
     ) -> Result<Pool<SqliteConnectionManager>, r2d2::Error> {
-        let manager = SqliteConnectionManager::file(path);
-        Pool::builder().max_size(max_size).build(manager)
+        let pragmas: String = config
+            .pragmas
+            .iter()
+            .map(|(name, value)| format!("PRAGMA {} = {};", name, value))
+            .collect();
+        let mut manager = SqliteConnectionManager::file(sqlite_path(&config.database_name));
+        if config.read_only {
+            manager = manager.with_flags(
+                OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_URI
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            );
+        }
+        let manager = manager.with_init(move |conn| conn.execute_batch(&pragmas));
+        Pool::builder()
+            .max_size(config.max_size)
+            .test_on_check_out(config.test_on_check_out)
+            .min_idle(config.min_idle)
+            .build(manager)
     }
 
     fn value_to_sql(value: &Value) -> Box<dyn ToSql> {
@@ -25,12 +48,19 @@ impl SqliteDatabase {
             Value::Null => Box::new(None::<String>),
             Value::Int(i) => Box::new(*i),
             Value::Bigint(i) => Box::new(*i),
+            Value::Smallint(i) => Box::new(*i),
+            Value::TinyInt(i) => Box::new(*i),
+            // 超出 i64 范围时由 rusqlite 报错
+            Value::UBigint(i) => Box::new(*i),
             Value::Float(f) => Box::new(*f),
             Value::Double(f) => Box::new(*f),
             Value::Text(s) => Box::new(s.clone()),
             Value::Boolean(b) => Box::new(*b),
             Value::Bytes(b) => Box::new(b.to_vec()),
+            Value::Array(values) => Box::new(encode_array(values)),
             Value::DateTime(dt) => Box::new(dt.to_rfc3339()),
+            // SQLite 没有时间间隔类型, 存为秒数
+            Value::Interval(d) => Box::new(interval_seconds(d)),
             _ => unimplemented!(),
         }
     }
@@ -54,24 +84,18 @@ impl SqliteDatabase {
         let transaction_guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         let conn = if let Some(ref conn) = *transaction_guard {
             conn
         } else {
-            &self
-                .pool
-                .get()
-                .map_err(|e| DbError::ConnectionError(e.to_string()))?
+            &self.pool.get(DbError::ConnectionError)?
         };
 
         f(conn)
     }
     pub async fn get_connection(&self) -> Result<Connection, DbError> {
-        let _conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let _conn = self.pool.get(DbError::PoolError)?;
         Ok(Connection {})
     }
 
@@ -86,44 +110,67 @@ impl RelationalDatabase for SqliteDatabase {
         let placeholders: Vec<String> = (1..=keys.len()).map(|i| format!("${}", i)).collect();
         placeholders
     }
+    // SQLite 没有 TRUNCATE, 不带条件的 DELETE 会走同样的快速清空
+    fn truncate_statement(&self, table: &str) -> String {
+        format!("DELETE FROM {}", table)
+    }
     async fn connect(config: DatabaseConfig) -> Result<Self, DbError> {
-        let pool = Self::new_pool(&config.database_name, config.max_size)
+        let pool = Self::new_pool(&config)
             .await
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
 
         Ok(SqliteDatabase {
-            pool: Arc::new(pool),
+            pool: ClosablePool::new(pool, &config),
             current_transaction: Arc::new(Mutex::new(None)),
+            observer: config.observer,
+            slow_query_threshold: config.slow_query_threshold,
         })
     }
 
+    // 之后的操作返回 DbError::Closed, 空闲连接随池一起释放, 进行中的事务被丢弃
     async fn close(&self) -> Result<(), DbError> {
+        self.pool.close();
+        self.current_transaction
+            .lock()
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?
+            .take();
         Ok(())
     }
 
     async fn ping(&self) -> Result<(), DbError> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+        let conn = self.pool.get(DbError::ConnectionError)?;
         conn.prepare("SELECT 1")
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
         Ok(())
     }
 
+    // r2d2 取连接和查询都会阻塞线程, 放到阻塞线程池中执行, 整体受 timeout 限制
+    async fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
+        let pool = self.pool.current()?;
+        let ping = tokio::task::spawn_blocking(move || {
+            let conn = pool
+                .get_timeout(timeout)
+                .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?;
+            conn.prepare("SELECT 1")
+                .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
+            Ok(())
+        });
+        tokio::time::timeout(timeout, ping)
+            .await
+            .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?
+    }
+
     async fn begin_transaction(&self) -> Result<(), DbError> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+        let conn = self.pool.get(DbError::TransactionError)?;
 
         conn.execute("BEGIN TRANSACTION", [])
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
 
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
         *guard = Some(conn);
 
         Ok(())
@@ -133,11 +180,11 @@ impl RelationalDatabase for SqliteDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(conn) = guard.take() {
             conn.execute("COMMIT", [])
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
@@ -146,87 +193,141 @@ impl RelationalDatabase for SqliteDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(conn) = guard.take() {
             conn.execute("ROLLBACK", [])
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
 
+    fn session(&self) -> Self {
+        Self {
+            current_transaction: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
     async fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
-        self.execute_with_connection(|conn| {
-            let params: Vec<Box<dyn ToSql>> =
-                params.iter().map(SqliteDatabase::value_to_sql).collect();
-            let mut stmt = conn
-                .prepare(query)
-                .map_err(|e| DbError::ConversionError(e.to_string()))?;
-
-            stmt.execute(rusqlite::params_from_iter(params.iter()))
-                .map(|rows| rows as u64)
-                .map_err(|e| DbError::QueryError(e.to_string().into()))
-        })
-        .await
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self
+            .execute_with_connection(|conn| {
+                let params: Vec<Box<dyn ToSql>> =
+                    params.iter().map(SqliteDatabase::value_to_sql).collect();
+                let mut stmt = conn
+                    .prepare(query)
+                    .map_err(|e| DbError::ConversionError(e.to_string()))?;
+
+                stmt.execute(rusqlite::params_from_iter(params.iter()))
+                    .map(|rows| rows as u64)
+                    .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))
+            })
+            .await;
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     async fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
-        self.execute_with_connection(|conn| {
-            let mut stmt = conn
-                .prepare(query)
-                .map_err(|e| DbError::QueryError(e.to_string().into()))?;
-
-            let column_names: Vec<String> = stmt
-                .column_names()
-                .iter()
-                .map(|&name| name.to_string())
-                .collect();
-
-            let column_count = stmt.column_count();
-
-            let params: Vec<Box<dyn ToSql>> =
-                params.iter().map(SqliteDatabase::value_to_sql).collect();
-
-            let rows = stmt
-                .query_map(rusqlite::params_from_iter(params.iter()), |row| {
-                    let mut values = Vec::new();
-                    for i in 0..column_count {
-                        let value = Self::convert_sql_to_value(row.get_ref(i).map_err(|e| {
-                            rusqlite::Error::FromSqlConversionFailure(
-                                i,
-                                rusqlite::types::Type::Text,
-                                Box::new(e),
-                            )
-                        })?)
-                        .map_err(|e| {
-                            rusqlite::Error::FromSqlConversionFailure(
-                                i,
-                                rusqlite::types::Type::Text,
-                                Box::new(e),
-                            )
-                        })?;
-                        values.push(value);
-                    }
-                    Ok(Row {
-                        columns: column_names.clone(),
-                        values,
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self
+            .execute_with_connection(|conn| {
+                let mut stmt = conn
+                    .prepare(query)
+                    .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))?;
+
+                let column_names: Vec<String> = stmt
+                    .column_names()
+                    .iter()
+                    .map(|&name| name.to_string())
+                    .collect();
+
+                let column_count = stmt.column_count();
+
+                let params: Vec<Box<dyn ToSql>> =
+                    params.iter().map(SqliteDatabase::value_to_sql).collect();
+
+                let rows = stmt
+                    .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                        let mut values = Vec::new();
+                        for i in 0..column_count {
+                            let value =
+                                Self::convert_sql_to_value(row.get_ref(i).map_err(|e| {
+                                    rusqlite::Error::FromSqlConversionFailure(
+                                        i,
+                                        rusqlite::types::Type::Text,
+                                        Box::new(e),
+                                    )
+                                })?)
+                                .map_err(|e| {
+                                    rusqlite::Error::FromSqlConversionFailure(
+                                        i,
+                                        rusqlite::types::Type::Text,
+                                        Box::new(e),
+                                    )
+                                })?;
+                            values.push(value);
+                        }
+                        Ok(Row {
+                            columns: column_names.clone(),
+                            values,
+                        })
                     })
-                })
-                .map_err(|e| DbError::QueryError(e.to_string().into()))?;
-
-            let mut results = Vec::new();
-            for row in rows {
-                results.push(row.map_err(|e| DbError::QueryError(e.to_string().into()))?);
-            }
-            Ok(results)
-        })
-        .await
+                    .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))?;
+
+                let mut results = Vec::new();
+                for row in rows {
+                    results.push(row.map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))?);
+                }
+                Ok(results)
+            })
+            .await;
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     async fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
         let mut rows = self.query(query, params).await?;
         Ok(rows.pop())
     }
+
+    async fn batch_execute(&self, sql: &str) -> Result<(), DbError> {
+        let start = Instant::now();
+        let result = self
+            .execute_with_connection(|conn| {
+                conn.execute_batch(sql)
+                    .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))
+            })
+            .await;
+        observe_query(&self.observer, self.slow_query_threshold, sql, 0, start);
+        result
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        let Ok(pool) = self.pool.current() else {
+            return PoolStatus::default();
+        };
+        let state = pool.state();
+        PoolStatus {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: pool.max_size(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -250,6 +351,217 @@ mod tests {
         assert!(db.ping().await.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_close() {
+        let db = setup_test_db().await;
+        let clone = db.clone();
+        db.execute("CREATE TABLE t (id INTEGER)", vec![])
+            .await
+            .unwrap();
+        db.begin_transaction().await.unwrap();
+
+        db.close().await.unwrap();
+        // 克隆共享同一个池, 关闭后不会重新建立连接
+        let result = clone.query("SELECT * FROM t", vec![]).await;
+        assert!(matches!(result, Err(DbError::Closed)));
+        assert!(matches!(
+            db.execute("INSERT INTO t VALUES (1)", vec![]).await,
+            Err(DbError::Closed)
+        ));
+        assert!(matches!(db.ping().await, Err(DbError::Closed)));
+        assert_eq!(db.pool_status(), PoolStatus::default());
+        assert!(db.close().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pool_status() {
+        let db = setup_test_db().await;
+        assert!(db.ping_timeout(Duration::from_secs(5)).await.is_ok());
+
+        let status = db.pool_status();
+        assert!(status.connections <= DatabaseConfig::default().max_size);
+        assert!(status.idle_connections <= status.connections);
+        assert!(status.connections <= status.max_size);
+
+        // 取出两个连接后空闲数相应减少
+        let held = vec![
+            db.pool.current().unwrap().get().unwrap(),
+            db.pool.current().unwrap().get().unwrap(),
+        ];
+        let status = db.pool_status();
+        assert_eq!(status.connections - status.idle_connections, 2);
+        drop(held);
+    }
+
+    #[tokio::test]
+    async fn test_ping_timeout_when_pool_exhausted() {
+        let db = setup_test_db().await;
+        let pool = db.pool.current().unwrap();
+        let held: Vec<_> = (0..pool.max_size()).map(|_| pool.get().unwrap()).collect();
+
+        let start = std::time::Instant::now();
+        let result = db.ping_timeout(Duration::from_millis(100)).await;
+        assert!(matches!(result, Err(DbError::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        drop(held);
+        assert!(db.ping_timeout(Duration::from_secs(5)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_min_idle_warmup() {
+        let db = SqliteDatabase::connect(
+            DatabaseConfig::builder()
+                .database_name(":memory:")
+                .max_size(4)
+                .min_idle(2)
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        let status = db.pool_status();
+        assert!(status.idle_connections >= 2);
+    }
+
+    // 记录取连接过慢的次数
+    #[derive(Default)]
+    struct SlowAcquires(std::sync::atomic::AtomicUsize);
+
+    impl QueryObserver for SlowAcquires {
+        fn on_query(&self, _sql: &str, _param_count: usize, _elapsed: Duration) {}
+
+        fn on_slow_acquire(&self, _elapsed: Duration) {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_acquire_threshold() {
+        let slow_acquires = Arc::new(SlowAcquires::default());
+        let db = SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            max_size: 1,
+            observer: Some(slow_acquires.clone()),
+            slow_acquire_threshold: Some(Duration::from_millis(50)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let count = || slow_acquires.0.load(std::sync::atomic::Ordering::SeqCst);
+
+        db.query("SELECT 1", vec![]).await.unwrap();
+        assert_eq!(count(), 0);
+
+        // 唯一的连接被占用, 查询要等它归还后才能取到连接
+        let held = db.pool.current().unwrap().get().unwrap();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(held);
+        });
+        db.query("SELECT 1", vec![]).await.unwrap();
+        release.join().unwrap();
+        assert_eq!(count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("read_only.db")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let writer = SqliteDatabase::connect(DatabaseConfig {
+            database_name: path.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        writer
+            .execute("CREATE TABLE items (id INTEGER PRIMARY KEY)", vec![])
+            .await
+            .unwrap();
+        writer
+            .execute("INSERT INTO items (id) VALUES (1)", vec![])
+            .await
+            .unwrap();
+
+        let reader = SqliteDatabase::connect(DatabaseConfig {
+            database_name: path,
+            read_only: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let rows = reader.query("SELECT id FROM items", vec![]).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(reader
+            .execute("INSERT INTO items (id) VALUES (2)", vec![])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pragmas() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DatabaseConfig {
+            database_name: dir.path().join("pragma.db").to_str().unwrap().to_string(),
+            pragmas: vec![
+                ("journal_mode".to_string(), "WAL".to_string()),
+                ("busy_timeout".to_string(), "5000".to_string()),
+                ("foreign_keys".to_string(), "ON".to_string()),
+            ],
+            ..Default::default()
+        };
+        let db = SqliteDatabase::connect(config).await.unwrap();
+
+        let row = db
+            .query_one("PRAGMA journal_mode", vec![])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.values[0], Value::Text("wal".to_string()));
+        let row = db
+            .query_one("PRAGMA foreign_keys", vec![])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.values[0], Value::Bigint(1));
+    }
+
+    #[tokio::test]
+    async fn test_memory_pool_shared() {
+        let config = DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            max_size: 5,
+            ..Default::default()
+        };
+        let db = SqliteDatabase::connect(config).await.unwrap();
+
+        // 同时取出两个连接, 在一个上建表, 在另一个上查询
+        let first = db.pool.current().unwrap().get().unwrap();
+        let second = db.pool.current().unwrap().get().unwrap();
+        first
+            .execute_batch("CREATE TABLE shared (id INTEGER); INSERT INTO shared VALUES (1);")
+            .unwrap();
+        let count: i64 = second
+            .query_row("SELECT COUNT(*) FROM shared", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        drop(first);
+        drop(second);
+
+        // 另一个 :memory: 库互不影响
+        let other = SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(other.query("SELECT * FROM shared", vec![]).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_execute_query() {
         let db = setup_test_db().await;
@@ -270,6 +582,73 @@ mod tests {
         assert_eq!(result.unwrap(), 1);
     }
 
+    #[tokio::test]
+    async fn test_query_raw() {
+        let db = setup_test_db().await;
+        db.execute_raw("CREATE TABLE raw_test (id INTEGER, score REAL, note TEXT)")
+            .await
+            .unwrap();
+        db.execute_raw("INSERT INTO raw_test VALUES (1, 2.5, NULL)")
+            .await
+            .unwrap();
+
+        let rows = db.query_raw("SELECT * FROM raw_test").await.unwrap();
+        assert_eq!(
+            rows[0].values,
+            vec![
+                Value::Text("1".to_string()),
+                Value::Text("2.5".to_string()),
+                Value::Null
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_execute() {
+        let db = setup_test_db().await;
+        db.batch_execute(
+            "CREATE TABLE batch_test (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO batch_test (id, name) VALUES (1, 'a;b');",
+        )
+        .await
+        .unwrap();
+
+        let rows = db
+            .query("SELECT name FROM batch_test", vec![])
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<String>("name").unwrap(), "a;b");
+
+        // 出错的语句之前的语句已经执行
+        assert!(db
+            .batch_execute("INSERT INTO batch_test (id) VALUES (2); INSERT INTO missing VALUES (1)")
+            .await
+            .is_err());
+        let rows = db.query("SELECT id FROM batch_test", vec![]).await.unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_error_source() {
+        use std::error::Error;
+
+        let db = setup_test_db().await;
+        db.execute("CREATE TABLE tags (id INTEGER PRIMARY KEY)", vec![])
+            .await
+            .unwrap();
+        db.execute("INSERT INTO tags (id) VALUES (1)", vec![])
+            .await
+            .unwrap();
+        let err = db
+            .execute("INSERT INTO tags (id) VALUES (1)", vec![])
+            .await
+            .unwrap_err();
+        let source = err.source().expect("driver error should be kept");
+        assert!(source.downcast_ref::<rusqlite::Error>().is_some());
+        assert!(err.to_string().contains("UNIQUE constraint failed"));
+    }
+
     #[tokio::test]
     async fn test_query() {
         let db = setup_test_db().await;
@@ -348,6 +727,58 @@ mod tests {
         assert_eq!(rows.len(), 1); // 应该还是1条记录
     }
 
+    #[tokio::test]
+    async fn test_transaction_guard() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = SqliteDatabase::connect(DatabaseConfig {
+            database_name: dir.path().join("tx.db").to_str().unwrap().to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        db.execute(
+            "CREATE TABLE test (id INTEGER PRIMARY KEY, value TEXT)",
+            vec![],
+        )
+        .await
+        .unwrap();
+
+        // 守卫在自己的连接上执行, 未提交的数据对其他克隆不可见
+        let tx = db.start_transaction().await.unwrap();
+        tx.execute("INSERT INTO test (value) VALUES ($1)", vec!["kept".into()])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.query("SELECT * FROM test", vec![]).await.unwrap().len(),
+            0
+        );
+        tx.commit().await.unwrap();
+        assert_eq!(
+            db.query("SELECT * FROM test", vec![]).await.unwrap().len(),
+            1
+        );
+
+        // 没有 commit 就离开作用域时回滚
+        {
+            let tx = db.start_transaction().await.unwrap();
+            tx.execute(
+                "INSERT INTO test (value) VALUES ($1)",
+                vec!["dropped".into()],
+            )
+            .await
+            .unwrap();
+        }
+        // 回滚在后台任务中执行, 让出一次等它完成
+        tokio::task::yield_now().await;
+        db.execute("INSERT INTO test (value) VALUES ($1)", vec!["after".into()])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.query("SELECT * FROM test", vec![]).await.unwrap().len(),
+            2
+        );
+    }
+
     #[tokio::test]
     async fn test_value_conversions() {
         let db = setup_test_db().await;
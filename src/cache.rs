@@ -1,10 +1,15 @@
+use crate::asyncdao::Dao;
+use crate::asyncdatabase::{DbError, Value};
 use async_trait::async_trait;
-use bb8::Pool;
+use bb8::{Pool, PooledConnection};
 use bb8_redis::RedisConnectionManager;
-use redis::{AsyncCommands, ErrorKind, RedisError};
+use redis::{aio::MultiplexedConnection, AsyncCommands, ErrorKind, RedisError};
 use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
 use std::marker::PhantomData;
-use std::time::Duration;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 // data cache object
 #[async_trait]
@@ -16,111 +21,442 @@ where
 
     async fn get(&self, key: &str) -> Result<Option<T>, Self::Error>;
     async fn set(&self, key: &str, value: T, ttl: Option<Duration>) -> Result<(), Self::Error>;
+    async fn mget(&self, keys: &[&str]) -> Result<Vec<Option<T>>, Self::Error>;
+    async fn mset(&self, items: &[(&str, T)], ttl: Option<Duration>) -> Result<(), Self::Error>;
     async fn del(&self, key: &str) -> Result<(), Self::Error>;
+    async fn del_many(&self, keys: &[&str]) -> Result<u64, Self::Error>;
     async fn exists(&self, key: &str) -> Result<bool, Self::Error>;
+    // 按通配符匹配键, 用于批量失效
+    async fn scan(&self, pattern: &str) -> Result<Vec<String>, Self::Error>;
+    async fn del_matching(&self, pattern: &str) -> Result<u64, Self::Error>;
 }
 
-pub struct RedisCache<T> {
+// 每批 SCAN/DEL 的键数
+const SCAN_BATCH: usize = 100;
+
+// 用 SCAN 游标遍历, 避免 KEYS 阻塞服务器
+async fn scan_keys(
+    conn: &mut MultiplexedConnection,
+    pattern: &str,
+) -> Result<Vec<String>, RedisError> {
+    let mut keys = Vec::new();
+    let mut cursor: u64 = 0;
+    loop {
+        let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(SCAN_BATCH)
+            .query_async(conn)
+            .await?;
+        keys.extend(batch);
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    // SCAN 可能重复返回同一个键
+    keys.sort();
+    keys.dedup();
+    Ok(keys)
+}
+
+async fn del_keys<K>(conn: &mut MultiplexedConnection, keys: &[K]) -> Result<u64, RedisError>
+where
+    K: redis::ToRedisArgs + Send + Sync,
+{
+    let mut deleted = 0;
+    for batch in keys.chunks(SCAN_BATCH) {
+        let count: u64 = conn.del(batch).await?;
+        deleted += count;
+    }
+    Ok(deleted)
+}
+
+// 缓存值的编码方式
+pub trait CacheCodec: Send + Sync + 'static {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, RedisError>;
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, RedisError>;
+}
+
+// bincode 编码, 默认
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+impl CacheCodec for Bincode {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, RedisError> {
+        bincode::serialize(value).map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Serialization error",
+                e.to_string(),
+            ))
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, RedisError> {
+        bincode::deserialize(bytes).map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Deserialization error",
+                e.to_string(),
+            ))
+        })
+    }
+}
+
+// JSON 编码, 便于与其他语言的服务共享缓存
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl CacheCodec for Json {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, RedisError> {
+        serde_json::to_vec(value).map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Serialization error",
+                e.to_string(),
+            ))
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, RedisError> {
+        serde_json::from_slice(bytes).map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Deserialization error",
+                e.to_string(),
+            ))
+        })
+    }
+}
+
+// 键前缀, 多个服务共用一个 Redis 时隔离命名空间
+#[derive(Debug, Clone, Default)]
+struct KeyPrefix(String);
+
+impl KeyPrefix {
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.0, key)
+    }
+
+    fn keys(&self, keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| self.key(key)).collect()
+    }
+
+    // SCAN 返回的是完整键名, 去掉前缀后再交给调用方
+    fn strip(&self, keys: Vec<String>) -> Vec<String> {
+        keys.into_iter()
+            .map(|key| match key.strip_prefix(&self.0) {
+                Some(stripped) => stripped.to_string(),
+                None => key,
+            })
+            .collect()
+    }
+}
+
+pub struct RedisCache<T, C = Bincode> {
     pool: Pool<RedisConnectionManager>,
+    codec: C,
+    key_prefix: KeyPrefix,
     _table: PhantomData<T>,
 }
 
 impl<T> RedisCache<T> {
     pub async fn new(url: &str) -> Result<Self, RedisError> {
+        Self::new_with_codec(url, Bincode).await
+    }
+}
+
+impl<T, C: CacheCodec> RedisCache<T, C> {
+    pub async fn new_with_codec(url: &str, codec: C) -> Result<Self, RedisError> {
         let manager = RedisConnectionManager::new(url)?;
         let pool = Pool::builder().build(manager).await?;
         Ok(RedisCache {
             pool: pool,
+            codec,
+            key_prefix: KeyPrefix::default(),
             _table: PhantomData,
         })
     }
+
+    /// 所有键自动加上前缀, 例如 `"app1:"`
+    pub fn with_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = KeyPrefix(prefix.into());
+        self
+    }
+
+    // 从连接池取一个连接
+    async fn conn(&self) -> Result<PooledConnection<'_, RedisConnectionManager>, RedisError> {
+        self.pool
+            .get()
+            .await
+            .map_err(|_| RedisError::from((ErrorKind::ClientError, "error getting connect")))
+    }
 }
 
 #[async_trait]
-impl<T> Dco<T> for RedisCache<T>
+impl<T, C> Dco<T> for RedisCache<T, C>
 where
     T: 'static + Sized + Sync + Send + Serialize + DeserializeOwned,
+    C: CacheCodec,
 {
     type Error = RedisError;
 
     async fn get(&self, key: &str) -> Result<Option<T>, Self::Error> {
-        let mut conn = match self.pool.get().await {
-            Ok(conn) => conn,
-            _ => {
-                return Err(RedisError::from((
-                    ErrorKind::ClientError,
-                    "error getting connect",
-                )))
-            }
-        };
+        let mut conn = self.conn().await?;
 
-        let result: Option<Vec<u8>> = conn.get(key).await?;
+        let result: Option<Vec<u8>> = conn.get(self.key_prefix.key(key)).await?;
         match result {
-            Some(bytes) => {
-                let value: T = bincode::deserialize(&bytes).map_err(|e| {
-                    redis::RedisError::from((
-                        redis::ErrorKind::TypeError,
-                        "Deserialization error",
-                        e.to_string(),
-                    ))
-                })?;
-                Ok(Some(value))
-            }
+            Some(bytes) => Ok(Some(self.codec.decode(&bytes)?)),
             None => Ok(None),
         }
     }
 
     async fn set(&self, key: &str, value: T, ttl: Option<Duration>) -> Result<(), Self::Error> {
-        let mut conn = match self.pool.get().await {
-            Ok(conn) => conn,
-            _ => {
-                return Err(RedisError::from((
-                    ErrorKind::ClientError,
-                    "error getting connect",
-                )))
-            }
-        };
+        let mut conn = self.conn().await?;
 
-        let bytes = bincode::serialize(&value).map_err(|e| {
-            redis::RedisError::from((
-                redis::ErrorKind::TypeError,
-                "Serialization error",
-                e.to_string(),
-            ))
-        })?;
+        let bytes = self.codec.encode(&value)?;
 
         match ttl {
-            Some(duration) => conn.set_ex(key, bytes, duration.as_secs() as u64).await,
-            None => conn.set(key, bytes).await,
+            Some(duration) => {
+                conn.set_ex(self.key_prefix.key(key), bytes, duration.as_secs() as u64)
+                    .await
+            }
+            None => conn.set(self.key_prefix.key(key), bytes).await,
         }
     }
 
+    async fn mget(&self, keys: &[&str]) -> Result<Vec<Option<T>>, Self::Error> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.conn().await?;
+
+        let result: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg(self.key_prefix.keys(keys))
+            .query_async(&mut *conn)
+            .await?;
+        // 单个键反序列化失败时该位置返回 None
+        Ok(result
+            .into_iter()
+            .map(|bytes| bytes.and_then(|bytes| self.codec.decode(&bytes).ok()))
+            .collect())
+    }
+
+    async fn mset(&self, items: &[(&str, T)], ttl: Option<Duration>) -> Result<(), Self::Error> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.conn().await?;
+
+        let mut pipe = redis::pipe();
+        for (key, value) in items {
+            let bytes = self.codec.encode(value)?;
+            match ttl {
+                Some(duration) => pipe
+                    .set_ex(self.key_prefix.key(key), bytes, duration.as_secs())
+                    .ignore(),
+                None => pipe.set(self.key_prefix.key(key), bytes).ignore(),
+            };
+        }
+        pipe.query_async(&mut *conn).await
+    }
+
     async fn del(&self, key: &str) -> Result<(), Self::Error> {
-        let mut conn = match self.pool.get().await {
-            Ok(conn) => conn,
-            _ => {
-                return Err(RedisError::from((
-                    ErrorKind::ClientError,
-                    "error getting connect",
-                )))
-            }
-        };
-        conn.del(key).await
+        let mut conn = self.conn().await?;
+        conn.del(self.key_prefix.key(key)).await
+    }
+
+    async fn del_many(&self, keys: &[&str]) -> Result<u64, Self::Error> {
+        let mut conn = self.conn().await?;
+        del_keys(&mut conn, &self.key_prefix.keys(keys)).await
     }
 
     async fn exists(&self, key: &str) -> Result<bool, Self::Error> {
-        // let mut conn = self.pool.get().await.map_err(redis::RedisError::from)?;
-        let mut conn = match self.pool.get().await {
-            Ok(conn) => conn,
-            _ => {
-                return Err(RedisError::from((
-                    ErrorKind::ClientError,
-                    "error getting connect",
-                )))
+        let mut conn = self.conn().await?;
+
+        conn.exists(self.key_prefix.key(key)).await
+    }
+
+    async fn scan(&self, pattern: &str) -> Result<Vec<String>, Self::Error> {
+        let mut conn = self.conn().await?;
+        let keys = scan_keys(&mut conn, &self.key_prefix.key(pattern)).await?;
+        Ok(self.key_prefix.strip(keys))
+    }
+
+    async fn del_matching(&self, pattern: &str) -> Result<u64, Self::Error> {
+        let mut conn = self.conn().await?;
+        let keys = scan_keys(&mut conn, &self.key_prefix.key(pattern)).await?;
+        del_keys(&mut conn, &keys).await
+    }
+}
+
+// Redis 风格的通配符匹配, 支持 * 和 ?
+fn glob_match(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let key: Vec<char> = key.chars().collect();
+    let (mut p, mut k) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while k < key.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == key[k]) {
+            p += 1;
+            k += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, k));
+            p += 1;
+        } else if let Some((sp, sk)) = star {
+            // 回溯, 让上一个 * 多匹配一个字符
+            p = sp + 1;
+            k = sk + 1;
+            star = Some((sp, sk + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+type Entry = (Vec<u8>, Option<Instant>);
+
+/// 进程内缓存, 不依赖 Redis, 主要用于测试
+///
+/// 过期时间在读取时检查, 过期的键读到时才会被清理
+pub struct InMemoryCache<T, C = Bincode> {
+    entries: Mutex<HashMap<String, Entry>>,
+    codec: C,
+    _table: PhantomData<T>,
+}
+
+impl<T> InMemoryCache<T> {
+    pub fn new() -> Self {
+        Self::new_with_codec(Bincode)
+    }
+}
+
+impl<T> Default for InMemoryCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, C: CacheCodec> InMemoryCache<T, C> {
+    pub fn new_with_codec(codec: C) -> Self {
+        InMemoryCache {
+            entries: Mutex::new(HashMap::new()),
+            codec,
+            _table: PhantomData,
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, Entry>>, RedisError> {
+        self.entries
+            .lock()
+            .map_err(|_| RedisError::from((ErrorKind::ClientError, "cache lock poisoned")))
+    }
+
+    // 读取未过期的值, 顺便清理已过期的键
+    fn live<'a>(entries: &'a mut HashMap<String, Entry>, key: &str) -> Option<&'a Vec<u8>> {
+        let expired = matches!(entries.get(key), Some((_, Some(at))) if *at <= Instant::now());
+        if expired {
+            entries.remove(key);
+            return None;
+        }
+        entries.get(key).map(|(bytes, _)| bytes)
+    }
+
+    fn live_keys(entries: &mut HashMap<String, Entry>, pattern: &str) -> Vec<String> {
+        let now = Instant::now();
+        entries.retain(|_, (_, expires)| expires.is_none_or(|at| at > now));
+        let mut keys: Vec<String> = entries
+            .keys()
+            .filter(|key| glob_match(pattern, key))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+#[async_trait]
+impl<T, C> Dco<T> for InMemoryCache<T, C>
+where
+    T: 'static + Sized + Sync + Send + Serialize + DeserializeOwned,
+    C: CacheCodec,
+{
+    type Error = RedisError;
+
+    async fn get(&self, key: &str) -> Result<Option<T>, Self::Error> {
+        let mut entries = self.lock()?;
+        match Self::live(&mut entries, key) {
+            Some(bytes) => Ok(Some(self.codec.decode(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set(&self, key: &str, value: T, ttl: Option<Duration>) -> Result<(), Self::Error> {
+        let bytes = self.codec.encode(&value)?;
+        let expires = ttl.map(|duration| Instant::now() + duration);
+        self.lock()?.insert(key.to_string(), (bytes, expires));
+        Ok(())
+    }
+
+    async fn mget(&self, keys: &[&str]) -> Result<Vec<Option<T>>, Self::Error> {
+        let mut entries = self.lock()?;
+        // 与 RedisCache 一致, 单个键反序列化失败时该位置返回 None
+        Ok(keys
+            .iter()
+            .map(|key| {
+                Self::live(&mut entries, key).and_then(|bytes| self.codec.decode(bytes).ok())
+            })
+            .collect())
+    }
+
+    async fn mset(&self, items: &[(&str, T)], ttl: Option<Duration>) -> Result<(), Self::Error> {
+        let expires = ttl.map(|duration| Instant::now() + duration);
+        let mut encoded = Vec::with_capacity(items.len());
+        for (key, value) in items {
+            encoded.push((key.to_string(), (self.codec.encode(value)?, expires)));
+        }
+        self.lock()?.extend(encoded);
+        Ok(())
+    }
+
+    async fn del(&self, key: &str) -> Result<(), Self::Error> {
+        self.lock()?.remove(key);
+        Ok(())
+    }
+
+    async fn del_many(&self, keys: &[&str]) -> Result<u64, Self::Error> {
+        let mut entries = self.lock()?;
+        let mut deleted = 0;
+        for key in keys {
+            if Self::live(&mut entries, key).is_some() {
+                entries.remove(*key);
+                deleted += 1;
             }
-        };
+        }
+        Ok(deleted)
+    }
 
-        conn.exists(key).await
+    async fn exists(&self, key: &str) -> Result<bool, Self::Error> {
+        let mut entries = self.lock()?;
+        Ok(Self::live(&mut entries, key).is_some())
+    }
+
+    async fn scan(&self, pattern: &str) -> Result<Vec<String>, Self::Error> {
+        let mut entries = self.lock()?;
+        Ok(Self::live_keys(&mut entries, pattern))
+    }
+
+    async fn del_matching(&self, pattern: &str) -> Result<u64, Self::Error> {
+        let mut entries = self.lock()?;
+        let keys = Self::live_keys(&mut entries, pattern);
+        for key in &keys {
+            entries.remove(key);
+        }
+        Ok(keys.len() as u64)
     }
 }
 
@@ -136,19 +472,72 @@ pub trait CacheDb {
         value: T,
         ttl: Option<Duration>,
     ) -> Result<(), Self::Error>;
+    async fn mget<T: CachedData>(&self, keys: &[&str]) -> Result<Vec<Option<T>>, Self::Error>;
+    async fn mset<T: CachedData>(
+        &self,
+        items: &[(&str, T)],
+        ttl: Option<Duration>,
+    ) -> Result<(), Self::Error>;
     async fn del<T: CachedData>(&self, key: &str) -> Result<(), Self::Error>;
     async fn exists<T: CachedData>(&self, key: &str) -> Result<bool, Self::Error>;
+
+    // 未命中时调用 f 计算并写入缓存; f 出错时不写入, 错误原样返回
+    async fn get_or_set<T, F, Fut>(
+        &self,
+        key: &str,
+        ttl: Option<Duration>,
+        f: F,
+    ) -> Result<T, Self::Error>
+    where
+        Self: Sync,
+        T: CachedData + Clone,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<T, Self::Error>> + Send,
+    {
+        if let Some(value) = self.get::<T>(key).await? {
+            return Ok(value);
+        }
+        let value = f().await?;
+        self.set(key, value.clone(), ttl).await?;
+        Ok(value)
+    }
+
+    // 计数器以 Redis 原生整数存储, 不经过 bincode, 不能与 get/set 共用同一个键
+    async fn incr(&self, key: &str, by: i64) -> Result<i64, Self::Error>;
+    async fn decr(&self, key: &str, by: i64) -> Result<i64, Self::Error>;
+
+    // 按通配符匹配键, 用于批量失效
+    async fn scan(&self, pattern: &str) -> Result<Vec<String>, Self::Error>;
+    async fn del_matching(&self, pattern: &str) -> Result<u64, Self::Error>;
 }
 
 pub struct Redis {
     pool: Pool<RedisConnectionManager>,
+    key_prefix: KeyPrefix,
 }
 
 impl Redis {
     pub async fn new(url: &str) -> Result<Self, RedisError> {
         let manager = RedisConnectionManager::new(url)?;
         let pool = Pool::builder().build(manager).await?;
-        Ok(Redis { pool: pool })
+        Ok(Redis {
+            pool: pool,
+            key_prefix: KeyPrefix::default(),
+        })
+    }
+
+    /// 所有键自动加上前缀, 例如 `"app1:"`
+    pub fn with_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = KeyPrefix(prefix.into());
+        self
+    }
+
+    // 从连接池取一个连接
+    async fn conn(&self) -> Result<PooledConnection<'_, RedisConnectionManager>, RedisError> {
+        self.pool
+            .get()
+            .await
+            .map_err(|_| RedisError::from((ErrorKind::ClientError, "error getting connect")))
     }
 }
 
@@ -166,17 +555,9 @@ impl CacheDb for Redis {
     type Error = RedisError;
 
     async fn get<T: CachedData>(&self, key: &str) -> Result<Option<T>, Self::Error> {
-        let mut conn = match self.pool.get().await {
-            Ok(conn) => conn,
-            _ => {
-                return Err(RedisError::from((
-                    ErrorKind::ClientError,
-                    "error getting connect",
-                )))
-            }
-        };
+        let mut conn = self.conn().await?;
 
-        let result: Option<Vec<u8>> = conn.get(key).await?;
+        let result: Option<Vec<u8>> = conn.get(self.key_prefix.key(key)).await?;
         match result {
             Some(bytes) => {
                 let value: T = bincode::deserialize(&bytes).map_err(|e| {
@@ -198,15 +579,7 @@ impl CacheDb for Redis {
         value: T,
         ttl: Option<Duration>,
     ) -> Result<(), Self::Error> {
-        let mut conn = match self.pool.get().await {
-            Ok(conn) => conn,
-            _ => {
-                return Err(RedisError::from((
-                    ErrorKind::ClientError,
-                    "error getting connect",
-                )))
-            }
-        };
+        let mut conn = self.conn().await?;
 
         let bytes = bincode::serialize(&value).map_err(|e| {
             redis::RedisError::from((
@@ -217,37 +590,254 @@ impl CacheDb for Redis {
         })?;
 
         match ttl {
-            Some(duration) => conn.set_ex(key, bytes, duration.as_secs() as u64).await,
-            None => conn.set(key, bytes).await,
+            Some(duration) => {
+                conn.set_ex(self.key_prefix.key(key), bytes, duration.as_secs() as u64)
+                    .await
+            }
+            None => conn.set(self.key_prefix.key(key), bytes).await,
+        }
+    }
+
+    async fn mget<T: CachedData>(&self, keys: &[&str]) -> Result<Vec<Option<T>>, Self::Error> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
         }
+        let mut conn = self.conn().await?;
+
+        let result: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg(self.key_prefix.keys(keys))
+            .query_async(&mut *conn)
+            .await?;
+        // 单个键反序列化失败时该位置返回 None
+        Ok(result
+            .into_iter()
+            .map(|bytes| bytes.and_then(|bytes| bincode::deserialize(&bytes).ok()))
+            .collect())
+    }
+
+    async fn mset<T: CachedData>(
+        &self,
+        items: &[(&str, T)],
+        ttl: Option<Duration>,
+    ) -> Result<(), Self::Error> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.conn().await?;
+
+        let mut pipe = redis::pipe();
+        for (key, value) in items {
+            let bytes = bincode::serialize(value).map_err(|e| {
+                redis::RedisError::from((
+                    redis::ErrorKind::TypeError,
+                    "Serialization error",
+                    e.to_string(),
+                ))
+            })?;
+            match ttl {
+                Some(duration) => pipe
+                    .set_ex(self.key_prefix.key(key), bytes, duration.as_secs())
+                    .ignore(),
+                None => pipe.set(self.key_prefix.key(key), bytes).ignore(),
+            };
+        }
+        pipe.query_async(&mut *conn).await
     }
 
     async fn del<T: CachedData>(&self, key: &str) -> Result<(), Self::Error> {
-        let mut conn = match self.pool.get().await {
-            Ok(conn) => conn,
-            _ => {
-                return Err(RedisError::from((
-                    ErrorKind::ClientError,
-                    "error getting connect",
-                )))
-            }
-        };
-        conn.del(key).await
+        let mut conn = self.conn().await?;
+        conn.del(self.key_prefix.key(key)).await
     }
 
     async fn exists<T: CachedData>(&self, key: &str) -> Result<bool, Self::Error> {
-        // let mut conn = self.pool.get().await.map_err(redis::RedisError::from)?;
-        let mut conn = match self.pool.get().await {
-            Ok(conn) => conn,
-            _ => {
-                return Err(RedisError::from((
-                    ErrorKind::ClientError,
-                    "error getting connect",
-                )))
-            }
-        };
+        let mut conn = self.conn().await?;
+
+        conn.exists(self.key_prefix.key(key)).await
+    }
+
+    async fn incr(&self, key: &str, by: i64) -> Result<i64, Self::Error> {
+        let mut conn = self.conn().await?;
+        conn.incr(self.key_prefix.key(key), by).await
+    }
+
+    async fn decr(&self, key: &str, by: i64) -> Result<i64, Self::Error> {
+        let mut conn = self.conn().await?;
+        conn.decr(self.key_prefix.key(key), by).await
+    }
 
-        conn.exists(key).await
+    async fn scan(&self, pattern: &str) -> Result<Vec<String>, Self::Error> {
+        let mut conn = self.conn().await?;
+        let keys = scan_keys(&mut conn, &self.key_prefix.key(pattern)).await?;
+        Ok(self.key_prefix.strip(keys))
+    }
+
+    async fn del_matching(&self, pattern: &str) -> Result<u64, Self::Error> {
+        let mut conn = self.conn().await?;
+        let keys = scan_keys(&mut conn, &self.key_prefix.key(pattern)).await?;
+        del_keys(&mut conn, &keys).await
+    }
+}
+
+// 读穿透缓存: find_by_id 先查缓存, 未命中再查库并回填; 写操作删除对应的键,
+// 删除失败只记录日志, 不影响写操作的结果
+pub struct CachedDao<D, C> {
+    dao: D,
+    cache: C,
+    ttl: Option<Duration>,
+}
+
+impl<D, C> CachedDao<D, C> {
+    pub fn new(dao: D, cache: C) -> Self {
+        CachedDao {
+            dao,
+            cache,
+            ttl: None,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn dao(&self) -> &D {
+        &self.dao
+    }
+
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    // 表名加上各主键列的值, 复合主键的值依次以 `:` 连接
+    fn cache_key<T>(&self, key: &[Value]) -> String
+    where
+        T: CachedData + Clone,
+        D: Dao<T>,
+    {
+        let key: Vec<String> = key
+            .iter()
+            .map(|id| match id {
+                Value::Int(i) => i.to_string(),
+                Value::Bigint(i) => i.to_string(),
+                Value::Text(s) | Value::Varchar(s) => s.clone(),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        format!("{}:{}", self.dao.table(), key.join(":"))
+    }
+
+    fn entity_key<T>(&self, entity: &T) -> Option<String>
+    where
+        T: CachedData + Clone,
+        D: Dao<T>,
+    {
+        let map = D::entity_to_map(entity);
+        let key = self
+            .dao
+            .primary_keys()
+            .iter()
+            .map(|column| {
+                map.iter()
+                    .find(|(c, _)| c == column)
+                    .map(|(_, v)| v.clone())
+            })
+            .collect::<Option<Vec<Value>>>()?;
+        Some(self.cache_key::<T>(&key))
+    }
+
+    // 数据库已经写入, 删除缓存失败时只记录日志, 旧值最迟在 ttl 到期后失效
+    async fn invalidate<T>(&self, key: &str)
+    where
+        T: CachedData + Clone,
+        C: Dco<T>,
+        C::Error: std::fmt::Display,
+    {
+        if let Err(e) = self.cache.del(key).await {
+            log::warn!("failed to invalidate cache key {}: {}", key, e);
+        }
+    }
+
+    // 缓存读写失败时直接走数据库, 不影响查询结果
+    pub async fn find_by_id<T>(&self, id: Value) -> Result<Option<T>, DbError>
+    where
+        T: CachedData + Clone,
+        D: Dao<T> + Sync,
+        C: Dco<T> + Sync,
+        C::Error: std::fmt::Display,
+    {
+        self.find_by_key(vec![id]).await
+    }
+
+    /// 按复合主键查找, 值的顺序与 primary_key_columns 一致
+    pub async fn find_by_key<T>(&self, key: Vec<Value>) -> Result<Option<T>, DbError>
+    where
+        T: CachedData + Clone,
+        D: Dao<T> + Sync,
+        C: Dco<T> + Sync,
+        C::Error: std::fmt::Display,
+    {
+        let cache_key = self.cache_key::<T>(&key);
+        if let Ok(Some(entity)) = self.cache.get(&cache_key).await {
+            return Ok(Some(entity));
+        }
+
+        let entity = self.dao.find_by_key(key).await?;
+        if let Some(entity) = &entity {
+            let _ = self.cache.set(&cache_key, entity.clone(), self.ttl).await;
+        }
+        Ok(entity)
+    }
+
+    pub async fn create<T>(&self, entity: &T) -> Result<u64, DbError>
+    where
+        T: CachedData + Clone,
+        D: Dao<T> + Sync,
+        C: Dco<T> + Sync,
+        C::Error: std::fmt::Display,
+    {
+        let result = self.dao.create(entity).await?;
+        if let Some(key) = self.entity_key(entity) {
+            self.invalidate::<T>(&key).await;
+        }
+        Ok(result)
+    }
+
+    pub async fn update<T>(&self, entity: &T) -> Result<u64, DbError>
+    where
+        T: CachedData + Clone,
+        D: Dao<T> + Sync,
+        C: Dco<T> + Sync,
+        C::Error: std::fmt::Display,
+    {
+        let result = self.dao.update(entity).await?;
+        if let Some(key) = self.entity_key(entity) {
+            self.invalidate::<T>(&key).await;
+        }
+        Ok(result)
+    }
+
+    pub async fn delete<T>(&self, id: Value) -> Result<u64, DbError>
+    where
+        T: CachedData + Clone,
+        D: Dao<T> + Sync,
+        C: Dco<T> + Sync,
+        C::Error: std::fmt::Display,
+    {
+        self.delete_by_key(vec![id]).await
+    }
+
+    /// 按复合主键删除, 值的顺序与 primary_key_columns 一致
+    pub async fn delete_by_key<T>(&self, key: Vec<Value>) -> Result<u64, DbError>
+    where
+        T: CachedData + Clone,
+        D: Dao<T> + Sync,
+        C: Dco<T> + Sync,
+        C::Error: std::fmt::Display,
+    {
+        let cache_key = self.cache_key::<T>(&key);
+        let result = self.dao.delete_by_key(key).await?;
+        self.invalidate::<T>(&cache_key).await;
+        Ok(result)
     }
 }
 
@@ -259,7 +849,7 @@ mod tests {
     use std::time::Duration;
     use tokio::time::sleep; // Import sleep for testing TTL
 
-    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
     struct TestData {
         a: i32,
         b: String,
@@ -361,6 +951,133 @@ mod tests {
         assert!(!cache.exists(key).await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_batch_keeps_order() {
+        let cache = setup_cache().await;
+        let keys: Vec<String> = (0..10).map(|i| format!("test_batch_{}", i)).collect();
+        let items: Vec<(&str, TestData)> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                (
+                    key.as_str(),
+                    TestData {
+                        a: i as i32,
+                        b: key.clone(),
+                    },
+                )
+            })
+            .collect();
+        cache
+            .mset(&items, Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+
+        // 倒序读取, 结果顺序与传入的键一致
+        let reversed: Vec<&str> = keys.iter().rev().map(|key| key.as_str()).collect();
+        let values = cache.mget(&reversed).await.unwrap();
+        for (key, value) in reversed.iter().zip(values) {
+            assert_eq!(value.map(|v| v.b), Some(key.to_string()));
+        }
+
+        let all: Vec<&str> = keys.iter().map(|key| key.as_str()).collect();
+        assert_eq!(cache.del_many(&all).await.unwrap(), 10);
+        let values = cache.mget(&all).await.unwrap();
+        assert!(values.iter().all(|value| value.is_none()));
+    }
+
+    #[test]
+    fn test_codecs_round_trip() {
+        let value = TestData {
+            a: 42,
+            b: "hello".to_string(),
+        };
+
+        let bytes = Bincode.encode(&value).unwrap();
+        assert_eq!(Bincode.decode::<TestData>(&bytes).unwrap(), value);
+
+        let bytes = Json.encode(&value).unwrap();
+        assert_eq!(bytes, br#"{"a":42,"b":"hello"}"#.to_vec());
+        assert_eq!(Json.decode::<TestData>(&bytes).unwrap(), value);
+
+        assert!(Json.decode::<TestData>(b"not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_codec() {
+        let cache: RedisCache<TestData, Json> =
+            RedisCache::new_with_codec("redis://root@127.0.0.1:6379/1", Json)
+                .await
+                .unwrap();
+        let key = "test_json_codec";
+        let value = TestData {
+            a: 1,
+            b: "json".to_string(),
+        };
+        cache.set(key, value.clone(), None).await.unwrap();
+        assert_eq!(cache.get(key).await.unwrap(), Some(value));
+
+        // 存储内容是可读的 JSON 文本
+        let raw: RedisCache<String> = setup_cache().await;
+        let mut conn = raw.pool.get().await.unwrap();
+        let stored: String = conn.get(key).await.unwrap();
+        assert_eq!(stored, r#"{"a":1,"b":"json"}"#);
+
+        // 其他服务写入的 JSON 也能读出
+        let _: () = conn.set(key, r#"{"a":2,"b":"raw"}"#).await.unwrap();
+        drop(conn);
+        assert_eq!(cache.get(key).await.unwrap().map(|v| v.a), Some(2));
+
+        cache.del(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_json_codec() {
+        let cache: InMemoryCache<TestData, Json> = InMemoryCache::new_with_codec(Json);
+        let value = TestData {
+            a: 3,
+            b: "mem".to_string(),
+        };
+        cache.set("mem_json", value.clone(), None).await.unwrap();
+        assert_eq!(cache.get("mem_json").await.unwrap(), Some(value));
+
+        let stored = cache.lock().unwrap().get("mem_json").unwrap().0.clone();
+        let json: serde_json::Value = serde_json::from_slice(&stored).unwrap();
+        assert_eq!(json["b"], "mem");
+
+        // 非 JSON 内容解码失败时返回 TypeError
+        let err = Json.decode::<TestData>(b"\x00\x01").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TypeError);
+    }
+
+    #[tokio::test]
+    async fn test_key_prefix_isolation() {
+        let app1: RedisCache<TestData> = setup_cache().await.with_key_prefix("app1:");
+        let app2: RedisCache<TestData> = setup_cache().await.with_key_prefix("app2:");
+        let key = "test_prefixed";
+        let value = TestData {
+            a: 1,
+            b: "app1".to_string(),
+        };
+
+        app1.set(key, value.clone(), None).await.unwrap();
+        assert_eq!(app1.get(key).await.unwrap(), Some(value));
+        assert!(app1.exists(key).await.unwrap());
+        assert!(!app2.exists(key).await.unwrap());
+        assert_eq!(app2.get(key).await.unwrap(), None);
+
+        // 底层键名带前缀, scan 返回的键不带前缀
+        let plain: RedisCache<TestData> = setup_cache().await;
+        assert!(plain.exists("app1:test_prefixed").await.unwrap());
+        assert_eq!(app1.scan("test_prefix*").await.unwrap(), vec![key]);
+        assert!(app2.scan("test_prefix*").await.unwrap().is_empty());
+
+        app2.del(key).await.unwrap();
+        assert!(app1.exists(key).await.unwrap());
+        app1.del(key).await.unwrap();
+        assert!(!plain.exists("app1:test_prefixed").await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_get_nonexistent() {
         let cache = setup_cache().await;
@@ -381,6 +1098,136 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_mset_mget() {
+        let cache = setup_cache().await;
+        let items = vec![
+            (
+                "test_mkey_1",
+                TestData {
+                    a: 1,
+                    b: "one".to_string(),
+                },
+            ),
+            (
+                "test_mkey_2",
+                TestData {
+                    a: 2,
+                    b: "two".to_string(),
+                },
+            ),
+            (
+                "test_mkey_3",
+                TestData {
+                    a: 3,
+                    b: "three".to_string(),
+                },
+            ),
+        ];
+
+        cache.mset(&items, None).await.unwrap();
+
+        let values = cache
+            .mget(&[
+                "test_mkey_1",
+                "test_mkey_2",
+                "test_mkey_3",
+                "test_mkey_missing",
+            ])
+            .await
+            .unwrap();
+        assert_eq!(values.len(), 4);
+        for (i, value) in values.iter().take(3).enumerate() {
+            assert_eq!(value.as_ref(), Some(&items[i].1));
+        }
+        assert_eq!(values[3], None);
+
+        // 类型不匹配的键只影响自己的位置
+        let other: RedisCache<String> = setup_cache().await;
+        other
+            .set("test_mkey_2", "x".to_string(), None)
+            .await
+            .unwrap();
+        let values = cache.mget(&["test_mkey_1", "test_mkey_2"]).await.unwrap();
+        assert_eq!(values, vec![Some(items[0].1.clone()), None]);
+
+        for (key, _) in &items {
+            cache.del(key).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_set_get_del() {
+        let cache = InMemoryCache::new();
+        let value = TestData {
+            a: 42,
+            b: "hello".to_string(),
+        };
+
+        assert!(!cache.exists("mem_key").await.unwrap());
+        cache.set("mem_key", value.clone(), None).await.unwrap();
+        assert!(cache.exists("mem_key").await.unwrap());
+        assert_eq!(cache.get("mem_key").await.unwrap(), Some(value));
+
+        cache.del("mem_key").await.unwrap();
+        assert!(!cache.exists("mem_key").await.unwrap());
+        assert_eq!(cache.get("mem_key").await.unwrap(), None);
+        // 删除不存在的键不报错
+        cache.del("mem_key").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_ttl() {
+        let cache = InMemoryCache::new();
+        let value = TestData {
+            a: 123,
+            b: "world".to_string(),
+        };
+
+        cache
+            .set("mem_ttl", value.clone(), Some(Duration::from_millis(50)))
+            .await
+            .unwrap();
+        cache.set("mem_keep", value.clone(), None).await.unwrap();
+        assert_eq!(cache.get("mem_ttl").await.unwrap(), Some(value.clone()));
+
+        sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(cache.get("mem_ttl").await.unwrap(), None);
+        assert!(!cache.exists("mem_ttl").await.unwrap());
+        assert_eq!(cache.get("mem_keep").await.unwrap(), Some(value));
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_batch_and_scan() {
+        let cache = InMemoryCache::new();
+        let items: Vec<(String, i32)> = (0..5).map(|i| (format!("user:{}", i), i)).collect();
+        let refs: Vec<(&str, i32)> = items.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        cache.mset(&refs, None).await.unwrap();
+        cache.set("order:1", 100, None).await.unwrap();
+
+        let values = cache.mget(&["user:3", "user:9", "user:0"]).await.unwrap();
+        assert_eq!(values, vec![Some(3), None, Some(0)]);
+
+        assert_eq!(cache.scan("user:*").await.unwrap().len(), 5);
+        assert_eq!(cache.scan("user:?").await.unwrap().len(), 5);
+        assert_eq!(cache.scan("*:1").await.unwrap(), vec!["order:1", "user:1"]);
+
+        assert_eq!(cache.del_many(&["user:0", "user:9"]).await.unwrap(), 1);
+        assert_eq!(cache.del_matching("user:*").await.unwrap(), 4);
+        assert!(cache.exists("order:1").await.unwrap());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("user:*", "user:1"));
+        assert!(glob_match("u*r:?", "user:1"));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(!glob_match("user:?", "user:10"));
+        assert!(!glob_match("user:*", "order:1"));
+    }
+
     async fn setup_cache_db() -> Redis {
         // Use a different database number for testing to avoid conflicts
         // with any existing data in the default database.
@@ -495,4 +1342,371 @@ mod tests {
         let result = cache.del::<TestData>(key).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_db_incr_decr() {
+        let cache = setup_cache_db().await;
+        let key = "test_counter";
+        cache.del::<i64>(key).await.unwrap();
+
+        assert_eq!(cache.incr(key, 1).await.unwrap(), 1);
+        assert_eq!(cache.incr(key, 1).await.unwrap(), 2);
+        assert_eq!(cache.incr(key, 5).await.unwrap(), 7);
+        assert_eq!(cache.decr(key, 3).await.unwrap(), 4);
+
+        cache.del::<i64>(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_db_get_or_set() {
+        let cache = setup_cache_db().await;
+        let key = "test_get_or_set";
+        cache.del::<TestData>(key).await.unwrap();
+
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let compute = || async {
+            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(TestData {
+                a: 7,
+                b: "computed".to_string(),
+            })
+        };
+
+        let first = cache.get_or_set(key, None, compute).await.unwrap();
+        let second = cache.get_or_set(key, None, compute).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // 计算失败时不写入缓存
+        let failed_key = "test_get_or_set_failed";
+        let result: Result<TestData, RedisError> = cache
+            .get_or_set(failed_key, None, || async {
+                Err(RedisError::from((ErrorKind::TypeError, "compute failed")))
+            })
+            .await;
+        assert!(result.is_err());
+        assert!(!cache.exists::<TestData>(failed_key).await.unwrap());
+
+        cache.del::<TestData>(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_db_scan_del_matching() {
+        let cache = setup_cache_db().await;
+        for key in ["user:123:profile", "user:123:orders", "user:124:profile"] {
+            cache.set(key, 1i64, None).await.unwrap();
+        }
+
+        let mut keys = cache.scan("user:123:*").await.unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["user:123:orders", "user:123:profile"]);
+
+        assert_eq!(cache.del_matching("user:123:*").await.unwrap(), 2);
+        assert!(!cache.exists::<i64>("user:123:profile").await.unwrap());
+        assert!(!cache.exists::<i64>("user:123:orders").await.unwrap());
+        assert!(cache.exists::<i64>("user:124:profile").await.unwrap());
+
+        assert_eq!(cache.del_matching("user:124:*").await.unwrap(), 1);
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    use crate::asyncdatabase::sqlite::SqliteDatabase;
+
+    #[cfg(feature = "sqlite_async")]
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct CachedUser {
+        id: i64,
+        name: String,
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    struct CachedUserDao {
+        database: SqliteDatabase,
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    impl Dao<CachedUser> for CachedUserDao {
+        type Database = SqliteDatabase;
+
+        fn new(database: Self::Database) -> Self {
+            CachedUserDao { database }
+        }
+
+        fn database(&self) -> &Self::Database {
+            &self.database
+        }
+
+        fn table_name() -> String {
+            "cached_users".to_string()
+        }
+
+        fn primary_key_column() -> String {
+            "id".to_string()
+        }
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    #[tokio::test]
+    #[serial]
+    async fn test_cached_dao_find_by_id() {
+        use crate::asyncdatabase::{DatabaseConfig, QueryObserver, RelationalDatabase};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Debug, Default)]
+        struct QueryCounter(AtomicUsize);
+
+        impl QueryObserver for QueryCounter {
+            fn on_query(&self, _sql: &str, _param_count: usize, _elapsed: Duration) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let counter = Arc::new(QueryCounter::default());
+        let db = SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            observer: Some(counter.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        db.execute(
+            "CREATE TABLE cached_users (id INTEGER PRIMARY KEY, name TEXT NOT NULL)",
+            vec![],
+        )
+        .await
+        .unwrap();
+
+        let cached = CachedDao::new(CachedUserDao::new(db), setup_cache().await)
+            .with_ttl(Duration::from_secs(60));
+        let user = CachedUser {
+            id: 1,
+            name: "alice".to_string(),
+        };
+        cached.create(&user).await.unwrap();
+
+        let before = counter.0.load(Ordering::SeqCst);
+        let first: Option<CachedUser> = cached.find_by_id(Value::Bigint(1)).await.unwrap();
+        assert_eq!(first, Some(user.clone()));
+        assert_eq!(counter.0.load(Ordering::SeqCst), before + 1);
+
+        // 第二次命中 Redis, 不再访问数据库
+        let second: Option<CachedUser> = cached.find_by_id(Value::Bigint(1)).await.unwrap();
+        assert_eq!(second, Some(user.clone()));
+        assert_eq!(counter.0.load(Ordering::SeqCst), before + 1);
+
+        // 更新后缓存失效, 重新从数据库加载
+        let renamed = CachedUser {
+            id: 1,
+            name: "bob".to_string(),
+        };
+        cached.update(&renamed).await.unwrap();
+        let third: Option<CachedUser> = cached.find_by_id(Value::Bigint(1)).await.unwrap();
+        assert_eq!(third, Some(renamed));
+
+        cached.delete::<CachedUser>(Value::Bigint(1)).await.unwrap();
+        let gone: Option<CachedUser> = cached.find_by_id(Value::Bigint(1)).await.unwrap();
+        assert_eq!(gone, None);
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    #[tokio::test]
+    #[serial]
+    async fn test_cached_dao_survives_dropped_table() {
+        use crate::asyncdatabase::{DatabaseConfig, RelationalDatabase};
+
+        let db = SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        db.execute(
+            "CREATE TABLE cached_users (id INTEGER PRIMARY KEY, name TEXT NOT NULL)",
+            vec![],
+        )
+        .await
+        .unwrap();
+
+        let cached = CachedDao::new(CachedUserDao::new(db.clone()), setup_cache().await)
+            .with_ttl(Duration::from_secs(60));
+        let user = CachedUser {
+            id: 2,
+            name: "carol".to_string(),
+        };
+        cached.create(&user).await.unwrap();
+        let first: Option<CachedUser> = cached.find_by_id(Value::Bigint(2)).await.unwrap();
+        assert_eq!(first, Some(user.clone()));
+
+        // 表已删除, 第二次只能从缓存取到
+        db.execute("DROP TABLE cached_users", vec![]).await.unwrap();
+        let second: Option<CachedUser> = cached.find_by_id(Value::Bigint(2)).await.unwrap();
+        assert_eq!(second, Some(user));
+        assert!(cached.dao().find_by_id(Value::Bigint(2)).await.is_err());
+
+        cached.cache().del("cached_users:2").await.unwrap();
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    #[tokio::test]
+    async fn test_cached_dao_writes_when_invalidation_fails() {
+        use crate::asyncdatabase::{DatabaseConfig, RelationalDatabase};
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let db = SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        db.execute(
+            "CREATE TABLE cached_users (id INTEGER PRIMARY KEY, name TEXT NOT NULL)",
+            vec![],
+        )
+        .await
+        .unwrap();
+
+        // 锁中毒后缓存的每个操作都返回错误
+        let cache = InMemoryCache::<CachedUser>::new();
+        let _ = catch_unwind(AssertUnwindSafe(|| {
+            let _entries = cache.entries.lock().unwrap();
+            panic!("poison the cache lock");
+        }));
+        assert!(cache.del("cached_users:3").await.is_err());
+
+        let cached = CachedDao::new(CachedUserDao::new(db), cache);
+        let user = CachedUser {
+            id: 3,
+            name: "dave".to_string(),
+        };
+        assert_eq!(cached.create(&user).await.unwrap(), 1);
+        let renamed = CachedUser {
+            id: 3,
+            name: "erin".to_string(),
+        };
+        assert_eq!(cached.update(&renamed).await.unwrap(), 1);
+        let found: Option<CachedUser> = cached.find_by_id(Value::Bigint(3)).await.unwrap();
+        assert_eq!(found, Some(renamed));
+        assert_eq!(
+            cached.delete::<CachedUser>(Value::Bigint(3)).await.unwrap(),
+            1
+        );
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct CachedFavorite {
+        user_id: i64,
+        product_id: i64,
+        note: String,
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    struct CachedFavoriteDao {
+        database: SqliteDatabase,
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    impl Dao<CachedFavorite> for CachedFavoriteDao {
+        type Database = SqliteDatabase;
+
+        fn new(database: Self::Database) -> Self {
+            CachedFavoriteDao { database }
+        }
+
+        fn database(&self) -> &Self::Database {
+            &self.database
+        }
+
+        fn table_name() -> String {
+            "cached_favorites".to_string()
+        }
+
+        fn primary_key_column() -> String {
+            "user_id".to_string()
+        }
+
+        fn primary_key_columns() -> Vec<String> {
+            vec!["user_id".to_string(), "product_id".to_string()]
+        }
+    }
+
+    #[cfg(feature = "sqlite_async")]
+    #[tokio::test]
+    async fn test_cached_dao_composite_key() {
+        use crate::asyncdatabase::{DatabaseConfig, RelationalDatabase};
+
+        let db = SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        db.execute(
+            "CREATE TABLE cached_favorites (user_id INTEGER NOT NULL, product_id INTEGER NOT NULL, \
+             note TEXT NOT NULL, PRIMARY KEY (user_id, product_id))",
+            vec![],
+        )
+        .await
+        .unwrap();
+
+        let cached = CachedDao::new(
+            CachedFavoriteDao::new(db),
+            InMemoryCache::<CachedFavorite>::new(),
+        );
+        let first = CachedFavorite {
+            user_id: 1,
+            product_id: 1,
+            note: "first".to_string(),
+        };
+        let second = CachedFavorite {
+            user_id: 1,
+            product_id: 2,
+            note: "second".to_string(),
+        };
+        cached.create(&first).await.unwrap();
+        cached.create(&second).await.unwrap();
+
+        // 两行共享 user_id, 缓存键仍然各不相同
+        let found: Option<CachedFavorite> = cached
+            .find_by_key(vec![Value::Bigint(1), Value::Bigint(1)])
+            .await
+            .unwrap();
+        assert_eq!(found, Some(first.clone()));
+        let found: Option<CachedFavorite> = cached
+            .find_by_key(vec![Value::Bigint(1), Value::Bigint(2)])
+            .await
+            .unwrap();
+        assert_eq!(found, Some(second.clone()));
+        assert!(cached.cache.exists("cached_favorites:1:1").await.unwrap());
+        assert!(cached.cache.exists("cached_favorites:1:2").await.unwrap());
+
+        // 更新只失效对应的那一行
+        let renamed = CachedFavorite {
+            note: "renamed".to_string(),
+            ..first
+        };
+        assert_eq!(cached.update(&renamed).await.unwrap(), 1);
+        assert!(!cached.cache.exists("cached_favorites:1:1").await.unwrap());
+        assert!(cached.cache.exists("cached_favorites:1:2").await.unwrap());
+        let found: Option<CachedFavorite> = cached
+            .find_by_key(vec![Value::Bigint(1), Value::Bigint(1)])
+            .await
+            .unwrap();
+        assert_eq!(found, Some(renamed));
+
+        assert_eq!(
+            cached
+                .delete_by_key::<CachedFavorite>(vec![Value::Bigint(1), Value::Bigint(2)])
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(!cached.cache.exists("cached_favorites:1:2").await.unwrap());
+        let found: Option<CachedFavorite> = cached
+            .find_by_key(vec![Value::Bigint(1), Value::Bigint(2)])
+            .await
+            .unwrap();
+        assert_eq!(found, None);
+    }
 }
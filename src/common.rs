@@ -1,5 +1,354 @@
+use std::sync::Arc;
+use std::time::Duration;
 use std::{error::Error, fmt};
 
+/// 查询观察者, 每条语句执行完毕后被调用
+///
+/// 只会拿到 SQL 文本和参数个数, 参数值本身不会传出, 避免邮箱等敏感数据进入日志或监控
+pub trait QueryObserver: Send + Sync {
+    fn on_query(&self, sql: &str, param_count: usize, elapsed: Duration);
+
+    /// 耗时超过 slow_query_threshold 的语句, 在 on_query 之后调用
+    fn on_slow_query(&self, _sql: &str, _param_count: usize, _elapsed: Duration) {}
+
+    /// 从连接池取连接耗时超过 slow_acquire_threshold 时调用, 取连接失败也算在内
+    fn on_slow_acquire(&self, _elapsed: Duration) {}
+}
+
+impl fmt::Debug for dyn QueryObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "QueryObserver")
+    }
+}
+
+#[cfg(any(
+    feature = "sqlite",
+    feature = "postgresql",
+    feature = "mysql",
+    feature = "sqlite_async",
+    feature = "postgresql_async",
+    feature = "mysql_async"
+))]
+pub(crate) fn observe_query(
+    observer: &Option<Arc<dyn QueryObserver>>,
+    slow_query_threshold: Option<Duration>,
+    sql: &str,
+    param_count: usize,
+    start: std::time::Instant,
+) {
+    let elapsed = start.elapsed();
+    if let Some(observer) = observer {
+        observer.on_query(sql, param_count, elapsed);
+    }
+    if slow_query_threshold.is_some_and(|threshold| elapsed > threshold) {
+        log::warn!("slow query ({:?}): {}", elapsed, sql);
+        if let Some(observer) = observer {
+            observer.on_slow_query(sql, param_count, elapsed);
+        }
+    }
+}
+
+/// 按 SQL 文本缓存预编译语句, 超出容量时淘汰最久未使用的
+#[cfg(any(feature = "postgresql", feature = "postgresql_async"))]
+#[derive(Debug)]
+pub(crate) struct StatementCache<S> {
+    capacity: usize,
+    statements: std::collections::HashMap<String, S>,
+    // 最近使用的在队尾
+    order: std::collections::VecDeque<String>,
+    // 实际预编译的次数, 用于观察命中率
+    pub(crate) prepared: u64,
+}
+
+#[cfg(any(feature = "postgresql", feature = "postgresql_async"))]
+impl<S: Clone> StatementCache<S> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            statements: std::collections::HashMap::new(),
+            order: std::collections::VecDeque::new(),
+            prepared: 0,
+        }
+    }
+
+    pub(crate) fn get(&mut self, sql: &str) -> Option<S> {
+        let statement = self.statements.get(sql)?.clone();
+        self.touch(sql);
+        Some(statement)
+    }
+
+    pub(crate) fn insert(&mut self, sql: &str, statement: S) {
+        self.prepared += 1;
+        if self.capacity == 0 {
+            return;
+        }
+        if self.statements.insert(sql.to_string(), statement).is_some() {
+            self.touch(sql);
+            return;
+        }
+        self.order.push_back(sql.to_string());
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.statements.remove(&oldest);
+            }
+        }
+    }
+
+    // 语句执行失败时移除, 表结构变化后下次会重新预编译
+    pub(crate) fn remove(&mut self, sql: &str) {
+        if self.statements.remove(sql).is_some() {
+            self.order.retain(|s| s != sql);
+        }
+    }
+
+    fn touch(&mut self, sql: &str) {
+        if let Some(pos) = self.order.iter().position(|s| s == sql) {
+            if let Some(key) = self.order.remove(pos) {
+                self.order.push_back(key);
+            }
+        }
+    }
+}
+
+// 每个 :memory: 连接默认各自是一个独立的库, 换成带唯一名字的共享缓存库,
+// 让同一个连接池里的连接看到同一份数据
+#[cfg(any(feature = "sqlite", feature = "sqlite_async"))]
+pub(crate) fn sqlite_path(database_name: &str) -> String {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static MEMORY_DB_ID: AtomicUsize = AtomicUsize::new(0);
+
+    if database_name == ":memory:" {
+        format!(
+            "file:bootrust_memory_{}?mode=memory&cache=shared",
+            MEMORY_DB_ID.fetch_add(1, Ordering::Relaxed)
+        )
+    } else {
+        database_name.to_string()
+    }
+}
+
+/// 可主动关闭的连接池, 克隆之间共享同一个池
+///
+/// close 之后不再借出连接, 空闲连接随最后一个池句柄一起释放,
+/// 借出中的连接在归还时释放
+#[cfg(any(
+    feature = "sqlite",
+    feature = "postgresql",
+    feature = "mysql",
+    feature = "sqlite_async",
+    feature = "postgresql_async",
+    feature = "mysql_async"
+))]
+#[derive(Debug)]
+pub(crate) struct ClosablePool<P> {
+    inner: Arc<std::sync::RwLock<Option<P>>>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    slow_acquire_threshold: Option<Duration>,
+}
+
+#[cfg(any(
+    feature = "sqlite",
+    feature = "postgresql",
+    feature = "mysql",
+    feature = "sqlite_async",
+    feature = "postgresql_async",
+    feature = "mysql_async"
+))]
+impl<P> Clone for ClosablePool<P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            observer: self.observer.clone(),
+            slow_acquire_threshold: self.slow_acquire_threshold,
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "sqlite",
+    feature = "postgresql",
+    feature = "mysql",
+    feature = "sqlite_async",
+    feature = "postgresql_async",
+    feature = "mysql_async"
+))]
+impl<P: Clone> ClosablePool<P> {
+    pub(crate) fn new(pool: P, config: &DatabaseConfig) -> Self {
+        Self {
+            inner: Arc::new(std::sync::RwLock::new(Some(pool))),
+            observer: config.observer.clone(),
+            slow_acquire_threshold: config.slow_acquire_threshold,
+        }
+    }
+
+    /// 当前的池, 已关闭时返回 DbError::Closed
+    pub(crate) fn current(&self) -> Result<P, DbError> {
+        self.inner
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+            .ok_or(DbError::Closed)
+    }
+
+    pub(crate) fn close(&self) {
+        self.inner
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take();
+    }
+
+    // 取连接慢说明连接池不够用, 与慢查询分开报告
+    fn observe_acquire(&self, start: std::time::Instant) {
+        let elapsed = start.elapsed();
+        if self
+            .slow_acquire_threshold
+            .is_some_and(|threshold| elapsed > threshold)
+        {
+            log::warn!("slow connection acquire ({:?})", elapsed);
+            if let Some(observer) = &self.observer {
+                observer.on_slow_acquire(elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "sqlite",
+    feature = "postgresql",
+    feature = "mysql",
+    feature = "sqlite_async",
+    feature = "mysql_async"
+))]
+impl<M: r2d2::ManageConnection> ClosablePool<r2d2::Pool<M>> {
+    /// 从池中取连接, 池的错误由 error 包装
+    pub(crate) fn get(
+        &self,
+        error: fn(ErrorDetail) -> DbError,
+    ) -> Result<r2d2::PooledConnection<M>, DbError> {
+        let pool = self.current()?;
+        let start = std::time::Instant::now();
+        let conn = pool.get();
+        self.observe_acquire(start);
+        conn.map_err(|e| error(ErrorDetail::new(e)))
+    }
+}
+
+/// 在单独的线程中执行 f, 超时未返回时返回 DbError::Timeout
+///
+/// 用于同步后端的健康检查, 服务器挂起时调用方不会一直阻塞; 线程在 f 结束后退出
+#[cfg(any(feature = "postgresql", feature = "mysql"))]
+pub(crate) fn run_with_timeout<T, F>(timeout: Duration, f: F) -> Result<T, DbError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, DbError> + Send + 'static,
+{
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+    receiver
+        .recv_timeout(timeout)
+        .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?
+}
+
+// 要求 TLS 却没有启用 tls-rustls 或 tls-native 时直接报错, 不退回明文连接
+#[cfg(any(
+    feature = "postgresql",
+    feature = "mysql",
+    feature = "postgresql_async",
+    feature = "mysql_async"
+))]
+pub(crate) fn check_tls(config: &DatabaseConfig) -> Result<(), DbError> {
+    if config.tls && cfg!(not(any(feature = "tls-rustls", feature = "tls-native"))) {
+        return Err(DbError::ConnectionError(
+            "TLS requested but neither tls-rustls nor tls-native is enabled"
+                .to_string()
+                .into(),
+        ));
+    }
+    Ok(())
+}
+
+// Postgres 连接使用的 TLS 实现, 两个特性同时启用时 rustls 优先
+#[cfg(all(
+    any(feature = "postgresql", feature = "postgresql_async"),
+    feature = "tls-rustls"
+))]
+pub(crate) type PgTls = tokio_postgres_rustls::MakeRustlsConnect;
+#[cfg(all(
+    any(feature = "postgresql", feature = "postgresql_async"),
+    feature = "tls-native",
+    not(feature = "tls-rustls")
+))]
+pub(crate) type PgTls = postgres_native_tls::MakeTlsConnector;
+#[cfg(all(
+    feature = "postgresql_async",
+    not(any(feature = "tls-rustls", feature = "tls-native"))
+))]
+pub(crate) type PgTls = tokio_postgres::NoTls;
+#[cfg(all(
+    feature = "postgresql",
+    not(feature = "postgresql_async"),
+    not(any(feature = "tls-rustls", feature = "tls-native"))
+))]
+pub(crate) type PgTls = postgres::NoTls;
+
+/// 按启用的特性创建 Postgres 的 TLS 连接器, 是否真正加密由 DatabaseConfig::tls 决定
+///
+/// rustls 使用 webpki-roots 内置的根证书, native-tls 使用系统证书
+#[cfg(any(feature = "postgresql", feature = "postgresql_async"))]
+pub(crate) fn pg_tls(config: &DatabaseConfig) -> Result<PgTls, DbError> {
+    check_tls(config)?;
+    #[cfg(feature = "tls-rustls")]
+    let tls = {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let client = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        tokio_postgres_rustls::MakeRustlsConnect::new(client)
+    };
+    #[cfg(all(feature = "tls-native", not(feature = "tls-rustls")))]
+    let tls = native_tls::TlsConnector::new()
+        .map(postgres_native_tls::MakeTlsConnector::new)
+        .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
+    #[cfg(not(any(feature = "tls-rustls", feature = "tls-native")))]
+    let tls = PgTls {};
+    Ok(tls)
+}
+
+#[cfg(feature = "postgresql_async")]
+impl<M: bb8::ManageConnection> ClosablePool<bb8::Pool<M>>
+where
+    M::Error: Error + Send + Sync + 'static,
+{
+    /// 从池中取连接, 池的错误由 error 包装
+    pub(crate) async fn get_owned(
+        &self,
+        error: fn(ErrorDetail) -> DbError,
+    ) -> Result<bb8::PooledConnection<'static, M>, DbError> {
+        let pool = self.current()?;
+        let start = std::time::Instant::now();
+        let conn = pool.get_owned().await;
+        self.observe_acquire(start);
+        conn.map_err(|e| error(ErrorDetail::new(e)))
+    }
+}
+
+/// ReplicatedDatabase 的读请求路由方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadPolicy {
+    /// 读请求在各从库间轮询
+    #[default]
+    RoundRobinReplicas,
+    /// 读请求也走主库, 只有 query_replica 会访问从库
+    PrimaryOnly,
+}
+
+#[derive(Clone)]
 pub struct DatabaseConfig {
     pub host: String,
     pub port: u16,
@@ -7,43 +356,341 @@ pub struct DatabaseConfig {
     pub password: String,
     pub database_name: String,
     pub max_size: u32,
+    pub observer: Option<Arc<dyn QueryObserver>>,
+    // 只读从库, 由 ReplicatedDatabase 使用
+    pub replica_config: Option<Box<DatabaseConfig>>,
+    // 其他从库地址, 账号和库名等与主库相同
+    pub replica_hosts: Vec<(String, u16)>,
+    pub read_policy: ReadPolicy,
+    // SQLite 建立连接后执行的 PRAGMA, 如 ("journal_mode", "WAL")
+    pub pragmas: Vec<(String, String)>,
+    // 以只读方式打开, 目前仅 SQLite 支持
+    pub read_only: bool,
+    // 每个连接缓存的预编译语句数, 0 表示不缓存, 仅 Postgres 和 MySQL 使用
+    pub statement_cache_size: usize,
+    // 超过该耗时的语句以 warn 级别写日志, None 表示不检查
+    pub slow_query_threshold: Option<Duration>,
+    // 从连接池取连接超过该耗时以 warn 级别写日志, None 表示不检查
+    pub slow_acquire_threshold: Option<Duration>,
+    // 从连接池取出连接时先检查是否可用, 丢弃已被服务端断开的连接
+    pub test_on_check_out: bool,
+    // 连接池保持的最少空闲连接数, connect 时预先建立. None 时 r2d2 取 max_size, bb8 取 0
+    pub min_idle: Option<u32>,
+    // 使用 TLS 连接, 需要启用 tls-rustls 或 tls-native 特性, 仅 Postgres 和 MySQL 使用
+    pub tls: bool,
 }
 
 impl Default for DatabaseConfig {
     fn default() -> Self {
+        Self::from_env().unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl DatabaseConfig {
+    pub fn builder() -> DatabaseConfigBuilder {
+        DatabaseConfigBuilder::default()
+    }
+
+    /// 从 BOOTRUST_DB_* 环境变量读取配置, 未设置的项使用默认值
+    pub fn from_env() -> Result<Self, DbError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// 以当前配置为模板生成指向另一台主机的配置
+    pub fn with_host(&self, host: &str, port: u16) -> Self {
         Self {
-            host: std::env::var("BOOTRUST_DB_HOST").unwrap_or_else(|_| "localhost".to_string()),
-            port: std::env::var("BOOTRUST_DB_PORT")
-                .unwrap_or_else(|_| "3306".to_string())
-                .parse::<u16>()
-                .expect("DB_PORT must be a number"),
-            username: std::env::var("BOOTRUST_DB_USERNAME").unwrap_or_else(|_| "root".to_string()),
-            password: std::env::var("BOOTRUST_DB_PASSWORD")
-                .unwrap_or_else(|_| "password".to_string()),
-            database_name: std::env::var("BOOTRUST_DB_DATABASE")
-                .unwrap_or_else(|_| "bootrust_default_db".to_string()),
-            max_size: std::env::var("DB_MAX_SIZE")
-                .unwrap_or_else(|_| "20".to_string())
+            host: host.to_string(),
+            port,
+            username: self.username.clone(),
+            password: self.password.clone(),
+            database_name: self.database_name.clone(),
+            max_size: self.max_size,
+            observer: self.observer.clone(),
+            replica_config: None,
+            replica_hosts: Vec::new(),
+            read_policy: self.read_policy,
+            pragmas: self.pragmas.clone(),
+            read_only: self.read_only,
+            statement_cache_size: self.statement_cache_size,
+            slow_query_threshold: self.slow_query_threshold,
+            slow_acquire_threshold: self.slow_acquire_threshold,
+            test_on_check_out: self.test_on_check_out,
+            min_idle: self.min_idle,
+            tls: self.tls,
+        }
+    }
+
+    // 内置默认值, 不读取环境变量
+    fn builtin() -> Self {
+        Self::from_vars(|_| None).expect("built-in defaults are valid")
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, DbError> {
+        let number = |name: &str, default: &str| {
+            var(name)
+                .unwrap_or_else(|| default.to_string())
                 .parse::<u32>()
-                .expect("DB_MAX_SIZE must be a number"),
+                .map_err(|_| DbError::ConversionError(format!("{} must be a number", name)))
+        };
+        let port = number("BOOTRUST_DB_PORT", "3306")?;
+        Ok(Self {
+            host: var("BOOTRUST_DB_HOST").unwrap_or_else(|| "localhost".to_string()),
+            port: u16::try_from(port).map_err(|_| {
+                DbError::ConversionError("BOOTRUST_DB_PORT must be a port number".to_string())
+            })?,
+            username: var("BOOTRUST_DB_USERNAME").unwrap_or_else(|| "root".to_string()),
+            password: var("BOOTRUST_DB_PASSWORD").unwrap_or_else(|| "password".to_string()),
+            database_name: var("BOOTRUST_DB_DATABASE")
+                .unwrap_or_else(|| "bootrust_default_db".to_string()),
+            max_size: number("DB_MAX_SIZE", "20")?,
+            observer: None,
+            replica_config: None,
+            replica_hosts: Vec::new(),
+            read_policy: ReadPolicy::default(),
+            pragmas: Vec::new(),
+            read_only: false,
+            statement_cache_size: 64,
+            slow_query_threshold: None,
+            slow_acquire_threshold: None,
+            test_on_check_out: true,
+            min_idle: None,
+            tls: false,
+        })
+    }
+}
+
+/// 逐项设置 DatabaseConfig, 未设置的项取内置默认值, 不读取环境变量
+#[derive(Default)]
+pub struct DatabaseConfigBuilder {
+    host: Option<String>,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    database_name: Option<String>,
+    max_size: Option<u32>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    replica_config: Option<DatabaseConfig>,
+    replica_hosts: Vec<(String, u16)>,
+    read_policy: ReadPolicy,
+    pragmas: Vec<(String, String)>,
+    read_only: bool,
+    statement_cache_size: Option<usize>,
+    slow_query_threshold: Option<Duration>,
+    slow_acquire_threshold: Option<Duration>,
+    test_on_check_out: Option<bool>,
+    min_idle: Option<u32>,
+    tls: bool,
+}
+
+impl DatabaseConfigBuilder {
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn database_name(mut self, database_name: impl Into<String>) -> Self {
+        self.database_name = Some(database_name.into());
+        self
+    }
+
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    pub fn observer(mut self, observer: Arc<dyn QueryObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub fn replica(mut self, replica_config: DatabaseConfig) -> Self {
+        self.replica_config = Some(replica_config);
+        self
+    }
+
+    pub fn replica_host(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.replica_hosts.push((host.into(), port));
+        self
+    }
+
+    pub fn read_policy(mut self, read_policy: ReadPolicy) -> Self {
+        self.read_policy = read_policy;
+        self
+    }
+
+    pub fn pragma(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.pragmas.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn statement_cache_size(mut self, size: usize) -> Self {
+        self.statement_cache_size = Some(size);
+        self
+    }
+
+    pub fn slow_query_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_query_threshold = Some(threshold);
+        self
+    }
+
+    pub fn slow_acquire_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_acquire_threshold = Some(threshold);
+        self
+    }
+
+    /// 默认开启; 关闭后省去每次取连接时的检查, 但连接空闲超时被服务端断开后,
+    /// 下一条语句会失败
+    pub fn test_on_check_out(mut self, test_on_check_out: bool) -> Self {
+        self.test_on_check_out = Some(test_on_check_out);
+        self
+    }
+
+    /// connect 时预先建立的连接数, 之后空闲连接少于此数时在后台补足
+    pub fn min_idle(mut self, min_idle: u32) -> Self {
+        self.min_idle = Some(min_idle);
+        self
+    }
+
+    pub fn tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn build(self) -> DatabaseConfig {
+        let defaults = DatabaseConfig::builtin();
+        DatabaseConfig {
+            host: self.host.unwrap_or(defaults.host),
+            port: self.port.unwrap_or(defaults.port),
+            username: self.username.unwrap_or(defaults.username),
+            password: self.password.unwrap_or(defaults.password),
+            database_name: self.database_name.unwrap_or(defaults.database_name),
+            max_size: self.max_size.unwrap_or(defaults.max_size),
+            observer: self.observer,
+            replica_config: self.replica_config.map(Box::new),
+            replica_hosts: self.replica_hosts,
+            read_policy: self.read_policy,
+            pragmas: self.pragmas,
+            read_only: self.read_only,
+            statement_cache_size: self
+                .statement_cache_size
+                .unwrap_or(defaults.statement_cache_size),
+            slow_query_threshold: self.slow_query_threshold,
+            slow_acquire_threshold: self.slow_acquire_threshold,
+            test_on_check_out: self.test_on_check_out.unwrap_or(defaults.test_on_check_out),
+            min_idle: self.min_idle,
+            tls: self.tls,
         }
     }
 }
 
+/// 错误信息, 由驱动错误转换而来时保留原始错误, 通过 `DbError::source` 取得
+#[derive(Debug)]
+pub struct ErrorDetail {
+    message: String,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl ErrorDetail {
+    /// 包装原始错误, 信息取原始错误的 Display
+    pub fn new<E: Error + Send + Sync + 'static>(source: E) -> Self {
+        Self::with_source(source.to_string(), source)
+    }
+
+    /// 自定义信息, 同时保留原始错误
+    pub fn with_source<E: Error + Send + Sync + 'static>(
+        message: impl Into<String>,
+        source: E,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
+    }
+}
+
+impl From<String> for ErrorDetail {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            source: None,
+        }
+    }
+}
+
+impl From<&str> for ErrorDetail {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl fmt::Display for ErrorDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
 #[derive(Debug)]
 pub enum QueryErrorKind {
-    SyntaxError(String),
-    ForeignKeyViolation(String),
-    UniqueViolation(String),
-    NotNullViolation(String),
-    CheckViolation(String),
-    ExclusionViolation(String),
-    Other(String),
+    SyntaxError(ErrorDetail),
+    ForeignKeyViolation(ErrorDetail),
+    UniqueViolation(ErrorDetail),
+    NotNullViolation(ErrorDetail),
+    CheckViolation(ErrorDetail),
+    ExclusionViolation(ErrorDetail),
+    Other(ErrorDetail),
+}
+
+impl QueryErrorKind {
+    fn detail(&self) -> &ErrorDetail {
+        match self {
+            QueryErrorKind::SyntaxError(detail)
+            | QueryErrorKind::ForeignKeyViolation(detail)
+            | QueryErrorKind::UniqueViolation(detail)
+            | QueryErrorKind::NotNullViolation(detail)
+            | QueryErrorKind::CheckViolation(detail)
+            | QueryErrorKind::ExclusionViolation(detail)
+            | QueryErrorKind::Other(detail) => detail,
+        }
+    }
 }
 
 impl From<String> for QueryErrorKind {
     fn from(s: String) -> Self {
-        QueryErrorKind::Other(s)
+        QueryErrorKind::Other(s.into())
+    }
+}
+
+impl From<ErrorDetail> for QueryErrorKind {
+    fn from(detail: ErrorDetail) -> Self {
+        QueryErrorKind::Other(detail)
     }
 }
 
@@ -62,13 +709,26 @@ impl fmt::Display for QueryErrorKind {
 }
 
 // 定义通用的数据库错误类型
+//
+// 连接, 查询, 事务, 连接池和超时错误多由驱动错误转换而来, 用 ErrorDetail 保留原始错误
 #[derive(Debug)]
 pub enum DbError {
-    ConnectionError(String),
+    ConnectionError(ErrorDetail),
     QueryError(QueryErrorKind),
-    TransactionError(String),
-    PoolError(String),
+    TransactionError(ErrorDetail),
+    PoolError(ErrorDetail),
     ConversionError(String),
+    Timeout(ErrorDetail),
+    // 乐观锁版本号不匹配, 或按主键操作时影响了多行
+    Conflict(String),
+    // 按主键操作时没有命中任何记录
+    NotFound(String),
+    // 拼入 SQL 的列名, 排序等标识符不合法
+    InvalidIdentifier(String),
+    // 构造器中给出的参数个数与占位符个数不一致
+    ParameterCountMismatch { expected: usize, actual: usize },
+    // 调用 close 之后继续使用数据库
+    Closed,
     // 其他错误类型...
 }
 
@@ -80,13 +740,30 @@ impl fmt::Display for DbError {
             DbError::TransactionError(msg) => write!(f, "Transaction error: {}", msg),
             DbError::PoolError(msg) => write!(f, "Pool error: {}", msg),
             DbError::ConversionError(msg) => write!(f, "Conversion error: {}", msg),
+            DbError::Timeout(msg) => write!(f, "Timeout: {}", msg),
+            DbError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            DbError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            DbError::InvalidIdentifier(msg) => write!(f, "Invalid identifier: {}", msg),
+            DbError::ParameterCountMismatch { expected, actual } => write!(
+                f,
+                "Parameter count mismatch: expected {} values, got {}",
+                expected, actual
+            ),
+            DbError::Closed => write!(f, "Database closed"),
         }
     }
 }
 
 impl Error for DbError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
+        match self {
+            DbError::ConnectionError(detail)
+            | DbError::TransactionError(detail)
+            | DbError::PoolError(detail)
+            | DbError::Timeout(detail) => detail.source(),
+            DbError::QueryError(kind) => kind.detail().source(),
+            _ => None,
+        }
     }
 }
 
@@ -104,6 +781,17 @@ pub enum Value {
     Byte(u8),
     Bytes(Vec<u8>),
     DateTime(chrono::DateTime<chrono::Utc>),
+    // 数组, Postgres 对应数组列, 其他数据库及 BYTEA 列以 bincode 编码存为二进制
+    Array(Vec<Value>),
+    // Postgres 的 SMALLINT, MySQL 的 SMALLINT 和无符号 TINYINT
+    Smallint(i16),
+    // MySQL 的 TINYINT
+    TinyInt(i8),
+    // MySQL 的 BIGINT UNSIGNED, 其他数据库超出 i64 范围时报错
+    UBigint(u64),
+    // 时间间隔, Postgres 的 INTERVAL, MySQL 的 TIME
+    // 月和年的长度不固定, Postgres 的月按 30 天换算, 精度为微秒
+    Interval(chrono::Duration),
     // 其他数据类型...
 }
 
@@ -117,6 +805,18 @@ impl<T: Into<Value>> From<Option<T>> for Value {
     }
 }
 
+impl From<i8> for Value {
+    fn from(v: i8) -> Self {
+        Value::TinyInt(v)
+    }
+}
+
+impl From<i16> for Value {
+    fn from(v: i16) -> Self {
+        Value::Smallint(v)
+    }
+}
+
 impl From<i32> for Value {
     fn from(v: i32) -> Self {
         Value::Int(v)
@@ -129,6 +829,18 @@ impl From<i64> for Value {
     }
 }
 
+impl From<u64> for Value {
+    fn from(v: u64) -> Self {
+        Value::UBigint(v)
+    }
+}
+
+impl From<chrono::Duration> for Value {
+    fn from(v: chrono::Duration) -> Self {
+        Value::Interval(v)
+    }
+}
+
 impl From<f32> for Value {
     fn from(v: f32) -> Self {
         Value::Float(v)
@@ -171,14 +883,312 @@ impl From<chrono::DateTime<chrono::Utc>> for Value {
     }
 }
 
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+
+// Value 转回 Rust 类型, 类型不匹配时返回 ConversionError
+macro_rules! try_from_value {
+    ($ty:ty, $name:expr, $($pattern:pat => $result:expr),+) => {
+        impl TryFrom<Value> for $ty {
+            type Error = DbError;
+
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                match value {
+                    $($pattern => $result,)+
+                    other => Err(DbError::ConversionError(format!(
+                        "expected {}, found {:?}",
+                        $name, other
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+try_from_value!(i8, "i8",
+    Value::TinyInt(v) => Ok(v),
+    Value::Bigint(v) => i8::try_from(v)
+        .map_err(|_| DbError::ConversionError(format!("{} out of range for i8", v)))
+);
+try_from_value!(i16, "i16",
+    Value::Smallint(v) => Ok(v),
+    Value::TinyInt(v) => Ok(v.into()),
+    Value::Bigint(v) => i16::try_from(v)
+        .map_err(|_| DbError::ConversionError(format!("{} out of range for i16", v)))
+);
+try_from_value!(i32, "i32",
+    Value::Int(v) => Ok(v),
+    Value::Smallint(v) => Ok(v.into()),
+    Value::TinyInt(v) => Ok(v.into()),
+    // SQLite 的整数都以 Bigint 返回
+    Value::Bigint(v) => i32::try_from(v)
+        .map_err(|_| DbError::ConversionError(format!("{} out of range for i32", v)))
+);
+try_from_value!(i64, "i64",
+    Value::Bigint(v) => Ok(v),
+    Value::Int(v) => Ok(v as i64),
+    Value::Smallint(v) => Ok(v.into()),
+    Value::TinyInt(v) => Ok(v.into())
+);
+try_from_value!(u64, "u64",
+    Value::UBigint(v) => Ok(v),
+    Value::Bigint(v) => u64::try_from(v)
+        .map_err(|_| DbError::ConversionError(format!("{} out of range for u64", v)))
+);
+try_from_value!(chrono::Duration, "Duration", Value::Interval(v) => Ok(v));
+try_from_value!(f32, "f32", Value::Float(v) => Ok(v));
+try_from_value!(f64, "f64",
+    Value::Double(v) => Ok(v),
+    Value::Float(v) => Ok(v as f64)
+);
+try_from_value!(String, "String",
+    Value::Text(v) => Ok(v),
+    Value::Varchar(v) => Ok(v)
+);
+try_from_value!(bool, "bool", Value::Boolean(v) => Ok(v));
+try_from_value!(u8, "u8", Value::Byte(v) => Ok(v));
+try_from_value!(Vec<u8>, "Vec<u8>", Value::Bytes(v) => Ok(v));
+try_from_value!(chrono::DateTime<chrono::Utc>, "DateTime<Utc>",
+    Value::DateTime(v) => Ok(v)
+);
+
+// 没有数组类型的数据库中, 数组以 bincode 编码存储, 读取时由 deserialize_seq 还原
+#[cfg(any(
+    feature = "sqlite",
+    feature = "postgresql",
+    feature = "mysql",
+    feature = "sqlite_async",
+    feature = "postgresql_async",
+    feature = "mysql_async"
+))]
+pub(crate) fn encode_array(values: &[Value]) -> Vec<u8> {
+    bincode::serialize(values).expect("Value is always serializable")
+}
+
+#[cfg(feature = "postgresql_async")]
+use tokio_postgres::types as pg_types;
+
+#[cfg(all(not(feature = "postgresql_async"), feature = "postgresql"))]
+use postgres::types as pg_types;
+
+// 绑定 Postgres 参数, 数组按数组列的元素类型逐个编码
+#[cfg(any(feature = "postgresql", feature = "postgresql_async"))]
+impl pg_types::ToSql for Value {
+    fn to_sql(
+        &self,
+        ty: &pg_types::Type,
+        out: &mut bytes::BytesMut,
+    ) -> Result<pg_types::IsNull, Box<dyn Error + Sync + Send>> {
+        match self {
+            Value::Null => Ok(pg_types::IsNull::Yes),
+            Value::Int(v) => v.to_sql_checked(ty, out),
+            Value::Bigint(v) => v.to_sql_checked(ty, out),
+            Value::Float(v) => v.to_sql_checked(ty, out),
+            Value::Double(v) => v.to_sql_checked(ty, out),
+            Value::Text(v) | Value::Varchar(v) => v.to_sql_checked(ty, out),
+            Value::Boolean(v) => v.to_sql_checked(ty, out),
+            Value::Byte(v) => i16::from(*v).to_sql_checked(ty, out),
+            Value::Smallint(v) => v.to_sql_checked(ty, out),
+            Value::TinyInt(v) => i16::from(*v).to_sql_checked(ty, out),
+            Value::UBigint(v) => i64::try_from(*v)?.to_sql_checked(ty, out),
+            Value::Bytes(v) => v.to_sql_checked(ty, out),
+            Value::DateTime(v) => v.to_sql_checked(ty, out),
+            Value::Array(values) if *ty == pg_types::Type::BYTEA => {
+                encode_array(values).to_sql_checked(ty, out)
+            }
+            Value::Array(values) => values.to_sql_checked(ty, out),
+            Value::Interval(v) if *ty == pg_types::Type::INTERVAL => {
+                // 二进制格式: 微秒, 天, 月; 全部折算为微秒
+                let micros = v.num_microseconds().ok_or("interval out of range")?;
+                out.extend_from_slice(&micros.to_be_bytes());
+                out.extend_from_slice(&0i32.to_be_bytes());
+                out.extend_from_slice(&0i32.to_be_bytes());
+                Ok(pg_types::IsNull::No)
+            }
+            Value::Interval(_) => Err(format!("interval cannot be bound to {}", ty).into()),
+            Value::Table(_) => Err("table value cannot be bound as a parameter".into()),
+        }
+    }
+
+    // 只接受非 NULL 值能编码的类型, NULL 在 to_sql_checked 中对任意类型放行
+    fn accepts(ty: &pg_types::Type) -> bool {
+        use pg_types::ToSql;
+        <i16 as ToSql>::accepts(ty)
+            || <i32 as ToSql>::accepts(ty)
+            || <i64 as ToSql>::accepts(ty)
+            || <f32 as ToSql>::accepts(ty)
+            || <f64 as ToSql>::accepts(ty)
+            || <String as ToSql>::accepts(ty)
+            || <bool as ToSql>::accepts(ty)
+            || <Vec<u8> as ToSql>::accepts(ty)
+            || <chrono::DateTime<chrono::Utc> as ToSql>::accepts(ty)
+            || <Vec<Value> as ToSql>::accepts(ty)
+            || *ty == pg_types::Type::INTERVAL
+    }
+
+    fn to_sql_checked(
+        &self,
+        ty: &pg_types::Type,
+        out: &mut bytes::BytesMut,
+    ) -> Result<pg_types::IsNull, Box<dyn Error + Sync + Send>> {
+        if !matches!(self, Value::Null) && !<Self as pg_types::ToSql>::accepts(ty) {
+            return Err(Box::new(pg_types::WrongType::new::<Value>(ty.clone())));
+        }
+        self.to_sql(ty, out)
+    }
+}
+
+// 读取 Postgres 列, 数组元素的类型取自数组列的元素类型
+#[cfg(any(feature = "postgresql", feature = "postgresql_async"))]
+impl<'a> pg_types::FromSql<'a> for Value {
+    fn from_sql(ty: &pg_types::Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        use pg_types::{Kind, Type};
+
+        if let Kind::Array(_) = ty.kind() {
+            let values = Vec::<Option<Value>>::from_sql(ty, raw)?;
+            return Ok(Value::Array(
+                values
+                    .into_iter()
+                    .map(|v| v.unwrap_or(Value::Null))
+                    .collect(),
+            ));
+        }
+        match *ty {
+            Type::INT2 => Ok(Value::Smallint(i16::from_sql(ty, raw)?)),
+            Type::INT4 => Ok(Value::Int(i32::from_sql(ty, raw)?)),
+            Type::INT8 => Ok(Value::Bigint(i64::from_sql(ty, raw)?)),
+            Type::FLOAT4 => Ok(Value::Float(f32::from_sql(ty, raw)?)),
+            Type::FLOAT8 => Ok(Value::Double(f64::from_sql(ty, raw)?)),
+            Type::BOOL => Ok(Value::Boolean(bool::from_sql(ty, raw)?)),
+            Type::BYTEA => Ok(Value::Bytes(Vec::<u8>::from_sql(ty, raw)?)),
+            Type::TIMESTAMPTZ => Ok(Value::DateTime(chrono::DateTime::from_sql(ty, raw)?)),
+            Type::INTERVAL => Ok(Value::Interval(decode_interval(raw)?)),
+            _ => Ok(Value::Text(String::from_sql(ty, raw)?)),
+        }
+    }
+
+    fn accepts(ty: &pg_types::Type) -> bool {
+        use pg_types::{Kind, Type};
+
+        match ty.kind() {
+            Kind::Array(member) => Self::accepts(member),
+            _ => matches!(
+                *ty,
+                Type::INT2
+                    | Type::INT4
+                    | Type::INT8
+                    | Type::FLOAT4
+                    | Type::FLOAT8
+                    | Type::BOOL
+                    | Type::BYTEA
+                    | Type::TIMESTAMPTZ
+                    | Type::INTERVAL
+                    | Type::TEXT
+                    | Type::VARCHAR
+                    | Type::BPCHAR
+                    | Type::NAME
+            ),
+        }
+    }
+}
+
+// 时间间隔的秒数, 含小数部分
+pub(crate) fn interval_seconds(d: &chrono::Duration) -> f64 {
+    d.num_seconds() as f64 + f64::from(d.subsec_nanos()) / 1e9
+}
+
+// Postgres INTERVAL 的二进制格式为 8 字节微秒, 4 字节天数, 4 字节月数
+#[cfg(any(feature = "postgresql", feature = "postgresql_async"))]
+fn decode_interval(raw: &[u8]) -> Result<chrono::Duration, Box<dyn Error + Sync + Send>> {
+    let raw: [u8; 16] = raw.try_into().map_err(|_| "invalid interval length")?;
+    let micros = i64::from_be_bytes(raw[0..8].try_into()?);
+    let days = i32::from_be_bytes(raw[8..12].try_into()?);
+    let months = i32::from_be_bytes(raw[12..16].try_into()?);
+    let days = i64::from(days) + i64::from(months) * 30;
+    chrono::Duration::try_days(days)
+        .and_then(|d| d.checked_add(&chrono::Duration::microseconds(micros)))
+        .ok_or_else(|| "interval out of range".into())
+}
+
+// MySQL TIME 的各部分: 是否为负, 天, 时, 分, 秒, 微秒
+#[cfg(any(feature = "mysql", feature = "mysql_async"))]
+pub(crate) fn interval_to_mysql_time(d: &chrono::Duration) -> (bool, u32, u8, u8, u8, u32) {
+    let negative = *d < chrono::Duration::zero();
+    let d = d.abs();
+    let seconds = d.num_seconds();
+    (
+        negative,
+        (seconds / 86_400) as u32,
+        (seconds / 3_600 % 24) as u8,
+        (seconds / 60 % 60) as u8,
+        (seconds % 60) as u8,
+        d.subsec_nanos() as u32 / 1_000,
+    )
+}
+
+#[cfg(any(feature = "mysql", feature = "mysql_async"))]
+pub(crate) fn mysql_time_to_interval(
+    negative: bool,
+    days: u32,
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+    micros: u32,
+) -> Value {
+    let seconds = i64::from(days) * 86_400
+        + i64::from(hours) * 3_600
+        + i64::from(minutes) * 60
+        + i64::from(seconds);
+    let d = chrono::Duration::seconds(seconds) + chrono::Duration::microseconds(micros.into());
+    Value::Interval(if negative { -d } else { d })
+}
+
 // 定义通用的结果行类型
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Row {
     pub columns: Vec<String>,
+    // 与 columns 一一对应, 直接按下标访问依赖列顺序
     pub values: Vec<Value>,
 }
 
 impl Row {
+    // 各列转为 Value::Text, 供 query_raw 的默认实现使用
+    pub(crate) fn into_text(self) -> Row {
+        let values = self
+            .values
+            .into_iter()
+            .map(|value| match value {
+                Value::Null => Value::Null,
+                Value::Int(v) => Value::Text(v.to_string()),
+                Value::Bigint(v) => Value::Text(v.to_string()),
+                Value::Float(v) => Value::Text(v.to_string()),
+                Value::Double(v) => Value::Text(v.to_string()),
+                Value::Text(v) | Value::Varchar(v) => Value::Text(v),
+                Value::Boolean(v) => Value::Text(v.to_string()),
+                Value::Byte(v) => Value::Text(v.to_string()),
+                Value::Smallint(v) => Value::Text(v.to_string()),
+                Value::TinyInt(v) => Value::Text(v.to_string()),
+                Value::UBigint(v) => Value::Text(v.to_string()),
+                Value::Bytes(v) => Value::Text(
+                    std::iter::once("\\x".to_string())
+                        .chain(v.iter().map(|b| format!("{:02x}", b)))
+                        .collect(),
+                ),
+                Value::DateTime(v) => Value::Text(v.to_rfc3339()),
+                Value::Table(v) => Value::Text(format!("{:?}", v)),
+                Value::Array(v) => Value::Text(format!("{:?}", v)),
+                Value::Interval(v) => Value::Text(v.to_string()),
+            })
+            .collect();
+        Row {
+            columns: self.columns,
+            values,
+        }
+    }
+
     pub fn to_table(&self) -> Value {
         let table: Vec<(String, Value)> = self
             .columns
@@ -188,6 +1198,44 @@ impl Row {
             .collect();
         Value::Table(table)
     }
+
+    /// 按列名取值并转换类型, 不依赖 SELECT 的列顺序
+    pub fn get<T>(&self, column: &str) -> Result<T, DbError>
+    where
+        T: TryFrom<Value, Error = DbError>,
+    {
+        let index = self
+            .columns
+            .iter()
+            .position(|c| c == column)
+            .ok_or_else(|| DbError::ConversionError(format!("column {} not found", column)))?;
+        self.get_by_index(index)
+    }
+
+    /// 按列名取原始值, 没有该列时返回 None
+    pub fn get_by_name(&self, name: &str) -> Option<&Value> {
+        self.columns
+            .iter()
+            .position(|c| c == name)
+            .and_then(|index| self.values.get(index))
+    }
+
+    /// 按位置取值并转换类型
+    ///
+    /// 位置依赖 SELECT 的列顺序, `SELECT *` 在 ALTER TABLE 之后列顺序可能变化,
+    /// 值会静默地对应到错误的字段; 能用列名时优先使用 get
+    pub fn get_by_index<T>(&self, index: usize) -> Result<T, DbError>
+    where
+        T: TryFrom<Value, Error = DbError>,
+    {
+        match self.values.get(index) {
+            Some(value) => T::try_from(value.clone()),
+            None => Err(DbError::ConversionError(format!(
+                "column index {} out of range",
+                index
+            ))),
+        }
+    }
 }
 
 // 定义连接类型（可以根据需要扩展）
@@ -195,9 +1243,215 @@ pub struct Connection {
     // 连接相关字段
 }
 
+// 连接池状态, 供健康检查和监控使用
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStatus {
+    // 当前已建立的连接数, 包括空闲的
+    pub connections: u32,
+    pub idle_connections: u32,
+    pub max_size: u32,
+}
+
 // 定义数据库连接池类型
 pub enum _DatabaseType {
     Postgres,
     MySQL,
     SQLite,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[cfg(any(feature = "postgresql", feature = "postgresql_async"))]
+    #[test]
+    fn test_statement_cache_lru() {
+        let mut cache = StatementCache::new(2);
+        cache.insert("SELECT 1", 1);
+        cache.insert("SELECT 2", 2);
+        // 命中的语句移到队尾, 淘汰的是 SELECT 2
+        assert_eq!(cache.get("SELECT 1"), Some(1));
+        cache.insert("SELECT 3", 3);
+        assert_eq!(cache.get("SELECT 2"), None);
+        assert_eq!(cache.get("SELECT 1"), Some(1));
+        assert_eq!(cache.prepared, 3);
+
+        cache.remove("SELECT 1");
+        assert_eq!(cache.get("SELECT 1"), None);
+
+        // 容量为 0 时不缓存, 但仍记录预编译次数
+        let mut disabled = StatementCache::new(0);
+        disabled.insert("SELECT 1", 1);
+        assert_eq!(disabled.get("SELECT 1"), None);
+        assert_eq!(disabled.prepared, 1);
+    }
+
+    #[test]
+    fn test_error_source() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        let err = DbError::ConnectionError(ErrorDetail::new(io));
+        assert_eq!(err.to_string(), "Connection error: refused");
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+
+        let err = DbError::QueryError(QueryErrorKind::UniqueViolation(ErrorDetail::with_source(
+            "duplicate key",
+            std::fmt::Error,
+        )));
+        assert!(err.source().unwrap().is::<std::fmt::Error>());
+
+        // 只有信息的错误没有 source
+        assert!(DbError::TransactionError("no transaction".into())
+            .source()
+            .is_none());
+        assert!(DbError::ConversionError("bad value".to_string())
+            .source()
+            .is_none());
+    }
+
+    #[test]
+    fn test_value_from_primitives() {
+        assert_eq!(Value::from(7i32), Value::Int(7));
+        assert_eq!(Value::from(7i64), Value::Bigint(7));
+        assert_eq!(Value::from(1.5f64), Value::Double(1.5));
+        assert_eq!(Value::from(true), Value::Boolean(true));
+        assert_eq!(Value::from("abc"), Value::Text("abc".to_string()));
+        assert_eq!(
+            Value::from("abc".to_string()),
+            Value::Text("abc".to_string())
+        );
+        assert_eq!(Value::from(vec![1u8, 2]), Value::Bytes(vec![1, 2]));
+        assert_eq!(Value::from(None::<i32>), Value::Null);
+    }
+
+    #[test]
+    fn test_value_try_into_primitives() {
+        assert_eq!(i32::try_from(Value::Int(7)).unwrap(), 7);
+        assert_eq!(i32::try_from(Value::Bigint(7)).unwrap(), 7);
+        assert_eq!(i64::try_from(Value::Int(7)).unwrap(), 7);
+        assert_eq!(f64::try_from(Value::Double(1.5)).unwrap(), 1.5);
+        assert_eq!(f64::try_from(Value::Float(1.5)).unwrap(), 1.5);
+        assert_eq!(f32::try_from(Value::Float(1.5)).unwrap(), 1.5);
+        assert_eq!(u8::try_from(Value::Byte(3)).unwrap(), 3);
+        assert!(bool::try_from(Value::Boolean(true)).unwrap());
+        assert_eq!(String::try_from(Value::Varchar("v".into())).unwrap(), "v");
+        assert_eq!(Vec::<u8>::try_from(Value::Bytes(vec![1])).unwrap(), vec![1]);
+
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let value: Value = now.into();
+        let back: chrono::DateTime<Utc> = value.try_into().unwrap();
+        assert_eq!(back, now);
+    }
+
+    #[test]
+    fn test_config_builder() {
+        let config = DatabaseConfig::builder()
+            .host("db.internal")
+            .port(5432)
+            .username("app")
+            .database_name(":memory:")
+            .max_size(4)
+            .pragma("journal_mode", "WAL")
+            .read_only(true)
+            .min_idle(2)
+            .build();
+
+        assert_eq!(config.host, "db.internal");
+        assert_eq!(config.port, 5432);
+        assert_eq!(config.username, "app");
+        assert_eq!(config.database_name, ":memory:");
+        assert_eq!(config.max_size, 4);
+        assert_eq!(
+            config.pragmas,
+            vec![("journal_mode".to_string(), "WAL".to_string())]
+        );
+        assert!(config.read_only);
+        assert_eq!(config.min_idle, Some(2));
+        assert!(config.replica_config.is_none());
+        // 未设置的项取内置默认值, 与环境变量无关
+        assert!(config.test_on_check_out);
+        assert_eq!(config.password, "password");
+        assert_eq!(DatabaseConfig::builder().build().port, 3306);
+    }
+
+    #[test]
+    fn test_config_env_overrides() {
+        let vars = |name: &str| match name {
+            "BOOTRUST_DB_HOST" => Some("10.0.0.1".to_string()),
+            "BOOTRUST_DB_PORT" => Some("5433".to_string()),
+            "DB_MAX_SIZE" => Some("8".to_string()),
+            _ => None,
+        };
+        let config = DatabaseConfig::from_vars(vars).unwrap();
+        assert_eq!(config.host, "10.0.0.1");
+        assert_eq!(config.port, 5433);
+        assert_eq!(config.max_size, 8);
+        assert_eq!(config.username, "root");
+        assert_eq!(config.database_name, "bootrust_default_db");
+
+        let bad_port = |name: &str| (name == "BOOTRUST_DB_PORT").then(|| "70000".to_string());
+        assert!(matches!(
+            DatabaseConfig::from_vars(bad_port),
+            Err(DbError::ConversionError(_))
+        ));
+        let bad_size = |name: &str| (name == "DB_MAX_SIZE").then(|| "many".to_string());
+        assert!(matches!(
+            DatabaseConfig::from_vars(bad_size),
+            Err(DbError::ConversionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_row_get_by_name() {
+        let row = Row {
+            columns: vec!["email".into(), "id".into(), "active".into(), "name".into()],
+            values: vec![
+                Value::Text("a@b.c".into()),
+                Value::Bigint(7),
+                Value::Boolean(true),
+                Value::Null,
+            ],
+        };
+
+        assert_eq!(row.get::<i64>("id").unwrap(), 7);
+        assert_eq!(row.get::<String>("email").unwrap(), "a@b.c");
+        assert!(row.get::<bool>("active").unwrap());
+        assert_eq!(row.get_by_index::<i32>(1).unwrap(), 7);
+        assert_eq!(row.get_by_name("id"), Some(&Value::Bigint(7)));
+        assert_eq!(row.get_by_name("name"), Some(&Value::Null));
+        assert_eq!(row.get_by_name("missing"), None);
+
+        assert!(matches!(
+            row.get::<i64>("missing"),
+            Err(DbError::ConversionError(_))
+        ));
+        assert!(matches!(
+            row.get::<String>("name"),
+            Err(DbError::ConversionError(_))
+        ));
+        assert!(matches!(
+            row.get_by_index::<i64>(4),
+            Err(DbError::ConversionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_value_try_into_mismatch() {
+        let err = i64::try_from(Value::Text("abc".to_string())).unwrap_err();
+        assert!(matches!(err, DbError::ConversionError(_)));
+        assert!(matches!(
+            String::try_from(Value::Null),
+            Err(DbError::ConversionError(_))
+        ));
+        assert!(matches!(
+            i32::try_from(Value::Bigint(i64::MAX)),
+            Err(DbError::ConversionError(_))
+        ));
+        assert!(f32::try_from(Value::Double(1.5)).is_err());
+        assert!(f64::try_from(Value::Text("1.5".into())).is_err());
+        assert!(bool::try_from(Value::Bigint(1)).is_err());
+        assert!(Vec::<u8>::try_from(Value::Text("ab".into())).is_err());
+        assert!(chrono::DateTime::<Utc>::try_from(Value::Text("2024-01-02".into())).is_err());
+    }
+}
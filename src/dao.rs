@@ -1,8 +1,14 @@
-use crate::database::{DbError, RelationalDatabase, Row, Value};
-use crate::serde::{EntityConvertor, EntityDeserializer};
+use crate::database::{DbError, QueryErrorKind, RelationalDatabase, Row, Value};
+use crate::entity::{Entity, EntityData};
+use crate::serde::{EntityConvertor, EntityDeserializer, RenamePolicy};
 // use crate::sql_builder::SqlExecutor;
+use chrono::Utc;
 use serde::{de::Deserialize, ser::Serialize};
 use std::io::Cursor;
+use std::marker::PhantomData;
+
+#[cfg(feature = "derive")]
+pub use bootrust_derive::Dao;
 
 /// 通用的数据访问对象trait
 pub trait Dao<T>: Sized
@@ -23,7 +29,8 @@ where
     fn new(database: Self::Database) -> Self;
 
     fn row_to_entity(row: Row) -> Result<T, DbError> {
-        let de = EntityDeserializer::from_value(row.to_table());
+        let de = EntityDeserializer::from_value(row.to_table())
+            .with_rename_policy(Self::rename_policy());
         T::deserialize(de).map_err(|e| DbError::ConversionError(e.to_string()))
     }
 
@@ -39,7 +46,7 @@ where
 
     fn entity_to_map(entity: &T) -> Vec<(String, Value)> {
         let cursor = Cursor::new(Vec::new());
-        let mut convertor = EntityConvertor::new(cursor);
+        let mut convertor = EntityConvertor::new(cursor).with_rename_policy(Self::rename_policy());
         let result = entity.serialize(&mut convertor);
         match result {
             Ok(Value::Table(table)) => table,
@@ -73,15 +80,104 @@ where
     /// 获取主键列名
     fn primary_key_column() -> String;
 
+    /// 字段名到列名的转换规则, 默认与字段同名
+    fn rename_policy() -> RenamePolicy {
+        RenamePolicy::Verbatim
+    }
+
+    /// 自动维护的 (created_at, updated_at) 列, 默认不维护
+    ///
+    /// 实体实现了 Timestamped 时重写为 `T::timestamps()`, 派生时用 `#[dao(timestamped)]`
+    fn timestamps() -> Option<(String, String)> {
+        None
+    }
+
+    /// 获取全部主键列名, 复合主键时重写
+    fn primary_key_columns() -> Vec<String> {
+        vec![Self::primary_key_column()]
+    }
+
+    /// 实例操作的表名, 生成的 SQL 都以此为准, 默认为 table_name()
+    fn table(&self) -> String {
+        Self::table_name()
+    }
+
+    /// 实例操作的主键列名, 默认为 primary_key_column()
+    fn primary_key(&self) -> String {
+        Self::primary_key_column()
+    }
+
+    /// 实例操作的全部主键列名, 默认为 primary_key_columns()
+    fn primary_keys(&self) -> Vec<String> {
+        Self::primary_key_columns()
+    }
+
+    /// 生成主键条件, 占位符从第 offset 个参数开始
+    fn primary_key_condition(&self, offset: usize) -> String {
+        let columns = self.primary_keys();
+        let placeholders = self.placeholders(&vec![String::new(); offset + columns.len()]);
+        columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} = {}", c, placeholders[offset + i]))
+            .collect::<Vec<String>>()
+            .join(" AND ")
+    }
+
+    /// 生成 `a = ? AND b > ?` 形式的条件, 每个条件后接一个占位符
+    fn where_condition(&self, condition: &[&str]) -> String {
+        let conditions: Vec<String> = condition.iter().map(|s| s.to_string()).collect();
+        let placeholders = self.placeholders(&conditions);
+        conditions
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} {}", c, placeholders[i]))
+            .collect::<Vec<String>>()
+            .join(" AND ")
+    }
+
+    fn check_primary_key(&self, key: &[Value]) -> Result<(), DbError> {
+        let expected = self.primary_keys().len();
+        if key.len() != expected {
+            return Err(DbError::QueryError(QueryErrorKind::Other(
+                format!(
+                    "expected {} primary key values, got {}",
+                    expected,
+                    key.len()
+                )
+                .into(),
+            )));
+        }
+        Ok(())
+    }
+
+    // 按主键操作应当恰好影响一行
+    fn check_one_affected(&self, affected: u64) -> Result<(), DbError> {
+        match affected {
+            0 => Err(DbError::NotFound(format!(
+                "no row in {} matches the primary key",
+                self.table()
+            ))),
+            1 => Ok(()),
+            n => Err(DbError::Conflict(format!(
+                "{} rows in {} match the primary key",
+                n,
+                self.table()
+            ))),
+        }
+    }
+
     /// 创建新记录
     fn create(&self, entity: &T) -> Result<u64, DbError> {
-        let values = self.entity_to_values(entity);
-        let keys = self.entity_to_keys(entity);
+        let mut map = Self::entity_to_map(entity);
+        stamp_timestamps(&mut map, Self::timestamps(), true);
+        let (keys, values): (Vec<String>, Vec<Value>) = map.into_iter().unzip();
         let placeholders: Vec<String> = self.placeholders(&keys);
 
         let query = format!(
-            "INSERT INTO {} VALUES ({})",
-            Self::table_name(),
+            "INSERT INTO {} ({}) VALUES ({})",
+            self.table(),
+            keys.join(", "),
             placeholders.join(", ")
         );
 
@@ -90,15 +186,19 @@ where
 
     /// 根据ID查找记录
     fn find_by_id(&self, id: Value) -> Result<Option<T>, DbError> {
-        let placeholder = self.placeholders(&[Self::primary_key_column()])[0].clone();
+        self.find_by_key(vec![id])
+    }
+
+    /// 根据主键查找记录, 值按 primary_key_columns 的顺序对应
+    fn find_by_key(&self, key: Vec<Value>) -> Result<Option<T>, DbError> {
+        self.check_primary_key(&key)?;
         let query = format!(
-            "SELECT * FROM {} WHERE {} = {}",
-            Self::table_name(),
-            Self::primary_key_column(),
-            placeholder
+            "SELECT * FROM {} WHERE {}",
+            self.table(),
+            self.primary_key_condition(0)
         );
 
-        let result = self.database().query_one(&query, vec![id])?;
+        let result = self.database().query_one(&query, key)?;
         match result {
             Some(row) => Ok(Some(Self::row_to_entity(row)?)),
             None => Ok(None),
@@ -107,7 +207,7 @@ where
 
     /// 查找所有记录
     fn find_all(&self) -> Result<Vec<T>, DbError> {
-        let query = format!("SELECT * FROM {}", Self::table_name());
+        let query = format!("SELECT * FROM {}", self.table());
         let rows = self.database().query(&query, vec![])?;
 
         let mut entities = Vec::with_capacity(rows.len());
@@ -119,18 +219,14 @@ where
 
     /// 更新记录
     fn update(&self, entity: &T) -> Result<u64, DbError> {
-        let map = Self::entity_to_map(entity);
+        let mut map = Self::entity_to_map(entity);
+        stamp_timestamps(&mut map, Self::timestamps(), false);
         let mut values: Vec<Value> = Vec::new();
 
-        let mut primary_value = None;
+        let primary_columns = self.primary_keys();
         let update_columns: Vec<String> = map
             .iter()
-            .inspect(|kv| {
-                if kv.0 == Self::primary_key_column() {
-                    primary_value = Some(kv.1.clone());
-                }
-            })
-            .filter(|kv| kv.0 != Self::primary_key_column())
+            .filter(|kv| !primary_columns.contains(&kv.0))
             .enumerate()
             .map(|(i, kv)| {
                 let placeholder = self.placeholders(&vec![kv.0.clone(); i + 1])[i].clone();
@@ -140,53 +236,97 @@ where
             })
             .collect();
 
-        if let Some(id_value) = primary_value {
-            values.push(id_value.clone());
+        let offset = values.len();
+        for column in &primary_columns {
+            if let Some(kv) = map.iter().find(|kv| &kv.0 == column) {
+                values.push(kv.1.clone());
+            }
         }
 
         let query = format!(
-            "UPDATE {} SET {} WHERE {} = {}",
-            Self::table_name(),
+            "UPDATE {} SET {} WHERE {}",
+            self.table(),
             update_columns.join(", "),
-            Self::primary_key_column(),
-            self.placeholders(&vec![Self::primary_key_column(); values.len()])[values.len() - 1]
-                .clone(),
+            self.primary_key_condition(offset),
+        );
+
+        self.database().execute(&query, values)
+    }
+
+    /// 更新记录, 没有命中时返回 DbError::NotFound
+    fn update_expect_one(&self, entity: &T) -> Result<(), DbError> {
+        self.check_one_affected(self.update(entity)?)
+    }
+
+    /// 只更新给定的列, 其余列保持不变
+    fn update_fields(&self, id: Value, fields: Vec<(&str, Value)>) -> Result<u64, DbError> {
+        if fields.is_empty() {
+            return Err(DbError::QueryError(QueryErrorKind::Other(
+                "update_fields requires at least one field".into(),
+            )));
+        }
+        let key = vec![id];
+        self.check_primary_key(&key)?;
+
+        let placeholders = self.placeholders(&vec![String::new(); fields.len()]);
+        let (columns, mut values): (Vec<&str>, Vec<Value>) = fields.into_iter().unzip();
+        let assignments: Vec<String> = columns
+            .iter()
+            .zip(&placeholders)
+            .map(|(column, placeholder)| format!("{} = {}", column, placeholder))
+            .collect();
+
+        let query = format!(
+            "UPDATE {} SET {} WHERE {}",
+            self.table(),
+            assignments.join(", "),
+            self.primary_key_condition(values.len()),
         );
+        values.extend(key);
 
         self.database().execute(&query, values)
     }
 
     /// 删除记录
     fn delete(&self, id: Value) -> Result<u64, DbError> {
-        let placeholder = self.placeholders(&[Self::primary_key_column()])[0].clone();
+        self.delete_by_key(vec![id])
+    }
+
+    /// 删除记录, 没有命中时返回 DbError::NotFound
+    fn delete_expect_one(&self, id: Value) -> Result<(), DbError> {
+        self.check_one_affected(self.delete(id)?)
+    }
+
+    /// 根据主键删除记录, 值按 primary_key_columns 的顺序对应
+    fn delete_by_key(&self, key: Vec<Value>) -> Result<u64, DbError> {
+        self.check_primary_key(&key)?;
         let query = format!(
-            "DELETE FROM {} WHERE {} = {}",
-            Self::table_name(),
-            Self::primary_key_column(),
-            placeholder
+            "DELETE FROM {} WHERE {}",
+            self.table(),
+            self.primary_key_condition(0)
         );
 
-        self.database().execute(&query, vec![id])
+        self.database().execute(&query, key)
     }
 
     /// 自定义条件查询
+    ///
+    /// 每个条件对应一个参数, 如 `vec!["id =", "amount <"]`, 个数不一致时返回 ParameterCountMismatch
     fn find_by_condition(
         &self,
         condition: Vec<&str>,
         params: Vec<Value>,
     ) -> Result<Vec<T>, DbError> {
-        let conditions: Vec<String> = condition.iter().map(|s| s.to_string()).collect();
-        let placeholders = self.placeholders(&conditions);
-        let where_condition: String = conditions
-            .iter()
-            .enumerate()
-            .map(|(i, c)| format!("{} {}", c, placeholders[i]))
-            .collect::<Vec<String>>()
-            .join(" AND ");
+        if condition.len() != params.len() {
+            return Err(DbError::ParameterCountMismatch {
+                expected: condition.len(),
+                actual: params.len(),
+            });
+        }
         let query = format!(
             "SELECT * FROM {} WHERE {}",
-            Self::table_name(),
-            where_condition
+            self.table(),
+            self.where_condition(&condition)
         );
 
         let rows = self.database().query(&query, params)?;
@@ -197,6 +337,54 @@ where
         Ok(entities)
     }
 
+    /// 条件按原样拼入 WHERE, 占位符由调用方按数据库的写法给出, 如 `name = $1 OR email = $2`
+    fn find_by_raw_condition(
+        &self,
+        condition: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<T>, DbError> {
+        let query = format!("SELECT * FROM {} WHERE {}", self.table(), condition);
+
+        let rows = self.database().query(&query, params)?;
+        let mut entities = Vec::with_capacity(rows.len());
+        for row in rows {
+            entities.push(Self::row_to_entity(row)?);
+        }
+        Ok(entities)
+    }
+
+    /// 按条件查找唯一记录, 没有时返回 None, 多于一条时返回 DbError::Conflict
+    ///
+    /// 条件的写法与 find_by_condition 相同, 个数与参数不一致时返回 ParameterCountMismatch
+    fn find_one_by_condition(
+        &self,
+        condition: Vec<&str>,
+        params: Vec<Value>,
+    ) -> Result<Option<T>, DbError> {
+        if condition.len() != params.len() {
+            return Err(DbError::ParameterCountMismatch {
+                expected: condition.len(),
+                actual: params.len(),
+            });
+        }
+        // 取两条即可判断是否唯一
+        let query = format!(
+            "SELECT * FROM {} WHERE {} LIMIT 2",
+            self.table(),
+            self.where_condition(&condition)
+        );
+
+        let mut rows = self.database().query(&query, params)?;
+        if rows.len() > 1 {
+            return Err(DbError::Conflict(format!(
+                "more than one row in {} matches {}",
+                self.table(),
+                condition.join(" AND ")
+            )));
+        }
+        rows.pop().map(Self::row_to_entity).transpose()
+    }
+
     fn begin_transaction(&self) -> Result<(), DbError> {
         self.database().begin_transaction()
     }
@@ -213,3 +401,189 @@ where
     // SqlExecutor::new(self.database(), Self::table_name())
     // }
 }
+
+/// 由 create/update 自动写入创建时间和更新时间的实体
+///
+/// 需要在 Dao 中重写 timestamps 才会生效, 派生时用 `#[dao(timestamped)]` 或 `#[entity(timestamped)]`
+pub trait Timestamped {
+    fn created_at_column() -> String {
+        "created_at".to_string()
+    }
+
+    fn updated_at_column() -> String {
+        "updated_at".to_string()
+    }
+
+    fn timestamps() -> Option<(String, String)> {
+        Some((Self::created_at_column(), Self::updated_at_column()))
+    }
+}
+
+// 创建时写入两列; 更新时只写 updated_at, 并且不改动 created_at
+pub(crate) fn stamp_timestamps(
+    map: &mut Vec<(String, Value)>,
+    timestamps: Option<(String, String)>,
+    creating: bool,
+) {
+    let Some((created_at, updated_at)) = timestamps else {
+        return;
+    };
+    let now = Value::DateTime(Utc::now());
+    if creating {
+        set_column(map, created_at, now.clone());
+    } else {
+        map.retain(|kv| kv.0 != created_at);
+    }
+    set_column(map, updated_at, now);
+}
+
+fn set_column(map: &mut Vec<(String, Value)>, column: String, value: Value) {
+    match map.iter_mut().find(|kv| kv.0 == column) {
+        Some(kv) => kv.1 = value,
+        None => map.push((column, value)),
+    }
+}
+
+/// 乐观锁: 通过版本列检测并发修改
+pub trait VersionedDao<T>: Dao<T>
+where
+    T: Sized + Sync + Serialize + for<'de> Deserialize<'de>,
+{
+    fn version_column() -> String {
+        "version".to_string()
+    }
+
+    /// 带版本号检查的更新, 成功后数据库中的版本号加一
+    ///
+    /// 实体中的版本号需是读取时的值, 不匹配时返回 DbError::Conflict
+    fn update_versioned(&self, entity: &T) -> Result<u64, DbError> {
+        let map = Self::entity_to_map(entity);
+        let version_column = Self::version_column();
+        let primary_columns = self.primary_keys();
+        let version = map
+            .iter()
+            .find(|kv| kv.0 == version_column)
+            .map(|kv| kv.1.clone())
+            .ok_or_else(|| {
+                DbError::ConversionError(format!("missing version column {}", version_column))
+            })?;
+
+        let fields: Vec<&(String, Value)> = map
+            .iter()
+            .filter(|kv| !primary_columns.contains(&kv.0) && kv.0 != version_column)
+            .collect();
+        let placeholders = self.placeholders(&vec![
+            String::new();
+            fields.len() + primary_columns.len() + 1
+        ]);
+        let mut values: Vec<Value> = Vec::new();
+        let mut update_columns: Vec<String> = Vec::new();
+        for (i, kv) in fields.iter().enumerate() {
+            update_columns.push(format!("{} = {}", kv.0, placeholders[i]));
+            values.push(kv.1.clone());
+        }
+        update_columns.push(format!("{0} = {0} + 1", version_column));
+
+        let offset = values.len();
+        for column in &primary_columns {
+            if let Some(kv) = map.iter().find(|kv| &kv.0 == column) {
+                values.push(kv.1.clone());
+            }
+        }
+        values.push(version);
+
+        let query = format!(
+            "UPDATE {} SET {} WHERE {} AND {} = {}",
+            self.table(),
+            update_columns.join(", "),
+            self.primary_key_condition(offset),
+            version_column,
+            placeholders[values.len() - 1],
+        );
+
+        let affected = self.database().execute(&query, values)?;
+        if affected == 0 {
+            return Err(DbError::Conflict(format!(
+                "{} was modified or deleted concurrently",
+                self.table()
+            )));
+        }
+        Ok(affected)
+    }
+}
+
+/// 通用的 Dao, 表名, 主键和列名映射取自实体的 Entity 实现, 与 asyncdao::DataAccessory 相同
+pub struct DataAccessory<T: Sized, D: RelationalDatabase> {
+    database: D,
+    table: Option<String>,
+    primary_key: Option<String>,
+    _table: PhantomData<T>,
+}
+
+impl<T: Sized, D: RelationalDatabase> DataAccessory<T, D> {
+    /// 指定表名和主键, 同一实体类型可以操作多张结构相同的表
+    pub fn new_for(database: D, table: &str, primary_key: &str) -> Self {
+        Self {
+            database,
+            table: Some(table.to_string()),
+            primary_key: Some(primary_key.to_string()),
+            _table: PhantomData,
+        }
+    }
+}
+
+impl<T, D> Dao<T> for DataAccessory<T, D>
+where
+    T: Entity + EntityData,
+    D: RelationalDatabase,
+{
+    type Database = D;
+    fn database(&self) -> &Self::Database {
+        &self.database
+    }
+
+    fn new(database: Self::Database) -> Self {
+        Self {
+            database,
+            table: None,
+            primary_key: None,
+            _table: PhantomData,
+        }
+    }
+
+    fn row_to_entity(row: Row) -> Result<T, DbError> {
+        T::row_to_entity(row)
+    }
+
+    fn entity_to_map(entity: &T) -> Vec<(String, Value)> {
+        T::entity_to_map(entity)
+    }
+
+    fn table_name() -> String {
+        T::table()
+    }
+
+    fn primary_key_column() -> String {
+        T::primary_key()
+    }
+
+    fn table(&self) -> String {
+        self.table.clone().unwrap_or_else(T::table)
+    }
+
+    fn primary_key(&self) -> String {
+        self.primary_key.clone().unwrap_or_else(T::primary_key)
+    }
+
+    fn primary_keys(&self) -> Vec<String> {
+        vec![self.primary_key()]
+    }
+
+    fn rename_policy() -> RenamePolicy {
+        T::rename_policy()
+    }
+
+    fn timestamps() -> Option<(String, String)> {
+        T::timestamps()
+    }
+}
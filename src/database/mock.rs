@@ -0,0 +1,646 @@
+use crate::database::{
+    Connection, DatabaseConfig, DbError, QueryErrorKind, RelationalDatabase, Row, Value,
+};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+type Record = Vec<(String, Value)>;
+
+/// 内存中的模拟数据库, 用于在没有数据库服务的情况下测试 DAO
+///
+/// 支持 Dao 生成的简单语句: INSERT, `SELECT *`, `SELECT 1`, UPDATE, DELETE,
+/// 条件只支持用 AND 连接的 `列 = 占位符`, 另外支持 ORDER BY, LIMIT 和 OFFSET.
+/// 其他语句可以通过 respond_query / respond_execute 预设结果.
+/// 表不需要事先创建, 不存在的表视为空表
+#[derive(Debug, Clone, Default)]
+pub struct MockDatabase {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    tables: HashMap<String, Vec<Record>>,
+    // 事务开始时的快照, 回滚时恢复
+    snapshot: Option<HashMap<String, Vec<Record>>>,
+    query_responses: Vec<(String, Vec<Row>)>,
+    execute_responses: Vec<(String, u64)>,
+    statements: Vec<(String, Vec<Value>)>,
+}
+
+impl MockDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// SQL 包含 pattern 的查询直接返回 rows, 先注册的优先
+    pub fn respond_query(&self, pattern: &str, rows: Vec<Row>) {
+        self.lock().query_responses.push((normalize(pattern), rows));
+    }
+
+    /// SQL 包含 pattern 的 execute 直接返回 affected, 不修改数据
+    pub fn respond_execute(&self, pattern: &str, affected: u64) {
+        self.lock()
+            .execute_responses
+            .push((normalize(pattern), affected));
+    }
+
+    /// 表中当前的全部行
+    pub fn rows(&self, table: &str) -> Vec<Row> {
+        self.lock()
+            .tables
+            .get(table)
+            .map(|records| records.iter().map(to_row).collect())
+            .unwrap_or_default()
+    }
+
+    /// 已执行的语句和参数, 按执行顺序
+    pub fn statements(&self) -> Vec<(String, Vec<Value>)> {
+        self.lock().statements.clone()
+    }
+
+    fn run_query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
+        let sql = normalize(query);
+        let mut state = self.lock();
+        state.statements.push((sql.clone(), params.clone()));
+        if let Some((_, rows)) = state
+            .query_responses
+            .iter()
+            .find(|(pattern, _)| sql.contains(pattern.as_str()))
+        {
+            return Ok(rows.clone());
+        }
+
+        let mut params = Params::new(params);
+        // SELECT 1 只关心是否有匹配的记录
+        let (rest, constant) = match strip_keyword(&sql, "SELECT * FROM ") {
+            Some(rest) => (rest, false),
+            None => (
+                strip_keyword(&sql, "SELECT 1 FROM ").ok_or_else(|| unsupported(&sql))?,
+                true,
+            ),
+        };
+        let (table, clauses) = split_name(rest);
+        let clauses = Clauses::parse(clauses, &sql)?;
+        let conditions = parse_conditions(clauses.filter, &mut params, &sql)?;
+
+        let mut records: Vec<&Record> = state
+            .tables
+            .get(table)
+            .map(|records| records.iter().filter(|r| matches(r, &conditions)).collect())
+            .unwrap_or_default();
+        if let Some(order) = clauses.order_by {
+            let order = parse_order(order, &sql)?;
+            records.sort_by(|a, b| {
+                order
+                    .iter()
+                    .map(|(column, desc)| {
+                        let ordering = compare(get(a, column), get(b, column));
+                        if *desc {
+                            ordering.reverse()
+                        } else {
+                            ordering
+                        }
+                    })
+                    .find(|o| *o != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        let limit = clauses
+            .limit
+            .map(|token| params.number(token))
+            .transpose()?;
+        let offset = clauses
+            .offset
+            .map(|token| params.number(token))
+            .transpose()?
+            .unwrap_or(0);
+
+        Ok(records
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|record| {
+                if constant {
+                    Row {
+                        columns: vec!["1".to_string()],
+                        values: vec![Value::Bigint(1)],
+                    }
+                } else {
+                    to_row(record)
+                }
+            })
+            .collect())
+    }
+
+    fn run_execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
+        let sql = normalize(query);
+        let mut state = self.lock();
+        state.statements.push((sql.clone(), params.clone()));
+        if let Some((_, affected)) = state
+            .execute_responses
+            .iter()
+            .find(|(pattern, _)| sql.contains(pattern.as_str()))
+        {
+            return Ok(*affected);
+        }
+
+        let mut params = Params::new(params);
+        if let Some(rest) = strip_keyword(&sql, "INSERT INTO ") {
+            let (table, rest) = split_name(rest);
+            let (columns, rest) = parenthesized(rest).ok_or_else(|| unsupported(&sql))?;
+            let rest = strip_keyword(rest, "VALUES").ok_or_else(|| unsupported(&sql))?;
+            let (values, _) = parenthesized(rest).ok_or_else(|| unsupported(&sql))?;
+            let columns: Vec<&str> = columns.split(',').map(str::trim).collect();
+            let values: Vec<&str> = values.split(',').map(str::trim).collect();
+            if columns.len() != values.len() {
+                return Err(unsupported(&sql));
+            }
+            let mut record = Vec::with_capacity(columns.len());
+            for (column, token) in columns.into_iter().zip(values) {
+                record.push((column.to_string(), params.take(token)?));
+            }
+            state
+                .tables
+                .entry(table.to_string())
+                .or_default()
+                .push(record);
+            return Ok(1);
+        }
+
+        if let Some(rest) = strip_keyword(&sql, "UPDATE ") {
+            let (table, rest) = split_name(rest);
+            let rest = strip_keyword(rest, "SET ").ok_or_else(|| unsupported(&sql))?;
+            let (assignments, filter) = match find_keyword(rest, " WHERE ") {
+                Some(i) => (&rest[..i], Some(&rest[i + " WHERE ".len()..])),
+                None => (rest, None),
+            };
+            let mut changes = Vec::new();
+            for assignment in assignments.split(',') {
+                let (column, token) = assignment
+                    .split_once('=')
+                    .ok_or_else(|| unsupported(&sql))?;
+                changes.push((column.trim().to_string(), params.take(token.trim())?));
+            }
+            let conditions = parse_conditions(filter, &mut params, &sql)?;
+
+            let mut affected = 0;
+            for record in state.tables.entry(table.to_string()).or_default() {
+                if !matches(record, &conditions) {
+                    continue;
+                }
+                for (column, value) in &changes {
+                    match record.iter_mut().find(|(c, _)| c == column) {
+                        Some(kv) => kv.1 = value.clone(),
+                        None => record.push((column.clone(), value.clone())),
+                    }
+                }
+                affected += 1;
+            }
+            return Ok(affected);
+        }
+
+        if let Some(rest) = strip_keyword(&sql, "DELETE FROM ") {
+            let (table, clauses) = split_name(rest);
+            let filter = strip_keyword(clauses, "WHERE ");
+            if filter.is_none() && !clauses.is_empty() {
+                return Err(unsupported(&sql));
+            }
+            let conditions = parse_conditions(filter, &mut params, &sql)?;
+            let records = state.tables.entry(table.to_string()).or_default();
+            let before = records.len();
+            records.retain(|record| !matches(record, &conditions));
+            return Ok((before - records.len()) as u64);
+        }
+
+        if let Some(rest) = strip_keyword(&sql, "CREATE TABLE ") {
+            let rest = strip_keyword(rest, "IF NOT EXISTS ").unwrap_or(rest);
+            let (table, _) = split_name(rest);
+            state.tables.entry(table.to_string()).or_default();
+            return Ok(0);
+        }
+
+        if let Some(rest) = strip_keyword(&sql, "DROP TABLE ") {
+            let rest = strip_keyword(rest, "IF EXISTS ").unwrap_or(rest);
+            let (table, _) = split_name(rest);
+            state.tables.remove(table);
+            return Ok(0);
+        }
+
+        Err(unsupported(&sql))
+    }
+}
+
+impl RelationalDatabase for MockDatabase {
+    fn placeholders(&self, keys: &[String]) -> Vec<String> {
+        keys.iter().map(|_| "?".to_string()).collect()
+    }
+
+    fn connect(_config: DatabaseConfig) -> Result<Self, DbError> {
+        Ok(Self::new())
+    }
+
+    fn close(&self) -> Result<(), DbError> {
+        Ok(())
+    }
+
+    fn ping(&self) -> Result<(), DbError> {
+        Ok(())
+    }
+
+    fn ping_timeout(&self, _timeout: Duration) -> Result<(), DbError> {
+        Ok(())
+    }
+
+    fn begin_transaction(&self) -> Result<(), DbError> {
+        let mut state = self.lock();
+        if state.snapshot.is_some() {
+            return Err(DbError::TransactionError(
+                "transaction already started".into(),
+            ));
+        }
+        state.snapshot = Some(state.tables.clone());
+        Ok(())
+    }
+
+    fn commit(&self) -> Result<(), DbError> {
+        self.lock()
+            .snapshot
+            .take()
+            .map(|_| ())
+            .ok_or_else(|| DbError::TransactionError("no transaction in progress".into()))
+    }
+
+    fn rollback(&self) -> Result<(), DbError> {
+        let mut state = self.lock();
+        let snapshot = state
+            .snapshot
+            .take()
+            .ok_or_else(|| DbError::TransactionError("no transaction in progress".into()))?;
+        state.tables = snapshot;
+        Ok(())
+    }
+
+    // 只有一份内存状态, 事务快照与其他克隆共享
+    fn session(&self) -> Self {
+        self.clone()
+    }
+
+    fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
+        self.run_execute(query, params)
+    }
+
+    fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
+        self.run_query(query, params)
+    }
+
+    fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
+        Ok(self.run_query(query, params)?.into_iter().next())
+    }
+
+    fn get_connection(&self) -> Result<Connection, DbError> {
+        Ok(Connection {})
+    }
+
+    fn release_connection(&self, _conn: Connection) -> Result<(), DbError> {
+        Ok(())
+    }
+}
+
+// 按顺序取参数, 同时支持 ? 和 $n
+struct Params {
+    values: Vec<Value>,
+    next: usize,
+}
+
+impl Params {
+    fn new(values: Vec<Value>) -> Self {
+        Self { values, next: 0 }
+    }
+
+    fn take(&mut self, token: &str) -> Result<Value, DbError> {
+        let index = match token.strip_prefix('$') {
+            Some(n) => n.parse::<usize>().ok().and_then(|n| n.checked_sub(1)),
+            None if token == "?" => {
+                self.next += 1;
+                Some(self.next - 1)
+            }
+            None => None,
+        };
+        index
+            .and_then(|i| self.values.get(i).cloned())
+            .ok_or_else(|| {
+                DbError::QueryError(QueryErrorKind::Other(
+                    format!("MockDatabase expects a bound parameter, got {}", token).into(),
+                ))
+            })
+    }
+
+    // LIMIT/OFFSET 可以是占位符也可以是字面量
+    fn number(&mut self, token: &str) -> Result<usize, DbError> {
+        if let Ok(n) = token.parse::<usize>() {
+            return Ok(n);
+        }
+        match self.take(token)? {
+            Value::Int(n) if n >= 0 => Ok(n as usize),
+            Value::Bigint(n) if n >= 0 => Ok(n as usize),
+            other => Err(DbError::ConversionError(format!(
+                "expected a non-negative number, got {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+// SELECT 表名之后的子句
+struct Clauses<'a> {
+    filter: Option<&'a str>,
+    order_by: Option<&'a str>,
+    limit: Option<&'a str>,
+    offset: Option<&'a str>,
+}
+
+impl<'a> Clauses<'a> {
+    fn parse(clauses: &'a str, sql: &str) -> Result<Self, DbError> {
+        let keywords = ["WHERE ", "ORDER BY ", "LIMIT ", "OFFSET "];
+        let mut found: Vec<(usize, &str)> = keywords
+            .iter()
+            .filter_map(|k| {
+                let at = if clauses.to_ascii_uppercase().starts_with(k) {
+                    Some(0)
+                } else {
+                    find_keyword(clauses, &format!(" {}", k)).map(|i| i + 1)
+                };
+                at.map(|i| (i, *k))
+            })
+            .collect();
+        found.sort();
+        if !clauses.is_empty() && found.first().map(|f| f.0) != Some(0) {
+            return Err(unsupported(sql));
+        }
+
+        let mut parsed = Self {
+            filter: None,
+            order_by: None,
+            limit: None,
+            offset: None,
+        };
+        for (i, (start, keyword)) in found.iter().enumerate() {
+            let end = found.get(i + 1).map(|f| f.0).unwrap_or(clauses.len());
+            let body = Some(clauses[start + keyword.len()..end].trim());
+            match *keyword {
+                "WHERE " => parsed.filter = body,
+                "ORDER BY " => parsed.order_by = body,
+                "LIMIT " => parsed.limit = body,
+                _ => parsed.offset = body,
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn parse_conditions(
+    filter: Option<&str>,
+    params: &mut Params,
+    sql: &str,
+) -> Result<Vec<(String, Value)>, DbError> {
+    let Some(filter) = filter else {
+        return Ok(Vec::new());
+    };
+    let mut conditions = Vec::new();
+    let mut rest = filter;
+    loop {
+        let (condition, next) = match find_keyword(rest, " AND ") {
+            Some(i) => (&rest[..i], Some(&rest[i + " AND ".len()..])),
+            None => (rest, None),
+        };
+        let (column, token) = condition.split_once('=').ok_or_else(|| unsupported(sql))?;
+        let column = column.trim();
+        if column.is_empty() || column.contains(['<', '>', '!', ' ']) {
+            return Err(unsupported(sql));
+        }
+        conditions.push((column.to_string(), params.take(token.trim())?));
+        match next {
+            Some(next) => rest = next,
+            None => return Ok(conditions),
+        }
+    }
+}
+
+fn parse_order(order: &str, sql: &str) -> Result<Vec<(String, bool)>, DbError> {
+    order
+        .split(',')
+        .map(|item| {
+            let mut parts = item.split_whitespace();
+            let column = parts.next().ok_or_else(|| unsupported(sql))?;
+            let desc = match parts.next().map(|d| d.to_ascii_uppercase()) {
+                None => false,
+                Some(d) if d == "ASC" => false,
+                Some(d) if d == "DESC" => true,
+                Some(_) => return Err(unsupported(sql)),
+            };
+            Ok((column.to_string(), desc))
+        })
+        .collect()
+}
+
+fn unsupported(sql: &str) -> DbError {
+    DbError::QueryError(QueryErrorKind::Other(
+        format!("MockDatabase does not support: {}", sql).into(),
+    ))
+}
+
+// 合并空白, 便于按关键字切分
+fn normalize(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn find_keyword(sql: &str, keyword: &str) -> Option<usize> {
+    sql.to_ascii_uppercase().find(keyword)
+}
+
+fn strip_keyword<'a>(sql: &'a str, keyword: &str) -> Option<&'a str> {
+    match sql.get(..keyword.len()) {
+        Some(head) if head.eq_ignore_ascii_case(keyword) => Some(sql[keyword.len()..].trim_start()),
+        _ => None,
+    }
+}
+
+// 切出开头的表名, 返回 (表名, 剩余部分)
+fn split_name(sql: &str) -> (&str, &str) {
+    let end = sql
+        .find(|c: char| c.is_whitespace() || c == '(' || c == ';')
+        .unwrap_or(sql.len());
+    (&sql[..end], sql[end..].trim())
+}
+
+// 切出开头括号中的内容, 返回 (括号内, 剩余部分)
+fn parenthesized(sql: &str) -> Option<(&str, &str)> {
+    let rest = sql.trim_start().strip_prefix('(')?;
+    let end = rest.find(')')?;
+    Some((&rest[..end], rest[end + 1..].trim()))
+}
+
+fn get<'a>(record: &'a Record, column: &str) -> &'a Value {
+    record
+        .iter()
+        .find(|(c, _)| c == column)
+        .map(|(_, v)| v)
+        .unwrap_or(&Value::Null)
+}
+
+fn matches(record: &Record, conditions: &[(String, Value)]) -> bool {
+    conditions
+        .iter()
+        .all(|(column, value)| compare(get(record, column), value) == Ordering::Equal)
+}
+
+// 数字和字符串之间不区分具体类型, Null 排在最前
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn number(value: &Value) -> Option<f64> {
+        match value {
+            Value::Int(n) => Some(*n as f64),
+            Value::Bigint(n) => Some(*n as f64),
+            Value::Float(n) => Some(*n as f64),
+            Value::Double(n) => Some(*n),
+            Value::Byte(n) => Some(*n as f64),
+            Value::Smallint(n) => Some(*n as f64),
+            Value::TinyInt(n) => Some(*n as f64),
+            Value::UBigint(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (Value::Text(x) | Value::Varchar(x), Value::Text(y) | Value::Varchar(y)) => x.cmp(y),
+        (Value::Boolean(x), Value::Boolean(y)) => x.cmp(y),
+        (Value::DateTime(x), Value::DateTime(y)) => x.cmp(y),
+        (Value::Bytes(x), Value::Bytes(y)) => x.cmp(y),
+        _ => match (number(a), number(b)) {
+            (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            _ if a == b => Ordering::Equal,
+            _ => Ordering::Less,
+        },
+    }
+}
+
+fn to_row(record: &Record) -> Row {
+    let (columns, values) = record.iter().cloned().unzip();
+    Row { columns, values }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: i64, name: &str) -> Vec<Value> {
+        vec![Value::Bigint(id), Value::Text(name.to_string())]
+    }
+
+    #[test]
+    fn test_insert_and_select() {
+        let db = MockDatabase::new();
+        for (id, name) in [(2, "bob"), (1, "alice"), (3, "carol")] {
+            db.execute("INSERT INTO users (id, name) VALUES (?, ?)", user(id, name))
+                .unwrap();
+        }
+
+        let row = db
+            .query_one("SELECT * FROM users WHERE id = ?", vec![Value::Bigint(1)])
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.get::<String>("name").unwrap(), "alice");
+
+        let rows = db
+            .query(
+                "SELECT * FROM users ORDER BY name DESC LIMIT $1 OFFSET $2",
+                vec![Value::Bigint(2), Value::Bigint(1)],
+            )
+            .unwrap();
+        let names: Vec<String> = rows.iter().map(|r| r.get("name").unwrap()).collect();
+        assert_eq!(names, vec!["bob", "alice"]);
+
+        assert!(db
+            .query("SELECT * FROM missing", vec![])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let db = MockDatabase::new();
+        db.execute(
+            "INSERT INTO users (id, name) VALUES (?, ?)",
+            user(1, "alice"),
+        )
+        .unwrap();
+
+        let affected = db
+            .execute(
+                "UPDATE users SET name = ? WHERE id = ?",
+                vec![Value::Text("alicia".to_string()), Value::Int(1)],
+            )
+            .unwrap();
+        assert_eq!(affected, 1);
+        assert_eq!(db.rows("users")[0].get::<String>("name").unwrap(), "alicia");
+
+        assert_eq!(
+            db.execute("DELETE FROM users WHERE id = ?", vec![Value::Bigint(2)])
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            db.execute("DELETE FROM users WHERE id = ?", vec![Value::Bigint(1)])
+                .unwrap(),
+            1
+        );
+        assert!(db.rows("users").is_empty());
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let db = MockDatabase::new();
+        db.begin_transaction().unwrap();
+        db.execute(
+            "INSERT INTO users (id, name) VALUES (?, ?)",
+            user(1, "alice"),
+        )
+        .unwrap();
+        db.rollback().unwrap();
+        assert!(db.rows("users").is_empty());
+        assert!(db.commit().is_err());
+    }
+
+    #[test]
+    fn test_canned_responses() {
+        let db = MockDatabase::new();
+        db.respond_query(
+            "COUNT(*)",
+            vec![Row {
+                columns: vec!["count".to_string()],
+                values: vec![Value::Bigint(42)],
+            }],
+        );
+        db.respond_execute("TRUNCATE", 7);
+
+        let row = db
+            .query_one("SELECT COUNT(*) AS count FROM users", vec![])
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.get::<i64>("count").unwrap(), 42);
+        assert_eq!(db.execute("TRUNCATE users", vec![]).unwrap(), 7);
+
+        // 不支持的语句直接报错, 不会静默返回空结果
+        assert!(db
+            .query("SELECT * FROM users WHERE age > ?", vec![Value::Int(1)])
+            .is_err());
+        assert_eq!(db.statements().len(), 3);
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "mock")]
+pub mod mock;
 #[cfg(feature = "mysql")]
 pub mod mysql;
 #[cfg(feature = "postgresql")]
@@ -5,25 +7,46 @@ pub mod postgres;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 
-pub use crate::common::{Connection, DatabaseConfig, DbError, QueryErrorKind, Row, Value};
+pub use crate::common::{
+    Connection, DatabaseConfig, DatabaseConfigBuilder, DbError, ErrorDetail, PoolStatus,
+    QueryErrorKind, QueryObserver, ReadPolicy, Row, Value,
+};
+use std::ops::Deref;
+use std::time::Duration;
 
 #[cfg(all(not(feature = "full"), feature = "mysql"))]
 pub fn auto_config() -> mysql::MySqlDatabase {
-    let config = DatabaseConfig::default();
-    mysql::MySqlDatabase::connect(config).unwrap()
+    try_auto_config().unwrap()
+}
+
+#[cfg(all(not(feature = "full"), feature = "mysql"))]
+pub fn try_auto_config() -> Result<mysql::MySqlDatabase, DbError> {
+    let config = DatabaseConfig::from_env()?;
+    mysql::MySqlDatabase::connect(config)
 }
 
 #[cfg(all(not(feature = "full"), feature = "postgresql"))]
 pub fn auto_config() -> postgres::PostgresDatabase {
-    let config = DatabaseConfig::default();
-    postgres::PostgresDatabase::connect(config).unwrap()
+    try_auto_config().unwrap()
+}
+
+#[cfg(all(not(feature = "full"), feature = "postgresql"))]
+pub fn try_auto_config() -> Result<postgres::PostgresDatabase, DbError> {
+    let config = DatabaseConfig::from_env()?;
+    postgres::PostgresDatabase::connect(config)
 }
 
 #[cfg(all(not(feature = "full"), feature = "sqlite"))]
 pub fn auto_config() -> sqlite::SqliteDatabase {
-    let config = DatabaseConfig::default();
-    sqlite::SqliteDatabase::connect(config).unwrap()
+    try_auto_config().unwrap()
+}
+
+#[cfg(all(not(feature = "full"), feature = "sqlite"))]
+pub fn try_auto_config() -> Result<sqlite::SqliteDatabase, DbError> {
+    let config = DatabaseConfig::from_env()?;
+    sqlite::SqliteDatabase::connect(config)
 }
+
 // 定义关系型数据库通用接口
 pub trait RelationalDatabase: Clone {
     fn placeholders(&self, keys: &[String]) -> Vec<String>;
@@ -33,18 +56,95 @@ pub trait RelationalDatabase: Clone {
         Self: Sized;
     fn close(&self) -> Result<(), DbError>;
     fn ping(&self) -> Result<(), DbError>;
+    // 超时内拿不到连接返回 DbError::Timeout
+    fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError>;
 
     // 事务相关
+    /// 事务状态由所有克隆共享, 在一个克隆上开始事务后, 其他克隆的语句也在该事务中执行,
+    /// 直到 commit 或 rollback. 优先使用 start_transaction, 由守卫负责结束事务
     fn begin_transaction(&self) -> Result<(), DbError>;
     fn commit(&self) -> Result<(), DbError>;
     fn rollback(&self) -> Result<(), DbError>;
 
+    /// 共享连接池, 但事务状态独立的句柄, 在其上开始的事务不影响其他克隆
+    fn session(&self) -> Self
+    where
+        Self: Sized;
+
+    /// 在新的 session 上开始事务并返回守卫, 事务独占一个连接, 守卫被丢弃前没有 commit 则回滚
+    fn start_transaction(&self) -> Result<Transaction<Self>, DbError>
+    where
+        Self: Sized,
+    {
+        let db = self.session();
+        db.begin_transaction()?;
+        Ok(Transaction {
+            db,
+            finished: false,
+        })
+    }
+
     // 查询相关
     fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError>;
     fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError>;
     fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError>;
 
+    /// 不带参数, 不预编译直接执行
+    fn execute_raw(&self, sql: &str) -> Result<u64, DbError> {
+        self.execute(sql, vec![])
+    }
+
+    /// 不带参数的查询, 各列均以 Value::Text 返回, NULL 仍为 Value::Null
+    ///
+    /// 用于 Value 无法表示的列类型. Postgres 使用文本协议, 其他数据库转换普通查询的结果
+    fn query_raw(&self, sql: &str) -> Result<Vec<Row>, DbError> {
+        let rows = self.query(sql, vec![])?;
+        Ok(rows.into_iter().map(Row::into_text).collect())
+    }
+
     // 连接池相关
     fn get_connection(&self) -> Result<Connection, DbError>;
     fn release_connection(&self, conn: Connection) -> Result<(), DbError>;
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus::default()
+    }
+}
+
+/// 事务守卫, 由 `start_transaction` 返回, 可以像数据库本身一样执行语句
+///
+/// 调用 commit 提交, rollback 回滚; 两者都没有调用就被丢弃时回滚,
+/// 忘记提交的事务不会一直占着连接
+pub struct Transaction<D: RelationalDatabase> {
+    db: D,
+    finished: bool,
+}
+
+impl<D: RelationalDatabase> Transaction<D> {
+    pub fn commit(mut self) -> Result<(), DbError> {
+        self.finished = true;
+        self.db.commit()
+    }
+
+    pub fn rollback(mut self) -> Result<(), DbError> {
+        self.finished = true;
+        self.db.rollback()
+    }
+}
+
+impl<D: RelationalDatabase> Deref for Transaction<D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        &self.db
+    }
+}
+
+impl<D: RelationalDatabase> Drop for Transaction<D> {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.db.rollback() {
+                log::warn!("rollback of dropped transaction failed: {}", e);
+            }
+        }
+    }
 }
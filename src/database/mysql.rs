@@ -1,17 +1,25 @@
+use crate::common::{
+    check_tls, encode_array, interval_to_mysql_time, mysql_time_to_interval, observe_query,
+    run_with_timeout, ClosablePool,
+};
 use crate::database::{
-    Connection, DatabaseConfig, DbError, QueryErrorKind, RelationalDatabase, Row, Value,
+    Connection, DatabaseConfig, DbError, ErrorDetail, PoolStatus, QueryErrorKind, QueryObserver,
+    RelationalDatabase, Row, Value,
 };
 use chrono::{Datelike, NaiveDateTime, TimeZone, Timelike, Utc};
-use mysql::OptsBuilder;
+use mysql::{OptsBuilder, SslOpts};
 use r2d2::{Pool, PooledConnection};
 use r2d2_mysql::mysql::{prelude::*, Value as MySqlValue};
 use r2d2_mysql::MySqlConnectionManager;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct MySqlDatabase {
-    pool: Arc<Pool<MySqlConnectionManager>>,
+    pool: ClosablePool<Pool<MySqlConnectionManager>>,
     current_transaction: Arc<Mutex<Option<PooledConnection<MySqlConnectionManager>>>>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    slow_query_threshold: Option<Duration>,
 }
 
 impl MySqlDatabase {
@@ -21,22 +29,37 @@ impl MySqlDatabase {
             .tcp_port(config.port)
             .user(Some(&config.username))
             .pass(Some(&config.password))
-            .db_name(Some(&config.database_name));
+            .db_name(Some(&config.database_name))
+            .ssl_opts(config.tls.then(SslOpts::default))
+            // mysql 自带按连接的语句缓存, prep 时按 SQL 文本复用
+            .stmt_cache_size(config.statement_cache_size);
 
         let manager = MySqlConnectionManager::new(opts);
-        Pool::builder().max_size(config.max_size).build(manager)
+        Pool::builder()
+            .max_size(config.max_size)
+            .test_on_check_out(config.test_on_check_out)
+            .min_idle(config.min_idle)
+            .build(manager)
     }
 
     fn value_to_mysql(value: &Value) -> MySqlValue {
         match value {
             Value::Null => MySqlValue::NULL,
             Value::Bigint(i) => MySqlValue::Int(*i),
+            Value::Smallint(i) => MySqlValue::Int((*i).into()),
+            Value::TinyInt(i) => MySqlValue::Int((*i).into()),
+            Value::UBigint(i) => MySqlValue::UInt(*i),
             Value::Float(f) => MySqlValue::Float(*f as f32),
             Value::Double(f) => MySqlValue::Double(*f),
             // Value::Text(s) => MySqlValue::Bytes(s.clone().into_bytes()),
             Value::Text(s) => MySqlValue::from(s),
             Value::Boolean(b) => MySqlValue::Int(if *b { 1 } else { 0 }),
             Value::Bytes(b) => MySqlValue::from(b),
+            Value::Array(values) => MySqlValue::from(encode_array(values)),
+            Value::Interval(d) => {
+                let (negative, days, hours, minutes, seconds, micros) = interval_to_mysql_time(d);
+                MySqlValue::Time(negative, days, hours, minutes, seconds, micros)
+            }
             Value::DateTime(dt) => MySqlValue::Date(
                 dt.year() as u16,
                 dt.month() as u8,
@@ -50,13 +73,34 @@ impl MySqlDatabase {
         }
     }
 
+    // TINYINT 和 SMALLINT 列按列类型还原为窄整数, 无符号列放宽一级
+    fn narrow_integer(value: Value, column: &mysql::Column) -> Value {
+        use mysql::consts::{ColumnFlags, ColumnType};
+
+        let Value::Bigint(i) = value else {
+            return value;
+        };
+        let unsigned = column.flags().contains(ColumnFlags::UNSIGNED_FLAG);
+        match (column.column_type(), unsigned) {
+            (ColumnType::MYSQL_TYPE_TINY, false) => Value::TinyInt(i as i8),
+            (ColumnType::MYSQL_TYPE_TINY, true) | (ColumnType::MYSQL_TYPE_SHORT, false) => {
+                Value::Smallint(i as i16)
+            }
+            _ => Value::Bigint(i),
+        }
+    }
+
     fn convert_mysql_to_value(value: MySqlValue) -> Result<Value, DbError> {
         match value {
             MySqlValue::NULL => Ok(Value::Null),
             MySqlValue::Int(i) => Ok(Value::Bigint(i)),
+            MySqlValue::UInt(i) => Ok(Value::UBigint(i)),
             MySqlValue::Float(f) => Ok(Value::Float(f)),
             MySqlValue::Double(f) => Ok(Value::Double(f)),
             MySqlValue::Bytes(bytes) => Ok(Value::Bytes(bytes)),
+            MySqlValue::Time(negative, days, hours, minutes, seconds, micros) => Ok(
+                mysql_time_to_interval(negative, days, hours, minutes, seconds, micros),
+            ),
             MySqlValue::Date(year, month, day, hour, minute, second, micros) => {
                 let naive = NaiveDateTime::new(
                     chrono::NaiveDate::from_ymd_opt(year as i32, month as u32, day as u32)
@@ -71,9 +115,6 @@ impl MySqlDatabase {
                 );
                 Ok(Value::DateTime(Utc.from_utc_datetime(&naive)))
             }
-            _ => Err(DbError::ConversionError(
-                "Unsupported MySQL type".to_string(),
-            )),
         }
     }
 
@@ -85,15 +126,12 @@ impl MySqlDatabase {
         let mut transaction_guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         let mut conn = if let Some(conn) = &mut *transaction_guard {
             conn
         } else {
-            &mut self
-                .pool
-                .get()
-                .map_err(|e| DbError::ConnectionError(e.to_string()))?
+            &mut self.pool.get(DbError::ConnectionError)?
         };
 
         // f(conn)
@@ -106,41 +144,57 @@ impl RelationalDatabase for MySqlDatabase {
         vec!["?".to_string(); keys.len()]
     }
     fn connect(config: DatabaseConfig) -> Result<Self, DbError> {
-        let pool = Self::new_pool(&config).map_err(|e| DbError::ConnectionError(e.to_string()))?;
+        check_tls(&config)?;
+        let pool =
+            Self::new_pool(&config).map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
 
         Ok(MySqlDatabase {
-            pool: Arc::new(pool),
+            pool: ClosablePool::new(pool, &config),
             current_transaction: Arc::new(Mutex::new(None)),
+            observer: config.observer,
+            slow_query_threshold: config.slow_query_threshold,
         })
     }
 
+    // 之后的操作返回 DbError::Closed, 空闲连接随池一起释放, 进行中的事务被丢弃
     fn close(&self) -> Result<(), DbError> {
+        self.pool.close();
+        self.current_transaction
+            .lock()
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?
+            .take();
         Ok(())
     }
 
     fn ping(&self) -> Result<(), DbError> {
-        let mut conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+        let mut conn = self.pool.get(DbError::ConnectionError)?;
         conn.query_drop("SELECT 1")
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
         Ok(())
     }
 
+    // 取连接和查询都限制在超时内, 服务器挂起时也不会一直阻塞
+    fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
+        let pool = self.pool.current()?;
+        run_with_timeout(timeout, move || {
+            let mut conn = pool
+                .get_timeout(timeout)
+                .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?;
+            conn.query_drop("SELECT 1")
+                .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))
+        })
+    }
+
     fn begin_transaction(&self) -> Result<(), DbError> {
-        let mut conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+        let mut conn = self.pool.get(DbError::TransactionError)?;
 
         conn.query_drop("START TRANSACTION")
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
 
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
         *guard = Some(conn);
 
         Ok(())
@@ -150,11 +204,11 @@ impl RelationalDatabase for MySqlDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(mut conn) = guard.take() {
             conn.query_drop("COMMIT")
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
@@ -163,17 +217,26 @@ impl RelationalDatabase for MySqlDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(mut conn) = guard.take() {
             conn.query_drop("ROLLBACK")
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
 
+    fn session(&self) -> Self {
+        Self {
+            current_transaction: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
     fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
-        self.execute_with_connection(|conn| {
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self.execute_with_connection(|conn| {
             let params: Vec<mysql::Value> =
                 params.iter().map(MySqlDatabase::value_to_mysql).collect();
 
@@ -189,38 +252,59 @@ impl RelationalDatabase for MySqlDatabase {
                             1451 | 1452 => {
                                 // 外键约束错误
                                 DbError::QueryError(QueryErrorKind::ForeignKeyViolation(
-                                    mysql_err.message.clone(),
+                                    ErrorDetail::with_source(mysql_err.message.clone(), e),
                                 ))
                             }
                             1062 => {
                                 // 唯一约束错误
                                 DbError::QueryError(QueryErrorKind::UniqueViolation(
-                                    mysql_err.message.clone(),
+                                    ErrorDetail::with_source(mysql_err.message.clone(), e),
                                 ))
                             }
                             1048 => {
                                 // 非空约束错误
                                 DbError::QueryError(QueryErrorKind::NotNullViolation(
-                                    mysql_err.message.clone(),
+                                    ErrorDetail::with_source(mysql_err.message.clone(), e),
+                                ))
+                            }
+                            3819 => {
+                                // 检查约束错误
+                                DbError::QueryError(QueryErrorKind::CheckViolation(
+                                    ErrorDetail::with_source(mysql_err.message.clone(), e),
                                 ))
                             }
                             // 其他错误
-                            other_code => DbError::QueryError(QueryErrorKind::Other(format!(
-                                "code: {}, message: {}",
-                                other_code, mysql_err.message
-                            ))),
+                            other_code => DbError::QueryError(QueryErrorKind::Other(
+                                ErrorDetail::with_source(
+                                    format!("code: {}, message: {}", other_code, mysql_err.message),
+                                    e,
+                                ),
+                            )),
                         }
                     }
                     // 其他类型的错误（比如连接错误、IO错误等）
-                    _ => DbError::QueryError(QueryErrorKind::Other(format!("message: {}", e))),
+                    _ => DbError::QueryError(QueryErrorKind::Other(ErrorDetail::with_source(
+                        format!("message: {}", e),
+                        e,
+                    ))),
                 }
             })?;
             Ok(conn.affected_rows() as u64)
-        })
+        });
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
-        self.execute_with_connection(|conn| {
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self.execute_with_connection(|conn| {
             let params: Vec<mysql::Value> =
                 params.iter().map(MySqlDatabase::value_to_mysql).collect();
             let stmt = conn
@@ -232,11 +316,14 @@ impl RelationalDatabase for MySqlDatabase {
                     let mut values = Vec::new();
                     let columns = row.columns();
 
-                    for (i, _column) in columns.iter().enumerate() {
+                    for (i, column) in columns.iter().enumerate() {
                         let value = row.get(i).ok_or_else(|| {
                             DbError::QueryError("Missing column value".to_string().into())
                         })?;
-                        values.push(Self::convert_mysql_to_value(value)?);
+                        values.push(Self::narrow_integer(
+                            Self::convert_mysql_to_value(value)?,
+                            column,
+                        ));
                     }
 
                     Ok::<Row, DbError>(Row {
@@ -244,14 +331,22 @@ impl RelationalDatabase for MySqlDatabase {
                         values,
                     })
                 })
-                .map_err(|e| DbError::QueryError(e.to_string().into()))?;
+                .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))?;
 
             let mut rows = Vec::new();
             for row_result in result {
                 rows.push(row_result?);
             }
             Ok(rows)
-        })
+        });
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
@@ -260,16 +355,25 @@ impl RelationalDatabase for MySqlDatabase {
     }
 
     fn get_connection(&self) -> Result<Connection, DbError> {
-        let _conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let _conn = self.pool.get(DbError::PoolError)?;
         Ok(Connection {})
     }
 
     fn release_connection(&self, _conn: Connection) -> Result<(), DbError> {
         Ok(())
     }
+
+    fn pool_status(&self) -> PoolStatus {
+        let Ok(pool) = self.pool.current() else {
+            return PoolStatus::default();
+        };
+        let state = pool.state();
+        PoolStatus {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: pool.max_size(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -286,6 +390,7 @@ mod tests {
             password: "root".to_string(),
             database_name: "test".to_string(),
             max_size: 10,
+            ..Default::default()
         };
         MySqlDatabase::connect(config).unwrap()
     }
@@ -298,6 +403,27 @@ mod tests {
         assert!(db.ping().is_ok());
     }
 
+    #[test]
+    #[serial]
+    fn test_pool_status() {
+        let db = setup_test_db();
+        assert!(db.ping_timeout(Duration::from_secs(5)).is_ok());
+
+        let status = db.pool_status();
+        assert!(status.connections <= 10);
+        assert!(status.idle_connections <= status.connections);
+        assert!(status.connections <= status.max_size);
+
+        // 取出两个连接后空闲数相应减少
+        let held = vec![
+            db.pool.current().unwrap().get().unwrap(),
+            db.pool.current().unwrap().get().unwrap(),
+        ];
+        let status = db.pool_status();
+        assert_eq!(status.connections - status.idle_connections, 2);
+        drop(held);
+    }
+
     #[test]
     // #[ignore]
     #[serial]
@@ -482,4 +608,58 @@ mod tests {
             panic!("Expected DateTime");
         }
     }
+
+    #[test]
+    #[serial]
+    fn test_execute_unique_violation() {
+        let db = setup_test_db();
+        db.execute("DROP TABLE IF EXISTS unique_test", vec![])
+            .unwrap();
+        db.execute(
+            "CREATE TABLE unique_test (id INT AUTO_INCREMENT PRIMARY KEY, name VARCHAR(255) UNIQUE)",
+            vec![],
+        )
+        .unwrap();
+
+        let insert = "INSERT INTO unique_test (name) VALUES (?)";
+        db.execute(insert, vec![Value::Text("Alice".to_string())])
+            .unwrap();
+        match db.execute(insert, vec![Value::Text("Alice".to_string())]) {
+            Err(DbError::QueryError(QueryErrorKind::UniqueViolation(msg))) => {
+                println!("Unique violation error: {}", msg);
+            }
+            Err(e) => panic!("期望 UniqueViolation, 但得到了其他错误: {:?}", e),
+            Ok(_) => panic!("期望错误, 但执行成功"),
+        }
+
+        db.execute("DROP TABLE unique_test", vec![]).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_execute_check_violation() {
+        let db = setup_test_db();
+        db.execute("DROP TABLE IF EXISTS check_test", vec![])
+            .unwrap();
+        // MySQL 8.0.16 起才会执行 CHECK 约束
+        db.execute(
+            "CREATE TABLE check_test (id INT PRIMARY KEY, age INT CHECK (age >= 0))",
+            vec![],
+        )
+        .unwrap();
+
+        let res = db.execute(
+            "INSERT INTO check_test (id, age) VALUES (?, ?)",
+            vec![Value::Int(1), Value::Int(-1)],
+        );
+        match res {
+            Err(DbError::QueryError(QueryErrorKind::CheckViolation(msg))) => {
+                println!("Check violation error: {}", msg);
+            }
+            Err(e) => panic!("期望 CheckViolation, 但得到了其他错误: {:?}", e),
+            Ok(_) => panic!("期望错误, 但执行成功"),
+        }
+
+        db.execute("DROP TABLE check_test", vec![]).unwrap();
+    }
 }
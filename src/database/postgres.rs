@@ -1,118 +1,187 @@
+use crate::common::{observe_query, pg_tls, run_with_timeout, ClosablePool, PgTls, StatementCache};
 use crate::database::{
-    Connection, DatabaseConfig, DbError, QueryErrorKind, RelationalDatabase, Row, Value,
+    Connection, DatabaseConfig, DbError, ErrorDetail, PoolStatus, QueryErrorKind, QueryObserver,
+    RelationalDatabase, Row, Value,
 };
 use chrono::{DateTime, Utc};
-use postgres::{config::Config as PostgresConfig, NoTls};
-use r2d2::{Pool, PooledConnection};
+use postgres::{
+    config::{Config as PostgresConfig, SslMode},
+    types::{Kind, Type},
+    Client, SimpleQueryMessage, Statement,
+};
+use r2d2::{ManageConnection, Pool, PooledConnection};
 use r2d2_postgres::PostgresConnectionManager;
+use std::ops::{Deref, DerefMut};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 连接与它自己的预编译语句缓存, 语句只能在创建它的连接上使用
+struct CachedClient {
+    client: Client,
+    statements: StatementCache<Statement>,
+}
+
+impl CachedClient {
+    fn prepare_cached(&mut self, query: &str) -> Result<Statement, postgres::Error> {
+        if let Some(stmt) = self.statements.get(query) {
+            return Ok(stmt);
+        }
+        let stmt = self.client.prepare(query)?;
+        self.statements.insert(query, stmt.clone());
+        Ok(stmt)
+    }
+}
+
+impl Deref for CachedClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl DerefMut for CachedClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+}
+
+struct CachedConnectionManager {
+    inner: PostgresConnectionManager<PgTls>,
+    statement_cache_size: usize,
+}
+
+impl ManageConnection for CachedConnectionManager {
+    type Connection = CachedClient;
+    type Error = postgres::Error;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(CachedClient {
+            client: self.inner.connect()?,
+            statements: StatementCache::new(self.statement_cache_size),
+        })
+    }
+
+    fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        self.inner.is_valid(&mut conn.client)
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        self.inner.has_broken(&mut conn.client)
+    }
+}
 
 #[derive(Clone)]
 pub struct PostgresDatabase {
-    pool: Arc<Pool<PostgresConnectionManager<NoTls>>>,
-    current_transaction: Arc<Mutex<Option<PooledConnection<PostgresConnectionManager<NoTls>>>>>,
+    pool: ClosablePool<Pool<CachedConnectionManager>>,
+    current_transaction: Arc<Mutex<Option<PooledConnection<CachedConnectionManager>>>>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    slow_query_threshold: Option<Duration>,
 }
 
 impl PostgresDatabase {
     fn new_pool(
         config: &DatabaseConfig,
-    ) -> Result<Pool<PostgresConnectionManager<NoTls>>, r2d2::Error> {
+        tls: PgTls,
+    ) -> Result<Pool<CachedConnectionManager>, r2d2::Error> {
         let mut pg_config = PostgresConfig::new();
         pg_config
             .host(&config.host)
             .port(config.port)
             .user(&config.username)
             .password(&config.password)
-            .dbname(&config.database_name);
+            .dbname(&config.database_name)
+            .ssl_mode(if config.tls {
+                SslMode::Require
+            } else {
+                SslMode::Disable
+            });
 
-        let manager = PostgresConnectionManager::new(pg_config, NoTls);
-        Pool::builder().max_size(config.max_size).build(manager)
+        let manager = CachedConnectionManager {
+            inner: PostgresConnectionManager::new(pg_config, tls),
+            statement_cache_size: config.statement_cache_size,
+        };
+        Pool::builder()
+            .max_size(config.max_size)
+            .test_on_check_out(config.test_on_check_out)
+            .min_idle(config.min_idle)
+            .build(manager)
     }
 
-    fn params_to_postgres(params: &Vec<Value>) -> Vec<&(dyn postgres::types::ToSql + Sync)> {
+    fn params_to_postgres(params: &[Value]) -> Vec<&(dyn postgres::types::ToSql + Sync)> {
+        // 统一按 Value 的 ToSql 编码, 类型不匹配时返回错误而不是 panic
         params
             .iter()
-            .map(|v| match v {
-                Value::Int(i) => i as &(dyn postgres::types::ToSql + Sync),
-                Value::Bigint(i) => i as &(dyn postgres::types::ToSql + Sync),
-                Value::Text(s) => s as &(dyn postgres::types::ToSql + Sync),
-                Value::Varchar(s) => s as &(dyn postgres::types::ToSql + Sync),
-                Value::Float(f) => f as &(dyn postgres::types::ToSql + Sync),
-                Value::Double(d) => d as &(dyn postgres::types::ToSql + Sync),
-                Value::Boolean(b) => b as &(dyn postgres::types::ToSql + Sync),
-                Value::Bytes(by) => by as &(dyn postgres::types::ToSql + Sync),
-                Value::DateTime(dt) => dt as &(dyn postgres::types::ToSql + Sync),
-                Value::Null => &None::<&str> as &(dyn postgres::types::ToSql + Sync),
-                _ => unimplemented!(),
-            })
+            .map(|v| v as &(dyn postgres::types::ToSql + Sync))
             .collect::<Vec<_>>()
     }
 
+    // NULL 列统一转换为 Value::Null
     fn convert_postgres_to_value(
         value: &postgres::row::Row,
         index: usize,
     ) -> Result<Value, DbError> {
         let column = &value.columns()[index];
-        match *column.type_() {
-            postgres::types::Type::VOID => Ok(Value::Null),
-            postgres::types::Type::INT8 => {
-                let val: i64 = value.get(index);
-                Ok(Value::Bigint(val))
-            }
-            postgres::types::Type::INT4 => {
-                let val: i32 = value.get(index);
-                Ok(Value::Int(val))
-            }
-            postgres::types::Type::FLOAT4 => {
-                let val: f32 = value.get(index);
-                Ok(Value::Float(val))
+        let value = match *column.type_() {
+            Type::VOID => None,
+            Type::INT8 => value.get::<_, Option<i64>>(index).map(Value::Bigint),
+            Type::INT4 => value.get::<_, Option<i32>>(index).map(Value::Int),
+            Type::INT2 => value.get::<_, Option<i16>>(index).map(Value::Smallint),
+            Type::FLOAT4 => value.get::<_, Option<f32>>(index).map(Value::Float),
+            Type::FLOAT8 => value.get::<_, Option<f64>>(index).map(Value::Double),
+            Type::TEXT => value.get::<_, Option<String>>(index).map(Value::Text),
+            Type::VARCHAR => value.get::<_, Option<String>>(index).map(Value::Varchar),
+            Type::BOOL => value.get::<_, Option<bool>>(index).map(Value::Boolean),
+            Type::BYTEA => value.get::<_, Option<Vec<u8>>>(index).map(Value::Bytes),
+            Type::TIMESTAMPTZ => value
+                .get::<_, Option<DateTime<Utc>>>(index)
+                .map(Value::DateTime),
+            Type::INTERVAL => value.get::<_, Option<Value>>(index),
+            ref ty if matches!(ty.kind(), Kind::Array(_)) => value.get::<_, Option<Value>>(index),
+            _ => {
+                return Err(DbError::ConversionError(
+                    "Unsupported Postgres type".to_string(),
+                ))
             }
-            postgres::types::Type::FLOAT8 => {
-                let val: f64 = value.get(index);
-                Ok(Value::Double(val))
-            }
-            postgres::types::Type::TEXT => {
-                let val: String = value.get(index);
-                Ok(Value::Text(val))
-            }
-            postgres::types::Type::VARCHAR => {
-                let val: String = value.get(index);
-                Ok(Value::Varchar(val))
-            }
-            postgres::types::Type::BOOL => {
-                let val: bool = value.get(index);
-                Ok(Value::Boolean(val))
-            }
-            postgres::types::Type::BYTEA => {
-                let val: Vec<u8> = value.get(index);
-                Ok(Value::Bytes(val))
-            }
-            postgres::types::Type::TIMESTAMPTZ => {
-                let val: DateTime<Utc> = value.get(index);
-                Ok(Value::DateTime(val))
+        };
+        Ok(value.unwrap_or(Value::Null))
+    }
+
+    // 文本协议的结果, 返回 (行, 最后一条语句影响的行数)
+    fn simple_rows(messages: Vec<SimpleQueryMessage>) -> (Vec<Row>, u64) {
+        let mut rows = Vec::new();
+        let mut affected = 0;
+        for message in messages {
+            match message {
+                SimpleQueryMessage::Row(row) => rows.push(Row {
+                    columns: row.columns().iter().map(|c| c.name().to_string()).collect(),
+                    values: (0..row.len())
+                        .map(|i| {
+                            row.get(i)
+                                .map_or(Value::Null, |v| Value::Text(v.to_string()))
+                        })
+                        .collect(),
+                }),
+                SimpleQueryMessage::CommandComplete(n) => affected = n,
+                _ => {}
             }
-            _ => Err(DbError::ConversionError(
-                "Unsupported Postgres type".to_string(),
-            )),
         }
+        (rows, affected)
     }
 
     fn execute_with_connection<F, T>(&self, f: F) -> Result<T, DbError>
     where
-        F: FnOnce(&mut PooledConnection<PostgresConnectionManager<NoTls>>) -> Result<T, DbError>,
+        F: FnOnce(&mut PooledConnection<CachedConnectionManager>) -> Result<T, DbError>,
     {
         let mut transaction_guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         let mut conn = if let Some(conn) = &mut *transaction_guard {
             conn
         } else {
-            &mut self
-                .pool
-                .get()
-                .map_err(|e| DbError::ConnectionError(e.to_string()))?
+            &mut self.pool.get(DbError::ConnectionError)?
         };
 
         f(&mut conn)
@@ -122,7 +191,7 @@ impl PostgresDatabase {
 #[cfg(all(not(feature = "full"), feature = "postgresql"))]
 impl From<postgres::Error> for DbError {
     fn from(err: postgres::Error) -> DbError {
-        DbError::QueryError(err.to_string().into())
+        DbError::QueryError(ErrorDetail::new(err).into())
     }
 }
 
@@ -135,41 +204,56 @@ impl RelationalDatabase for PostgresDatabase {
     }
 
     fn connect(config: DatabaseConfig) -> Result<Self, DbError> {
-        let pool = Self::new_pool(&config).map_err(|e| DbError::ConnectionError(e.to_string()))?;
+        let pool = Self::new_pool(&config, pg_tls(&config)?)
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
 
         Ok(PostgresDatabase {
-            pool: Arc::new(pool),
+            pool: ClosablePool::new(pool, &config),
             current_transaction: Arc::new(Mutex::new(None)),
+            observer: config.observer,
+            slow_query_threshold: config.slow_query_threshold,
         })
     }
 
+    // 之后的操作返回 DbError::Closed, 空闲连接随池一起释放, 进行中的事务被丢弃
     fn close(&self) -> Result<(), DbError> {
+        self.pool.close();
+        self.current_transaction
+            .lock()
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?
+            .take();
         Ok(())
     }
 
     fn ping(&self) -> Result<(), DbError> {
-        let mut conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+        let mut conn = self.pool.get(DbError::ConnectionError)?;
         conn.execute("SELECT 1", &[])
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
         Ok(())
     }
 
+    // 取连接和检查都限制在超时内, 服务器挂起时也不会一直阻塞
+    fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
+        let pool = self.pool.current()?;
+        run_with_timeout(timeout, move || {
+            let mut conn = pool
+                .get_timeout(timeout)
+                .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?;
+            conn.is_valid(timeout)
+                .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))
+        })
+    }
+
     fn begin_transaction(&self) -> Result<(), DbError> {
-        let mut conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+        let mut conn = self.pool.get(DbError::TransactionError)?;
 
         conn.execute("START TRANSACTION", &[])
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
 
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
         *guard = Some(conn);
 
         Ok(())
@@ -179,11 +263,11 @@ impl RelationalDatabase for PostgresDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(mut conn) = guard.take() {
             conn.execute("COMMIT", &[])
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
@@ -192,81 +276,113 @@ impl RelationalDatabase for PostgresDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(mut conn) = guard.take() {
             conn.execute("ROLLBACK", &[])
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
 
+    fn session(&self) -> Self {
+        Self {
+            current_transaction: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
     fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
-        self.execute_with_connection(|conn| {
-            let stmt = conn.prepare(query)?;
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self.execute_with_connection(|conn| {
+            let stmt = conn.prepare_cached(query)?;
             let params = Self::params_to_postgres(&params);
 
             // let rows_affected = conn.execute(&stmt, &params[..])?;
 
             // Ok(rows_affected)
-            conn.execute(&stmt, &params).map_err(|e| {
+            let result = conn.execute(&stmt, &params).map_err(|e| {
                 if let Some(db_err) = e.as_db_error() {
                     match db_err.code().code() {
                         "23503" => {
                             // 外键约束错误
                             DbError::QueryError(QueryErrorKind::ForeignKeyViolation(
-                                db_err.message().to_string(),
+                                ErrorDetail::with_source(db_err.message().to_string(), e),
                             ))
                         }
                         "23505" => {
                             // 唯一约束错误（包括主键冲突）
                             DbError::QueryError(QueryErrorKind::UniqueViolation(
-                                db_err.message().to_string(),
+                                ErrorDetail::with_source(db_err.message().to_string(), e),
                             ))
                         }
                         "23502" => {
                             // 非空约束错误
                             DbError::QueryError(QueryErrorKind::NotNullViolation(
-                                db_err.message().to_string(),
+                                ErrorDetail::with_source(db_err.message().to_string(), e),
                             ))
                         }
                         "23514" => {
                             // 检查约束错误
                             DbError::QueryError(QueryErrorKind::CheckViolation(
-                                db_err.message().to_string(),
+                                ErrorDetail::with_source(db_err.message().to_string(), e),
                             ))
                         }
                         "23P01" => {
                             // 排他约束错误
                             DbError::QueryError(QueryErrorKind::ExclusionViolation(
-                                db_err.message().to_string(),
+                                ErrorDetail::with_source(db_err.message().to_string(), e),
                             ))
                         }
                         _ => {
                             // 其他数据库错误
-                            DbError::QueryError(QueryErrorKind::Other(format!(
-                                "code: {}, message: {}",
-                                db_err.code().code(),
-                                db_err.message().to_string()
+                            DbError::QueryError(QueryErrorKind::Other(ErrorDetail::with_source(
+                                format!(
+                                    "code: {}, message: {}",
+                                    db_err.code().code(),
+                                    db_err.message().to_string()
+                                ),
+                                e,
                             )))
                         }
                     }
                 } else {
                     // 如果不是数据库错误，比如 IO 错误等
-                    DbError::QueryError(QueryErrorKind::Other(format!(
-                        "message: {}",
-                        e.to_string()
+                    DbError::QueryError(QueryErrorKind::Other(ErrorDetail::with_source(
+                        format!("message: {}", e.to_string()),
+                        e,
                     )))
                 }
-            })
-        })
+            });
+            if result.is_err() {
+                conn.statements.remove(query);
+            }
+            result
+        });
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
-        self.execute_with_connection(|conn| {
-            let stmt = conn.prepare(query)?;
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self.execute_with_connection(|conn| {
+            let stmt = conn.prepare_cached(query)?;
             let params = Self::params_to_postgres(&params);
-            let result = conn.query(&stmt, &params[..])?;
+            let result = match conn.query(&stmt, &params[..]) {
+                Ok(result) => result,
+                Err(e) => {
+                    conn.statements.remove(query);
+                    return Err(e.into());
+                }
+            };
 
             let mut rows = Vec::new();
             for row in result {
@@ -283,7 +399,15 @@ impl RelationalDatabase for PostgresDatabase {
                 });
             }
             Ok(rows)
-        })
+        });
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
@@ -291,17 +415,40 @@ impl RelationalDatabase for PostgresDatabase {
         Ok(rows.pop())
     }
 
+    fn execute_raw(&self, sql: &str) -> Result<u64, DbError> {
+        let start = Instant::now();
+        let result = self.execute_with_connection(|conn| Ok(conn.simple_query(sql)?));
+        observe_query(&self.observer, self.slow_query_threshold, sql, 0, start);
+        Ok(Self::simple_rows(result?).1)
+    }
+
+    fn query_raw(&self, sql: &str) -> Result<Vec<Row>, DbError> {
+        let start = Instant::now();
+        let result = self.execute_with_connection(|conn| Ok(conn.simple_query(sql)?));
+        observe_query(&self.observer, self.slow_query_threshold, sql, 0, start);
+        Ok(Self::simple_rows(result?).0)
+    }
+
     fn get_connection(&self) -> Result<Connection, DbError> {
-        let _conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let _conn = self.pool.get(DbError::PoolError)?;
         Ok(Connection {})
     }
 
     fn release_connection(&self, _conn: Connection) -> Result<(), DbError> {
         Ok(())
     }
+
+    fn pool_status(&self) -> PoolStatus {
+        let Ok(pool) = self.pool.current() else {
+            return PoolStatus::default();
+        };
+        let state = pool.state();
+        PoolStatus {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: pool.max_size(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -309,6 +456,9 @@ mod tests {
     use super::*;
     use chrono::Utc;
     use serial_test::serial;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     fn setup_test_db() -> PostgresDatabase {
         let config = DatabaseConfig {
@@ -318,6 +468,7 @@ mod tests {
             password: "root".to_string(),
             database_name: "test".to_string(),
             max_size: 10,
+            ..Default::default()
         };
         PostgresDatabase::connect(config).unwrap()
     }
@@ -330,6 +481,138 @@ mod tests {
         assert!(db.ping().is_ok());
     }
 
+    // 转发到本地 PostgreSQL 的代理, stalled 置位后丢弃数据, 模拟挂起的服务器
+    fn stalling_proxy(stalled: Arc<AtomicBool>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for client in listener.incoming() {
+                let client = client.unwrap();
+                let server = TcpStream::connect("localhost:5432").unwrap();
+                let pipes = [
+                    (client.try_clone().unwrap(), server.try_clone().unwrap()),
+                    (server, client),
+                ];
+                for (mut from, mut to) in pipes {
+                    let stalled = stalled.clone();
+                    std::thread::spawn(move || {
+                        let mut buf = [0u8; 8192];
+                        loop {
+                            let n = match from.read(&mut buf) {
+                                Ok(0) | Err(_) => break,
+                                Ok(n) => n,
+                            };
+                            if stalled.load(Ordering::SeqCst) {
+                                continue;
+                            }
+                            if to.write_all(&buf[..n]).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+            }
+        });
+        port
+    }
+
+    #[test]
+    #[serial]
+    fn test_ping_timeout_when_server_hangs() {
+        let stalled = Arc::new(AtomicBool::new(false));
+        let port = stalling_proxy(stalled.clone());
+        let db = PostgresDatabase::connect(DatabaseConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: "root".to_string(),
+            password: "root".to_string(),
+            database_name: "test".to_string(),
+            max_size: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(db.ping_timeout(Duration::from_secs(5)).is_ok());
+
+        // 连接已建立, 服务器不再响应时按超时返回
+        stalled.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+        let result = db.ping_timeout(Duration::from_millis(300));
+        assert!(matches!(result, Err(DbError::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    #[serial]
+    fn test_pool_status() {
+        let db = setup_test_db();
+        assert!(db.ping_timeout(Duration::from_secs(5)).is_ok());
+
+        let status = db.pool_status();
+        assert!(status.connections <= 10);
+        assert!(status.idle_connections <= status.connections);
+        assert!(status.connections <= status.max_size);
+
+        // 取出两个连接后空闲数相应减少
+        let held = vec![
+            db.pool.current().unwrap().get().unwrap(),
+            db.pool.current().unwrap().get().unwrap(),
+        ];
+        let status = db.pool_status();
+        assert_eq!(status.connections - status.idle_connections, 2);
+        drop(held);
+    }
+
+    #[test]
+    #[serial]
+    fn test_statement_cache() {
+        // 单连接, 方便检查同一个连接上的缓存
+        let db = PostgresDatabase::connect(DatabaseConfig {
+            host: "localhost".to_string(),
+            port: 5432,
+            username: "root".to_string(),
+            password: "root".to_string(),
+            database_name: "test".to_string(),
+            max_size: 1,
+            statement_cache_size: 2,
+            ..Default::default()
+        })
+        .unwrap();
+
+        for i in 0..5 {
+            let row = db
+                .query_one("SELECT $1::INT8 AS n", vec![Value::Bigint(i)])
+                .unwrap()
+                .unwrap();
+            assert_eq!(row.values[0], Value::Bigint(i));
+        }
+        assert_eq!(
+            db.pool
+                .current()
+                .unwrap()
+                .get()
+                .unwrap()
+                .statements
+                .prepared,
+            1
+        );
+
+        // 超出容量后最久未使用的语句被淘汰, 再次执行时重新预编译
+        db.query("SELECT 1", vec![]).unwrap();
+        db.query("SELECT 2", vec![]).unwrap();
+        db.query("SELECT $1::INT8 AS n", vec![Value::Bigint(1)])
+            .unwrap();
+        assert_eq!(
+            db.pool
+                .current()
+                .unwrap()
+                .get()
+                .unwrap()
+                .statements
+                .prepared,
+            4
+        );
+    }
+
     #[test]
     #[serial]
     fn test_execute() {
@@ -605,6 +888,33 @@ mod tests {
         db.execute("DROP TABLE notnull_test", vec![]).unwrap();
     }
 
+    #[test]
+    #[serial]
+    fn test_null_params_of_any_type() {
+        let db = setup_test_db();
+        db.execute("DROP TABLE IF EXISTS typed_nulls", vec![])
+            .unwrap();
+        db.execute(
+            "CREATE TABLE typed_nulls (id INT4, due DATE, elapsed INTERVAL)",
+            vec![],
+        )
+        .unwrap();
+        db.execute(
+            "INSERT INTO typed_nulls (id, due, elapsed) VALUES ($1, $2, $3)",
+            vec![Value::Null; 3],
+        )
+        .unwrap();
+
+        let rows = db
+            .query(
+                "SELECT id FROM typed_nulls WHERE due IS NULL AND elapsed IS NULL",
+                vec![],
+            )
+            .unwrap();
+        assert_eq!(rows[0].values, vec![Value::Null]);
+        db.execute("DROP TABLE typed_nulls", vec![]).unwrap();
+    }
+
     #[test]
     #[serial]
     fn test_execute_check_violation() {
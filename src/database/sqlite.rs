@@ -1,19 +1,43 @@
-use crate::database::{Connection, DatabaseConfig, DbError, RelationalDatabase, Row, Value};
+use crate::common::{encode_array, interval_seconds, observe_query, sqlite_path, ClosablePool};
+use crate::database::{
+    Connection, DatabaseConfig, DbError, ErrorDetail, PoolStatus, QueryObserver,
+    RelationalDatabase, Row, Value,
+};
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::ToSql;
+use rusqlite::{OpenFlags, ToSql};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct SqliteDatabase {
-    pool: Arc<Pool<SqliteConnectionManager>>,
+    pool: ClosablePool<Pool<SqliteConnectionManager>>,
     current_transaction: Arc<Mutex<Option<PooledConnection<SqliteConnectionManager>>>>,
+    observer: Option<Arc<dyn QueryObserver>>,
+    slow_query_threshold: Option<Duration>,
 }
 
 impl SqliteDatabase {
-    fn new_pool(path: &str, max_size: u32) -> Result<Pool<SqliteConnectionManager>, r2d2::Error> {
-        let manager = SqliteConnectionManager::file(path);
-        Pool::builder().max_size(max_size).build(manager)
+    fn new_pool(config: &DatabaseConfig) -> Result<Pool<SqliteConnectionManager>, r2d2::Error> {
+        let pragmas: String = config
+            .pragmas
+            .iter()
+            .map(|(name, value)| format!("PRAGMA {} = {};", name, value))
+            .collect();
+        let mut manager = SqliteConnectionManager::file(sqlite_path(&config.database_name));
+        if config.read_only {
+            manager = manager.with_flags(
+                OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_URI
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            );
+        }
+        let manager = manager.with_init(move |conn| conn.execute_batch(&pragmas));
+        Pool::builder()
+            .max_size(config.max_size)
+            .test_on_check_out(config.test_on_check_out)
+            .min_idle(config.min_idle)
+            .build(manager)
     }
 
     fn value_to_sql(value: &Value) -> Box<dyn ToSql> {
@@ -21,12 +45,19 @@ impl SqliteDatabase {
             Value::Null => Box::new(None::<String>),
             Value::Int(i) => Box::new(*i),
             Value::Bigint(i) => Box::new(*i),
+            Value::Smallint(i) => Box::new(*i),
+            Value::TinyInt(i) => Box::new(*i),
+            // 超出 i64 范围时由 rusqlite 报错
+            Value::UBigint(i) => Box::new(*i),
             Value::Float(f) => Box::new(*f),
             Value::Double(f) => Box::new(*f),
             Value::Text(s) => Box::new(s.clone()),
             Value::Boolean(b) => Box::new(*b),
             Value::Bytes(b) => Box::new(b.to_vec()),
+            Value::Array(values) => Box::new(encode_array(values)),
             Value::DateTime(dt) => Box::new(dt.to_rfc3339()),
+            // SQLite 没有时间间隔类型, 存为秒数
+            Value::Interval(d) => Box::new(interval_seconds(d)),
             _ => unimplemented!(),
         }
     }
@@ -50,15 +81,12 @@ impl SqliteDatabase {
         let transaction_guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         let conn = if let Some(ref conn) = *transaction_guard {
             conn
         } else {
-            &self
-                .pool
-                .get()
-                .map_err(|e| DbError::ConnectionError(e.to_string()))?
+            &self.pool.get(DbError::ConnectionError)?
         };
 
         f(conn)
@@ -71,42 +99,55 @@ impl RelationalDatabase for SqliteDatabase {
         placeholders
     }
     fn connect(config: DatabaseConfig) -> Result<Self, DbError> {
-        let pool = Self::new_pool(&config.database_name, config.max_size)
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+        let pool =
+            Self::new_pool(&config).map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
 
         Ok(SqliteDatabase {
-            pool: Arc::new(pool),
+            pool: ClosablePool::new(pool, &config),
             current_transaction: Arc::new(Mutex::new(None)),
+            observer: config.observer,
+            slow_query_threshold: config.slow_query_threshold,
         })
     }
 
+    // 之后的操作返回 DbError::Closed, 空闲连接随池一起释放, 进行中的事务被丢弃
     fn close(&self) -> Result<(), DbError> {
+        self.pool.close();
+        self.current_transaction
+            .lock()
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?
+            .take();
         Ok(())
     }
 
     fn ping(&self) -> Result<(), DbError> {
+        let conn = self.pool.get(DbError::ConnectionError)?;
+        conn.prepare("SELECT 1")
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
+        Ok(())
+    }
+
+    fn ping_timeout(&self, timeout: Duration) -> Result<(), DbError> {
         let conn = self
             .pool
-            .get()
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+            .current()?
+            .get_timeout(timeout)
+            .map_err(|e| DbError::Timeout(ErrorDetail::new(e)))?;
         conn.prepare("SELECT 1")
-            .map_err(|e| DbError::ConnectionError(e.to_string()))?;
+            .map_err(|e| DbError::ConnectionError(ErrorDetail::new(e)))?;
         Ok(())
     }
 
     fn begin_transaction(&self) -> Result<(), DbError> {
-        let conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+        let conn = self.pool.get(DbError::TransactionError)?;
 
         conn.execute("BEGIN TRANSACTION", [])
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
 
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
         *guard = Some(conn);
 
         Ok(())
@@ -116,11 +157,11 @@ impl RelationalDatabase for SqliteDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(conn) = guard.take() {
             conn.execute("COMMIT", [])
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
@@ -129,17 +170,26 @@ impl RelationalDatabase for SqliteDatabase {
         let mut guard = self
             .current_transaction
             .lock()
-            .map_err(|e| DbError::TransactionError(e.to_string()))?;
+            .map_err(|e| DbError::TransactionError(e.to_string().into()))?;
 
         if let Some(conn) = guard.take() {
             conn.execute("ROLLBACK", [])
-                .map_err(|e| DbError::TransactionError(e.to_string()))?;
+                .map_err(|e| DbError::TransactionError(ErrorDetail::new(e)))?;
         }
         Ok(())
     }
 
+    fn session(&self) -> Self {
+        Self {
+            current_transaction: Arc::new(Mutex::new(None)),
+            ..self.clone()
+        }
+    }
+
     fn execute(&self, query: &str, params: Vec<Value>) -> Result<u64, DbError> {
-        self.execute_with_connection(|conn| {
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self.execute_with_connection(|conn| {
             let params: Vec<Box<dyn ToSql>> =
                 params.iter().map(SqliteDatabase::value_to_sql).collect();
             let mut stmt = conn
@@ -148,15 +198,25 @@ impl RelationalDatabase for SqliteDatabase {
 
             stmt.execute(rusqlite::params_from_iter(params.iter()))
                 .map(|rows| rows as u64)
-                .map_err(|e| DbError::QueryError(e.to_string().into()))
-        })
+                .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))
+        });
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     fn query(&self, query: &str, params: Vec<Value>) -> Result<Vec<Row>, DbError> {
-        self.execute_with_connection(|conn| {
+        let start = Instant::now();
+        let param_count = params.len();
+        let result = self.execute_with_connection(|conn| {
             let mut stmt = conn
                 .prepare(query)
-                .map_err(|e| DbError::QueryError(e.to_string().into()))?;
+                .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))?;
 
             let column_names: Vec<String> = stmt
                 .column_names()
@@ -194,14 +254,22 @@ impl RelationalDatabase for SqliteDatabase {
                         values,
                     })
                 })
-                .map_err(|e| DbError::QueryError(e.to_string().into()))?;
+                .map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))?;
 
             let mut results = Vec::new();
             for row in rows {
-                results.push(row.map_err(|e| DbError::QueryError(e.to_string().into()))?);
+                results.push(row.map_err(|e| DbError::QueryError(ErrorDetail::new(e).into()))?);
             }
             Ok(results)
-        })
+        });
+        observe_query(
+            &self.observer,
+            self.slow_query_threshold,
+            query,
+            param_count,
+            start,
+        );
+        result
     }
 
     fn query_one(&self, query: &str, params: Vec<Value>) -> Result<Option<Row>, DbError> {
@@ -210,16 +278,25 @@ impl RelationalDatabase for SqliteDatabase {
     }
 
     fn get_connection(&self) -> Result<Connection, DbError> {
-        let _conn = self
-            .pool
-            .get()
-            .map_err(|e| DbError::PoolError(e.to_string()))?;
+        let _conn = self.pool.get(DbError::PoolError)?;
         Ok(Connection {})
     }
 
     fn release_connection(&self, _conn: Connection) -> Result<(), DbError> {
         Ok(())
     }
+
+    fn pool_status(&self) -> PoolStatus {
+        let Ok(pool) = self.pool.current() else {
+            return PoolStatus::default();
+        };
+        let state = pool.state();
+        PoolStatus {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: pool.max_size(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -243,6 +320,129 @@ mod tests {
         assert!(db.ping().is_ok());
     }
 
+    #[test]
+    fn test_close() {
+        let db = setup_test_db();
+        let clone = db.clone();
+        db.execute("CREATE TABLE t (id INTEGER)", vec![]).unwrap();
+
+        db.close().unwrap();
+        let result = clone.query("SELECT * FROM t", vec![]);
+        assert!(matches!(result, Err(DbError::Closed)));
+        assert_eq!(db.pool_status(), PoolStatus::default());
+    }
+
+    #[test]
+    fn test_pool_status() {
+        let db = setup_test_db();
+        assert!(db.ping_timeout(Duration::from_secs(5)).is_ok());
+
+        let status = db.pool_status();
+        assert!(status.connections <= DatabaseConfig::default().max_size);
+        assert!(status.idle_connections <= status.connections);
+        assert!(status.connections <= status.max_size);
+
+        // 取出两个连接后空闲数相应减少
+        let held = vec![
+            db.pool.current().unwrap().get().unwrap(),
+            db.pool.current().unwrap().get().unwrap(),
+        ];
+        let status = db.pool_status();
+        assert_eq!(status.connections - status.idle_connections, 2);
+        drop(held);
+    }
+
+    #[test]
+    fn test_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("read_only.db")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let writer = SqliteDatabase::connect(DatabaseConfig {
+            database_name: path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        writer
+            .execute("CREATE TABLE items (id INTEGER PRIMARY KEY)", vec![])
+            .unwrap();
+        writer
+            .execute("INSERT INTO items (id) VALUES (1)", vec![])
+            .unwrap();
+
+        let reader = SqliteDatabase::connect(DatabaseConfig {
+            database_name: path,
+            read_only: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let rows = reader.query("SELECT id FROM items", vec![]).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(reader
+            .execute("INSERT INTO items (id) VALUES (2)", vec![])
+            .is_err());
+    }
+
+    #[test]
+    fn test_pragmas() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DatabaseConfig {
+            database_name: dir.path().join("pragma.db").to_str().unwrap().to_string(),
+            pragmas: vec![
+                ("journal_mode".to_string(), "WAL".to_string()),
+                ("busy_timeout".to_string(), "5000".to_string()),
+                ("foreign_keys".to_string(), "ON".to_string()),
+            ],
+            ..Default::default()
+        };
+        let db = SqliteDatabase::connect(config).unwrap();
+
+        let row = db
+            .query_one("PRAGMA journal_mode", vec![])
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.values[0], Value::Text("wal".to_string()));
+        let row = db
+            .query_one("PRAGMA foreign_keys", vec![])
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.values[0], Value::Bigint(1));
+    }
+
+    #[test]
+    fn test_memory_pool_shared() {
+        let config = DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            max_size: 5,
+            ..Default::default()
+        };
+        let db = SqliteDatabase::connect(config).unwrap();
+
+        // 同时取出两个连接, 在一个上建表, 在另一个上查询
+        let first = db.pool.current().unwrap().get().unwrap();
+        let second = db.pool.current().unwrap().get().unwrap();
+        first
+            .execute_batch("CREATE TABLE shared (id INTEGER); INSERT INTO shared VALUES (1);")
+            .unwrap();
+        let count: i64 = second
+            .query_row("SELECT COUNT(*) FROM shared", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        drop(first);
+        drop(second);
+
+        // 另一个 :memory: 库互不影响
+        let other = SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(other.query("SELECT * FROM shared", vec![]).is_err());
+    }
+
     #[test]
     fn test_execute_query() {
         let db = setup_test_db();
@@ -333,6 +533,67 @@ mod tests {
         assert_eq!(rows.len(), 1); // 应该还是1条记录
     }
 
+    #[test]
+    fn test_transaction_guard() {
+        let db = setup_test_db();
+        db.execute(
+            "CREATE TABLE test (id INTEGER PRIMARY KEY, value TEXT)",
+            vec![],
+        )
+        .unwrap();
+
+        // 调用 commit 后提交
+        let tx = db.start_transaction().unwrap();
+        tx.execute("INSERT INTO test (value) VALUES ($1)", vec!["kept".into()])
+            .unwrap();
+        tx.commit().unwrap();
+        assert_eq!(db.query("SELECT * FROM test", vec![]).unwrap().len(), 1);
+
+        // 没有 commit 就离开作用域时回滚
+        {
+            let tx = db.start_transaction().unwrap();
+            tx.execute(
+                "INSERT INTO test (value) VALUES ($1)",
+                vec!["dropped".into()],
+            )
+            .unwrap();
+        }
+        assert_eq!(db.query("SELECT * FROM test", vec![]).unwrap().len(), 1);
+
+        // 守卫结束后不再处于事务中, 之后的语句直接生效
+        db.execute("INSERT INTO test (value) VALUES ($1)", vec!["after".into()])
+            .unwrap();
+        assert_eq!(db.query("SELECT * FROM test", vec![]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_transaction_guard_isolated() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = SqliteDatabase::connect(DatabaseConfig {
+            database_name: dir.path().join("tx.db").to_str().unwrap().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        db.execute(
+            "CREATE TABLE test (id INTEGER PRIMARY KEY, value TEXT)",
+            vec![],
+        )
+        .unwrap();
+
+        // 守卫在自己的连接上执行, 未提交的数据对其他克隆不可见
+        let tx = db.start_transaction().unwrap();
+        tx.execute(
+            "INSERT INTO test (value) VALUES ($1)",
+            vec!["pending".into()],
+        )
+        .unwrap();
+        assert_eq!(tx.query("SELECT * FROM test", vec![]).unwrap().len(), 1);
+        let other = db.clone();
+        assert_eq!(other.query("SELECT * FROM test", vec![]).unwrap().len(), 0);
+        tx.commit().unwrap();
+        assert_eq!(other.query("SELECT * FROM test", vec![]).unwrap().len(), 1);
+    }
+
     #[test]
     fn test_value_conversions() {
         let db = setup_test_db();
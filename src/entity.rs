@@ -1,18 +1,66 @@
+use crate::asyncdao::DataAccessory;
 use crate::asyncdatabase::{DbError, RelationalDatabase, Row, Value};
-use crate::serde::{EntityConvertor, EntityDeserializer};
-use crate::sql_builder::SqlExecutor;
+use crate::serde::{EntityConvertor, EntityDeserializer, RenamePolicy};
+use crate::sql_builder::{is_identifier, SqlExecutor};
 use serde::{
     de::{Deserialize, DeserializeOwned},
     Serialize,
 };
 use std::io::Cursor;
 
+#[cfg(feature = "derive")]
+pub use bootrust_derive::Entity;
+
 pub trait EntityData = 'static + Sized + Sync + Send + Serialize + DeserializeOwned + Clone;
 
+// 字段名改为列名, 显式映射优先于全局规则
+fn rename_columns(
+    table: Vec<(String, Value)>,
+    columns: &[(String, String)],
+    rename_policy: RenamePolicy,
+) -> Vec<(String, Value)> {
+    table
+        .into_iter()
+        .map(|(key, value)| {
+            let column = columns
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, c)| c.clone())
+                .unwrap_or_else(|| rename_policy.apply(&key));
+            (column, value)
+        })
+        .collect()
+}
+
+// `a = ? AND b > ?` 形式的条件, 每个条件后接一个占位符
+fn where_condition(condition: &[&str], placeholders: &[String]) -> String {
+    condition
+        .iter()
+        .zip(placeholders)
+        .map(|(c, p)| format!("{} {}", c, p))
+        .collect::<Vec<String>>()
+        .join(" AND ")
+}
+
 #[async_trait::async_trait]
 pub trait Entity: Sized + Sync + Serialize + for<'de> Deserialize<'de> {
     fn row_to_entity<T: EntityData>(row: Row) -> Result<T, DbError> {
-        let de = EntityDeserializer::from_value(row.to_table());
+        let columns = Self::columns();
+        let table = row
+            .columns
+            .into_iter()
+            .zip(row.values)
+            .map(|(column, value)| {
+                let key = columns
+                    .iter()
+                    .find(|(_, c)| *c == column)
+                    .map(|(k, _)| k.clone())
+                    .unwrap_or(column);
+                (key, value)
+            })
+            .collect();
+        let de = EntityDeserializer::from_value(Value::Table(table))
+            .with_rename_policy(Self::rename_policy());
 
         T::deserialize(de).map_err(|e| DbError::ConversionError(e.to_string()))
     }
@@ -32,7 +80,9 @@ pub trait Entity: Sized + Sync + Serialize + for<'de> Deserialize<'de> {
         let mut convertor = EntityConvertor::new(cursor);
         let result = entity.serialize(&mut convertor);
         match result {
-            Ok(Value::Table(table)) => table,
+            Ok(Value::Table(table)) => {
+                rename_columns(table, &Self::columns(), Self::rename_policy())
+            }
             _ => vec![("".to_string(), Value::Null)],
         }
     }
@@ -46,6 +96,65 @@ pub trait Entity: Sized + Sync + Serialize + for<'de> Deserialize<'de> {
 
     fn primary_key() -> String;
 
+    /// 字段名与列名不一致时的 (字段名, 列名) 映射, 默认同名
+    fn columns() -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// 未在 columns 中列出的字段按此规则生成列名
+    fn rename_policy() -> RenamePolicy {
+        RenamePolicy::Verbatim
+    }
+
+    /// DataAccessory 自动维护的 (created_at, updated_at) 列, 默认不维护
+    ///
+    /// `#[entity(timestamped)]` 会重写为 `<Self as Timestamped>::timestamps()`
+    fn timestamps() -> Option<(String, String)> {
+        None
+    }
+
+    /// 同一实体的 Dao, 表名, 主键和列名映射与静态方法一致
+    fn dao<D: RelationalDatabase>(db: D) -> DataAccessory<Self, D>
+    where
+        Self: EntityData,
+    {
+        DataAccessory::from_database(db)
+    }
+
+    /// 根据实体字段生成建表语句, 列类型由数据库决定
+    fn create_table_sql(db: &impl RelationalDatabase) -> String
+    where
+        Self: Default,
+    {
+        let mut convertor = EntityConvertor::new(Cursor::new(Vec::new()));
+        let fields = match Self::default().serialize(&mut convertor) {
+            Ok(Value::Table(table)) => {
+                rename_columns(table, &Self::columns(), Self::rename_policy())
+            }
+            _ => vec![],
+        };
+        let columns: Vec<String> = fields
+            .iter()
+            .map(|(name, value)| {
+                if *name == Self::primary_key() {
+                    format!("{} {} PRIMARY KEY", name, db.column_type(value))
+                } else {
+                    format!("{} {}", name, db.column_type(value))
+                }
+            })
+            .collect();
+
+        format!("CREATE TABLE {} ({})", Self::table(), columns.join(", "))
+    }
+
+    async fn create_table(db: &impl RelationalDatabase) -> Result<u64, DbError>
+    where
+        Self: Default,
+    {
+        let query = Self::create_table_sql(db);
+        db.execute(&query, vec![]).await
+    }
+
     async fn create(
         db: &impl RelationalDatabase,
         entity: &impl EntityData,
@@ -56,8 +165,9 @@ pub trait Entity: Sized + Sync + Serialize + for<'de> Deserialize<'de> {
         let placeholders: Vec<String> = db.placeholders(&keys);
 
         let query = format!(
-            "INSERT INTO {} VALUES ({})",
+            "INSERT INTO {} ({}) VALUES ({})",
             Self::table(),
+            keys.join(", "),
             placeholders.join(", ")
         );
 
@@ -161,7 +271,6 @@ pub trait Entity: Sized + Sync + Serialize + for<'de> Deserialize<'de> {
             db.placeholders(&vec![Self::primary_key(); values.len()])[values.len() - 1].clone(),
         );
 
-        dbg!(&query);
         db.execute(&query, values).await
     }
 
@@ -187,13 +296,11 @@ pub trait Entity: Sized + Sync + Serialize + for<'de> Deserialize<'de> {
     ) -> Result<Vec<T>, DbError> {
         let conditions: Vec<String> = condition.iter().map(|s| s.to_string()).collect();
         let placeholders = db.placeholders(&conditions);
-        let where_condition: String = conditions
-            .iter()
-            .enumerate()
-            .map(|(i, c)| format!("{} {}", c, placeholders[i]))
-            .collect::<Vec<String>>()
-            .join(" AND ");
-        let query = format!("SELECT * FROM {} WHERE {}", Self::table(), where_condition);
+        let query = format!(
+            "SELECT * FROM {} WHERE {}",
+            Self::table(),
+            where_condition(condition, &placeholders)
+        );
 
         let rows = db
             .query(
@@ -208,6 +315,76 @@ pub trait Entity: Sized + Sync + Serialize + for<'de> Deserialize<'de> {
         Ok(entities)
     }
 
+    /// 按条件删除, 条件写法与 find_by_conditions 相同, 如 `&["order_id =", "amount >"]`
+    async fn delete_by_conditions(
+        db: &impl RelationalDatabase,
+        condition: &[&str],
+        params: Vec<impl Into<Value> + Send>,
+    ) -> Result<u64, DbError> {
+        let conditions: Vec<String> = condition.iter().map(|s| s.to_string()).collect();
+        let placeholders = db.placeholders(&conditions);
+        let query = format!(
+            "DELETE FROM {} WHERE {}",
+            Self::table(),
+            where_condition(condition, &placeholders)
+        );
+
+        db.execute(
+            &query,
+            params.into_iter().map(|v| v.into()).collect::<Vec<Value>>(),
+        )
+        .await
+    }
+
+    /// 按条件更新 columns 中的列, params 先是各列的新值, 再是条件的值
+    async fn update_by_conditions(
+        db: &impl RelationalDatabase,
+        columns: &[&str],
+        condition: &[&str],
+        params: Vec<impl Into<Value> + Send>,
+    ) -> Result<u64, DbError> {
+        if let Some(column) = columns.iter().find(|c| !is_identifier(c)) {
+            return Err(DbError::InvalidIdentifier(column.to_string()));
+        }
+        let placeholders = db.placeholders(&vec![String::new(); columns.len() + condition.len()]);
+        let (set_placeholders, where_placeholders) = placeholders.split_at(columns.len());
+        let assignments: Vec<String> = columns
+            .iter()
+            .zip(set_placeholders)
+            .map(|(c, p)| format!("{} = {}", c, p))
+            .collect();
+        let query = format!(
+            "UPDATE {} SET {} WHERE {}",
+            Self::table(),
+            assignments.join(", "),
+            where_condition(condition, where_placeholders)
+        );
+
+        db.execute(
+            &query,
+            params.into_iter().map(|v| v.into()).collect::<Vec<Value>>(),
+        )
+        .await
+    }
+
+    /// 清空表; SQLite 没有 TRUNCATE, 改用 DELETE
+    async fn truncate(db: &impl RelationalDatabase) -> Result<(), DbError> {
+        db.execute(&db.truncate_statement(&Self::table()), vec![])
+            .await?;
+        Ok(())
+    }
+
+    /// 删除表, if_exists 为 true 时表不存在也不报错
+    async fn drop_table(db: &impl RelationalDatabase, if_exists: bool) -> Result<(), DbError> {
+        let query = format!(
+            "DROP TABLE {}{}",
+            if if_exists { "IF EXISTS " } else { "" },
+            Self::table()
+        );
+        db.execute(&query, vec![]).await?;
+        Ok(())
+    }
+
     async fn begin_transaction(db: &impl RelationalDatabase) -> Result<(), DbError> {
         db.begin_transaction().await
     }
@@ -222,7 +399,7 @@ pub trait Entity: Sized + Sync + Serialize + for<'de> Deserialize<'de> {
 
     fn prepare<T: EntityData>(
         db: &impl RelationalDatabase,
-    ) -> SqlExecutor<impl RelationalDatabase, T> {
-        SqlExecutor::new(db, Self::table())
+    ) -> SqlExecutor<'_, impl RelationalDatabase, T> {
+        SqlExecutor::new(db, Self::table()).rename_policy(Self::rename_policy())
     }
 }
@@ -1,4 +1,15 @@
 #![feature(trait_alias)]
+
+// mysql 的两种 TLS 实现不能同时编译
+#[cfg(all(
+    feature = "tls-rustls",
+    feature = "tls-native",
+    any(feature = "mysql", feature = "mysql_async")
+))]
+compile_error!(
+    "features `tls-rustls` and `tls-native` cannot be combined with `mysql`/`mysql_async`"
+);
+
 pub mod asyncdao;
 pub mod asyncdatabase;
 #[cfg(feature = "redis_async")]
@@ -9,5 +20,9 @@ mod serde;
 pub mod dao;
 pub mod database;
 pub mod entity;
+pub mod migration;
 mod sql_builder;
-pub use sql_builder::SqlExecutor;
+pub use crate::serde::RenamePolicy;
+pub use sql_builder::{
+    escape_like, like_contains, like_starts_with, ConflictAction, SortDir, SqlExecutor, LIKE_ESCAPE,
+};
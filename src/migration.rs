@@ -0,0 +1,219 @@
+use crate::asyncdatabase::{DbError, RelationalDatabase, Value};
+
+// 记录已执行版本的表
+const MIGRATIONS_TABLE: &str = "_migrations";
+
+/// 一次表结构变更, up_sql 执行变更, down_sql 撤销
+///
+/// 多条语句用分号分隔, 通过 batch_execute 一次执行
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: i64,
+    pub up_sql: String,
+    pub down_sql: String,
+}
+
+impl Migration {
+    pub fn new(version: i64, up_sql: impl Into<String>, down_sql: impl Into<String>) -> Self {
+        Self {
+            version,
+            up_sql: up_sql.into(),
+            down_sql: down_sql.into(),
+        }
+    }
+}
+
+/// 按版本号顺序执行迁移, 每个版本在单独的事务中执行
+///
+/// MySQL 的 DDL 会隐式提交, 失败时无法回滚已执行的语句
+pub struct Migrator {
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    pub fn new(mut migrations: Vec<Migration>) -> Self {
+        migrations.sort_by_key(|m| m.version);
+        Self { migrations }
+    }
+
+    async fn ensure_table(db: &impl RelationalDatabase) -> Result<(), DbError> {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS {} (version BIGINT PRIMARY KEY)",
+            MIGRATIONS_TABLE
+        );
+        db.execute(&query, vec![]).await?;
+        Ok(())
+    }
+
+    /// 已执行的版本, 从小到大
+    pub async fn applied(&self, db: &impl RelationalDatabase) -> Result<Vec<i64>, DbError> {
+        Self::ensure_table(db).await?;
+        let query = format!("SELECT version FROM {} ORDER BY version", MIGRATIONS_TABLE);
+        db.query(&query, vec![])
+            .await?
+            .iter()
+            .map(|row| row.get_by_index(0))
+            .collect()
+    }
+
+    /// 执行所有未执行的迁移, 返回本次执行的版本
+    pub async fn migrate(&self, db: &impl RelationalDatabase) -> Result<Vec<i64>, DbError> {
+        let applied = self.applied(db).await?;
+        let mut done = Vec::new();
+        for migration in &self.migrations {
+            if applied.contains(&migration.version) {
+                continue;
+            }
+            let record = format!(
+                "INSERT INTO {} (version) VALUES ({})",
+                MIGRATIONS_TABLE,
+                db.placeholders(&["version".to_string()])[0]
+            );
+            Self::run(db, &migration.up_sql, &record, migration.version).await?;
+            done.push(migration.version);
+        }
+        Ok(done)
+    }
+
+    /// 从新到旧撤销版本号大于 to 的迁移, 返回本次撤销的版本
+    pub async fn rollback(
+        &self,
+        db: &impl RelationalDatabase,
+        to: i64,
+    ) -> Result<Vec<i64>, DbError> {
+        let applied = self.applied(db).await?;
+        let mut undone = Vec::new();
+        for migration in self.migrations.iter().rev() {
+            if migration.version <= to || !applied.contains(&migration.version) {
+                continue;
+            }
+            let record = format!(
+                "DELETE FROM {} WHERE version = {}",
+                MIGRATIONS_TABLE,
+                db.placeholders(&["version".to_string()])[0]
+            );
+            Self::run(db, &migration.down_sql, &record, migration.version).await?;
+            undone.push(migration.version);
+        }
+        Ok(undone)
+    }
+
+    // 迁移语句和版本记录在同一个事务中
+    async fn run(
+        db: &impl RelationalDatabase,
+        sql: &str,
+        record: &str,
+        version: i64,
+    ) -> Result<(), DbError> {
+        db.begin_transaction().await?;
+        let result = async {
+            db.batch_execute(sql).await?;
+            db.execute(record, vec![Value::Bigint(version)]).await
+        }
+        .await;
+        match result {
+            Ok(_) => db.commit().await,
+            Err(e) => {
+                db.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite_async"))]
+mod tests {
+    use super::*;
+    use crate::asyncdatabase::{sqlite::SqliteDatabase, DatabaseConfig};
+
+    async fn setup_db() -> SqliteDatabase {
+        SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    fn migrations() -> Vec<Migration> {
+        vec![
+            Migration::new(
+                2,
+                "ALTER TABLE users ADD COLUMN email TEXT",
+                "ALTER TABLE users DROP COLUMN email",
+            ),
+            Migration::new(
+                1,
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+                 CREATE INDEX idx_users_name ON users (name)",
+                "DROP TABLE users",
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_migrate_and_rollback() {
+        let db = setup_db().await;
+        let migrator = Migrator::new(migrations());
+
+        assert_eq!(migrator.migrate(&db).await.unwrap(), vec![1, 2]);
+        assert_eq!(migrator.applied(&db).await.unwrap(), vec![1, 2]);
+        db.execute(
+            "INSERT INTO users (id, name, email) VALUES ($1, $2, $3)",
+            vec![Value::Bigint(1), "alice".into(), "a@example.com".into()],
+        )
+        .await
+        .unwrap();
+
+        // 已执行的版本不会重复执行
+        assert!(migrator.migrate(&db).await.unwrap().is_empty());
+
+        assert_eq!(migrator.rollback(&db, 1).await.unwrap(), vec![2]);
+        assert_eq!(migrator.applied(&db).await.unwrap(), vec![1]);
+        let row = db
+            .query_one("SELECT * FROM users", vec![])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.columns, vec!["id", "name"]);
+
+        assert_eq!(migrator.rollback(&db, 0).await.unwrap(), vec![1]);
+        assert!(migrator.applied(&db).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_statement_with_semicolon() {
+        let db = setup_db().await;
+        let migrator = Migrator::new(vec![Migration::new(
+            1,
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT);
+             INSERT INTO notes (id, body) VALUES (1, 'a; b')",
+            "DROP TABLE notes",
+        )]);
+
+        assert_eq!(migrator.migrate(&db).await.unwrap(), vec![1]);
+        let row = db
+            .query_one("SELECT body FROM notes", vec![])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.get::<String>("body").unwrap(), "a; b");
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_is_not_recorded() {
+        let db = setup_db().await;
+        let mut broken = migrations();
+        broken.push(Migration::new(
+            3,
+            "CREATE TABLE tags (id INTEGER PRIMARY KEY); ALTER TABLE missing ADD COLUMN x TEXT",
+            "DROP TABLE tags",
+        ));
+        let migrator = Migrator::new(broken);
+
+        assert!(migrator.migrate(&db).await.is_err());
+        assert_eq!(migrator.applied(&db).await.unwrap(), vec![1, 2]);
+        // 同一版本中已执行的语句一起回滚
+        assert!(db.query("SELECT * FROM tags", vec![]).await.is_err());
+    }
+}
@@ -1,5 +1,9 @@
+use super::RenamePolicy;
 use crate::asyncdatabase::Value;
-use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use crate::common::interval_seconds;
+use serde::de::{
+    self, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
 // use serde::de::value::Error;
 use serde::de::value::Error as ValueError;
 use serde::de::Error;
@@ -8,12 +12,22 @@ use serde::de::Error;
 #[derive(Debug)]
 pub struct EntityDeserializer {
     value: Value,
+    rename_policy: RenamePolicy,
 }
 
 impl EntityDeserializer {
     // 从 Value 创建反序列化器
     pub fn from_value(value: Value) -> Self {
-        EntityDeserializer { value }
+        EntityDeserializer {
+            value,
+            rename_policy: RenamePolicy::default(),
+        }
+    }
+
+    // 列名按规则映射回字段名
+    pub fn with_rename_policy(mut self, rename_policy: RenamePolicy) -> Self {
+        self.rename_policy = rename_policy;
+        self
     }
 }
 
@@ -31,6 +45,35 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
         }
     }
 
+    // SQLite 的整数都以 Bigint 返回, 超出范围时报错
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::TinyInt(i) => visitor.visit_i8(i),
+            Value::Bigint(i) => visitor.visit_i8(
+                i8::try_from(i).map_err(|_| Error::custom(format!("{} out of range for i8", i)))?,
+            ),
+            _ => Err(Error::custom("Expected i8 value")),
+        }
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Smallint(i) => visitor.visit_i16(i),
+            Value::TinyInt(i) => visitor.visit_i16(i.into()),
+            Value::Bigint(i) => visitor.visit_i16(
+                i16::try_from(i)
+                    .map_err(|_| Error::custom(format!("{} out of range for i16", i)))?,
+            ),
+            _ => Err(Error::custom("Expected i16 value")),
+        }
+    }
+
     // 反序列化 i32
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
@@ -38,6 +81,8 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
     {
         match self.value {
             Value::Int(i) => visitor.visit_i32(i),
+            Value::Smallint(i) => visitor.visit_i32(i.into()),
+            Value::TinyInt(i) => visitor.visit_i32(i.into()),
             _ => Err(Error::custom("Expected i32 value")),
         }
     }
@@ -47,9 +92,33 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
     {
         match self.value {
             Value::Bigint(i) => visitor.visit_i64(i),
+            Value::Smallint(i) => visitor.visit_i64(i.into()),
+            Value::TinyInt(i) => visitor.visit_i64(i.into()),
+            // 时间间隔取整秒
+            Value::Interval(d) => visitor.visit_i64(d.num_seconds()),
+            // 时间按时间戳读取, 对应 ts_seconds 字段; 文本须为可解析的时间
+            Value::DateTime(dt) => visitor.visit_i64(dt.timestamp()),
+            Value::Text(s) => match s.parse::<chrono::DateTime<chrono::Utc>>() {
+                Ok(dt) => visitor.visit_i64(dt.timestamp()),
+                Err(_) => Err(Error::custom("Expected i64 value")),
+            },
             _ => Err(Error::custom("Expected i64 value")),
         }
     }
+    // SQLite 等没有无符号类型的数据库返回非负的 Bigint
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::UBigint(i) => visitor.visit_u64(i),
+            Value::Bigint(i) => visitor.visit_u64(
+                u64::try_from(i)
+                    .map_err(|_| Error::custom(format!("{} out of range for u64", i)))?,
+            ),
+            _ => Err(Error::custom("Expected u64 value")),
+        }
+    }
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -65,6 +134,7 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
     {
         match self.value {
             Value::Double(f) => visitor.visit_f64(f),
+            Value::Interval(d) => visitor.visit_f64(interval_seconds(&d)),
             _ => Err(Error::custom("Expected f64 value")),
         }
     }
@@ -75,6 +145,11 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
     {
         match self.value {
             Value::Boolean(b) => visitor.visit_bool(b),
+            // MySQL 的 BOOLEAN 即 TINYINT(1), SQLite 没有布尔类型, 读出的都是整数
+            Value::TinyInt(i @ (0 | 1)) => visitor.visit_bool(i == 1),
+            Value::Smallint(i @ (0 | 1)) => visitor.visit_bool(i == 1),
+            Value::Int(i @ (0 | 1)) => visitor.visit_bool(i == 1),
+            Value::Bigint(i @ (0 | 1)) => visitor.visit_bool(i == 1),
             _ => Err(Error::custom("Expected boolean value")),
         }
     }
@@ -87,6 +162,8 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
         match self.value {
             Value::Text(s) => visitor.visit_string(s),
             Value::Bytes(s) => visitor.visit_bytes(&s),
+            // 时间格式化为 RFC3339, String 和 DateTime<Utc> 字段都能读取
+            Value::DateTime(dt) => visitor.visit_string(dt.to_rfc3339()),
             _ => Err(Error::custom("Expected string value")),
         }
     }
@@ -97,6 +174,7 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
         match self.value {
             Value::Text(s) => visitor.visit_str(&s),
             Value::Bytes(s) => visitor.visit_bytes(&s),
+            Value::DateTime(dt) => visitor.visit_str(&dt.to_rfc3339()),
             _ => Err(Error::custom("Expected string value")),
         }
     }
@@ -120,22 +198,54 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
         }
     }
 
+    // 文本按变体名还原为单元变体
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Text(s) | Value::Varchar(s) => {
+                visitor.visit_enum(IntoDeserializer::<ValueError>::into_deserializer(s))
+            }
+            _ => Err(Error::custom("Expected string value for enum")),
+        }
+    }
+
     // 反序列化结构体
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        field_names: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        let rename_policy = self.rename_policy;
         match self.value {
-            Value::Table(fields) => {
+            Value::Table(mut fields) => {
+                if rename_policy != RenamePolicy::Verbatim {
+                    for (column, _) in fields.iter_mut() {
+                        if let Some(name) = field_names
+                            .iter()
+                            .find(|name| rename_policy.apply(name) == *column)
+                        {
+                            *column = name.to_string();
+                        }
+                    }
+                }
+                disambiguate_columns(&mut fields, field_names);
                 let deserializer = StructDeserializer { fields, current: 0 };
 
                 visitor.visit_map(deserializer)
             }
+            // std::time::Duration 按 (secs, nanos) 读取
+            Value::Interval(d) => visitor.visit_seq(EntitySeqAccess::new(interval_parts(d))),
             _ => Err(Error::custom("Expected struct value")),
         }
     }
@@ -155,17 +265,20 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
         V: Visitor<'de>,
     {
         match self.value {
-            Value::Bytes(ref bytes) => {
+            Value::Array(values) => visitor.visit_seq(EntitySeqAccess::new(values)),
+            Value::Bytes(bytes) => {
                 // 使用 bincode 将字节反序列化为 Vec<Value>
-
-                let vec_values: Vec<Value> = bincode::deserialize(bytes).unwrap();
-                // .map_err(|e| de::Error::custom(&e.to_string()))?;
+                // 不是 bincode 编码的二进制 (如其他程序写入的 BLOB) 按字节逐个读出, 对应 Vec<u8>
+                let vec_values: Vec<Value> = bincode::deserialize(&bytes)
+                    .unwrap_or_else(|_| bytes.into_iter().map(Value::Byte).collect());
 
                 // 构造自定义的 SeqAccess 实现
                 let seq_access = EntitySeqAccess::new(vec_values);
                 visitor.visit_seq(seq_access)
             }
-            _ => Err(de::Error::custom("Expected Value::Bytes for sequence")),
+            _ => Err(de::Error::custom(
+                "Expected Value::Array or Value::Bytes for sequence",
+            )),
         }
     }
 
@@ -183,35 +296,67 @@ impl<'de> Deserializer<'de> for EntityDeserializer {
             Value::Bigint(i) => visitor.visit_i64(i),
             Value::Float(f) => visitor.visit_f32(f),
             Value::Double(f) => visitor.visit_f64(f),
-            Value::Text(s) => visitor.visit_string(s),
+            Value::Text(s) | Value::Varchar(s) => visitor.visit_string(s),
+            Value::Byte(b) => visitor.visit_u8(b),
+            Value::Smallint(i) => visitor.visit_i16(i),
+            Value::TinyInt(i) => visitor.visit_i8(i),
+            Value::UBigint(i) => visitor.visit_u64(i),
             Value::Bytes(b) => visitor.visit_byte_buf(b), // or visit_bytes
             // Value::Bytes(b) => visitor.visit_bytes(&b),
             Value::Table(_) => self.deserialize_struct("", &[], visitor), // Treat Table as struct
-            /*
-            Value::DateTime(dt) => {
-                // Assuming you want to deserialize DateTime from a string
-                let s = dt.to_rfc3339();
-                visitor.visit_string(s)
-            }
-            */
-            // Add other Value variants as needed
-            _ => Err(Error::custom("Unsupported value type for deserialize_any")),
+            Value::Array(values) => visitor.visit_seq(EntitySeqAccess::new(values)),
+            // chrono::Duration 按 (secs, nanos) 元组读取
+            Value::Interval(d) => visitor.visit_seq(EntitySeqAccess::new(interval_parts(d))),
+            Value::DateTime(dt) => visitor.visit_string(dt.to_rfc3339()),
         }
     }
 
     serde::forward_to_deserialize_any! {
 
-         i8 i16   i128
-        u16 u32 u64 u128
+         i128
+        u16 u32 u128
          char
          unit unit_struct
         newtype_struct tuple
-        tuple_struct map enum
+        tuple_struct map
         identifier ignored_any
 
     }
 }
 
+// 秒向下取整, 纳秒总是非负, 与 chrono::Duration 的序列化格式一致
+fn interval_parts(d: chrono::Duration) -> Vec<Value> {
+    let (mut secs, mut nanos) = (d.num_seconds(), d.subsec_nanos());
+    if nanos < 0 {
+        secs -= 1;
+        nanos += 1_000_000_000;
+    }
+    vec![Value::Bigint(secs), Value::Int(nanos)]
+}
+
+// 多表查询中重名的列 (如两个 id) 按位置对应字段: 列数与字段数相同时,
+// 重名列改用同一位置的字段名, 该字段名已被其他列占用时保持不变
+fn disambiguate_columns(fields: &mut [(String, Value)], field_names: &[&str]) {
+    if fields.len() != field_names.len() {
+        return;
+    }
+    let count = |name: &str, fields: &[(String, Value)]| {
+        fields.iter().filter(|(column, _)| column == name).count()
+    };
+    let duplicated: Vec<usize> = (0..fields.len())
+        .filter(|&i| count(&fields[i].0, fields) > 1)
+        .collect();
+    if duplicated.is_empty() {
+        return;
+    }
+    for i in duplicated {
+        let name = field_names[i];
+        if fields[i].0 == name || count(name, fields) == 0 {
+            fields[i].0 = name.to_string();
+        }
+    }
+}
+
 // 用于反序列化结构体的辅助结构体
 struct StructDeserializer {
     fields: Vec<(String, Value)>,
@@ -307,20 +452,104 @@ mod tests {
         let result = i64::deserialize(de).unwrap();
         assert_eq!(result, 1234567890);
     }
+    #[test]
+    fn test_deserialize_i16() {
+        let de = EntityDeserializer::from_value(Value::Smallint(-7));
+        assert_eq!(i16::deserialize(de).unwrap(), -7);
+        let de = EntityDeserializer::from_value(Value::TinyInt(3));
+        assert_eq!(i16::deserialize(de).unwrap(), 3);
+        // SQLite 返回的 Bigint 在范围内时可以收窄
+        let de = EntityDeserializer::from_value(Value::Bigint(300));
+        assert_eq!(i16::deserialize(de).unwrap(), 300);
+        let de = EntityDeserializer::from_value(Value::Bigint(300));
+        assert!(i8::deserialize(de).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_u64() {
+        let de = EntityDeserializer::from_value(Value::UBigint(u64::MAX - 1));
+        assert_eq!(u64::deserialize(de).unwrap(), u64::MAX - 1);
+        let de = EntityDeserializer::from_value(Value::Bigint(42));
+        assert_eq!(u64::deserialize(de).unwrap(), 42);
+        let de = EntityDeserializer::from_value(Value::Bigint(-1));
+        assert!(u64::deserialize(de).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_datetime_as_string() {
+        let dt = chrono::DateTime::parse_from_rfc3339("2024-03-01T08:30:00Z")
+            .unwrap()
+            .to_utc();
+        let de = EntityDeserializer::from_value(Value::DateTime(dt));
+        assert_eq!(
+            String::deserialize(de).unwrap(),
+            "2024-03-01T08:30:00+00:00"
+        );
+        let de = EntityDeserializer::from_value(Value::DateTime(dt));
+        assert_eq!(
+            chrono::DateTime::<chrono::Utc>::deserialize(de).unwrap(),
+            dt
+        );
+    }
+
+    #[test]
+    fn test_deserialize_text_as_datetime() {
+        #[derive(Debug, Deserialize)]
+        struct Event {
+            #[serde(with = "chrono::serde::ts_seconds")]
+            created_at: chrono::DateTime<chrono::Utc>,
+            updated_at: chrono::DateTime<chrono::Utc>,
+        }
+
+        let dt = chrono::DateTime::parse_from_rfc3339("2024-03-01T08:30:00Z")
+            .unwrap()
+            .to_utc();
+        let de = EntityDeserializer::from_value(Value::Table(vec![
+            ("created_at".to_string(), "2024-03-01T08:30:00Z".into()),
+            // Utc::now().to_string() 的格式
+            ("updated_at".to_string(), dt.to_string().into()),
+        ]));
+        let event = Event::deserialize(de).unwrap();
+        assert_eq!(event.created_at, dt);
+        assert_eq!(event.updated_at, dt);
+
+        let de = EntityDeserializer::from_value(Value::Text("not a time".to_string()));
+        assert!(i64::deserialize(de).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_interval() {
+        let interval = chrono::Duration::milliseconds(90_500);
+        let de = EntityDeserializer::from_value(Value::Interval(interval));
+        assert_eq!(
+            std::time::Duration::deserialize(de).unwrap(),
+            std::time::Duration::from_millis(90_500)
+        );
+        let de = EntityDeserializer::from_value(Value::Interval(-interval));
+        assert_eq!(chrono::Duration::deserialize(de).unwrap(), -interval);
+        let de = EntityDeserializer::from_value(Value::Interval(interval));
+        assert_eq!(i64::deserialize(de).unwrap(), 90);
+        let de = EntityDeserializer::from_value(Value::Interval(interval));
+        assert_eq!(f64::deserialize(de).unwrap(), 90.5);
+        // 负的间隔无法表示为 std::time::Duration
+        let de = EntityDeserializer::from_value(Value::Interval(-interval));
+        assert!(std::time::Duration::deserialize(de).is_err());
+    }
+
     #[test]
     fn test_deserialize_f32() {
-        let value = Value::Float(3.14);
+        let value = Value::Float(1.25);
         let de = EntityDeserializer::from_value(value);
         let result = f32::deserialize(de).unwrap();
-        assert_eq!(result, 3.14);
+        assert_eq!(result, 1.25);
     }
 
     #[test]
     fn test_deserialize_f64() {
-        let value = Value::Double(2.71828);
+        let value = Value::Double(6.02214);
         let de = EntityDeserializer::from_value(value);
         let result = f64::deserialize(de).unwrap();
-        const F: f64 = 2.71828;
+        const F: f64 = 6.02214;
         assert_eq!(result, F);
     }
 
@@ -330,6 +559,21 @@ mod tests {
         let de = EntityDeserializer::from_value(value);
         let result = bool::deserialize(de).unwrap();
         assert!(result);
+
+        #[derive(Debug, Deserialize)]
+        struct User {
+            active: bool,
+        }
+        let value = Value::Table(vec![("active".to_string(), Value::Bigint(1))]);
+        assert!(
+            User::deserialize(EntityDeserializer::from_value(value))
+                .unwrap()
+                .active
+        );
+        let de = EntityDeserializer::from_value(Value::TinyInt(0));
+        assert!(!bool::deserialize(de).unwrap());
+        let de = EntityDeserializer::from_value(Value::Bigint(2));
+        assert!(bool::deserialize(de).is_err());
     }
 
     #[test]
@@ -381,4 +625,74 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_deserialize_duplicate_columns() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Line {
+            id: i64,
+            created_at: String,
+            product_created_at: String,
+        }
+
+        let value = Value::Table(vec![
+            ("id".to_string(), Value::Bigint(1)),
+            ("created_at".to_string(), Value::Text("a".to_string())),
+            ("created_at".to_string(), Value::Text("b".to_string())),
+        ]);
+        let result = Line::deserialize(EntityDeserializer::from_value(value)).unwrap();
+        assert_eq!(
+            result,
+            Line {
+                id: 1,
+                created_at: "a".to_string(),
+                product_created_at: "b".to_string(),
+            }
+        );
+
+        // 列数与字段数不同时无法按位置对应
+        let value = Value::Table(vec![
+            ("created_at".to_string(), Value::Text("a".to_string())),
+            ("created_at".to_string(), Value::Text("b".to_string())),
+        ]);
+        assert!(Line::deserialize(EntityDeserializer::from_value(value)).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_seq() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Scores {
+            id: i64,
+            scores: Vec<i64>,
+        }
+
+        let value = Value::Table(vec![
+            ("id".to_string(), Value::Bigint(1)),
+            (
+                "scores".to_string(),
+                Value::Array(vec![Value::Bigint(5), Value::Bigint(3)]),
+            ),
+        ]);
+        let result = Scores::deserialize(EntityDeserializer::from_value(value)).unwrap();
+        assert_eq!(
+            result,
+            Scores {
+                id: 1,
+                scores: vec![5, 3]
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_bytes_as_seq() {
+        // 未经 bincode 编码的原始字节
+        let value = Value::Bytes(vec![1, 2, 3]);
+        let result = Vec::<u8>::deserialize(EntityDeserializer::from_value(value)).unwrap();
+        assert_eq!(result, vec![1, 2, 3]);
+
+        let encoded = bincode::serialize(&vec![Value::Byte(4), Value::Byte(5)]).unwrap();
+        let value = Value::Bytes(encoded);
+        let result = Vec::<u8>::deserialize(EntityDeserializer::from_value(value)).unwrap();
+        assert_eq!(result, vec![4, 5]);
+    }
 }
@@ -1,6 +1,7 @@
 use serde::ser::Error;
 use serde::ser::{Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
 // use std::error::Error;
+use super::RenamePolicy;
 use crate::asyncdatabase::Value;
 use std::fmt::Display;
 use std::io;
@@ -10,6 +11,7 @@ use std::io;
 pub struct EntityConvertor<W> {
     _writer: W,                    // 写入器
     _fields: Vec<(String, Value)>, // 字段集合
+    rename_policy: RenamePolicy,   // 列名转换规则
 }
 
 // 为 EntityConvertor 实现构造函数
@@ -18,8 +20,14 @@ impl<W> EntityConvertor<W> {
         EntityConvertor {
             _writer: writer,
             _fields: Vec::new(),
+            rename_policy: RenamePolicy::default(),
         }
     }
+
+    pub fn with_rename_policy(mut self, rename_policy: RenamePolicy) -> Self {
+        self.rename_policy = rename_policy;
+        self
+    }
 }
 
 // 为 EntityConvertor 实现 Serializer trait
@@ -63,13 +71,13 @@ where
     }
 
     // 序列化 i8 值
-    fn serialize_i8(self, _v: i8) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::TinyInt(v))
     }
 
     // 序列化 i16 值
-    fn serialize_i16(self, _v: i16) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Smallint(v))
     }
 
     // 序列化 i32 值
@@ -103,8 +111,8 @@ where
         unimplemented!()
     }
     // 序列化 u64 值
-    fn serialize_u64(self, _v: u64) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::UBigint(v))
     }
 
     // 序列化 u128 值
@@ -146,7 +154,7 @@ where
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+        Ok(Value::Null)
     }
 
     // 序列化单元结构体（例如：struct Unit;）
@@ -155,13 +163,14 @@ where
     }
 
     // 序列化单元变体（例如：enum E { A, B } 中的 E::A）
+    // 单元变体按变体名存为文本, 已经过 serde(rename_all) 处理
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        unimplemented!()
+        Ok(Value::Text(variant.to_string()))
     }
 
     // 序列化 newtype 结构体（例如：struct Millimeters(u8);）
@@ -224,9 +233,13 @@ where
         unimplemented!()
     }
 
-    // 序列化 Map
+    // 序列化 Map, #[serde(flatten)] 的结构体也会走这里, 内层字段逐个展开为列
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        unimplemented!()
+        Ok(EntitySerializeStruct {
+            entity_convertor: self,
+            fields: Vec::new(),
+            key: None,
+        })
     }
 
     // 序列化结构体
@@ -238,6 +251,7 @@ where
         Ok(EntitySerializeStruct {
             entity_convertor: self,
             fields: Vec::new(),
+            key: None,
         })
     }
 
@@ -306,6 +320,7 @@ where
 pub struct EntitySerializeStruct<'a, W: 'a> {
     entity_convertor: &'a mut EntityConvertor<W>, // 实体转换器的可变引用
     fields: Vec<(String, Value)>,                 // 字段集合
+    key: Option<String>,                          // 等待写入值的 Map 键
 }
 
 // 为 EntitySerializeStruct 实现 SerializeStruct trait
@@ -325,7 +340,8 @@ where
     {
         // 递归地使用序列化器将每个字段转换为 Value
         let value = value.serialize(&mut *self.entity_convertor)?;
-        self.fields.push((key.to_string(), value));
+        let column = self.entity_convertor.rename_policy.apply(key);
+        self.fields.push((column, value));
         Ok(())
     }
 
@@ -348,23 +364,33 @@ where
     type Error = serde::de::value::Error;
 
     // 序列化字段
-    fn serialize_key<T>(&mut self, _key: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        match key.serialize(&mut *self.entity_convertor)? {
+            Value::Text(key) => {
+                self.key = Some(self.entity_convertor.rename_policy.apply(&key));
+                Ok(())
+            }
+            _ => Err(Self::Error::custom("map key must be a string")),
+        }
     }
-    fn serialize_value<T>(&mut self, _value: &T) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Self::Error::custom("serialize_value called before serialize_key"))?;
+        let value = value.serialize(&mut *self.entity_convertor)?;
+        self.fields.push((key, value));
+        Ok(())
     }
     // 结束序列化
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        // 将字段组合成一个单一的 Value::Struct 或类似的类型
-        // 为简单起见，这里返回 Value::Null，你需要根据实际情况构建正确的 Value 变体
-        Ok(Value::Null) // 占位符，替换为实际逻辑
+        Ok(Value::Table(self.fields))
     }
 }
 
@@ -397,11 +423,8 @@ where
 
     // 结束序列化并返回最终结果
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        // 组合所有元素为一个 Value::Array 类型
-        // Ok(Value::Array(self.elements))
-        let bytes =
-            bincode::serialize(&self.elements).map_err(|e| serde::de::value::Error::custom(&e))?;
-        Ok(Value::Bytes(bytes))
+        // 组合所有元素为一个 Value::Array 类型, 是否编码为二进制由各数据库决定
+        Ok(Value::Array(self.elements))
     }
 }
 
@@ -444,6 +467,35 @@ mod tests {
         // assert_eq!(convertor.fields, ...);
     }
 
+    #[test]
+    fn test_serialize_rename_and_skip() {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Account {
+            user_id: i64,
+            #[serde(rename = "mail")]
+            email: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            nickname: Option<String>,
+        }
+
+        let account = Account {
+            user_id: 1,
+            email: "a@b.c".to_string(),
+            nickname: None,
+        };
+        let mut convertor = EntityConvertor::new(Cursor::new(Vec::new()));
+        let result = account.serialize(&mut convertor).unwrap();
+
+        assert_eq!(
+            result,
+            Value::Table(vec![
+                ("userId".to_string(), Value::Bigint(1)),
+                ("mail".to_string(), Value::Text("a@b.c".to_string())),
+            ])
+        );
+    }
+
     #[test]
     fn test_serialize_bytes() {
         let cursor = Cursor::new(Vec::new());
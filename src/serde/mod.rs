@@ -3,9 +3,46 @@ mod autoser;
 pub use autode::EntityDeserializer;
 pub use autoser::EntityConvertor;
 
+/// 字段名到列名的全局转换规则, 在 serde(rename) 之后生效
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RenamePolicy {
+    /// 列名与序列化后的字段名相同
+    #[default]
+    Verbatim,
+    /// camelCase 字段名转换为 snake_case 列名
+    SnakeCase,
+}
+
+impl RenamePolicy {
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            RenamePolicy::Verbatim => name.to_string(),
+            RenamePolicy::SnakeCase => {
+                let chars: Vec<char> = name.chars().collect();
+                let mut column = String::with_capacity(name.len() + 4);
+                for (i, c) in chars.iter().enumerate() {
+                    if c.is_uppercase() {
+                        let prev_lower =
+                            i > 0 && !chars[i - 1].is_uppercase() && chars[i - 1] != '_';
+                        let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+                        if i > 0 && (prev_lower || (next_lower && chars[i - 1] != '_')) {
+                            column.push('_');
+                        }
+                        column.extend(c.to_lowercase());
+                    } else {
+                        column.push(*c);
+                    }
+                }
+                column
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::asyncdatabase::Value;
     use serde::{Deserialize, Serialize};
     use std::io::Cursor;
 
@@ -24,4 +61,86 @@ mod test {
 
         // let d1 = EntityDeserializer::from_value(d);
     }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Money {
+        amount: f64,
+        currency: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Invoice {
+        id: i64,
+        #[serde(flatten)]
+        total: Money,
+    }
+
+    #[test]
+    fn test_flatten_round_trip() {
+        let invoice = Invoice {
+            id: 1,
+            total: Money {
+                amount: 9.5,
+                currency: "CNY".to_string(),
+            },
+        };
+        let mut convertor = EntityConvertor::new(Cursor::new(Vec::new()));
+        let value = invoice.serialize(&mut convertor).unwrap();
+        match &value {
+            Value::Table(table) => assert_eq!(
+                table,
+                &vec![
+                    ("id".to_string(), Value::Bigint(1)),
+                    ("amount".to_string(), Value::Double(9.5)),
+                    ("currency".to_string(), Value::Text("CNY".to_string())),
+                ]
+            ),
+            _ => panic!("expected table"),
+        }
+
+        let de = EntityDeserializer::from_value(value);
+        assert_eq!(Invoice::deserialize(de).unwrap(), invoice);
+    }
+
+    #[test]
+    fn test_rename_policy() {
+        assert_eq!(RenamePolicy::Verbatim.apply("createdAt"), "createdAt");
+        assert_eq!(RenamePolicy::SnakeCase.apply("createdAt"), "created_at");
+        assert_eq!(RenamePolicy::SnakeCase.apply("id"), "id");
+        assert_eq!(RenamePolicy::SnakeCase.apply("orderID"), "order_id");
+        assert_eq!(RenamePolicy::SnakeCase.apply("HTTPStatus"), "http_status");
+        assert_eq!(
+            RenamePolicy::SnakeCase.apply("already_snake"),
+            "already_snake"
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[allow(non_snake_case)]
+    struct Account {
+        accountId: i64,
+        displayName: String,
+        #[serde(rename = "mail_address")]
+        email: String,
+    }
+
+    #[test]
+    fn test_rename_policy_round_trip() {
+        let account = Account {
+            accountId: 1,
+            displayName: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+        };
+        let mut convertor = EntityConvertor::new(Cursor::new(Vec::new()))
+            .with_rename_policy(RenamePolicy::SnakeCase);
+        let value = account.serialize(&mut convertor).unwrap();
+        let columns: Vec<String> = match &value {
+            Value::Table(table) => table.iter().map(|(k, _)| k.clone()).collect(),
+            _ => panic!("expected table"),
+        };
+        assert_eq!(columns, vec!["account_id", "display_name", "mail_address"]);
+
+        let de = EntityDeserializer::from_value(value).with_rename_policy(RenamePolicy::SnakeCase);
+        assert_eq!(Account::deserialize(de).unwrap(), account);
+    }
 }
@@ -1,8 +1,151 @@
-use crate::asyncdatabase::{DbError, RelationalDatabase, Row, Value};
-use crate::serde::EntityDeserializer;
+use crate::asyncdatabase::{DbError, QueryErrorKind, RelationalDatabase, Row, Value};
+use crate::serde::{EntityDeserializer, RenamePolicy};
 use serde::{de::Deserialize, ser::Serialize};
+use std::fmt;
 use std::marker::PhantomData;
 
+/// 排序方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDir {
+    Asc,
+    Desc,
+}
+
+impl fmt::Display for SortDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortDir::Asc => write!(f, "ASC"),
+            SortDir::Desc => write!(f, "DESC"),
+        }
+    }
+}
+
+/// INSERT 遇到唯一约束冲突时的处理方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictAction<'a> {
+    DoNothing,
+    /// (列名, 新值表达式), 表达式原样拼入 SQL, 不做检查;
+    /// Postgres/SQLite 用 `excluded.stock` 引用待插入的值, MySQL 用 `VALUES(stock)`
+    DoUpdate(Vec<(&'a str, &'a str)>),
+}
+
+// SQL 片段, 占位符在生成整条语句时才按出现顺序统一编号
+//
+// 每个占位符要么自带参数 (子查询, raw_where 等), 要么依次取 values 中的参数
+#[derive(Debug, Clone)]
+struct Fragment {
+    // 被占位符分隔开的文本, 总比占位符多一段
+    parts: Vec<String>,
+    params: Vec<Option<Value>>,
+}
+
+impl Fragment {
+    fn text(text: impl Into<String>) -> Self {
+        Self {
+            parts: vec![text.into()],
+            params: vec![],
+        }
+    }
+
+    // `price >` 之类的条件, 后接一个取自 values 的占位符
+    fn condition(condition: &str) -> Self {
+        let mut fragment = Self::text(format!("{} ", condition));
+        fragment.push_param(None);
+        fragment
+    }
+
+    // 原样写入的片段, `?` 依次对应 params, `$n` 对应 params[n - 1];
+    // params 为 None 时所有占位符都取自 values, 超出 params 范围的占位符原样保留
+    fn parse(raw: &str, params: Option<&[Value]>) -> Self {
+        let mut fragment = Self::text("");
+        let mut next = 0;
+        let mut chars = raw.chars().peekable();
+        while let Some(c) = chars.next() {
+            let mut token = c.to_string();
+            let index = match c {
+                '?' => {
+                    next += 1;
+                    Some(next - 1)
+                }
+                '$' => {
+                    while let Some(d) = chars.next_if(|d| d.is_ascii_digit()) {
+                        token.push(d);
+                    }
+                    token[1..]
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| n.checked_sub(1))
+                }
+                _ => None,
+            };
+            match (index, params) {
+                (Some(_), None) => fragment.push_param(None),
+                (Some(i), Some(params)) if i < params.len() => {
+                    fragment.push_param(Some(params[i].clone()))
+                }
+                _ => fragment.push_str(&token),
+            }
+        }
+        fragment
+    }
+
+    fn push_str(&mut self, text: &str) {
+        self.parts.last_mut().unwrap().push_str(text);
+    }
+
+    fn push_param(&mut self, param: Option<Value>) {
+        self.params.push(param);
+        self.parts.push(String::new());
+    }
+
+    fn append(&mut self, other: Fragment) {
+        let mut parts = other.parts.into_iter();
+        self.push_str(&parts.next().unwrap_or_default());
+        self.parts.extend(parts);
+        self.params.extend(other.params);
+    }
+
+    fn append_joined(&mut self, fragments: &[Fragment], sep: &str) {
+        for (i, fragment) in fragments.iter().enumerate() {
+            if i > 0 {
+                self.push_str(sep);
+            }
+            self.append(fragment.clone());
+        }
+    }
+
+    // 需要从 values 取值的占位符个数
+    fn unbound_count(&self) -> usize {
+        self.params.iter().filter(|p| p.is_none()).count()
+    }
+
+    // 未绑定的占位符依次取 values, 返回 SQL 和完整的参数列表
+    fn render(self, placeholders: &[String], values: &[Value]) -> (String, Vec<Value>) {
+        let mut sql = String::new();
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0 {
+                sql.push_str(&placeholders[i - 1]);
+            }
+            sql.push_str(part);
+        }
+        let mut values = values.iter().cloned();
+        let params = self
+            .params
+            .into_iter()
+            .filter_map(|p| p.or_else(|| values.next()))
+            .collect();
+        (sql, params)
+    }
+}
+
+/// SQL 构造器, 占位符统一由 `database.placeholders` 生成, 同一条构造链可用于任意后端
+///
+/// 占位符在生成 SQL 时按其在语句中出现的位置编号, 与各方法的调用顺序无关.
+/// `values` 给出的参数按语句中的位置依次填入没有自带参数的占位符:
+/// INSERT 的列, UPDATE 的 SET, JOIN 的 ON 条件, WHERE, HAVING;
+/// 同一部分内按调用顺序排列. where_in_subquery, raw_where, where_between 等
+/// 自带参数的条件不占用 values. values 的个数与此不符时, 执行返回
+/// `DbError::ParameterCountMismatch`
 pub struct SqlExecutor<'a, D, T>
 where
     D: RelationalDatabase,
@@ -13,15 +156,86 @@ where
     query_type: Option<String>,
     table: Option<String>,
     columns: Vec<String>,
-    set_clauses: Vec<String>,
+    set_clauses: Vec<Fragment>,
     values: Vec<Value>,
-    where_clauses: Vec<String>,
+    where_clauses: Vec<Fragment>,
     order_by: Vec<String>,
     group_by: Vec<String>,
-    having: Vec<String>,
-    joins: Vec<String>,
+    having: Vec<Fragment>,
+    joins: Vec<Fragment>,
     limit: Option<u32>,
     offset: Option<u32>,
+    rename_policy: RenamePolicy,
+    // 为 true 时不检查标识符
+    unchecked: bool,
+    // 第一个不合法的标识符, 执行时返回
+    error: Option<DbError>,
+    // INSERT 冲突处理子句, 由数据库按各自语法生成
+    on_conflict: Option<String>,
+    // 为 true 时 `表名.列名` 形式的查询列以原名作为结果的列名
+    qualify_columns: bool,
+    // UNION 的各个查询, 参数已绑定; 附带其查询列数, 生成时检查
+    unions: Vec<(Fragment, Option<usize>)>,
+}
+
+/// 是否为合法的列名: 字母, 数字, 下划线, 可带表名前缀
+pub(crate) fn is_identifier(name: &str) -> bool {
+    name.split('.').all(|part| {
+        part.chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Dao::find_by_like 使用的 LIKE 转义字符, 生成的条件带 `ESCAPE '!'`
+pub const LIKE_ESCAPE: char = '!';
+
+/// 转义 `%` `_` 和转义字符本身, 使文本在 LIKE 中按字面匹配
+pub fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | LIKE_ESCAPE) {
+            escaped.push(LIKE_ESCAPE);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// 包含 text 的 LIKE 模式, 即 `%text%`
+pub fn like_contains(text: &str) -> String {
+    format!("%{}%", escape_like(text))
+}
+
+/// 以 text 开头的 LIKE 模式, 即 `text%`
+pub fn like_starts_with(text: &str) -> String {
+    format!("{}%", escape_like(text))
+}
+
+// 查询列数, 含 `*` 时无法确定
+fn column_count(columns: &[String]) -> Option<usize> {
+    if columns.iter().any(|c| c == "*" || c.ends_with(".*")) {
+        return None;
+    }
+    Some(columns.len())
+}
+
+// 列名后可跟 ASC/DESC 或 AS 别名, 单独的 * 也允许
+fn is_column_expr(expr: &str) -> bool {
+    let parts: Vec<&str> = expr.split_whitespace().collect();
+    match parts.as_slice() {
+        ["*"] => true,
+        [name] => is_identifier(name) || name.strip_suffix(".*").is_some_and(is_identifier),
+        [name, dir] => {
+            is_identifier(name)
+                && (dir.eq_ignore_ascii_case("asc") || dir.eq_ignore_ascii_case("desc"))
+        }
+        [name, as_, alias] => {
+            is_identifier(name) && as_.eq_ignore_ascii_case("as") && is_identifier(alias)
+        }
+        _ => false,
+    }
 }
 
 impl<'a, D, T> SqlExecutor<'a, D, T>
@@ -46,116 +260,379 @@ where
             joins: vec![],
             limit: None,
             offset: None,
+            rename_policy: RenamePolicy::default(),
+            unchecked: false,
+            error: None,
+            on_conflict: None,
+            qualify_columns: false,
+            unions: Vec::new(),
+        }
+    }
+
+    /// 之后设置的列名, 排序等不再检查, 调用方需自行保证不含外部输入
+    pub fn raw_unchecked(mut self) -> Self {
+        self.unchecked = true;
+        self
+    }
+
+    // 记录第一个不合法的标识符, 留到执行时返回
+    fn check_identifiers<S: AsRef<str>>(&mut self, exprs: &[S]) {
+        if self.unchecked || self.error.is_some() {
+            return;
+        }
+        if let Some(expr) = exprs.iter().find(|e| !is_column_expr(e.as_ref())) {
+            self.error = Some(DbError::InvalidIdentifier(expr.as_ref().to_string()));
         }
     }
 
+    /// 设置结果映射时的列名转换规则
+    pub fn rename_policy(mut self, rename_policy: RenamePolicy) -> Self {
+        self.rename_policy = rename_policy;
+        self
+    }
+
     pub fn find(mut self) -> Self {
         self.query_type = Some("SELECT".to_string());
         self.columns = vec!["*".to_string()];
         self
     }
     /// 选择表和列
+    ///
+    /// 结果按列名而不是位置映射到字段, `amount as total` 对应字段 total
     pub fn select(mut self, columns: &[&str]) -> Self {
+        self.check_identifiers(columns);
         self.query_type = Some("SELECT".to_string());
         self.columns = columns.iter().map(|s| s.to_string()).collect();
         self
     }
 
+    /// 追加带别名的查询列, 如 `("SUM(amount)", "total")` 生成 `SUM(amount) AS "total"`
+    ///
+    /// 结果按列名映射到字段, 列名即别名, 对应同名的字段 (或 serde rename 后的名字).
+    /// 别名加引号, 各数据库返回的列名大小写不变. 表达式原样拼入 SQL, 只检查别名,
+    /// 不要传入外部输入. 与 select 一起使用时在 select 之后调用
+    pub fn select_as(mut self, columns: &[(&str, &str)]) -> Self {
+        let aliases: Vec<&str> = columns.iter().map(|(_, alias)| *alias).collect();
+        self.check_identifiers(&aliases);
+        self.query_type = Some("SELECT".to_string());
+        self.columns.extend(
+            columns.iter().map(|(expr, alias)| {
+                format!("{} AS {}", expr, self.database.quote_identifier(alias))
+            }),
+        );
+        self
+    }
+
     /// 选择要操作的表
     pub fn from(mut self, table: &str) -> Self {
+        self.check_identifiers(&[table]);
         self.table = Some(table.to_string());
         self
     }
 
-    /// 设定 WHERE 条件
+    /// 添加 WHERE 条件, 每个条件后接一个占位符, 参数依次取自 values
+    ///
+    /// 可多次调用, 所有 WHERE 条件按调用顺序以 AND 连接
     pub fn where_clauses(mut self, condition: Vec<&str>) -> Self {
-        match self.query_type.as_deref() {
-            Some("UPDATE") => {
-                let conditions: Vec<String> = condition.iter().map(|s| s.to_string()).collect();
-                let total: Vec<String> = self
-                    .set_clauses
-                    .iter()
-                    .cloned()
-                    .chain(conditions.iter().cloned())
-                    .collect();
-                let placeholders = self.database.placeholders(&total);
-                let where_clauses = conditions
-                    .iter()
-                    .enumerate()
-                    .map(|(i, c)| format!("{} {}", c, placeholders[conditions.len() + i]))
-                    .collect::<Vec<String>>();
-
-                self.where_clauses = where_clauses;
-                self
+        self.where_clauses
+            .extend(condition.iter().map(|c| Fragment::condition(c)));
+        self
+    }
+
+    /// 添加 `column IN (子查询)` 条件
+    ///
+    /// 子查询的占位符按所在位置重新编号, 其参数随子查询一起绑定,
+    /// 外层的 values 只需给出外层自己的参数
+    pub fn where_in_subquery<U>(mut self, column: &str, mut sub: SqlExecutor<'_, D, U>) -> Self
+    where
+        U: Sized + Sync + Serialize + for<'de> Deserialize<'de>,
+    {
+        self.check_identifiers(&[column]);
+        let sub_fragment = match sub.error.take() {
+            Some(e) => Err(e),
+            None => sub.bound_fragment(),
+        };
+        match sub_fragment {
+            Ok(sub_fragment) => {
+                let mut fragment = Fragment::text(format!("{} IN (", column));
+                fragment.append(sub_fragment);
+                fragment.push_str(")");
+                self.where_clauses.push(fragment);
+            }
+            Err(e) => {
+                if self.error.is_none() {
+                    self.error = Some(e);
+                }
+            }
+        }
+        self
+    }
+
+    /// 用 `UNION` 合并另一个查询的结果, 重复的行只保留一行
+    ///
+    /// other 的参数随其一起绑定, 本查询的 values 只需给出本查询自己的参数. 本查询的
+    /// ORDER BY, LIMIT 作用于合并后的结果, other 中设置了 ORDER BY, LIMIT 或 OFFSET 时
+    /// 执行返回错误. 两边都需要明确列出查询列, 列数不同或使用 `*` 时执行返回错误
+    pub fn union<U>(self, other: SqlExecutor<'_, D, U>) -> Self
+    where
+        U: Sized + Sync + Serialize + for<'de> Deserialize<'de>,
+    {
+        self.push_union("UNION", other)
+    }
+
+    /// 用 `UNION ALL` 合并另一个查询的结果, 保留重复的行
+    pub fn union_all<U>(self, other: SqlExecutor<'_, D, U>) -> Self
+    where
+        U: Sized + Sync + Serialize + for<'de> Deserialize<'de>,
+    {
+        self.push_union("UNION ALL", other)
+    }
+
+    fn push_union<U>(mut self, keyword: &str, mut other: SqlExecutor<'_, D, U>) -> Self
+    where
+        U: Sized + Sync + Serialize + for<'de> Deserialize<'de>,
+    {
+        let other_fragment = match other.error.take() {
+            Some(e) => Err(e),
+            None if !other.order_by.is_empty() || other.limit.is_some() || other.offset.is_some() => {
+                Err(DbError::QueryError(QueryErrorKind::Other(
+                    format!(
+                        "{} operand cannot have ORDER BY, LIMIT or OFFSET, set them on the outer query",
+                        keyword
+                    )
+                    .into(),
+                )))
             }
-            _ => {
-                let conditions: Vec<String> = condition.iter().map(|s| s.to_string()).collect();
-                let placeholders = self.database.placeholders(&conditions);
-                let where_conditions: Vec<String> = conditions
-                    .iter()
-                    .enumerate()
-                    .map(|(i, c)| format!("{} {}", c, placeholders[i]))
-                    .collect::<Vec<String>>();
-
-                self.where_clauses = where_conditions;
-                self
+            None => other.bound_fragment(),
+        };
+        match other_fragment {
+            Ok(other_fragment) => {
+                let mut fragment = Fragment::text(format!(" {} ", keyword));
+                fragment.append(other_fragment);
+                self.unions.push((fragment, column_count(&other.columns)));
+            }
+            Err(e) => {
+                if self.error.is_none() {
+                    self.error = Some(e);
+                }
+            }
+        }
+        self
+    }
+
+    /// 原样追加一个 WHERE 片段, params 按顺序绑定到片段中的占位符
+    ///
+    /// 片段中的占位符写作 `?` 或 `$1`, `$2`..., 会按所在位置重新编号;
+    /// 片段不做任何检查, 不要拼接外部输入
+    pub fn raw_where(mut self, fragment: &str, params: Vec<Value>) -> Self {
+        self.where_clauses
+            .push(Fragment::parse(&format!("({})", fragment), Some(&params)));
+        self
+    }
+
+    /// 添加 `column BETWEEN low AND high` 条件
+    pub fn where_between(mut self, column: &str, low: Value, high: Value) -> Self {
+        self.check_identifiers(&[column]);
+        let mut fragment = Fragment::text(format!("{} BETWEEN ", column));
+        fragment.push_param(Some(low));
+        fragment.push_str(" AND ");
+        fragment.push_param(Some(high));
+        self.where_clauses.push(fragment);
+        self
+    }
+
+    /// 添加 `column LIKE pattern` 条件; pattern 中的 `%` `_` 不做转义
+    pub fn where_like(mut self, column: &str, pattern: Value) -> Self {
+        self.check_identifiers(&[column]);
+        let mut fragment = Fragment::text(format!("{} LIKE ", column));
+        fragment.push_param(Some(pattern));
+        self.where_clauses.push(fragment);
+        self
+    }
+
+    /// 不区分大小写的 where_like, Postgres 生成 `ILIKE`, 其他数据库比较 LOWER() 之后的值
+    pub fn where_ilike(mut self, column: &str, pattern: Value) -> Self {
+        self.check_identifiers(&[column]);
+        let clause = self.database.ilike_clause(column);
+        self.where_clauses
+            .push(Fragment::parse(&clause, Some(&[pattern])));
+        self
+    }
+
+    /// 原样追加一个查询表达式, 如 `COALESCE(amount, 0) AS amount`, 不做任何检查
+    pub fn raw_select(mut self, expr: &str) -> Self {
+        self.query_type = Some("SELECT".to_string());
+        self.columns.push(expr.to_string());
+        self
+    }
+
+    // 子查询自己的 values 绑定到其占位符上, 个数不符时返回错误
+    fn bound_fragment(&self) -> Result<Fragment, DbError> {
+        let mut fragment = self.build_fragment();
+        self.check_param_count(&fragment)?;
+        let mut values = self.values.iter().cloned();
+        for param in fragment.params.iter_mut().filter(|p| p.is_none()) {
+            *param = values.next();
+        }
+        Ok(fragment)
+    }
+
+    fn check_param_count(&self, fragment: &Fragment) -> Result<(), DbError> {
+        let expected = fragment.unbound_count();
+        if expected != self.values.len() {
+            return Err(DbError::ParameterCountMismatch {
+                expected,
+                actual: self.values.len(),
+            });
+        }
+        Ok(())
+    }
+
+    // 生成最终的 SQL 和按占位符顺序排列的参数, values 个数不符时返回错误
+    pub(crate) fn build(&mut self) -> Result<(String, Vec<Value>), DbError> {
+        self.check_deferred()?;
+        let fragment = self.build_fragment();
+        self.check_param_count(&fragment)?;
+        Ok(self.render(fragment))
+    }
+
+    // 返回 setter 中记录的错误, 并检查 UNION 两边的列数
+    fn check_deferred(&mut self) -> Result<(), DbError> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let columns = column_count(&self.columns);
+        for (_, other) in &self.unions {
+            match (columns, *other) {
+                (Some(columns), Some(other)) if columns == other => {}
+                (Some(columns), Some(other)) => {
+                    return Err(DbError::QueryError(QueryErrorKind::Other(
+                        format!(
+                            "UNION requires the same number of columns, got {} and {}",
+                            columns, other
+                        )
+                        .into(),
+                    )));
+                }
+                // `*` 的列数无法确定, 不允许
+                _ => {
+                    return Err(DbError::QueryError(QueryErrorKind::Other(
+                        "UNION requires explicit select columns, * cannot be checked".into(),
+                    )));
+                }
             }
         }
+        Ok(())
+    }
+
+    fn render(&self, fragment: Fragment) -> (String, Vec<Value>) {
+        let placeholders = self
+            .database
+            .placeholders(&vec![String::new(); fragment.params.len()]);
+        fragment.render(&placeholders, &self.values)
+    }
+
+    /// 不执行, 返回最终的 SQL 和按顺序绑定的参数, 用于调试和日志
+    ///
+    /// 不合法的标识符等在构造时记录的错误在这里返回; 不检查 values 的个数, 多余的参数被忽略
+    pub fn to_sql(mut self) -> Result<(String, Vec<Value>), DbError> {
+        self.check_deferred()?;
+        Ok(self.render(self.build_fragment()))
     }
 
     /// 添加 ORDER BY 语句
     pub fn order_by(mut self, conditions: Vec<&str>) -> Self {
+        self.check_identifiers(&conditions);
         self.order_by = conditions.iter().map(|s| s.to_string()).collect();
         self
     }
 
+    /// 按列排序, 可多次调用, 按调用顺序拼接
+    pub fn order_by_col(mut self, column: &str, dir: SortDir) -> Self {
+        self.check_identifiers(&[column]);
+        self.order_by.push(format!("{} {}", column, dir));
+        self
+    }
+
     /// 设定 GROUP BY
     pub fn group_by(mut self, columns: Vec<&str>) -> Self {
+        self.check_identifiers(&columns);
         self.group_by = columns.iter().map(|s| s.to_string()).collect();
         self
     }
 
-    /// 设定 HAVING 条件
+    /// 添加 HAVING 条件, 如 `count(*) >`, 参数在 values 中排在 WHERE 的参数之后
     pub fn having(mut self, conditions: Vec<&str>) -> Self {
-        let conditions: Vec<String> = conditions.iter().map(|s| s.to_string()).collect();
-        let total: Vec<String> = self
-            .where_clauses
-            .iter()
-            .cloned()
-            .chain(conditions.iter().cloned())
-            .collect();
-        let placeholders = self.database.placeholders(&total);
-        let having_condition = conditions
-            .iter()
-            .enumerate()
-            .map(|(i, c)| format!("{} {}", c, placeholders[self.where_clauses.len() + i]))
-            .collect::<Vec<String>>();
-
-        self.having = having_condition;
-
+        self.having
+            .extend(conditions.iter().map(|c| Fragment::condition(c)));
         self
     }
 
-    /// 添加 JOIN
+    /// 添加 JOIN, ON 条件中的占位符 (`?` 或 `$n`) 取自 values, 排在 WHERE 的参数之前
     pub fn join(mut self, table: &str, on_condition: &str) -> Self {
-        self.joins
-            .push(format!("JOIN {} ON {}", table, on_condition));
+        self.joins.push(Fragment::parse(
+            &format!("JOIN {} ON {}", table, on_condition),
+            None,
+        ));
         self
     }
 
     pub fn left_join(mut self, table: &str, on_condition: &str) -> Self {
-        self.joins
-            .push(format!("LEFT JOIN {} ON {}", table, on_condition));
+        self.joins.push(Fragment::parse(
+            &format!("LEFT JOIN {} ON {}", table, on_condition),
+            None,
+        ));
         self
     }
 
+    pub fn right_join(mut self, table: &str, on_condition: &str) -> Self {
+        self.joins.push(Fragment::parse(
+            &format!("RIGHT JOIN {} ON {}", table, on_condition),
+            None,
+        ));
+        self
+    }
+
+    /// MySQL 不支持 FULL JOIN
+    pub fn full_join(mut self, table: &str, on_condition: &str) -> Self {
+        self.joins.push(Fragment::parse(
+            &format!("FULL JOIN {} ON {}", table, on_condition),
+            None,
+        ));
+        self
+    }
+
+    /// 连接查询的结果按列名映射到字段, 各表的同名列 (如 id) 会互相覆盖.
+    /// 开启后 select 中 `表名.列名` 形式的列以 `AS "表名.列名"` 保留原名,
+    /// 字段用 `#[serde(rename = "payments.id")]` 对应; `*` 和 `表名.*` 不展开
+    pub fn qualify_columns(mut self, qualify: bool) -> Self {
+        self.qualify_columns = qualify;
+        self
+    }
+
+    // 开启 qualify_columns 时给带表名的列加上同名别名
+    fn select_columns(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|column| {
+                if self.qualify_columns && column.contains('.') && is_identifier(column) {
+                    format!("{} AS {}", column, self.database.quote_identifier(column))
+                } else {
+                    column.clone()
+                }
+            })
+            .collect()
+    }
+
     pub fn cross_join(mut self, table: &str) -> Self {
-        self.joins.push(format!("CROSS JOIN {} ", table));
+        self.joins
+            .push(Fragment::text(format!("CROSS JOIN {} ", table)));
         self
     }
 
     pub fn natural_join(mut self, table: &str) -> Self {
-        self.joins.push(format!("NATURAL JOIN {} ", table));
+        self.joins
+            .push(Fragment::text(format!("NATURAL JOIN {} ", table)));
         self
     }
 
@@ -172,35 +649,44 @@ where
     }
 
     pub fn insert(mut self, columns: &[&str]) -> Self {
+        self.check_identifiers(columns);
         self.query_type = Some("INSERT".to_string());
 
         self.columns = columns.iter().map(|s| s.to_string()).collect();
         self
     }
 
-    /// 设定插入的 VALUES
+    /// 设定参数, 按占位符在语句中的位置依次填入, 见 [`SqlExecutor`] 的说明
     pub fn values(mut self, values: Vec<impl Into<Value>>) -> Self {
         self.values = values.into_iter().map(|v| v.into()).collect();
         self
     }
 
+    /// INSERT 冲突时的处理, Postgres/SQLite 生成 `ON CONFLICT (...) DO ...`,
+    /// MySQL 生成 `ON DUPLICATE KEY UPDATE ...`, 此时 columns 不起作用.
+    /// columns 不能为空, 否则执行时返回错误
+    pub fn on_conflict(mut self, columns: &[&str], action: ConflictAction<'_>) -> Self {
+        if columns.is_empty() && self.error.is_none() {
+            self.error = Some(DbError::QueryError(QueryErrorKind::Other(
+                "on_conflict requires at least one conflict column".into(),
+            )));
+        }
+        self.check_identifiers(columns);
+        if let ConflictAction::DoUpdate(assignments) = &action {
+            let targets: Vec<&str> = assignments.iter().map(|(column, _)| *column).collect();
+            self.check_identifiers(&targets);
+        }
+        self.on_conflict = Some(self.database.on_conflict_clause(columns, &action));
+        self
+    }
+
     pub fn update(mut self, columns: &[&str]) -> Self {
+        self.check_identifiers(columns);
         self.query_type = Some("UPDATE".to_string());
-        let placeholders = self.database.placeholders(
-            &columns
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>(),
-        );
-
-        let set_clauses: Vec<String> = columns
+        self.set_clauses = columns
             .iter()
-            .enumerate()
-            .map(|(i, c)| format!("{} = {}", c, placeholders[i]))
-            .collect::<Vec<String>>();
-        self.set_clauses = set_clauses;
-
-        // self.set_clauses = columns.iter().map(|s| s.to_string()).collect();
+            .map(|c| Fragment::condition(&format!("{} =", c)))
+            .collect();
         self
     }
 
@@ -210,26 +696,29 @@ where
         self
     }
 
-    /// 生成最终的 SQL 语句
-    pub async fn query(self) -> Result<Vec<T>, DbError> {
-        let mut sql = String::new();
+    fn push_where(&self, sql: &mut Fragment) {
+        if !self.where_clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.append_joined(&self.where_clauses, " AND ");
+        }
+    }
+
+    fn build_fragment(&self) -> Fragment {
+        let mut sql = Fragment::text("");
 
         match self.query_type.as_deref() {
             Some("SELECT") => {
                 sql.push_str("SELECT ");
-                sql.push_str(&self.columns.join(", "));
+                sql.push_str(&self.select_columns().join(", "));
                 sql.push_str(" FROM ");
-                sql.push_str(&self.table.unwrap());
+                sql.push_str(self.table.as_deref().unwrap());
 
                 if !self.joins.is_empty() {
-                    sql.push(' ');
-                    sql.push_str(&self.joins.join(" "));
+                    sql.push_str(" ");
+                    sql.append_joined(&self.joins, " ");
                 }
 
-                if !self.where_clauses.is_empty() {
-                    sql.push_str(" WHERE ");
-                    sql.push_str(&self.where_clauses.join(" AND "));
-                }
+                self.push_where(&mut sql);
 
                 if !self.group_by.is_empty() {
                     sql.push_str(" GROUP BY ");
@@ -238,7 +727,11 @@ where
 
                 if !self.having.is_empty() {
                     sql.push_str(" HAVING ");
-                    sql.push_str(&self.having.join(" AND "));
+                    sql.append_joined(&self.having, " AND ");
+                }
+
+                for (fragment, _) in &self.unions {
+                    sql.append(fragment.clone());
                 }
 
                 if !self.order_by.is_empty() {
@@ -257,137 +750,382 @@ where
 
             Some("INSERT") => {
                 sql.push_str("INSERT INTO ");
-                sql.push_str(&self.table.unwrap());
+                sql.push_str(self.table.as_deref().unwrap());
                 sql.push_str(" (");
                 sql.push_str(&self.columns.join(", "));
                 sql.push_str(") VALUES (");
-                let placeholders = self.database.placeholders(
-                    &self
-                        .columns
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<String>>(),
-                );
-                sql.push_str(&placeholders.join(", "));
-                // sql.push_str(&self.values.join(", "));
-                sql.push(')');
+                for i in 0..self.columns.len() {
+                    if i > 0 {
+                        sql.push_str(", ");
+                    }
+                    sql.push_param(None);
+                }
+                sql.push_str(")");
+                if let Some(on_conflict) = &self.on_conflict {
+                    sql.push_str(" ");
+                    sql.push_str(on_conflict);
+                }
             }
             Some("UPDATE") => {
                 sql.push_str("UPDATE ");
-                sql.push_str(&self.table.unwrap());
+                sql.push_str(self.table.as_deref().unwrap());
                 sql.push_str(" SET ");
-                sql.push_str(&self.set_clauses.join(", "));
-                if !self.where_clauses.is_empty() {
-                    sql.push_str(" WHERE ");
-                    sql.push_str(&self.where_clauses.join(" AND "));
-                }
+                sql.append_joined(&self.set_clauses, ", ");
+                self.push_where(&mut sql);
             }
             Some("DELETE") => {
                 sql.push_str("DELETE FROM ");
-                sql.push_str(&self.table.unwrap());
-                if !self.where_clauses.is_empty() {
-                    sql.push_str(" WHERE ");
-                    sql.push_str(&self.where_clauses.join(" AND "));
-                }
+                sql.push_str(self.table.as_deref().unwrap());
+                self.push_where(&mut sql);
             }
 
             _ => {}
         }
-        dbg!(&sql);
-        let rows: Vec<Row> = self.database.query(&sql, self.values).await?;
+        sql
+    }
+
+    /// 生成最终的 SQL 语句
+    pub async fn query(mut self) -> Result<Vec<T>, DbError> {
+        let (sql, params) = self.build()?;
+        let rows: Vec<Row> = self.database.query(&sql, params).await?;
+        let rename_policy = self.rename_policy;
 
         // self.dao.convert_rows_to_entitys(rows);
         rows.iter()
             .map(|row| {
-                let de = EntityDeserializer::from_value(row.to_table());
+                let de = EntityDeserializer::from_value(row.to_table())
+                    .with_rename_policy(rename_policy);
                 T::deserialize(de).map_err(|e| DbError::ConversionError(e.to_string()))
             })
             .collect()
     }
 
-    pub async fn execute(self) -> Result<u64, DbError> {
-        let mut sql = String::new();
+    /// 取第一条匹配的记录
+    pub async fn first(mut self) -> Result<Option<T>, DbError> {
+        if self.query_type.is_none() {
+            self = self.find();
+        }
+        self.limit = Some(1);
+        Ok(self.query().await?.into_iter().next())
+    }
 
-        match self.query_type.as_deref() {
-            Some("SELECT") => {
-                sql.push_str("SELECT ");
-                sql.push_str(&self.columns.join(", "));
-                sql.push_str(" FROM ");
-                sql.push_str(&self.table.unwrap());
+    /// 是否存在匹配的记录
+    pub async fn exists(mut self) -> Result<bool, DbError> {
+        if self.query_type.is_none() {
+            self = self.find();
+        }
+        self.limit = Some(1);
+        let (sql, params) = self.build()?;
+        let rows = self.database.query(&sql, params).await?;
+        Ok(!rows.is_empty())
+    }
 
-                if !self.joins.is_empty() {
-                    sql.push(' ');
-                    sql.push_str(&self.joins.join(" "));
-                }
+    pub async fn execute(mut self) -> Result<u64, DbError> {
+        let (sql, params) = self.build()?;
+        self.database.execute(&sql, params).await
+    }
+}
 
-                if !self.where_clauses.is_empty() {
-                    sql.push_str(" WHERE ");
-                    sql.push_str(&self.where_clauses.join(" AND "));
-                }
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::asyncdatabase::mock::MockDatabase;
 
-                if !self.group_by.is_empty() {
-                    sql.push_str(" GROUP BY ");
-                    sql.push_str(&self.group_by.join(", "));
-                }
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Item {
+        id: i64,
+    }
 
-                if !self.having.is_empty() {
-                    sql.push_str(" HAVING ");
-                    sql.push_str(&self.having.join(" AND "));
-                }
+    fn builder(db: &MockDatabase) -> SqlExecutor<'_, MockDatabase, Item> {
+        SqlExecutor::new(db, "items".to_string())
+    }
 
-                if !self.order_by.is_empty() {
-                    sql.push_str(" ORDER BY ");
-                    sql.push_str(&self.order_by.join(", "));
-                }
+    #[test]
+    fn test_to_sql_select() {
+        let db = MockDatabase::new();
+        let (sql, params) = builder(&db)
+            .raw_unchecked()
+            .select(&["items.id", "count(*)"])
+            .join("orders", "orders.item_id = items.id")
+            .where_clauses(vec!["items.price >", "orders.status ="])
+            .group_by(vec!["items.id"])
+            .having(vec!["count(*) >"])
+            .order_by(vec!["items.id desc"])
+            .limit(10)
+            .offset(20)
+            .values(vec![Value::Double(1.5), "paid".into(), Value::Bigint(2)])
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT items.id, count(*) FROM items JOIN orders ON orders.item_id = items.id \
+             WHERE items.price > ? AND orders.status = ? GROUP BY items.id \
+             HAVING count(*) > ? ORDER BY items.id desc LIMIT 10 OFFSET 20"
+        );
+        assert_eq!(
+            params,
+            vec![Value::Double(1.5), "paid".into(), Value::Bigint(2)]
+        );
+    }
 
-                if let Some(limit) = self.limit {
-                    sql.push_str(&format!(" LIMIT {}", limit));
-                }
+    #[test]
+    fn test_to_sql_returns_deferred_error() {
+        let db = MockDatabase::new();
+        let result = builder(&db)
+            .select(&["id", "count(*)"])
+            .order_by(vec!["id desc"])
+            .to_sql();
+        assert!(matches!(result, Err(DbError::InvalidIdentifier(e)) if e == "count(*)"));
+    }
 
-                if let Some(offset) = self.offset {
-                    sql.push_str(&format!(" OFFSET {}", offset));
-                }
-            }
+    #[test]
+    fn test_to_sql_qualify_columns() {
+        let db = MockDatabase::new();
+        let (sql, _) = builder(&db)
+            .select(&["items.id", "orders.id", "name", "orders.*"])
+            .right_join("orders", "orders.item_id = items.id")
+            .qualify_columns(true)
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT items.id AS \"items.id\", orders.id AS \"orders.id\", name, orders.* \
+             FROM items RIGHT JOIN orders ON orders.item_id = items.id"
+        );
+    }
 
-            Some("INSERT") => {
-                sql.push_str("INSERT INTO ");
-                sql.push_str(&self.table.unwrap());
-                sql.push_str(" (");
-                sql.push_str(&self.columns.join(", "));
-                sql.push_str(") VALUES (");
-                let placeholders = self.database.placeholders(
-                    &self
-                        .columns
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<String>>(),
-                );
-                sql.push_str(&placeholders.join(", "));
-                // sql.push_str(&self.values.join(", "));
-                sql.push(')');
-            }
-            Some("UPDATE") => {
-                sql.push_str("UPDATE ");
-                sql.push_str(&self.table.unwrap());
-                sql.push_str(" SET ");
-                sql.push_str(&self.set_clauses.join(", "));
-                if !self.where_clauses.is_empty() {
-                    sql.push_str(" WHERE ");
-                    sql.push_str(&self.where_clauses.join(" AND "));
-                }
-            }
-            Some("DELETE") => {
-                sql.push_str("DELETE FROM ");
-                sql.push_str(&self.table.unwrap());
-                if !self.where_clauses.is_empty() {
-                    sql.push_str(" WHERE ");
-                    sql.push_str(&self.where_clauses.join(" AND "));
-                }
-            }
+    #[test]
+    fn test_to_sql_union() {
+        let db = MockDatabase::new();
+        let archived = builder(&db)
+            .select(&["id", "name"])
+            .from("archived_items")
+            .where_clauses(vec!["price >"])
+            .values(vec![Value::Double(5.0)]);
+        let (sql, params) = builder(&db)
+            .select(&["id", "name"])
+            .where_clauses(vec!["stock >"])
+            .union_all(archived)
+            .order_by(vec!["id asc"])
+            .values(vec![Value::Bigint(0)])
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT id, name FROM items WHERE stock > ? \
+             UNION ALL SELECT id, name FROM archived_items WHERE price > ? ORDER BY id asc"
+        );
+        assert_eq!(params, vec![Value::Bigint(0), Value::Double(5.0)]);
 
-            _ => {}
-        }
-        dbg!(&sql);
-        self.database.execute(&sql, self.values).await
+        // 列数不同
+        let result = builder(&db)
+            .select(&["id", "name"])
+            .union(builder(&db).select(&["id"]))
+            .build();
+        assert!(matches!(result, Err(DbError::QueryError(_))));
+    }
+
+    #[test]
+    fn test_to_sql_select_as() {
+        let db = MockDatabase::new();
+        let (sql, _) = builder(&db)
+            .select(&["order_id"])
+            .select_as(&[("SUM(amount)", "total"), ("COUNT(*)", "payments")])
+            .group_by(vec!["order_id"])
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT order_id, SUM(amount) AS \"total\", COUNT(*) AS \"payments\" \
+             FROM items GROUP BY order_id"
+        );
+
+        let result = builder(&db)
+            .select_as(&[("SUM(amount)", "total; DROP TABLE items")])
+            .build();
+        assert!(matches!(result, Err(DbError::InvalidIdentifier(_))));
+    }
+
+    #[test]
+    fn test_to_sql_insert_update_delete() {
+        let db = MockDatabase::new();
+        let (sql, params) = builder(&db)
+            .insert(&["id", "name"])
+            .values(vec![Value::Bigint(1), "pen".into()])
+            .on_conflict(&["id"], ConflictAction::DoNothing)
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "INSERT INTO items (id, name) VALUES (?, ?) ON CONFLICT (id) DO NOTHING"
+        );
+        assert_eq!(params, vec![Value::Bigint(1), "pen".into()]);
+
+        let (sql, params) = builder(&db)
+            .update(&["name", "price"])
+            .where_clauses(vec!["id ="])
+            .values(vec!["pen".into(), Value::Double(2.5), Value::Bigint(1)])
+            .to_sql()
+            .unwrap();
+        assert_eq!(sql, "UPDATE items SET name = ?, price = ? WHERE id = ?");
+        assert_eq!(params.len(), 3);
+
+        let (sql, params) = builder(&db)
+            .delete()
+            .where_clauses(vec!["id ="])
+            .values(vec![Value::Bigint(1)])
+            .to_sql()
+            .unwrap();
+        assert_eq!(sql, "DELETE FROM items WHERE id = ?");
+        assert_eq!(params, vec![Value::Bigint(1)]);
+    }
+
+    #[test]
+    fn test_on_conflict_requires_columns() {
+        let db = MockDatabase::new();
+        let result = builder(&db)
+            .insert(&["id", "name"])
+            .values(vec![Value::Bigint(1), "pen".into()])
+            .on_conflict(&[], ConflictAction::DoNothing)
+            .to_sql();
+        assert!(matches!(result, Err(DbError::QueryError(_))));
+
+        let result = builder(&db)
+            .insert(&["id", "name"])
+            .values(vec![Value::Bigint(1), "pen".into()])
+            .on_conflict(
+                &[],
+                ConflictAction::DoUpdate(vec![("name", "excluded.name")]),
+            )
+            .to_sql();
+        assert!(matches!(result, Err(DbError::QueryError(_))));
+    }
+
+    #[test]
+    fn test_to_sql_merges_subquery_params() {
+        let db = MockDatabase::new();
+        let sub = builder(&db)
+            .select(&["item_id"])
+            .from("orders")
+            .where_clauses(vec!["user_id ="])
+            .values(vec![Value::Bigint(7)]);
+        let (sql, params) = builder(&db)
+            .find()
+            .where_clauses(vec!["price <"])
+            .where_in_subquery("id", sub)
+            .raw_where("COALESCE(stock, 0) > ?", vec![Value::Bigint(0)])
+            .values(vec![Value::Double(9.9)])
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM items WHERE price < ? \
+             AND id IN (SELECT item_id FROM orders WHERE user_id = ?) \
+             AND (COALESCE(stock, 0) > ?)"
+        );
+        assert_eq!(
+            params,
+            vec![Value::Double(9.9), Value::Bigint(7), Value::Bigint(0)]
+        );
+    }
+
+    #[test]
+    fn test_param_order_follows_sql() {
+        let db = MockDatabase::new();
+        // HAVING 先于 WHERE 调用, 参数仍按语句中的位置排列
+        let (sql, params) = builder(&db)
+            .select(&["orders.item_id"])
+            .group_by(vec!["orders.item_id"])
+            .having(vec!["count(*) >="])
+            .join("orders", "orders.item_id = items.id AND orders.status = ?")
+            .where_clauses(vec!["items.price <"])
+            .raw_where("items.stock > ?", vec![Value::Bigint(0)])
+            .values(vec!["paid".into(), Value::Double(9.9), Value::Bigint(3)])
+            .build()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT orders.item_id FROM items \
+             JOIN orders ON orders.item_id = items.id AND orders.status = ? \
+             WHERE items.price < ? AND (items.stock > ?) \
+             GROUP BY orders.item_id HAVING count(*) >= ?"
+        );
+        assert_eq!(
+            params,
+            vec![
+                "paid".into(),
+                Value::Double(9.9),
+                Value::Bigint(0),
+                Value::Bigint(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_param_count_mismatch() {
+        let db = MockDatabase::new();
+        let result = builder(&db)
+            .find()
+            .where_clauses(vec!["id >"])
+            .having(vec!["count(*) >"])
+            .values(vec![Value::Bigint(1)])
+            .build();
+        assert!(matches!(
+            result,
+            Err(DbError::ParameterCountMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+
+        // 子查询的参数个数不符时, 外层执行返回同样的错误
+        let sub = builder(&db)
+            .select(&["item_id"])
+            .from("orders")
+            .where_clauses(vec!["user_id ="]);
+        let result = builder(&db).find().where_in_subquery("id", sub).build();
+        assert!(matches!(
+            result,
+            Err(DbError::ParameterCountMismatch {
+                expected: 1,
+                actual: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_to_sql_between_and_like() {
+        let db = MockDatabase::new();
+        let (sql, params) = builder(&db)
+            .find()
+            .where_clauses(vec!["category ="])
+            .where_between("price", Value::Bigint(10), Value::Bigint(20))
+            .where_like("name", "pen%".into())
+            .where_ilike("brand", "acme%".into())
+            .values(vec!["office"])
+            .to_sql()
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM items WHERE category = ? AND price BETWEEN ? AND ? \
+             AND name LIKE ? AND LOWER(brand) LIKE LOWER(?)"
+        );
+        assert_eq!(
+            params,
+            vec![
+                "office".into(),
+                Value::Bigint(10),
+                Value::Bigint(20),
+                "pen%".into(),
+                "acme%".into()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("50%_off!"), "50!%!_off!!");
+        assert_eq!(like_contains("a_b"), "%a!_b%");
+        assert_eq!(like_starts_with("test"), "test%");
     }
 }
@@ -1,4 +1,6 @@
 mod entity_crud;
+#[cfg(feature = "mock")]
+mod mock;
 #[cfg(feature = "mysql")]
 mod mysql;
 #[cfg(feature = "postgresql")]
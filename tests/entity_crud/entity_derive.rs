@@ -0,0 +1,150 @@
+use bootrust::asyncdao::{Dao, Timestamped};
+use bootrust::asyncdatabase::{sqlite::SqliteDatabase, DatabaseConfig, RelationalDatabase, Value};
+use bootrust::entity::Entity;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// 商品实体, 由 derive 生成 Entity 实现
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Entity)]
+#[entity(table = "derived_products")]
+struct Product {
+    #[entity(primary_key)]
+    id: i64,
+    #[entity(column = "product_name")]
+    name: String,
+    price: f64,
+    stock: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Entity)]
+struct OrderLine {
+    #[entity(primary_key, column = "line_id")]
+    id: i64,
+    quantity: i64,
+}
+
+// DataAccessory 自动维护时间列
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Entity)]
+#[entity(table = "derived_comments", timestamped)]
+struct Comment {
+    #[entity(primary_key)]
+    id: i64,
+    body: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl Timestamped for Comment {}
+
+async fn setup_test_db() -> SqliteDatabase {
+    let config = DatabaseConfig {
+        database_name: ":memory:".to_string(),
+        ..Default::default()
+    };
+    SqliteDatabase::connect(config).await.unwrap()
+}
+
+#[test]
+fn test_derive_metadata() {
+    assert_eq!(Product::table(), "derived_products");
+    assert_eq!(Product::primary_key(), "id");
+    assert_eq!(
+        Product::columns(),
+        vec![("name".to_string(), "product_name".to_string())]
+    );
+
+    assert_eq!(OrderLine::table(), "order_line");
+    assert_eq!(OrderLine::primary_key(), "line_id");
+}
+
+#[tokio::test]
+async fn test_derive_round_trip() {
+    let db = setup_test_db().await;
+    Product::create_table(&db).await.unwrap();
+    assert_eq!(
+        Product::create_table_sql(&db),
+        "CREATE TABLE derived_products (id BIGINT PRIMARY KEY, product_name TEXT, price DOUBLE, stock BIGINT)"
+    );
+
+    let product = Product {
+        id: 1,
+        name: "Keyboard".to_string(),
+        price: 49.5,
+        stock: 10,
+    };
+    assert_eq!(Product::create(&db, &product).await.unwrap(), 1);
+
+    let found: Option<Product> = Product::find_by_id(&db, 1i64).await.unwrap();
+    assert_eq!(found, Some(product.clone()));
+
+    let renamed = Product {
+        name: "Mechanical Keyboard".to_string(),
+        ..product
+    };
+    Product::update(&db, &renamed).await.unwrap();
+    let found: Vec<Product> = Product::find_by_conditions(
+        &db,
+        &["product_name ="],
+        vec!["Mechanical Keyboard".to_string()],
+    )
+    .await
+    .unwrap();
+    assert_eq!(found, vec![renamed]);
+}
+
+#[tokio::test]
+async fn test_derive_renamed_primary_key() {
+    let db = setup_test_db().await;
+    db.execute(
+        "CREATE TABLE order_line (line_id INTEGER PRIMARY KEY, quantity INTEGER NOT NULL)",
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let line = OrderLine { id: 7, quantity: 3 };
+    OrderLine::create(&db, &line).await.unwrap();
+
+    let found: Option<OrderLine> = OrderLine::find_by_id(&db, 7i64).await.unwrap();
+    assert_eq!(found, Some(line));
+
+    OrderLine::delete(&db, 7i64).await.unwrap();
+    let found: Option<OrderLine> = OrderLine::find_by_id(&db, 7i64).await.unwrap();
+    assert_eq!(found, None);
+}
+
+#[tokio::test]
+async fn test_derive_timestamped() {
+    let db = setup_test_db().await;
+    db.execute(
+        "CREATE TABLE derived_comments (id INTEGER PRIMARY KEY, body TEXT NOT NULL, created_at TEXT NOT NULL, updated_at TEXT NOT NULL)",
+        vec![],
+    )
+    .await
+    .unwrap();
+    assert_eq!(<Product as Entity>::timestamps(), None);
+
+    let dao = Comment::dao(db);
+    let before = Utc::now();
+    dao.create(&Comment {
+        id: 1,
+        body: "first".to_string(),
+        created_at: DateTime::<Utc>::MIN_UTC,
+        updated_at: DateTime::<Utc>::MIN_UTC,
+    })
+    .await
+    .unwrap();
+    let created = dao.find_by_id(Value::Bigint(1)).await.unwrap().unwrap();
+    assert!(created.created_at >= before);
+    assert_eq!(created.created_at, created.updated_at);
+
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    let mut edited = created.clone();
+    edited.body = "edited".to_string();
+    edited.created_at = DateTime::<Utc>::MIN_UTC;
+    dao.update(&edited).await.unwrap();
+    let updated = dao.find_by_id(Value::Bigint(1)).await.unwrap().unwrap();
+    assert_eq!(updated.body, "edited");
+    assert_eq!(updated.created_at, created.created_at);
+    assert!(updated.updated_at > created.updated_at);
+}
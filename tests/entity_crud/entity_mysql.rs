@@ -76,6 +76,7 @@ async fn setup_test_db() -> MySqlDatabase {
         password: "root".to_string(),
         database_name: "test".to_string(),
         max_size: 10,
+        ..Default::default()
     };
     let db = MySqlDatabase::connect(config).await.unwrap();
 
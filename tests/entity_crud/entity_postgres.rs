@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use serial_test::serial;
 
 // 商品实体
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 struct Product {
     id: i64,
     name: String,
@@ -77,6 +77,7 @@ async fn setup_test_db() -> PostgresDatabase {
         password: "root".to_string(),
         database_name: "test".to_string(),
         max_size: 10,
+        ..Default::default()
     };
     let db = PostgresDatabase::connect(config).await.unwrap();
 
@@ -199,6 +200,25 @@ async fn test_entity_add_product_to_cart() {
     assert_eq!(item.product_id, product.id);
 }
 
+#[tokio::test]
+#[serial]
+async fn test_truncate_and_drop_table() {
+    let db = setup_test_db().await;
+    for id in 1..=3 {
+        let mut product = create_test_product();
+        product.id = id;
+        Product::create(&db, &product).await.unwrap();
+    }
+    assert_eq!(Product::find_all::<Product>(&db).await.unwrap().len(), 3);
+
+    Product::truncate(&db).await.unwrap();
+    assert!(Product::find_all::<Product>(&db).await.unwrap().is_empty());
+
+    Product::drop_table(&db, false).await.unwrap();
+    assert!(Product::find_all::<Product>(&db).await.is_err());
+    Product::drop_table(&db, true).await.unwrap();
+}
+
 // 测试从购物车移除商品
 #[tokio::test]
 #[serial]
@@ -694,3 +714,28 @@ async fn test_complex_insert() {
     dbg!(&item);
     assert_eq!(item.product_id, product.id);
 }
+
+#[tokio::test]
+#[serial]
+async fn test_create_table_sql() {
+    let db = setup_test_db().await;
+    db.execute("DROP TABLE IF EXISTS cart_items", vec![])
+        .await
+        .unwrap();
+    db.execute("DROP TABLE IF EXISTS products", vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        Product::create_table_sql(&db),
+        "CREATE TABLE products (id BIGINT PRIMARY KEY, name TEXT, description TEXT, price FLOAT8, stock BIGINT, created_at BIGINT)"
+    );
+    Product::create_table(&db).await.unwrap();
+
+    let product = create_test_product();
+    Product::create(&db, &product).await.unwrap();
+    let saved: Option<Product> = Product::find_by_id(&db, Value::Bigint(product.id))
+        .await
+        .unwrap();
+    assert_eq!(saved.unwrap().name, product.name);
+}
@@ -1,4 +1,7 @@
-use bootrust::asyncdatabase::{sqlite::SqliteDatabase, DatabaseConfig, RelationalDatabase, Value};
+use bootrust::asyncdao::{Dao, DataAccessory};
+use bootrust::asyncdatabase::{
+    sqlite::SqliteDatabase, DatabaseConfig, DbError, RelationalDatabase, Value,
+};
 use bootrust::entity::Entity;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -6,7 +9,7 @@ use serial_test::serial;
 use std::sync::Arc;
 
 // 商品实体
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 struct Product {
     id: i64,
     name: String,
@@ -76,6 +79,7 @@ async fn setup_test_db() -> SqliteDatabase {
         password: "root".to_string(),
         database_name: ":memory:".to_string(),
         max_size: 10,
+        ..Default::default()
     };
     let db = SqliteDatabase::connect(config).await.unwrap();
 
@@ -199,6 +203,130 @@ async fn test_entity_add_product_to_cart() {
     assert_eq!(item.product_id, product.id);
 }
 
+// 静态方法和 Dao 读写同一张表
+#[tokio::test]
+#[serial]
+async fn test_entity_and_dao() {
+    let db = setup_test_db().await;
+    let dao = Product::dao(db.clone());
+
+    let product = create_test_product();
+    Product::create(&db, &product).await.unwrap();
+    let found = dao
+        .find_by_id(Value::Bigint(product.id))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.name, product.name);
+
+    let mut updated = found.clone();
+    updated.id = 2;
+    updated.stock = 5;
+    dao.create(&updated).await.unwrap();
+    let found: Product = Product::find_by_id(&db, Value::Bigint(2))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.stock, 5);
+
+    assert_eq!(dao.find_all().await.unwrap().len(), 2);
+    assert_eq!(Product::find_all::<Product>(&db).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_truncate_and_drop_table() {
+    let db = setup_test_db().await;
+    for id in 1..=3 {
+        let mut product = create_test_product();
+        product.id = id;
+        Product::create(&db, &product).await.unwrap();
+    }
+    assert_eq!(Product::find_all::<Product>(&db).await.unwrap().len(), 3);
+
+    Product::truncate(&db).await.unwrap();
+    assert!(Product::find_all::<Product>(&db).await.unwrap().is_empty());
+
+    Product::drop_table(&db, false).await.unwrap();
+    assert!(Product::find_all::<Product>(&db).await.is_err());
+    Product::drop_table(&db, true).await.unwrap();
+}
+
+// 同一个泛型 Dao 按实体访问不同的表
+#[tokio::test]
+#[serial]
+async fn test_data_accessory_tables() {
+    let db = setup_test_db().await;
+    let product_dao = DataAccessory::<Product, _>::new(db.clone());
+    let cart_dao = DataAccessory::<CartItem, _>::new(db.clone());
+
+    let product = create_test_product();
+    product_dao.create(&product).await.unwrap();
+    let mut cart_item = create_test_cart_item();
+    cart_item.product_id = product.id;
+    cart_dao.create(&cart_item).await.unwrap();
+
+    assert_eq!(product_dao.count().await.unwrap(), 1);
+    assert_eq!(cart_dao.count().await.unwrap(), 1);
+    let found = cart_dao
+        .find_by_id(Value::Bigint(cart_item.id))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.product_id, product.id);
+
+    cart_dao.delete(Value::Bigint(cart_item.id)).await.unwrap();
+    assert_eq!(cart_dao.count().await.unwrap(), 0);
+    assert_eq!(product_dao.count().await.unwrap(), 1);
+}
+
+// new_for 指定表名, 同一实体类型操作两张表
+#[tokio::test]
+#[serial]
+async fn test_data_accessory_new_for() {
+    let db = setup_test_db().await;
+    db.execute(
+        "CREATE TABLE archived_products (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT,
+            price DOUBLE NOT NULL,
+            stock INTEGER NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let live = DataAccessory::<Product, _>::new_for(db.clone(), "products", "id");
+    let archive = DataAccessory::<Product, _>::new_for(db.clone(), "archived_products", "id");
+    assert_eq!(live.table(), "products");
+    assert_eq!(archive.table(), "archived_products");
+
+    let product = create_test_product();
+    live.create(&product).await.unwrap();
+    let mut archived = product.clone();
+    archived.id = 2;
+    archived.stock = 0;
+    archive.create(&archived).await.unwrap();
+    assert_eq!(live.count().await.unwrap(), 1);
+    assert_eq!(archive.count().await.unwrap(), 1);
+
+    // 两个实例互不可见
+    assert_eq!(archive.find_by_id(Value::Bigint(1)).await.unwrap(), None);
+    assert_eq!(live.find_by_id(Value::Bigint(2)).await.unwrap(), None);
+
+    archived.name = "Retired".to_string();
+    assert_eq!(archive.update(&archived).await.unwrap(), 1);
+    let found: Vec<Product> = archive.prepare().find().query().await.unwrap();
+    assert_eq!(found, vec![archived]);
+    assert_eq!(live.find_all().await.unwrap(), vec![product]);
+
+    assert_eq!(archive.delete(Value::Bigint(2)).await.unwrap(), 1);
+    assert_eq!(archive.count().await.unwrap(), 0);
+    assert_eq!(live.count().await.unwrap(), 1);
+}
+
 // 测试从购物车移除商品
 #[tokio::test]
 #[serial]
@@ -514,6 +642,53 @@ async fn test_complex_delete() {
     dbg!(&result);
 }
 
+#[tokio::test]
+#[serial]
+async fn test_delete_and_update_by_conditions() {
+    let db = setup_test_db().await;
+    for (id, order_id, amount) in [(1, 1, 50.0), (2, 1, 150.0), (3, 2, 150.0)] {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.order_id = order_id;
+        payment.amount = amount;
+        Payment::create(&db, &payment).await.unwrap();
+    }
+
+    // 先是 SET 的值, 再是条件的值
+    let updated = Payment::update_by_conditions(
+        &db,
+        &["payment_method", "transaction_id"],
+        &["order_id =", "amount >"],
+        vec![
+            Value::from("PayPal"),
+            "tx-refund".into(),
+            Value::Bigint(1),
+            Value::Double(100.0),
+        ],
+    )
+    .await
+    .unwrap();
+    assert_eq!(updated, 1);
+    let payment: Payment = Payment::find_by_id(&db, Value::Bigint(2))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(payment.payment_method, "PayPal");
+    assert_eq!(payment.transaction_id, "tx-refund");
+
+    let deleted = Payment::delete_by_conditions(
+        &db,
+        &["order_id =", "amount <"],
+        vec![Value::Bigint(1), Value::Double(100.0)],
+    )
+    .await
+    .unwrap();
+    assert_eq!(deleted, 1);
+    let left: Vec<Payment> = Payment::find_all(&db).await.unwrap();
+    let ids: Vec<i64> = left.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![2, 3]);
+}
+
 #[tokio::test]
 #[serial]
 async fn test_complex_select() {
@@ -572,6 +747,141 @@ async fn test_complex_select() {
     dbg!(&result);
 }
 
+#[tokio::test]
+#[serial]
+async fn test_union() {
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct PaymentId {
+        id: i64,
+    }
+
+    let db = setup_test_db().await;
+    for (id, order_id, amount) in [(1, 1, 10.0), (2, 1, 300.0), (3, 2, 50.0), (4, 3, 20.0)] {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.order_id = order_id;
+        payment.amount = amount;
+        Payment::create(&db, &payment).await.unwrap();
+    }
+
+    let large = Payment::prepare::<PaymentId>(&db)
+        .select(&["id"])
+        .where_clauses(vec!["amount >"])
+        .values(vec![Value::Double(100.0)]);
+    let ids: Vec<PaymentId> = Payment::prepare(&db)
+        .select(&["id"])
+        .where_clauses(vec!["order_id ="])
+        .union(large)
+        .order_by(vec!["id desc"])
+        .values(vec![Value::Bigint(1)])
+        .query()
+        .await
+        .unwrap();
+    // 2 同时满足两边的条件, UNION 只保留一行
+    assert_eq!(ids.iter().map(|p| p.id).collect::<Vec<_>>(), vec![2, 1]);
+
+    let small = Payment::prepare::<PaymentId>(&db)
+        .select(&["id"])
+        .where_clauses(vec!["amount <"])
+        .values(vec![Value::Double(100.0)]);
+    let ids: Vec<PaymentId> = Payment::prepare(&db)
+        .select(&["id"])
+        .where_clauses(vec!["order_id ="])
+        .union_all(small)
+        .values(vec![Value::Bigint(1)])
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(ids.len(), 5);
+
+    // 列数不同, 使用 `*`, 或 other 带 ORDER BY / LIMIT 时都不执行
+    let wider = Payment::prepare::<PaymentId>(&db).select(&["id", "amount"]);
+    let result: Result<Vec<PaymentId>, _> = Payment::prepare(&db)
+        .select(&["id"])
+        .union(wider)
+        .query()
+        .await;
+    assert!(matches!(result, Err(DbError::QueryError(_))));
+
+    let all = Payment::prepare::<PaymentId>(&db).find();
+    let result: Result<Vec<PaymentId>, _> = Payment::prepare(&db)
+        .select(&["id"])
+        .union(all)
+        .query()
+        .await;
+    assert!(matches!(result, Err(DbError::QueryError(_))));
+
+    let ordered = Payment::prepare::<PaymentId>(&db)
+        .select(&["id"])
+        .order_by(vec!["id desc"])
+        .limit(1);
+    let result: Result<Vec<PaymentId>, _> = Payment::prepare(&db)
+        .select(&["id"])
+        .union_all(ordered)
+        .query()
+        .await;
+    assert!(matches!(result, Err(DbError::QueryError(_))));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_select_alias() {
+    // 结果按列名映射, 别名就是字段名
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct OrderTotal {
+        order_id: i64,
+        total: f64,
+        payments: i64,
+    }
+
+    let db = setup_test_db().await;
+    for (id, order_id, amount) in [(1, 1, 10.0), (2, 1, 15.5), (3, 2, 100.0)] {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.order_id = order_id;
+        payment.amount = amount;
+        Payment::create(&db, &payment).await.unwrap();
+    }
+
+    let totals: Vec<OrderTotal> = Payment::prepare(&db)
+        .select(&["order_id"])
+        .select_as(&[("SUM(amount)", "total"), ("COUNT(*)", "payments")])
+        .group_by(vec!["order_id"])
+        .order_by(vec!["order_id asc"])
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(
+        totals,
+        vec![
+            OrderTotal {
+                order_id: 1,
+                total: 25.5,
+                payments: 2,
+            },
+            OrderTotal {
+                order_id: 2,
+                total: 100.0,
+                payments: 1,
+            },
+        ]
+    );
+
+    // select 中的 `列 as 别名` 同样按别名映射
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Amount {
+        total: f64,
+    }
+    let amounts: Vec<Amount> = Payment::prepare(&db)
+        .select(&["amount as total"])
+        .where_clauses(vec!["id ="])
+        .values(vec![Value::Bigint(3)])
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(amounts, vec![Amount { total: 100.0 }]);
+}
+
 #[tokio::test]
 #[serial]
 async fn test_join() {
@@ -632,6 +942,82 @@ async fn test_join() {
     dbg!(&result);
 }
 
+#[tokio::test]
+#[serial]
+async fn test_join_qualify_columns() {
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct CartLine {
+        #[serde(rename = "cart_items.id")]
+        item_id: i64,
+        #[serde(rename = "products.id")]
+        product_id: i64,
+        #[serde(rename = "products.name")]
+        name: String,
+    }
+
+    let db = setup_test_db().await;
+    let mut product = create_test_product();
+    product.id = 7;
+    Product::create(&db, &product).await.unwrap();
+    let mut cart_item = create_test_cart_item();
+    cart_item.id = 3;
+    cart_item.product_id = product.id;
+    CartItem::create(&db, &cart_item).await.unwrap();
+
+    // 两张表的 id 列都保留
+    let lines: Vec<CartLine> = CartItem::prepare(&db)
+        .select(&["cart_items.id", "products.id", "products.name"])
+        .join("products", "products.id = cart_items.product_id")
+        .qualify_columns(true)
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(
+        lines,
+        vec![CartLine {
+            item_id: 3,
+            product_id: 7,
+            name: "Test Product".to_string(),
+        }]
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_join_duplicate_columns() {
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct CartLine {
+        item_id: i64,
+        product_id: i64,
+        name: String,
+    }
+
+    let db = setup_test_db().await;
+    let mut product = create_test_product();
+    product.id = 7;
+    Product::create(&db, &product).await.unwrap();
+    let mut cart_item = create_test_cart_item();
+    cart_item.id = 3;
+    cart_item.product_id = product.id;
+    CartItem::create(&db, &cart_item).await.unwrap();
+
+    // 两个 id 列按位置对应 item_id 和 product_id
+    let lines: Vec<CartLine> = CartItem::prepare(&db)
+        .select(&["cart_items.id", "products.id", "products.name"])
+        .join("products", "products.id = cart_items.product_id")
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(
+        lines,
+        vec![CartLine {
+            item_id: 3,
+            product_id: 7,
+            name: "Test Product".to_string(),
+        }]
+    );
+}
+
 #[tokio::test]
 #[serial]
 async fn test_complex_update() {
@@ -695,3 +1081,27 @@ async fn test_complex_insert() {
     dbg!(&item);
     assert_eq!(item.product_id, product.id);
 }
+
+#[tokio::test]
+#[serial]
+async fn test_create_table_sql() {
+    let db = SqliteDatabase::connect(DatabaseConfig {
+        database_name: ":memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        Product::create_table_sql(&db),
+        "CREATE TABLE products (id BIGINT PRIMARY KEY, name TEXT, description TEXT, price DOUBLE, stock BIGINT, created_at TEXT)"
+    );
+    Product::create_table(&db).await.unwrap();
+
+    let product = create_test_product();
+    Product::create(&db, &product).await.unwrap();
+    let saved: Option<Product> = Product::find_by_id(&db, Value::Bigint(product.id))
+        .await
+        .unwrap();
+    assert_eq!(saved.unwrap().name, product.name);
+}
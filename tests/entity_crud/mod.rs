@@ -1,3 +1,5 @@
+#[cfg(all(feature = "derive", feature = "sqlite_async"))]
+mod entity_derive;
 #[cfg(feature = "mysql_async")]
 mod entity_mysql;
 #[cfg(feature = "postgresql_async")]
@@ -39,6 +39,7 @@ async fn setup_ecommerce_test_db() -> PostgresDatabase {
         password: "root".to_string(),
         database_name: "test".to_string(),
         max_size: 10,
+        ..Default::default()
     };
     let db = PostgresDatabase::connect(config).await.unwrap();
 
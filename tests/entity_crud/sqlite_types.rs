@@ -37,6 +37,7 @@ async fn setup_ecommerce_test_db() -> SqliteDatabase {
         password: "root".to_string(),
         database_name: ":memory:".to_string(),
         max_size: 10,
+        ..Default::default()
     };
     let db = SqliteDatabase::connect(config).await.unwrap();
 
@@ -0,0 +1,188 @@
+use bootrust::asyncdao::{Dao, DataAccessory};
+use bootrust::asyncdatabase::{mock::MockDatabase, DbError, RelationalDatabase, Row, Value};
+use bootrust::entity::Entity;
+use bootrust::SortDir;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Product {
+    id: i64,
+    name: String,
+    price: f64,
+}
+
+struct ProductDao<T: Sized, D: RelationalDatabase> {
+    database: D,
+    _table: PhantomData<T>,
+}
+
+impl<D: RelationalDatabase> Dao<Product> for ProductDao<Product, D> {
+    type Database = D;
+
+    fn new(database: Self::Database) -> Self {
+        ProductDao {
+            database,
+            _table: PhantomData,
+        }
+    }
+
+    fn database(&self) -> &Self::Database {
+        &self.database
+    }
+
+    fn table_name() -> String {
+        "products".to_string()
+    }
+
+    fn primary_key_column() -> String {
+        "id".to_string()
+    }
+}
+
+impl Entity for Product {
+    fn table() -> String {
+        "products".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+
+    fn columns() -> Vec<(String, String)> {
+        vec![("price".to_string(), "unit_price".to_string())]
+    }
+}
+
+fn product(id: i64, name: &str, price: f64) -> Product {
+    Product {
+        id,
+        name: name.to_string(),
+        price,
+    }
+}
+
+#[tokio::test]
+async fn test_mock_async_crud() {
+    let db = MockDatabase::new();
+    let dao = ProductDao::new(db.clone());
+
+    for p in [
+        product(3, "pen", 2.5),
+        product(1, "book", 12.0),
+        product(2, "lamp", 30.0),
+    ] {
+        dao.create(&p).await.unwrap();
+    }
+
+    let found: Product = dao.find_by_id(Value::Bigint(1)).await.unwrap().unwrap();
+    assert_eq!(found, product(1, "book", 12.0));
+    assert!(dao.exists_by_id(Value::Bigint(2)).await.unwrap());
+    assert!(!dao.exists_by_id(Value::Bigint(9)).await.unwrap());
+
+    // find_all_limited 按主键排序分页
+    let page: Vec<Product> = dao.find_all_limited(2, 1).await.unwrap();
+    let ids: Vec<i64> = page.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![2, 3]);
+    let result: Result<Vec<Product>, _> = dao.find_all_limited(u64::MAX, 0).await;
+    assert!(matches!(result, Err(DbError::ConversionError(_))));
+
+    let sorted: Vec<Product> = dao
+        .find_all_ordered(&[("price", SortDir::Desc)])
+        .await
+        .unwrap();
+    let names: Vec<&str> = sorted.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["lamp", "book", "pen"]);
+
+    assert_eq!(
+        dao.update_fields(Value::Bigint(3), vec![("price", Value::Double(3.0))])
+            .await
+            .unwrap(),
+        1
+    );
+    let pen: Product = dao.find_by_id(Value::Bigint(3)).await.unwrap().unwrap();
+    assert_eq!(pen.price, 3.0);
+
+    dao.delete_expect_one(Value::Bigint(3)).await.unwrap();
+    assert!(matches!(
+        dao.update_expect_one(&pen).await,
+        Err(DbError::NotFound(_))
+    ));
+    assert_eq!(db.rows("products").len(), 2);
+}
+
+#[tokio::test]
+async fn test_mock_canned_query() {
+    let db = MockDatabase::new();
+    db.respond_query(
+        "FROM products WHERE price >",
+        vec![Row {
+            columns: vec!["id".to_string(), "name".to_string(), "price".to_string()],
+            values: vec![
+                Value::Bigint(9),
+                Value::Text("desk".to_string()),
+                Value::Double(99.0),
+            ],
+        }],
+    );
+    let dao = ProductDao::new(db.clone());
+
+    let expensive: Vec<Product> = dao
+        .find_by_condition(vec!["price >"], vec![Value::Double(50.0)])
+        .await
+        .unwrap();
+    assert_eq!(expensive, vec![product(9, "desk", 99.0)]);
+    assert_eq!(db.statements().len(), 1);
+}
+
+#[tokio::test]
+async fn test_data_accessory() {
+    let db = MockDatabase::new();
+    let dao = DataAccessory::<Product, _>::new(db.clone());
+
+    dao.create(&product(1, "book", 12.0)).await.unwrap();
+    dao.create(&product(2, "lamp", 30.0)).await.unwrap();
+
+    // 表名和列名取自 Entity
+    let rows = db.rows("products");
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].get::<f64>("unit_price").unwrap(), 12.0);
+
+    let found: Product = dao.find_by_id(Value::Bigint(2)).await.unwrap().unwrap();
+    assert_eq!(found, product(2, "lamp", 30.0));
+    assert_eq!(dao.find_all().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_entity_static_api() {
+    let db = MockDatabase::new();
+
+    Product::create(&db, &product(1, "book", 12.0))
+        .await
+        .unwrap();
+    Product::create(&db, &product(2, "lamp", 30.0))
+        .await
+        .unwrap();
+    assert_eq!(
+        db.rows("products")[1].get::<f64>("unit_price").unwrap(),
+        30.0
+    );
+
+    let found: Option<Product> = Product::find_by_id(&db, Value::Bigint(1)).await.unwrap();
+    assert_eq!(found, Some(product(1, "book", 12.0)));
+    assert_eq!(Product::find_all::<Product>(&db).await.unwrap().len(), 2);
+
+    Product::update(&db, &product(2, "desk lamp", 35.0))
+        .await
+        .unwrap();
+    let found: Vec<Product> = Product::find_by_conditions(&db, &["name ="], vec!["desk lamp"])
+        .await
+        .unwrap();
+    assert_eq!(found, vec![product(2, "desk lamp", 35.0)]);
+
+    assert_eq!(Product::delete(&db, Value::Bigint(1)).await.unwrap(), 1);
+    assert_eq!(
+        Product::find_all::<Product>(&db).await.unwrap(),
+        vec![product(2, "desk lamp", 35.0)]
+    );
+}
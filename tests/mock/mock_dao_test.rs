@@ -0,0 +1,130 @@
+use bootrust::dao::{Dao, DataAccessory};
+use bootrust::database::{mock::MockDatabase, DbError, RelationalDatabase, Value};
+use bootrust::entity::Entity;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct User {
+    id: i64,
+    username: String,
+    active: bool,
+}
+
+struct UserDao<T: Sized> {
+    database: MockDatabase,
+    _marker: PhantomData<T>,
+}
+
+impl Dao<User> for UserDao<User> {
+    type Database = MockDatabase;
+
+    fn new(database: Self::Database) -> Self {
+        UserDao {
+            database,
+            _marker: PhantomData,
+        }
+    }
+
+    fn database(&self) -> &Self::Database {
+        &self.database
+    }
+
+    fn table_name() -> String {
+        "users".to_string()
+    }
+
+    fn primary_key_column() -> String {
+        "id".to_string()
+    }
+}
+
+impl Entity for User {
+    fn table() -> String {
+        "users".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
+fn user(id: i64, username: &str) -> User {
+    User {
+        id,
+        username: username.to_string(),
+        active: true,
+    }
+}
+
+#[test]
+fn test_mock_crud() {
+    let db = MockDatabase::new();
+    let dao = UserDao::new(db.clone());
+
+    assert_eq!(dao.create(&user(1, "alice")).unwrap(), 1);
+    assert_eq!(dao.create(&user(2, "bob")).unwrap(), 1);
+
+    let found = dao.find_by_id(Value::Bigint(1)).unwrap().unwrap();
+    assert_eq!(found, user(1, "alice"));
+    assert!(dao.find_by_id(Value::Bigint(3)).unwrap().is_none());
+    assert_eq!(dao.find_all().unwrap().len(), 2);
+
+    let mut updated = found;
+    updated.active = false;
+    assert_eq!(dao.update(&updated).unwrap(), 1);
+    assert!(!dao.find_by_id(Value::Bigint(1)).unwrap().unwrap().active);
+
+    let inactive = dao
+        .find_by_condition(vec!["active ="], vec![Value::Boolean(false)])
+        .unwrap();
+    assert_eq!(inactive, vec![updated]);
+    let bob = dao
+        .find_one_by_condition(vec!["username ="], vec![Value::Text("bob".to_string())])
+        .unwrap();
+    assert_eq!(bob, Some(user(2, "bob")));
+    assert!(matches!(
+        dao.find_one_by_condition(vec!["username =", "active ="], vec!["bob".into()]),
+        Err(DbError::ParameterCountMismatch {
+            expected: 2,
+            actual: 1
+        })
+    ));
+
+    assert_eq!(dao.delete(Value::Bigint(2)).unwrap(), 1);
+    assert!(matches!(
+        dao.delete_expect_one(Value::Bigint(2)),
+        Err(DbError::NotFound(_))
+    ));
+    assert_eq!(db.rows("users").len(), 1);
+}
+
+#[test]
+fn test_mock_transaction() {
+    let db = MockDatabase::new();
+    let dao = UserDao::new(db.clone());
+    dao.create(&user(1, "alice")).unwrap();
+
+    dao.begin_transaction().unwrap();
+    dao.create(&user(2, "bob")).unwrap();
+    dao.delete(Value::Bigint(1)).unwrap();
+    dao.rollback().unwrap();
+
+    assert_eq!(dao.find_all().unwrap(), vec![user(1, "alice")]);
+    assert!(db.ping().is_ok());
+}
+
+#[test]
+fn test_data_accessory() {
+    let db = MockDatabase::new();
+    let dao = DataAccessory::<User, _>::new(db.clone());
+
+    dao.create(&user(1, "alice")).unwrap();
+    dao.create(&user(2, "bob")).unwrap();
+    assert_eq!(db.rows("users").len(), 2);
+
+    let found: User = dao.find_by_id(Value::Bigint(2)).unwrap().unwrap();
+    assert_eq!(found, user(2, "bob"));
+    assert_eq!(dao.delete(Value::Bigint(1)).unwrap(), 1);
+    assert_eq!(dao.find_all().unwrap(), vec![user(2, "bob")]);
+}
@@ -0,0 +1,2 @@
+mod mock_async_dao_test;
+mod mock_dao_test;
@@ -135,6 +135,7 @@ fn setup_test_db() -> MySqlDatabase {
         password: "root".to_string(),
         database_name: "test".to_string(),
         max_size: 30,
+        ..Default::default()
     };
     let db = MySqlDatabase::connect(config).unwrap();
 
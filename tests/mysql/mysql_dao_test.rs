@@ -12,7 +12,7 @@ struct User {
     email: String,
     // created_at: DateTime<Utc>,
     created_at: String,
-    active: i64,
+    active: bool,
 }
 
 // UserDao实现
@@ -51,6 +51,7 @@ fn setup_test_db() -> MySqlDatabase {
         password: "root".to_string(),
         database_name: "test".to_string(),
         max_size: 15,
+        ..Default::default()
     };
     let db = MySqlDatabase::connect(config).unwrap();
 
@@ -62,7 +63,7 @@ fn setup_test_db() -> MySqlDatabase {
             username TEXT NOT NULL,
             email TEXT NOT NULL,
             created_at TEXT NOT NULL,
-            active INTEGER NOT NULL
+            active BOOLEAN NOT NULL
         )",
         vec![],
     )
@@ -77,7 +78,7 @@ fn create_test_user() -> User {
         username: "test_user".to_string(),
         email: "test@example.com".to_string(),
         created_at: Utc::now().to_string(),
-        active: 1,
+        active: true,
     }
 }
 
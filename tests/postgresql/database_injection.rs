@@ -297,6 +297,7 @@ fn setup_ecommerce_test_db() -> PostgresDatabase {
         password: "root".to_string(),
         database_name: "test".to_string(),
         max_size: 10,
+        ..Default::default()
     };
     let db = PostgresDatabase::connect(config).unwrap();
 
@@ -355,6 +356,7 @@ fn setup_ecommerce_test_db() -> PostgresDatabase {
         password: "root".to_string(),
         database_name: "test".to_string(),
         max_size: 10,
+        ..Default::default()
     };
     let db = PostgresDatabase::connect(config).unwrap();
 
@@ -1 +1,3 @@
 mod postgres_async_daos;
+mod postgres_async_migration;
+mod postgres_async_tls;
@@ -2,6 +2,7 @@ use bootrust::asyncdao::Dao;
 use bootrust::asyncdatabase::{
     postgres::PostgresDatabase, DatabaseConfig, DbError, RelationalDatabase, Row, Value,
 };
+use bootrust::{ConflictAction, SqlExecutor};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serial_test::serial;
@@ -43,6 +44,23 @@ struct Payment {
     paid_at: DateTime<Utc>,
 }
 
+// 优惠券实体, 可空列对应 Option 字段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Coupon {
+    id: i64,
+    code: String,
+    discount: Option<f64>,
+    note: Option<String>,
+}
+
+// 商品标签, 数组字段对应 Postgres 数组列
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ProductTags {
+    id: i64,
+    tags: Vec<String>,
+    scores: Vec<i64>,
+}
+
 // ECommerceDo实现
 struct ECommerceDo<T: Sized, D: RelationalDatabase> {
     database: D,
@@ -292,6 +310,52 @@ impl<D: RelationalDatabase> Dao<Payment> for ECommerceDo<Payment, D> {
     }
 }
 
+impl<D: RelationalDatabase> Dao<Coupon> for ECommerceDo<Coupon, D> {
+    type Database = D;
+
+    fn new(database: Self::Database) -> Self {
+        ECommerceDo {
+            database,
+            _table: PhantomData,
+        }
+    }
+
+    fn database(&self) -> &Self::Database {
+        &self.database
+    }
+
+    fn table_name() -> String {
+        "coupons".to_string()
+    }
+
+    fn primary_key_column() -> String {
+        "id".to_string()
+    }
+}
+
+impl<D: RelationalDatabase> Dao<ProductTags> for ECommerceDo<ProductTags, D> {
+    type Database = D;
+
+    fn new(database: Self::Database) -> Self {
+        ECommerceDo {
+            database,
+            _table: PhantomData,
+        }
+    }
+
+    fn database(&self) -> &Self::Database {
+        &self.database
+    }
+
+    fn table_name() -> String {
+        "product_tags".to_string()
+    }
+
+    fn primary_key_column() -> String {
+        "id".to_string()
+    }
+}
+
 // 设置测试数据库
 async fn setup_ecommerce_test_db() -> PostgresDatabase {
     let config = DatabaseConfig {
@@ -301,6 +365,7 @@ async fn setup_ecommerce_test_db() -> PostgresDatabase {
         password: "root".to_string(),
         database_name: "test".to_string(),
         max_size: 10,
+        ..Default::default()
     };
     let db = PostgresDatabase::connect(config).await.unwrap();
 
@@ -633,3 +698,507 @@ async fn test_arc_db() {
     assert!(added_item.is_some());
     assert_eq!(added_item.unwrap().id, product.id);
 }
+
+#[tokio::test]
+#[serial]
+async fn test_nullable_fields() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute("DROP TABLE IF EXISTS coupons", vec![])
+        .await
+        .unwrap();
+    db.execute(
+        "CREATE TABLE coupons (
+            id INT8 PRIMARY KEY,
+            code TEXT NOT NULL,
+            discount FLOAT8,
+            note TEXT
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let coupon_dao = ECommerceDo::new(db.clone());
+
+    let empty = Coupon {
+        id: 1,
+        code: "EMPTY".to_string(),
+        discount: None,
+        note: None,
+    };
+    let filled = Coupon {
+        id: 2,
+        code: "HALF".to_string(),
+        discount: Some(0.5),
+        note: Some("half price".to_string()),
+    };
+    coupon_dao.create(&empty).await.unwrap();
+    coupon_dao.create(&filled).await.unwrap();
+
+    let found: Option<Coupon> = coupon_dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    assert_eq!(found, Some(empty));
+    let found: Option<Coupon> = coupon_dao.find_by_id(Value::Bigint(2)).await.unwrap();
+    assert_eq!(found, Some(filled.clone()));
+
+    // 更新为 NULL
+    let cleared = Coupon {
+        discount: None,
+        note: None,
+        ..filled
+    };
+    coupon_dao.update(&cleared).await.unwrap();
+    let found: Option<Coupon> = coupon_dao.find_by_id(Value::Bigint(2)).await.unwrap();
+    assert_eq!(found, Some(cleared));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_array_columns() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute("DROP TABLE IF EXISTS product_tags", vec![])
+        .await
+        .unwrap();
+    db.execute(
+        "CREATE TABLE product_tags (
+            id INT8 PRIMARY KEY,
+            tags TEXT[] NOT NULL,
+            scores INT8[] NOT NULL
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let tag_dao = ECommerceDo::new(db.clone());
+
+    let tagged = ProductTags {
+        id: 1,
+        tags: vec!["new".to_string(), "sale".to_string()],
+        scores: vec![5, 3, 4],
+    };
+    let untagged = ProductTags {
+        id: 2,
+        tags: vec![],
+        scores: vec![],
+    };
+    tag_dao.create(&tagged).await.unwrap();
+    tag_dao.create(&untagged).await.unwrap();
+
+    let found: Option<ProductTags> = tag_dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    assert_eq!(found, Some(tagged.clone()));
+    let found: Option<ProductTags> = tag_dao.find_by_id(Value::Bigint(2)).await.unwrap();
+    assert_eq!(found, Some(untagged));
+
+    // 数组列按元素类型读取
+    let row = db
+        .query_one(
+            "SELECT tags, scores FROM product_tags WHERE id = $1",
+            vec![Value::Bigint(1)],
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        row.values[0],
+        Value::Array(vec!["new".into(), "sale".into()])
+    );
+    assert_eq!(
+        row.values[1],
+        Value::Array(vec![Value::Bigint(5), Value::Bigint(3), Value::Bigint(4)])
+    );
+
+    // 数组参数可直接用于查询条件
+    let rows = db
+        .query(
+            "SELECT id FROM product_tags WHERE tags && $1",
+            vec![Value::Array(vec!["sale".into()])],
+        )
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<i64>("id").unwrap(), 1);
+
+    let updated = ProductTags {
+        tags: vec!["clearance".to_string()],
+        ..tagged
+    };
+    tag_dao.update(&updated).await.unwrap();
+    let found: Option<ProductTags> = tag_dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    assert_eq!(found, Some(updated));
+}
+
+// 测试插入冲突时的处理
+#[tokio::test]
+#[serial]
+async fn test_insert_on_conflict() {
+    let db = setup_ecommerce_test_db().await;
+    let insert = |stock: i64| {
+        SqlExecutor::<_, Product>::new(&db, "products".to_string())
+            .insert(&["id", "name", "price", "stock"])
+            .values(vec![
+                Value::Bigint(1),
+                "pen".into(),
+                Value::Double(2.5),
+                Value::Bigint(stock),
+            ])
+    };
+    insert(10).execute().await.unwrap();
+    assert!(insert(20).execute().await.is_err());
+
+    // 冲突时保留原记录
+    let affected = insert(20)
+        .on_conflict(&["id"], ConflictAction::DoNothing)
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(affected, 0);
+
+    // 没有冲突列时不执行
+    let result = insert(20)
+        .on_conflict(&[], ConflictAction::DoNothing)
+        .execute()
+        .await;
+    assert!(matches!(result, Err(DbError::QueryError(_))));
+
+    // 冲突时更新库存
+    insert(30)
+        .on_conflict(
+            &["id"],
+            ConflictAction::DoUpdate(vec![("stock", "excluded.stock")]),
+        )
+        .execute()
+        .await
+        .unwrap();
+    let rows = db
+        .query("SELECT stock FROM products", vec![])
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<i64>("stock").unwrap(), 30);
+}
+
+// 只读取主键的视图
+#[derive(Debug, Serialize, Deserialize)]
+struct ProductId {
+    id: i64,
+}
+
+// 商品上架状态, SMALLINT 列对应 i16 字段
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ProductStatus {
+    id: i64,
+    status: i16,
+}
+
+#[tokio::test]
+#[serial]
+async fn test_smallint_column() {
+    let db = setup_ecommerce_test_db().await;
+    db.batch_execute(
+        "DROP TABLE IF EXISTS product_status;
+         CREATE TABLE product_status (id INT8 PRIMARY KEY, status SMALLINT NOT NULL)",
+    )
+    .await
+    .unwrap();
+    for (id, status) in [(1, -1i16), (2, i16::MAX)] {
+        SqlExecutor::<_, ProductStatus>::new(&db, "product_status".to_string())
+            .insert(&["id", "status"])
+            .values(vec![Value::Bigint(id), status.into()])
+            .execute()
+            .await
+            .unwrap();
+    }
+
+    let row = db
+        .query_one(
+            "SELECT status FROM product_status WHERE id = $1",
+            vec![Value::Bigint(1)],
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(row.values[0], Value::Smallint(-1)));
+
+    let statuses: Vec<ProductStatus> = SqlExecutor::new(&db, "product_status".to_string())
+        .select(&["id", "status"])
+        .order_by(vec!["id"])
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(
+        statuses,
+        vec![
+            ProductStatus { id: 1, status: -1 },
+            ProductStatus {
+                id: 2,
+                status: i16::MAX
+            }
+        ]
+    );
+}
+
+// 支付耗时, INTERVAL 列可读为 Duration 或秒数
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PaymentElapsed {
+    id: i64,
+    elapsed: std::time::Duration,
+    elapsed_secs: i64,
+}
+
+#[tokio::test]
+#[serial]
+async fn test_interval_column() {
+    let db = setup_ecommerce_test_db().await;
+    db.batch_execute(
+        "DROP TABLE IF EXISTS payment_runs;
+         CREATE TABLE payment_runs (
+            id INT8 PRIMARY KEY,
+            started_at TIMESTAMPTZ NOT NULL,
+            finished_at TIMESTAMPTZ NOT NULL
+         )",
+    )
+    .await
+    .unwrap();
+    let started_at = Utc::now();
+    for (id, millis) in [(1, 1_500), (2, 90_000)] {
+        db.execute(
+            "INSERT INTO payment_runs (id, started_at, finished_at) VALUES ($1, $2, $3)",
+            vec![
+                Value::Bigint(id),
+                Value::DateTime(started_at),
+                Value::DateTime(started_at + chrono::Duration::milliseconds(millis)),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+
+    // INTERVAL 参数
+    let runs: Vec<PaymentElapsed> = SqlExecutor::new(&db, "payment_runs".to_string())
+        .select(&["id"])
+        .raw_select("finished_at - started_at AS elapsed")
+        .raw_select("finished_at - started_at AS elapsed_secs")
+        .where_clauses(vec!["finished_at - started_at >"])
+        .values(vec![Value::Interval(chrono::Duration::seconds(1))])
+        .order_by(vec!["id"])
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(
+        runs,
+        vec![
+            PaymentElapsed {
+                id: 1,
+                elapsed: std::time::Duration::from_millis(1_500),
+                elapsed_secs: 1,
+            },
+            PaymentElapsed {
+                id: 2,
+                elapsed: std::time::Duration::from_secs(90),
+                elapsed_secs: 90,
+            },
+        ]
+    );
+
+    // 月按 30 天换算
+    let row = db
+        .query_one(
+            "SELECT INTERVAL '1 month 2 days 03:00:00' AS span, INTERVAL '-90 seconds' AS back",
+            vec![],
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        row.get::<chrono::Duration>("span").unwrap(),
+        chrono::Duration::days(32) + chrono::Duration::hours(3)
+    );
+    assert_eq!(
+        row.values[1],
+        Value::Interval(chrono::Duration::seconds(-90))
+    );
+}
+
+// 同一条构造链, 占位符由各自的数据库生成
+async fn cheap_stocked_products<D: RelationalDatabase>(db: &D) -> Vec<i64> {
+    let product_dao = ECommerceDo::new(db.clone());
+    for (id, price, stock) in [(1, 10.0, 5), (2, 20.0, 50), (3, 90.0, 50)] {
+        let mut product = create_test_product();
+        product.id = id;
+        product.price = price;
+        product.stock = stock;
+        product_dao.create(&product).await.unwrap();
+    }
+
+    let products: Vec<ProductId> = SqlExecutor::new(db, "products".to_string())
+        .select(&["id"])
+        .where_clauses(vec!["price <", "stock >="])
+        .order_by(vec!["id"])
+        .values(vec![Value::Double(50.0), Value::Bigint(10)])
+        .query()
+        .await
+        .unwrap();
+    products.iter().map(|p| p.id).collect()
+}
+
+// 测试同一条构造链在不同占位符风格的数据库上都能执行
+#[tokio::test]
+#[serial]
+async fn test_builder_placeholder_styles() {
+    let db = setup_ecommerce_test_db().await;
+    assert_eq!(cheap_stocked_products(&db).await, vec![2]);
+
+    #[cfg(feature = "sqlite_async")]
+    {
+        use bootrust::asyncdatabase::sqlite::SqliteDatabase;
+
+        let sqlite = SqliteDatabase::connect(DatabaseConfig {
+            database_name: ":memory:".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        sqlite
+            .execute(
+                "CREATE TABLE products (
+                    id INTEGER PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    description TEXT,
+                    price FLOAT8 NOT NULL,
+                    stock INT8 NOT NULL,
+                    created_at TIMESTAMPTZ
+                )",
+                vec![],
+            )
+            .await
+            .unwrap();
+        assert_eq!(cheap_stocked_products(&sqlite).await, vec![2]);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_delete_all_and_truncate() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = ECommerceDo::new(db.clone());
+    for id in 1..=5 {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment_dao.create(&payment).await.unwrap();
+    }
+    assert_eq!(payment_dao.count().await.unwrap(), 5);
+
+    assert_eq!(payment_dao.delete_all().await.unwrap(), 5);
+    assert_eq!(payment_dao.count().await.unwrap(), 0);
+
+    for id in 1..=3 {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment_dao.create(&payment).await.unwrap();
+    }
+    payment_dao.truncate().await.unwrap();
+    assert_eq!(payment_dao.count().await.unwrap(), 0);
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OrderPayments {
+    order_id: i64,
+    total: f64,
+}
+
+// WHERE 和 HAVING 都带参数时, $n 按语句中的位置编号
+#[tokio::test]
+#[serial]
+async fn test_where_and_having_placeholders() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = ECommerceDo::new(db.clone());
+    for (id, order_id, amount) in [(1, 1, 50.0), (2, 1, 80.0), (3, 2, 30.0), (4, 3, 500.0)] {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.order_id = order_id;
+        payment.amount = amount;
+        payment_dao.create(&payment).await.unwrap();
+    }
+
+    let totals: Vec<OrderPayments> = SqlExecutor::new(&db, "payments".to_string())
+        .select(&["order_id"])
+        .raw_select("SUM(amount) AS total")
+        .group_by(vec!["order_id"])
+        .having(vec!["SUM(amount) >"])
+        .where_clauses(vec!["amount <"])
+        .order_by(vec!["order_id"])
+        .values(vec![Value::Double(100.0), Value::Double(50.0)])
+        .query()
+        .await
+        .unwrap();
+    let totals: Vec<(i64, f64)> = totals.iter().map(|t| (t.order_id, t.total)).collect();
+    assert_eq!(totals, vec![(1, 130.0)]);
+
+    let result = SqlExecutor::<_, OrderPayments>::new(&db, "payments".to_string())
+        .select(&["order_id"])
+        .raw_select("SUM(amount) AS total")
+        .where_clauses(vec!["amount <"])
+        .group_by(vec!["order_id"])
+        .having(vec!["SUM(amount) >"])
+        .values(vec![Value::Double(100.0)])
+        .query()
+        .await;
+    assert!(matches!(
+        result,
+        Err(DbError::ParameterCountMismatch {
+            expected: 2,
+            actual: 1
+        })
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_where_between_and_like() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = ECommerceDo::new(db.clone());
+    for (id, name, price) in [
+        (1, "Pencil", 5.0),
+        (2, "pen holder", 15.0),
+        (3, "Pen", 25.0),
+        (4, "Notebook", 15.0),
+    ] {
+        let mut product = create_test_product();
+        product.id = id;
+        product.name = name.to_string();
+        product.price = price;
+        product_dao.create(&product).await.unwrap();
+    }
+
+    let products: Vec<ProductId> = SqlExecutor::new(&db, "products".to_string())
+        .select(&["id"])
+        .where_clauses(vec!["stock >"])
+        .where_between("price", Value::Double(10.0), Value::Double(20.0))
+        .order_by(vec!["id"])
+        .values(vec![Value::Bigint(0)])
+        .query()
+        .await
+        .unwrap();
+    let ids: Vec<i64> = products.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![2, 4]);
+
+    // Postgres 的 LIKE 区分大小写
+    let products: Vec<ProductId> = SqlExecutor::new(&db, "products".to_string())
+        .select(&["id"])
+        .where_like("name", "Pen%".into())
+        .order_by(vec!["id"])
+        .query()
+        .await
+        .unwrap();
+    let ids: Vec<i64> = products.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 3]);
+
+    let products: Vec<ProductId> = SqlExecutor::new(&db, "products".to_string())
+        .select(&["id"])
+        .where_ilike("name", "PEN%".into())
+        .where_between("price", Value::Double(0.0), Value::Double(20.0))
+        .order_by(vec!["id"])
+        .query()
+        .await
+        .unwrap();
+    let ids: Vec<i64> = products.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 2]);
+}
@@ -0,0 +1,144 @@
+use bootrust::asyncdatabase::{
+    postgres::PostgresDatabase, DatabaseConfig, RelationalDatabase, Value,
+};
+use bootrust::migration::{Migration, Migrator};
+use serial_test::serial;
+
+async fn setup_test_db() -> PostgresDatabase {
+    let config = DatabaseConfig {
+        host: "localhost".to_string(),
+        port: 5432,
+        username: "root".to_string(),
+        password: "root".to_string(),
+        database_name: "test".to_string(),
+        max_size: 10,
+        ..Default::default()
+    };
+    let db = PostgresDatabase::connect(config).await.unwrap();
+    for table in ["mig_tags", "mig_users", "_migrations"] {
+        db.execute(&format!("DROP TABLE IF EXISTS {}", table), vec![])
+            .await
+            .unwrap();
+    }
+    db
+}
+
+// 守卫的事务在独立的连接上执行, 其他克隆看不到未提交的数据, 丢弃时回滚
+#[tokio::test]
+#[serial]
+async fn test_transaction_guard_isolated() {
+    let db = setup_test_db().await;
+    db.execute("CREATE TABLE mig_users (id BIGINT PRIMARY KEY)", vec![])
+        .await
+        .unwrap();
+
+    let tx = db.start_transaction().await.unwrap();
+    tx.execute("INSERT INTO mig_users (id) VALUES (1)", vec![])
+        .await
+        .unwrap();
+    assert_eq!(
+        tx.query("SELECT id FROM mig_users", vec![])
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(db
+        .query("SELECT id FROM mig_users", vec![])
+        .await
+        .unwrap()
+        .is_empty());
+    // 其他克隆的语句不在守卫的事务中, 回滚不影响它们
+    db.execute("INSERT INTO mig_users (id) VALUES (2)", vec![])
+        .await
+        .unwrap();
+    tx.rollback().await.unwrap();
+    assert_eq!(
+        db.query("SELECT id FROM mig_users", vec![])
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let tx = db.start_transaction().await.unwrap();
+    tx.execute("INSERT INTO mig_users (id) VALUES (3)", vec![])
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(
+        db.query("SELECT id FROM mig_users", vec![])
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+// 多个连接时, 事务中的语句也要在同一个连接上执行
+#[tokio::test]
+#[serial]
+async fn test_transaction_uses_one_connection() {
+    let db = setup_test_db().await;
+    db.execute("CREATE TABLE mig_users (id BIGINT PRIMARY KEY)", vec![])
+        .await
+        .unwrap();
+
+    db.begin_transaction().await.unwrap();
+    for id in 1..=3 {
+        db.execute(
+            "INSERT INTO mig_users (id) VALUES ($1)",
+            vec![Value::Bigint(id)],
+        )
+        .await
+        .unwrap();
+    }
+    db.batch_execute(
+        "INSERT INTO mig_users (id) VALUES (4); INSERT INTO mig_users (id) VALUES (5)",
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        db.query("SELECT id FROM mig_users", vec![])
+            .await
+            .unwrap()
+            .len(),
+        5
+    );
+    db.rollback().await.unwrap();
+
+    assert!(db
+        .query("SELECT id FROM mig_users", vec![])
+        .await
+        .unwrap()
+        .is_empty());
+    db.execute("DROP TABLE mig_users", vec![]).await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_failed_migration_leaves_no_partial_schema() {
+    let db = setup_test_db().await;
+    let migrator = Migrator::new(vec![
+        Migration::new(
+            1,
+            "CREATE TABLE mig_users (id BIGINT PRIMARY KEY, name TEXT)",
+            "DROP TABLE mig_users",
+        ),
+        Migration::new(
+            2,
+            "CREATE TABLE mig_tags (id BIGINT PRIMARY KEY);
+             ALTER TABLE mig_missing ADD COLUMN x TEXT",
+            "DROP TABLE mig_tags",
+        ),
+    ]);
+
+    assert!(migrator.migrate(&db).await.is_err());
+    assert_eq!(migrator.applied(&db).await.unwrap(), vec![1]);
+    // 失败版本中已执行的 CREATE TABLE 一起回滚
+    assert!(db.query("SELECT * FROM mig_tags", vec![]).await.is_err());
+    assert!(db.query("SELECT * FROM mig_users", vec![]).await.is_ok());
+
+    assert_eq!(migrator.rollback(&db, 0).await.unwrap(), vec![1]);
+    db.execute("DROP TABLE _migrations", vec![]).await.unwrap();
+}
@@ -0,0 +1,45 @@
+use bootrust::asyncdatabase::{postgres::PostgresDatabase, DatabaseConfig, RelationalDatabase};
+
+fn test_config(tls: bool) -> DatabaseConfig {
+    DatabaseConfig {
+        host: "localhost".to_string(),
+        port: 5432,
+        username: "root".to_string(),
+        password: "root".to_string(),
+        database_name: "test".to_string(),
+        max_size: 2,
+        tls,
+        ..Default::default()
+    }
+}
+
+// 没有启用 TLS 特性时要求 TLS 直接报错, 不会退回明文连接
+#[cfg(not(any(feature = "tls-rustls", feature = "tls-native")))]
+#[tokio::test]
+async fn test_tls_requires_feature() {
+    let result = PostgresDatabase::connect(test_config(true)).await;
+    assert!(matches!(
+        result,
+        Err(bootrust::asyncdatabase::DbError::ConnectionError(_))
+    ));
+}
+
+// 启用 tls-rustls 后 connect 仍可建立明文连接
+#[cfg(feature = "tls-rustls")]
+#[tokio::test]
+async fn test_rustls_plain_connect() {
+    let db = PostgresDatabase::connect(test_config(false)).await.unwrap();
+    db.ping().await.unwrap();
+}
+
+// 测试库没有开启 SSL, require 模式下不能建立连接
+#[cfg(feature = "tls-rustls")]
+#[tokio::test]
+async fn test_rustls_requires_server_tls() {
+    // 握手失败的连接不会退回明文, 取不到可用连接
+    let result = match PostgresDatabase::connect(test_config(true)).await {
+        Ok(db) => db.ping_timeout(std::time::Duration::from_secs(2)).await,
+        Err(e) => Err(e),
+    };
+    assert!(result.is_err());
+}
@@ -1 +1,4 @@
+#[cfg(feature = "derive")]
+mod sqlite_dao_derive;
 mod sqlite_dao_test;
+mod sqlite_data_accessory;
@@ -0,0 +1,37 @@
+use bootrust::dao::Dao;
+use bootrust::database::{sqlite::SqliteDatabase, DatabaseConfig, RelationalDatabase, Value};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Tag {
+    id: i64,
+    label: String,
+}
+
+#[derive(Dao)]
+#[dao(entity = Tag, table = "tags", sync)]
+struct TagDao {
+    database: SqliteDatabase,
+}
+
+#[test]
+fn test_derived_sync_dao() {
+    let db = SqliteDatabase::connect(DatabaseConfig {
+        database_name: ":memory:".to_string(),
+        ..Default::default()
+    })
+    .unwrap();
+    db.execute(
+        "CREATE TABLE tags (id INTEGER PRIMARY KEY, label TEXT NOT NULL)",
+        vec![],
+    )
+    .unwrap();
+
+    let dao = TagDao::new(db);
+    let tag = Tag {
+        id: 1,
+        label: "rust".to_string(),
+    };
+    dao.create(&tag).unwrap();
+    assert_eq!(dao.find_by_id(Value::Bigint(1)).unwrap(), Some(tag));
+}
@@ -1,4 +1,4 @@
-use bootrust::dao::Dao;
+use bootrust::dao::{Dao, VersionedDao};
 use bootrust::database::{
     sqlite::SqliteDatabase, DatabaseConfig, DbError, RelationalDatabase, Row, Value,
 };
@@ -36,43 +36,12 @@ impl Dao<User> for UserDao<User> {
         &self.database
     }
     fn row_to_entity(row: Row) -> Result<User, DbError> {
-        if row.values.len() != 5 {
-            return Err(DbError::ConversionError(
-                "Invalid number of columns".to_string(),
-            ));
-        }
-
         Ok(User {
-            id: match &row.values[0] {
-                Value::Bigint(i) => *i,
-                _ => return Err(DbError::ConversionError("Invalid id type".to_string())),
-            },
-            username: match &row.values[1] {
-                Value::Text(s) => s.clone(),
-                _ => {
-                    return Err(DbError::ConversionError(
-                        "Invalid username type".to_string(),
-                    ))
-                }
-            },
-            email: match &row.values[2] {
-                Value::Text(s) => s.clone(),
-                _ => return Err(DbError::ConversionError("Invalid email type".to_string())),
-            },
-            created_at: match &row.values[3] {
-                // Value::DateTime(dt) => *dt,
-                Value::Text(dt) => dt.clone(),
-                _ => {
-                    return Err(DbError::ConversionError(
-                        "Invalid created_at type".to_string(),
-                    ))
-                }
-            },
-            active: match &row.values[4] {
-                // Value::Boolean(b) => *b as i64,
-                Value::Bigint(i) => *i,
-                _ => return Err(DbError::ConversionError("Invalid active type".to_string())),
-            },
+            id: row.get("id")?,
+            username: row.get("username")?,
+            email: row.get("email")?,
+            created_at: row.get("created_at")?,
+            active: row.get("active")?,
         })
     }
 
@@ -204,6 +173,50 @@ fn test_update_user() {
     assert_eq!(updated.email, "updated@example.com");
 }
 
+#[test]
+fn test_update_fields() {
+    let db = setup_test_db();
+    let dao = UserDao::new(db);
+    let user = create_test_user();
+    dao.create(&user).unwrap();
+
+    // 只更新 email, 其他列保持不变
+    let result = dao.update_fields(
+        Value::Bigint(1),
+        vec![("email", Value::Text("partial@example.com".to_string()))],
+    );
+    assert_eq!(result.unwrap(), 1);
+
+    let updated = dao.find_by_id(Value::Bigint(1)).unwrap().unwrap();
+    assert_eq!(updated.email, "partial@example.com");
+    assert_eq!(updated.username, user.username);
+    assert_eq!(updated.created_at, user.created_at);
+    assert_eq!(updated.active, user.active);
+
+    // 不允许空的字段列表
+    let result = dao.update_fields(Value::Bigint(1), vec![]);
+    assert!(matches!(result, Err(DbError::QueryError(_))));
+}
+
+#[test]
+fn test_expect_one_row() {
+    let db = setup_test_db();
+    let dao = UserDao::new(db);
+    let mut user = create_test_user();
+
+    // 记录不存在时更新和删除都返回 NotFound
+    let result = dao.update_expect_one(&user);
+    assert!(matches!(result, Err(DbError::NotFound(_))));
+    let result = dao.delete_expect_one(Value::Bigint(1));
+    assert!(matches!(result, Err(DbError::NotFound(_))));
+
+    dao.create(&user).unwrap();
+    user.email = "one@example.com".to_string();
+    assert!(dao.update_expect_one(&user).is_ok());
+    assert!(dao.delete_expect_one(Value::Bigint(1)).is_ok());
+    assert!(dao.find_by_id(Value::Bigint(1)).unwrap().is_none());
+}
+
 #[test]
 fn test_delete_user() {
     let db = setup_test_db();
@@ -241,6 +254,42 @@ fn test_find_by_condition() {
 
     assert_eq!(users.len(), 1);
     assert_eq!(users[0].username, "test_user");
+
+    // 条件与参数个数不一致时不发送查询
+    let result = dao.find_by_condition(
+        vec!["username =", "email ="],
+        vec![Value::Text("test_user".to_string())],
+    );
+    assert!(matches!(
+        result,
+        Err(DbError::ParameterCountMismatch {
+            expected: 2,
+            actual: 1
+        })
+    ));
+}
+
+#[test]
+fn test_find_by_raw_condition() {
+    let db = setup_test_db();
+    let dao = UserDao::new(db);
+
+    let user = create_test_user();
+    dao.create(&user).unwrap();
+
+    let users = dao
+        .find_by_raw_condition(
+            "username = $1 OR email = $2",
+            vec!["nobody".into(), user.email.clone().into()],
+        )
+        .unwrap();
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].username, "test_user");
+
+    let users = dao
+        .find_by_raw_condition("username LIKE $1", vec!["other%".into()])
+        .unwrap();
+    assert!(users.is_empty());
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -711,3 +760,159 @@ fn test_find_by_order_condition() {
     assert_eq!(orders.len(), 1);
     assert_eq!(orders[0].product_name, "Test Product");
 }
+
+// 带版本号的账户, 用于乐观锁
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Account {
+    id: i64,
+    balance: f64,
+    version: i64,
+}
+
+impl Dao<Account> for UserDao<Account> {
+    type Database = SqliteDatabase;
+
+    fn new(database: Self::Database) -> Self {
+        UserDao {
+            _marker: PhantomData,
+            database,
+        }
+    }
+
+    fn database(&self) -> &Self::Database {
+        &self.database
+    }
+
+    fn table_name() -> String {
+        "accounts".to_string()
+    }
+
+    fn primary_key_column() -> String {
+        "id".to_string()
+    }
+}
+
+impl VersionedDao<Account> for UserDao<Account> {}
+
+#[test]
+fn test_update_versioned_conflict() {
+    let db = setup_test_db();
+    db.execute(
+        "CREATE TABLE accounts (id INTEGER PRIMARY KEY, balance DOUBLE NOT NULL, version INTEGER NOT NULL)",
+        vec![],
+    )
+    .unwrap();
+    let dao: UserDao<Account> = UserDao::new(db);
+    let account = Account {
+        id: 1,
+        balance: 10.0,
+        version: 0,
+    };
+    dao.create(&account).unwrap();
+
+    let mut first = account.clone();
+    let mut second = account;
+    first.balance = 20.0;
+    assert_eq!(dao.update_versioned(&first).unwrap(), 1);
+
+    second.balance = 30.0;
+    let result = dao.update_versioned(&second);
+    assert!(matches!(result, Err(DbError::Conflict(_))));
+
+    let stored = dao.find_by_id(Value::Bigint(1)).unwrap().unwrap();
+    assert_eq!(
+        stored,
+        Account {
+            id: 1,
+            balance: 20.0,
+            version: 1
+        }
+    );
+}
+
+// 用户收藏, (user_id, product_id) 复合主键
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Favorite {
+    user_id: i64,
+    product_id: i64,
+    note: String,
+}
+
+impl Dao<Favorite> for UserDao<Favorite> {
+    type Database = SqliteDatabase;
+
+    fn new(database: Self::Database) -> Self {
+        UserDao {
+            _marker: PhantomData,
+            database,
+        }
+    }
+
+    fn database(&self) -> &Self::Database {
+        &self.database
+    }
+
+    fn table_name() -> String {
+        "favorites".to_string()
+    }
+
+    fn primary_key_column() -> String {
+        "user_id".to_string()
+    }
+
+    fn primary_key_columns() -> Vec<String> {
+        vec!["user_id".to_string(), "product_id".to_string()]
+    }
+}
+
+#[test]
+fn test_composite_primary_key() {
+    let db = setup_test_db();
+    db.execute(
+        "CREATE TABLE favorites (
+            user_id INTEGER NOT NULL,
+            product_id INTEGER NOT NULL,
+            note TEXT NOT NULL,
+            PRIMARY KEY (user_id, product_id)
+        )",
+        vec![],
+    )
+    .unwrap();
+    let dao: UserDao<Favorite> = UserDao::new(db);
+    for (user_id, product_id) in [(1, 1), (1, 2), (2, 1)] {
+        dao.create(&Favorite {
+            user_id,
+            product_id,
+            note: format!("{}-{}", user_id, product_id),
+        })
+        .unwrap();
+    }
+
+    let found = dao
+        .find_by_key(vec![Value::Bigint(1), Value::Bigint(2)])
+        .unwrap();
+    assert_eq!(found.unwrap().note, "1-2");
+
+    let updated = Favorite {
+        user_id: 2,
+        product_id: 1,
+        note: "changed".to_string(),
+    };
+    assert_eq!(dao.update(&updated).unwrap(), 1);
+    let untouched = dao
+        .find_by_key(vec![Value::Bigint(1), Value::Bigint(1)])
+        .unwrap()
+        .unwrap();
+    assert_eq!(untouched.note, "1-1");
+
+    assert_eq!(
+        dao.delete_by_key(vec![Value::Bigint(2), Value::Bigint(1)])
+            .unwrap(),
+        1
+    );
+    assert!(dao
+        .find_by_key(vec![Value::Bigint(2), Value::Bigint(1)])
+        .unwrap()
+        .is_none());
+    assert!(dao.find_by_key(vec![Value::Bigint(1)]).is_err());
+}
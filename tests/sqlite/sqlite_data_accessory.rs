@@ -0,0 +1,134 @@
+use bootrust::dao::{Dao, DataAccessory};
+use bootrust::database::{sqlite::SqliteDatabase, DatabaseConfig, RelationalDatabase, Value};
+use bootrust::entity::Entity;
+use serde::{Deserialize, Serialize};
+
+// 不需要单独实现 Dao, 表名和主键取自 Entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Account {
+    id: i64,
+    username: String,
+    email: String,
+    active: bool,
+}
+
+impl Entity for Account {
+    fn table() -> String {
+        "accounts".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
+type AccountDao = DataAccessory<Account, SqliteDatabase>;
+
+fn setup_test_db() -> AccountDao {
+    let db = SqliteDatabase::connect(DatabaseConfig {
+        database_name: ":memory:".to_string(),
+        ..Default::default()
+    })
+    .unwrap();
+    db.execute(
+        "CREATE TABLE accounts (
+            id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL,
+            email TEXT NOT NULL,
+            active INTEGER NOT NULL
+        )",
+        vec![],
+    )
+    .unwrap();
+    AccountDao::new(db)
+}
+
+fn account(id: i64, username: &str) -> Account {
+    Account {
+        id,
+        username: username.to_string(),
+        email: format!("{}@example.com", username),
+        active: true,
+    }
+}
+
+#[test]
+fn test_create_and_find_by_id() {
+    let dao = setup_test_db();
+    assert_eq!(dao.create(&account(1, "alice")).unwrap(), 1);
+
+    let found = dao.find_by_id(Value::Bigint(1)).unwrap();
+    assert_eq!(found, Some(account(1, "alice")));
+    assert!(dao.find_by_id(Value::Bigint(2)).unwrap().is_none());
+}
+
+#[test]
+fn test_find_all() {
+    let dao = setup_test_db();
+    dao.create(&account(1, "alice")).unwrap();
+    dao.create(&account(2, "bob")).unwrap();
+
+    assert_eq!(
+        dao.find_all().unwrap(),
+        vec![account(1, "alice"), account(2, "bob")]
+    );
+}
+
+#[test]
+fn test_update() {
+    let dao = setup_test_db();
+    let mut alice = account(1, "alice");
+    dao.create(&alice).unwrap();
+
+    alice.email = "updated@example.com".to_string();
+    alice.active = false;
+    assert_eq!(dao.update(&alice).unwrap(), 1);
+    assert_eq!(dao.find_by_id(Value::Bigint(1)).unwrap(), Some(alice));
+}
+
+#[test]
+fn test_delete() {
+    let dao = setup_test_db();
+    dao.create(&account(1, "alice")).unwrap();
+
+    assert_eq!(dao.delete(Value::Bigint(1)).unwrap(), 1);
+    assert!(dao.find_by_id(Value::Bigint(1)).unwrap().is_none());
+}
+
+#[test]
+fn test_find_by_condition() {
+    let dao = setup_test_db();
+    dao.create(&account(1, "alice")).unwrap();
+    dao.create(&account(2, "bob")).unwrap();
+
+    let accounts = dao
+        .find_by_condition(vec!["username ="], vec!["bob".into()])
+        .unwrap();
+    assert_eq!(accounts, vec![account(2, "bob")]);
+}
+
+#[test]
+fn test_new_for() {
+    let dao = setup_test_db();
+    dao.database()
+        .execute(
+            "CREATE TABLE archived_accounts (
+                id INTEGER PRIMARY KEY,
+                username TEXT NOT NULL,
+                email TEXT NOT NULL,
+                active INTEGER NOT NULL
+            )",
+            vec![],
+        )
+        .unwrap();
+    // 同一实体类型, 表名取自实例
+    let archive = AccountDao::new_for(dao.database().clone(), "archived_accounts", "id");
+    dao.create(&account(1, "alice")).unwrap();
+    archive.create(&account(2, "bob")).unwrap();
+
+    assert_eq!(dao.find_all().unwrap(), vec![account(1, "alice")]);
+    assert_eq!(archive.find_all().unwrap(), vec![account(2, "bob")]);
+    assert!(archive.find_by_id(Value::Bigint(1)).unwrap().is_none());
+    assert_eq!(archive.delete(Value::Bigint(2)).unwrap(), 1);
+    assert_eq!(dao.find_all().unwrap(), vec![account(1, "alice")]);
+}
@@ -1 +1,4 @@
+#[cfg(feature = "derive")]
+mod sqlite_async_dao_derive;
 mod sqlite_async_daos;
+mod sqlite_async_replica;
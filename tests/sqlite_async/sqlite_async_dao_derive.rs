@@ -0,0 +1,182 @@
+use bootrust::asyncdao::{Dao, Timestamped};
+use bootrust::asyncdatabase::{sqlite::SqliteDatabase, DatabaseConfig, RelationalDatabase, Value};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Customer {
+    customer_id: i64,
+    name: String,
+    level: i64,
+}
+
+// 表名和主键由 derive 生成
+#[derive(Dao)]
+#[dao(entity = Customer, table = "customers", primary_key = "customer_id")]
+struct CustomerDao<D: RelationalDatabase> {
+    database: D,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LoyaltyCard {
+    id: i64,
+    points: i64,
+}
+
+// 表名缺省为实体名的 snake_case, 主键缺省为 id
+#[derive(Dao)]
+#[dao(entity = LoyaltyCard)]
+struct CardDao<T, D: RelationalDatabase> {
+    database: D,
+    _table: PhantomData<T>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct HTTPLog {
+    id: i64,
+    path: String,
+}
+
+// 连续大写按缩写处理, 表名为 http_log
+#[derive(Dao)]
+#[dao(entity = HTTPLog)]
+struct HttpLogDao<D: RelationalDatabase> {
+    database: D,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Article {
+    id: i64,
+    title: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl Timestamped for Article {}
+
+// create/update 自动写入时间列
+#[derive(Dao)]
+#[dao(entity = Article, timestamped)]
+struct ArticleDao<D: RelationalDatabase> {
+    database: D,
+}
+
+async fn setup_test_db() -> SqliteDatabase {
+    let db = SqliteDatabase::connect(DatabaseConfig {
+        database_name: ":memory:".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.execute(
+        "CREATE TABLE customers (customer_id INTEGER PRIMARY KEY, name TEXT NOT NULL, level INTEGER NOT NULL)",
+        vec![],
+    )
+    .await
+    .unwrap();
+    db.execute(
+        "CREATE TABLE loyalty_card (id INTEGER PRIMARY KEY, points INTEGER NOT NULL)",
+        vec![],
+    )
+    .await
+    .unwrap();
+    db.execute(
+        "CREATE TABLE article (id INTEGER PRIMARY KEY, title TEXT NOT NULL, created_at TEXT NOT NULL, updated_at TEXT NOT NULL)",
+        vec![],
+    )
+    .await
+    .unwrap();
+    db.execute(
+        "CREATE TABLE http_log (id INTEGER PRIMARY KEY, path TEXT NOT NULL)",
+        vec![],
+    )
+    .await
+    .unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_derived_dao_crud() {
+    let db = setup_test_db().await;
+    assert_eq!(CustomerDao::<SqliteDatabase>::table_name(), "customers");
+    assert_eq!(
+        CustomerDao::<SqliteDatabase>::primary_key_column(),
+        "customer_id"
+    );
+
+    let dao = CustomerDao::new(db.clone());
+    let mut customer = Customer {
+        customer_id: 1,
+        name: "Alice".to_string(),
+        level: 1,
+    };
+    dao.create(&customer).await.unwrap();
+
+    customer.level = 2;
+    assert_eq!(dao.update(&customer).await.unwrap(), 1);
+    let found = dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    assert_eq!(found, Some(customer));
+
+    assert_eq!(dao.delete(Value::Bigint(1)).await.unwrap(), 1);
+    assert!(dao.find_all().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_derived_dao_defaults() {
+    let db = setup_test_db().await;
+    let dao: CardDao<LoyaltyCard, _> = CardDao::new(db);
+    assert_eq!(
+        CardDao::<LoyaltyCard, SqliteDatabase>::table_name(),
+        "loyalty_card"
+    );
+
+    let card = LoyaltyCard { id: 7, points: 120 };
+    dao.create(&card).await.unwrap();
+    assert_eq!(dao.find_all().await.unwrap(), vec![card]);
+}
+
+#[tokio::test]
+async fn test_derived_dao_acronym_table_name() {
+    let db = setup_test_db().await;
+    assert_eq!(HttpLogDao::<SqliteDatabase>::table_name(), "http_log");
+
+    let dao = HttpLogDao::new(db);
+    let log = HTTPLog {
+        id: 1,
+        path: "/index".to_string(),
+    };
+    dao.create(&log).await.unwrap();
+    assert_eq!(dao.find_all().await.unwrap(), vec![log]);
+}
+
+#[tokio::test]
+async fn test_derived_dao_timestamped() {
+    let db = setup_test_db().await;
+    assert_eq!(
+        ArticleDao::<SqliteDatabase>::timestamps(),
+        Some(("created_at".to_string(), "updated_at".to_string()))
+    );
+
+    let dao = ArticleDao::new(db);
+    let before = Utc::now();
+    dao.create(&Article {
+        id: 1,
+        title: "draft".to_string(),
+        created_at: DateTime::<Utc>::MIN_UTC,
+        updated_at: DateTime::<Utc>::MIN_UTC,
+    })
+    .await
+    .unwrap();
+    let created = dao.find_by_id(Value::Bigint(1)).await.unwrap().unwrap();
+    assert!(created.created_at >= before);
+    assert_eq!(created.created_at, created.updated_at);
+
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    let mut edited = created.clone();
+    edited.title = "published".to_string();
+    dao.update(&edited).await.unwrap();
+    let updated = dao.find_by_id(Value::Bigint(1)).await.unwrap().unwrap();
+    assert_eq!(updated.created_at, created.created_at);
+    assert!(updated.updated_at > created.updated_at);
+}
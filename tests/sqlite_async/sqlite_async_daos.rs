@@ -1,8 +1,15 @@
-use bootrust::asyncdao::Dao;
-use bootrust::asyncdatabase::{sqlite::SqliteDatabase, DatabaseConfig, RelationalDatabase, Value};
+use bootrust::asyncdao::{Dao, DataAccessory, Timestamped, VersionedDao};
+use bootrust::asyncdatabase::{
+    sqlite::SqliteDatabase, DatabaseConfig, DbError, QueryObserver, RelationalDatabase, Value,
+};
+use bootrust::entity::Entity;
+use bootrust::{
+    like_contains, like_starts_with, ConflictAction, RenamePolicy, SortDir, SqlExecutor,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use std::marker::PhantomData;
 
@@ -18,6 +25,16 @@ struct Product {
     created_at: DateTime<Utc>,
 }
 
+impl Entity for Product {
+    fn table() -> String {
+        "products".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
 // 购物车实体
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct CartItem {
@@ -29,6 +46,16 @@ struct CartItem {
     added_at: DateTime<Utc>,
 }
 
+impl Entity for CartItem {
+    fn table() -> String {
+        "cart_items".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
 // 支付信息实体
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct Payment {
@@ -41,13 +68,176 @@ struct Payment {
     paid_at: DateTime<Utc>,
 }
 
-// ECommerceDo实现
+impl Entity for Payment {
+    fn table() -> String {
+        "payments".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
+// 用户收藏实体, (user_id, product_id) 复合主键
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Favorite {
+    user_id: i64,
+    product_id: i64,
+    note: String,
+}
+
+// 评价实体, 字段名与列名不同, 且带有不入库的计算字段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Review {
+    id: i64,
+    #[serde(rename = "product_ref")]
+    product_id: i64,
+    rating: i64,
+    #[serde(skip)]
+    stars: String,
+}
+
+impl Entity for Review {
+    fn table() -> String {
+        "reviews".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
+// 物流实体, camelCase 字段按 snake_case 规则映射到列
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+struct Shipment {
+    id: i64,
+    orderId: i64,
+    trackingNumber: String,
+    #[serde(rename = "carrier_name")]
+    carrier: String,
+}
+
+impl Entity for Shipment {
+    fn table() -> String {
+        "shipments".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+
+    fn rename_policy() -> RenamePolicy {
+        RenamePolicy::SnakeCase
+    }
+}
+
+// 优惠券实体, 可空列对应 Option 字段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Coupon {
+    id: i64,
+    code: String,
+    discount: Option<f64>,
+    note: Option<String>,
+}
+
+impl Entity for Coupon {
+    fn table() -> String {
+        "coupons".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
+// 金额值对象, 通过 flatten 嵌入实体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Money {
+    amount: f64,
+    currency: String,
+}
+
+// 退款实体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Refund {
+    id: i64,
+    order_id: i64,
+    #[serde(flatten)]
+    total: Money,
+}
+
+impl Entity for Refund {
+    fn table() -> String {
+        "refunds".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
+// 转账状态, 以小写文本存储
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TransferStatus {
+    Pending,
+    Completed,
+    Failed,
+}
+
+// 转账实体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Transfer {
+    id: i64,
+    amount: f64,
+    status: TransferStatus,
+}
+
+impl Entity for Transfer {
+    fn table() -> String {
+        "transfers".to_string()
+    }
+
+    fn primary_key() -> String {
+        "id".to_string()
+    }
+}
+
+// 账户实体, version 列用于乐观锁
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Account {
+    id: i64,
+    balance: f64,
+    version: i64,
+}
+
+// 公告实体, 创建和更新时间由 Dao 自动写入
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Notice {
+    id: i64,
+    content: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl Timestamped for Notice {}
+
+// 备忘实体, 表中的 deleted_at 列标记软删除
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Memo {
+    id: i64,
+    content: String,
+}
+
+// 需要重写 Dao 钩子 (复合主键, 时间戳, 乐观锁, 软删除) 的实体使用 ECommerceDo,
+// 其他实体通过 Entity 实现使用 DataAccessory
 struct ECommerceDo<T: Sized, D: RelationalDatabase> {
     database: D,
     _table: PhantomData<T>,
 }
 
-impl<D: RelationalDatabase> Dao<Product> for ECommerceDo<Product, D> {
+impl<D: RelationalDatabase> Dao<Favorite> for ECommerceDo<Favorite, D> {
     type Database = D;
 
     fn new(database: Self::Database) -> Self {
@@ -62,7 +252,34 @@ impl<D: RelationalDatabase> Dao<Product> for ECommerceDo<Product, D> {
     }
 
     fn table_name() -> String {
-        "products".to_string()
+        "favorites".to_string()
+    }
+
+    fn primary_key_column() -> String {
+        "user_id".to_string()
+    }
+
+    fn primary_key_columns() -> Vec<String> {
+        vec!["user_id".to_string(), "product_id".to_string()]
+    }
+}
+
+impl<D: RelationalDatabase> Dao<Account> for ECommerceDo<Account, D> {
+    type Database = D;
+
+    fn new(database: Self::Database) -> Self {
+        ECommerceDo {
+            database,
+            _table: PhantomData,
+        }
+    }
+
+    fn database(&self) -> &Self::Database {
+        &self.database
+    }
+
+    fn table_name() -> String {
+        "accounts".to_string()
     }
 
     fn primary_key_column() -> String {
@@ -70,7 +287,9 @@ impl<D: RelationalDatabase> Dao<Product> for ECommerceDo<Product, D> {
     }
 }
 
-impl<D: RelationalDatabase> Dao<CartItem> for ECommerceDo<CartItem, D> {
+impl<D: RelationalDatabase> VersionedDao<Account> for ECommerceDo<Account, D> {}
+
+impl<D: RelationalDatabase> Dao<Notice> for ECommerceDo<Notice, D> {
     type Database = D;
 
     fn new(database: Self::Database) -> Self {
@@ -85,15 +304,19 @@ impl<D: RelationalDatabase> Dao<CartItem> for ECommerceDo<CartItem, D> {
     }
 
     fn table_name() -> String {
-        "cart_items".to_string()
+        "notices".to_string()
     }
 
     fn primary_key_column() -> String {
         "id".to_string()
     }
+
+    fn timestamps() -> Option<(String, String)> {
+        Notice::timestamps()
+    }
 }
 
-impl<D: RelationalDatabase> Dao<Payment> for ECommerceDo<Payment, D> {
+impl<D: RelationalDatabase> Dao<Memo> for ECommerceDo<Memo, D> {
     type Database = D;
 
     fn new(database: Self::Database) -> Self {
@@ -108,12 +331,16 @@ impl<D: RelationalDatabase> Dao<Payment> for ECommerceDo<Payment, D> {
     }
 
     fn table_name() -> String {
-        "payments".to_string()
+        "memos".to_string()
     }
 
     fn primary_key_column() -> String {
         "id".to_string()
     }
+
+    fn soft_delete_column() -> Option<String> {
+        Some("deleted_at".to_string())
+    }
 }
 
 // 设置测试数据库
@@ -219,8 +446,8 @@ fn create_test_payment() -> Payment {
 #[tokio::test]
 async fn test_add_product_to_cart() {
     let db = setup_ecommerce_test_db().await;
-    let product_dao = ECommerceDo::new(db.clone());
-    let cart_dao = ECommerceDo::new(db.clone());
+    let product_dao = DataAccessory::new(db.clone());
+    let cart_dao = DataAccessory::new(db.clone());
 
     // 创建测试商品
     let product = create_test_product();
@@ -245,7 +472,7 @@ async fn test_add_product_to_cart() {
 #[tokio::test]
 async fn test_remove_product_from_cart() {
     let db = setup_ecommerce_test_db().await;
-    let cart_dao = ECommerceDo::new(db.clone());
+    let cart_dao = DataAccessory::new(db.clone());
 
     // 添加商品到购物车
     let cart_item = create_test_cart_item();
@@ -268,7 +495,7 @@ async fn test_remove_product_from_cart() {
 #[tokio::test]
 async fn test_update_cart_item_quantity() {
     let db = setup_ecommerce_test_db().await;
-    let cart_dao = ECommerceDo::new(db.clone());
+    let cart_dao = DataAccessory::new(db.clone());
 
     // 添加商品到购物车
     let mut cart_item = create_test_cart_item();
@@ -291,7 +518,7 @@ async fn test_update_cart_item_quantity() {
 #[tokio::test]
 async fn test_payment_process() {
     let db = setup_ecommerce_test_db().await;
-    let payment_dao = ECommerceDo::new(db.clone());
+    let payment_dao = DataAccessory::new(db.clone());
 
     // 创建测试订单
     let order_id = 1;
@@ -315,7 +542,7 @@ async fn test_payment_process() {
 #[tokio::test]
 async fn test_stock_update() {
     let db = setup_ecommerce_test_db().await;
-    let product_dao = ECommerceDo::new(db.clone());
+    let product_dao = DataAccessory::new(db.clone());
 
     // 创建测试商品
     let mut product = create_test_product();
@@ -334,13 +561,107 @@ async fn test_stock_update() {
     assert_eq!(updated_product.unwrap().stock, 50);
 }
 
+// 按主键更新或删除时必须命中一行
+#[tokio::test]
+async fn test_expect_one_row() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    let mut product = create_test_product();
+
+    let result = product_dao.update_expect_one(&product).await;
+    assert!(matches!(result, Err(DbError::NotFound(_))));
+    let result = product_dao
+        .delete_expect_one(Value::Bigint(product.id))
+        .await;
+    assert!(matches!(result, Err(DbError::NotFound(_))));
+
+    product_dao.create(&product).await.unwrap();
+    product.stock = 1;
+    product_dao.update_expect_one(&product).await.unwrap();
+    product_dao
+        .delete_expect_one(Value::Bigint(product.id))
+        .await
+        .unwrap();
+}
+
+// 测试部分更新
+#[tokio::test]
+async fn test_find_one_by_condition() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = DataAccessory::new(db.clone());
+    for (id, method) in [(1, "card"), (2, "cash"), (3, "cash")] {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.payment_method = method.to_string();
+        payment_dao.create(&payment).await.unwrap();
+    }
+
+    let none: Option<Payment> = payment_dao
+        .find_one_by_condition(vec!["payment_method ="], vec!["wire".into()])
+        .await
+        .unwrap();
+    assert!(none.is_none());
+
+    let one: Option<Payment> = payment_dao
+        .find_one_by_condition(vec!["payment_method ="], vec!["card".into()])
+        .await
+        .unwrap();
+    assert_eq!(one.unwrap().id, 1);
+
+    let many: Result<Option<Payment>, DbError> = payment_dao
+        .find_one_by_condition(vec!["payment_method ="], vec!["cash".into()])
+        .await;
+    assert!(matches!(many, Err(DbError::Conflict(_))));
+
+    let mismatch: Result<Option<Payment>, DbError> = payment_dao
+        .find_one_by_condition(vec!["payment_method =", "id ="], vec!["card".into()])
+        .await;
+    assert!(matches!(
+        mismatch,
+        Err(DbError::ParameterCountMismatch {
+            expected: 2,
+            actual: 1
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_update_fields() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    let product = create_test_product();
+    product_dao.create(&product).await.unwrap();
+
+    let result = product_dao
+        .update_fields(
+            Value::Bigint(product.id),
+            vec![("stock", Value::Bigint(7)), ("price", Value::Double(1.5))],
+        )
+        .await;
+    assert_eq!(result.unwrap(), 1);
+
+    let updated: Product = product_dao
+        .find_by_id(Value::Bigint(product.id))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.stock, 7);
+    assert_eq!(updated.price, 1.5);
+    assert_eq!(updated.name, product.name);
+    assert_eq!(updated.description, product.description);
+    assert!(product_dao
+        .update_fields(Value::Bigint(product.id), vec![])
+        .await
+        .is_err());
+}
+
 // 测试事务处理
 #[tokio::test]
 async fn test_transaction() {
     let db = setup_ecommerce_test_db().await;
-    let product_dao = ECommerceDo::new(db.clone());
-    let cart_dao = ECommerceDo::new(db.clone());
-    let payment_dao = ECommerceDo::new(db.clone());
+    let product_dao = DataAccessory::new(db.clone());
+    let cart_dao = DataAccessory::new(db.clone());
+    let payment_dao = DataAccessory::new(db.clone());
 
     // 开始事务
     let result = product_dao.begin_transaction().await;
@@ -391,10 +712,10 @@ async fn test_transaction() {
 async fn test_transaction_rollback() {
     let db = setup_ecommerce_test_db().await;
     let arc_db = Arc::new(db);
-    let product_dao = ECommerceDo::new(Arc::clone(&arc_db));
-    let cart_dao = ECommerceDo::new(Arc::clone(&arc_db));
-    // let product_dao = ECommerceDo::new(db.clone());
-    // let cart_dao = ECommerceDo::new(db.clone());
+    let product_dao = DataAccessory::new(Arc::clone(&arc_db));
+    let cart_dao = DataAccessory::new(Arc::clone(&arc_db));
+    // let product_dao = DataAccessory::new(db.clone());
+    // let cart_dao = DataAccessory::new(db.clone());
 
     // 开始事务
     let result = product_dao.begin_transaction().await;
@@ -433,7 +754,7 @@ async fn test_transaction_rollback() {
 async fn test_arc_db() {
     let db = setup_ecommerce_test_db().await;
     let arc_db = Arc::new(db);
-    let product_dao = ECommerceDo::<Product, _>::new(Arc::clone(&arc_db));
+    let product_dao = DataAccessory::<Product, _>::new(Arc::clone(&arc_db));
 
     let product = create_test_product();
     product_dao.create(&product).await.unwrap();
@@ -449,7 +770,7 @@ async fn test_arc_db() {
 #[tokio::test]
 async fn test_complex_query() {
     let db = setup_ecommerce_test_db().await;
-    let payment_dao = ECommerceDo::new(db.clone());
+    let payment_dao = DataAccessory::new(db.clone());
 
     // 创建测试订单
     let order_id = 1;
@@ -513,3 +834,1197 @@ async fn test_complex_query() {
     assert_eq!(result.len(), 1);
     assert_eq!(result[0].order_id, 2);
 }
+
+#[tokio::test]
+async fn test_find_all_limited() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+
+    for id in 1..=5 {
+        let mut product = create_test_product();
+        product.id = id;
+        product_dao.create(&product).await.unwrap();
+    }
+
+    let page: Vec<Product> = product_dao.find_all_limited(2, 1).await.unwrap();
+    let ids: Vec<i64> = page.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![2, 3]);
+
+    let page: Vec<Product> = product_dao.find_all_limited(10, 4).await.unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].id, 5);
+
+    let page: Vec<Product> = product_dao.find_all_limited(10, 5).await.unwrap();
+    assert!(page.is_empty());
+}
+
+#[tokio::test]
+async fn test_first_and_exists() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = DataAccessory::new(db.clone());
+
+    for (id, order_id) in [(1, 7), (2, 7), (3, 8)] {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.order_id = order_id;
+        payment_dao.create(&payment).await.unwrap();
+    }
+
+    let first = payment_dao
+        .prepare()
+        .find()
+        .where_clauses(vec!["order_id ="])
+        .order_by_col("id", SortDir::Desc)
+        .values(vec![Value::Bigint(7)])
+        .first()
+        .await
+        .unwrap();
+    assert_eq!(first.map(|p| p.id), Some(2));
+
+    let missing = payment_dao
+        .prepare()
+        .find()
+        .where_clauses(vec!["order_id ="])
+        .values(vec![Value::Bigint(9)])
+        .first()
+        .await
+        .unwrap();
+    assert!(missing.is_none());
+
+    let exists = payment_dao
+        .prepare()
+        .where_clauses(vec!["order_id ="])
+        .values(vec![Value::Bigint(8)])
+        .exists()
+        .await
+        .unwrap();
+    assert!(exists);
+
+    let exists = payment_dao
+        .prepare()
+        .where_clauses(vec!["order_id ="])
+        .values(vec![Value::Bigint(9)])
+        .exists()
+        .await
+        .unwrap();
+    assert!(!exists);
+}
+
+#[tokio::test]
+async fn test_composite_primary_key() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE favorites (
+            user_id INT8 NOT NULL,
+            product_id INT8 NOT NULL,
+            note TEXT NOT NULL,
+            PRIMARY KEY (user_id, product_id)
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let favorite_dao = ECommerceDo::new(db.clone());
+
+    for (user_id, product_id) in [(1, 1), (1, 2), (2, 1)] {
+        let favorite = Favorite {
+            user_id,
+            product_id,
+            note: format!("{}-{}", user_id, product_id),
+        };
+        favorite_dao.create(&favorite).await.unwrap();
+    }
+
+    let found: Option<Favorite> = favorite_dao
+        .find_by_key(vec![Value::Bigint(1), Value::Bigint(2)])
+        .await
+        .unwrap();
+    assert_eq!(found.unwrap().note, "1-2");
+
+    // 只更新匹配两个主键列的记录
+    let updated = Favorite {
+        user_id: 1,
+        product_id: 1,
+        note: "changed".to_string(),
+    };
+    assert_eq!(favorite_dao.update(&updated).await.unwrap(), 1);
+    let untouched: Favorite = favorite_dao
+        .find_by_key(vec![Value::Bigint(2), Value::Bigint(1)])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(untouched.note, "2-1");
+
+    let deleted = favorite_dao
+        .delete_by_key(vec![Value::Bigint(1), Value::Bigint(1)])
+        .await
+        .unwrap();
+    assert_eq!(deleted, 1);
+    assert_eq!(favorite_dao.find_all().await.unwrap().len(), 2);
+
+    // 主键值个数不匹配
+    assert!(favorite_dao
+        .find_by_key(vec![Value::Bigint(1)])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_serde_rename_and_skip() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE reviews (
+            id INTEGER PRIMARY KEY,
+            rating INT8 NOT NULL,
+            product_ref INT8 NOT NULL
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let review_dao = DataAccessory::new(db.clone());
+
+    let review = Review {
+        id: 1,
+        product_id: 42,
+        rating: 5,
+        stars: "*****".to_string(),
+    };
+    review_dao.create(&review).await.unwrap();
+
+    let saved: Review = review_dao
+        .find_by_id(Value::Bigint(1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.product_id, 42);
+    assert_eq!(saved.rating, 5);
+    assert_eq!(saved.stars, "");
+}
+
+#[tokio::test]
+async fn test_rename_policy() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE shipments (
+            id INTEGER PRIMARY KEY,
+            order_id INT8 NOT NULL,
+            tracking_number TEXT NOT NULL,
+            carrier_name TEXT NOT NULL
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let shipment_dao = DataAccessory::new(db.clone());
+
+    let mut shipment = Shipment {
+        id: 1,
+        orderId: 7,
+        trackingNumber: "SF1234567890".to_string(),
+        carrier: "SF Express".to_string(),
+    };
+    shipment_dao.create(&shipment).await.unwrap();
+
+    let saved: Option<Shipment> = shipment_dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    assert_eq!(saved, Some(shipment.clone()));
+
+    shipment.trackingNumber = "SF0987654321".to_string();
+    shipment_dao.update(&shipment).await.unwrap();
+
+    let found: Vec<Shipment> = shipment_dao
+        .prepare()
+        .find()
+        .where_clauses(vec!["tracking_number ="])
+        .values(vec!["SF0987654321".to_string()])
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(found, vec![shipment]);
+}
+
+#[tokio::test]
+async fn test_nullable_fields() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute("DROP TABLE IF EXISTS coupons", vec![])
+        .await
+        .unwrap();
+    db.execute(
+        "CREATE TABLE coupons (
+            id INT8 PRIMARY KEY,
+            code TEXT NOT NULL,
+            discount DOUBLE,
+            note TEXT
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let coupon_dao = DataAccessory::new(db.clone());
+
+    let empty = Coupon {
+        id: 1,
+        code: "EMPTY".to_string(),
+        discount: None,
+        note: None,
+    };
+    let filled = Coupon {
+        id: 2,
+        code: "HALF".to_string(),
+        discount: Some(0.5),
+        note: Some("half price".to_string()),
+    };
+    coupon_dao.create(&empty).await.unwrap();
+    coupon_dao.create(&filled).await.unwrap();
+
+    let found: Option<Coupon> = coupon_dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    assert_eq!(found, Some(empty));
+    let found: Option<Coupon> = coupon_dao.find_by_id(Value::Bigint(2)).await.unwrap();
+    assert_eq!(found, Some(filled.clone()));
+
+    // 更新为 NULL
+    let cleared = Coupon {
+        discount: None,
+        note: None,
+        ..filled
+    };
+    coupon_dao.update(&cleared).await.unwrap();
+    let found: Option<Coupon> = coupon_dao.find_by_id(Value::Bigint(2)).await.unwrap();
+    assert_eq!(found, Some(cleared));
+}
+
+#[tokio::test]
+async fn test_flattened_value_object() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE refunds (
+            id INTEGER PRIMARY KEY,
+            order_id INT8 NOT NULL,
+            amount DOUBLE NOT NULL,
+            currency TEXT NOT NULL
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let refund_dao = DataAccessory::new(db.clone());
+
+    let mut refund = Refund {
+        id: 1,
+        order_id: 9,
+        total: Money {
+            amount: 19.99,
+            currency: "CNY".to_string(),
+        },
+    };
+    refund_dao.create(&refund).await.unwrap();
+
+    let rows = db
+        .query("SELECT amount, currency FROM refunds WHERE id = 1", vec![])
+        .await
+        .unwrap();
+    assert_eq!(rows[0].values[0], Value::Double(19.99));
+    assert_eq!(rows[0].values[1], Value::Text("CNY".to_string()));
+
+    let saved: Option<Refund> = refund_dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    assert_eq!(saved, Some(refund.clone()));
+
+    refund.total.amount = 5.0;
+    refund_dao.update(&refund).await.unwrap();
+    let saved: Option<Refund> = refund_dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    assert_eq!(saved, Some(refund));
+}
+
+#[tokio::test]
+async fn test_enum_as_text() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE transfers (
+            id INTEGER PRIMARY KEY,
+            amount DOUBLE NOT NULL,
+            status VARCHAR(16) NOT NULL
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let transfer_dao = DataAccessory::new(db.clone());
+
+    let statuses = [
+        TransferStatus::Pending,
+        TransferStatus::Completed,
+        TransferStatus::Failed,
+    ];
+    for (id, status) in statuses.iter().enumerate() {
+        let transfer = Transfer {
+            id: id as i64 + 1,
+            amount: 10.0,
+            status: *status,
+        };
+        transfer_dao.create(&transfer).await.unwrap();
+    }
+
+    let rows = db
+        .query("SELECT status FROM transfers ORDER BY id", vec![])
+        .await
+        .unwrap();
+    let stored: Vec<Value> = rows.into_iter().map(|row| row.values[0].clone()).collect();
+    assert_eq!(
+        stored,
+        vec![
+            Value::Text("pending".to_string()),
+            Value::Text("completed".to_string()),
+            Value::Text("failed".to_string()),
+        ]
+    );
+
+    for (id, status) in statuses.iter().enumerate() {
+        let transfer: Transfer = transfer_dao
+            .find_by_id(Value::Bigint(id as i64 + 1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(transfer.status, *status);
+    }
+
+    let failed: Vec<Transfer> = transfer_dao
+        .find_by_condition(vec!["status ="], vec![Value::Text("failed".to_string())])
+        .await
+        .unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].id, 3);
+}
+
+#[tokio::test]
+async fn test_optimistic_locking() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE accounts (
+            id INTEGER PRIMARY KEY,
+            balance DOUBLE NOT NULL,
+            version INT8 NOT NULL
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let account_dao = ECommerceDo::new(db.clone());
+    account_dao
+        .create(&Account {
+            id: 1,
+            balance: 100.0,
+            version: 0,
+        })
+        .await
+        .unwrap();
+
+    // 两个读者拿到同一个版本
+    let mut first: Account = account_dao
+        .find_by_id(Value::Bigint(1))
+        .await
+        .unwrap()
+        .unwrap();
+    let mut second = first.clone();
+
+    first.balance = 80.0;
+    assert_eq!(account_dao.update_versioned(&first).await.unwrap(), 1);
+
+    // 第二个写入基于旧版本, 被拒绝
+    second.balance = 50.0;
+    let result = account_dao.update_versioned(&second).await;
+    assert!(matches!(result, Err(DbError::Conflict(_))));
+
+    let stored: Account = account_dao
+        .find_by_id(Value::Bigint(1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.balance, 80.0);
+    assert_eq!(stored.version, 1);
+
+    // 重新读取后可以继续更新
+    let mut reloaded = stored;
+    reloaded.balance = 50.0;
+    assert_eq!(account_dao.update_versioned(&reloaded).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_auto_timestamps() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE notices (
+            id INTEGER PRIMARY KEY,
+            content TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let notice_dao = ECommerceDo::new(db.clone());
+
+    // 实体中的时间会被覆盖
+    let before = Utc::now();
+    notice_dao
+        .create(&Notice {
+            id: 1,
+            content: "draft".to_string(),
+            created_at: DateTime::<Utc>::MIN_UTC,
+            updated_at: DateTime::<Utc>::MIN_UTC,
+        })
+        .await
+        .unwrap();
+    let created: Notice = notice_dao
+        .find_by_id(Value::Bigint(1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(created.created_at >= before);
+    assert_eq!(created.created_at, created.updated_at);
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let mut edited = created.clone();
+    edited.content = "published".to_string();
+    edited.created_at = DateTime::<Utc>::MIN_UTC;
+    notice_dao.update(&edited).await.unwrap();
+
+    let updated: Notice = notice_dao
+        .find_by_id(Value::Bigint(1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.content, "published");
+    assert_eq!(updated.created_at, created.created_at);
+    assert!(updated.updated_at > created.updated_at);
+}
+
+#[tokio::test]
+async fn test_find_all_ordered() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = DataAccessory::new(db.clone());
+
+    for (id, amount) in [(3, 100.0), (1, 50.0), (4, 75.0), (2, 100.0)] {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.amount = amount;
+        payment_dao.create(&payment).await.unwrap();
+    }
+
+    let result: Vec<Payment> = payment_dao
+        .find_all_ordered(&[("amount", SortDir::Desc), ("id", SortDir::Asc)])
+        .await
+        .unwrap();
+    let ids: Vec<i64> = result.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![2, 3, 4, 1]);
+
+    // 非法列名直接拒绝, 不会拼进 SQL
+    let result: Result<Vec<Payment>, DbError> = payment_dao
+        .find_all_ordered(&[("amount; DROP TABLE payments", SortDir::Asc)])
+        .await;
+    assert!(matches!(result, Err(DbError::InvalidIdentifier(_))));
+}
+
+#[tokio::test]
+async fn test_soft_delete() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE memos (
+            id INTEGER PRIMARY KEY,
+            content TEXT NOT NULL,
+            deleted_at TEXT
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let memo_dao = ECommerceDo::new(db.clone());
+    for id in 1..=4 {
+        memo_dao
+            .create(&Memo {
+                id,
+                content: format!("memo {}", id),
+            })
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(memo_dao.delete(Value::Bigint(2)).await.unwrap(), 1);
+    // 已删除的记录不会再次删除
+    assert_eq!(memo_dao.delete(Value::Bigint(2)).await.unwrap(), 0);
+    assert_eq!(
+        memo_dao
+            .delete_by_ids(vec![Value::Bigint(2), Value::Bigint(3)])
+            .await
+            .unwrap(),
+        1
+    );
+
+    let ids: Vec<i64> = memo_dao
+        .find_all()
+        .await
+        .unwrap()
+        .iter()
+        .map(|m: &Memo| m.id)
+        .collect();
+    assert_eq!(ids, vec![1, 4]);
+    assert!(memo_dao
+        .find_by_id(Value::Bigint(2))
+        .await
+        .unwrap()
+        .is_none());
+    assert!(!memo_dao.exists_by_id(Value::Bigint(3)).await.unwrap());
+    assert_eq!(memo_dao.find_all_limited(10, 0).await.unwrap().len(), 2);
+    let found: Vec<Memo> = memo_dao
+        .find_by_condition(vec!["content ="], vec!["memo 2".into()])
+        .await
+        .unwrap();
+    assert!(found.is_empty());
+    // 原样条件中的 OR 同样不会查出已删除的记录
+    let found: Vec<Memo> = memo_dao
+        .find_by_raw_condition(
+            "content = $1 OR content = $2",
+            vec!["memo 1".into(), "memo 2".into()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(found.iter().map(|m| m.id).collect::<Vec<_>>(), vec![1]);
+
+    // 软删除的记录仍在表中
+    assert_eq!(memo_dao.find_with_deleted().await.unwrap().len(), 4);
+    let deleted = db
+        .query("SELECT id FROM memos WHERE deleted_at IS NOT NULL", vec![])
+        .await
+        .unwrap();
+    assert_eq!(deleted.len(), 2);
+
+    assert_eq!(memo_dao.hard_delete(Value::Bigint(2)).await.unwrap(), 1);
+    assert_eq!(memo_dao.find_with_deleted().await.unwrap().len(), 3);
+
+    // 3 已被删除, 只标记 4
+    assert_eq!(
+        memo_dao
+            .delete_by_condition("id >= ?", vec![Value::Bigint(3)])
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(memo_dao.find_with_deleted().await.unwrap().len(), 3);
+    assert_eq!(memo_dao.count().await.unwrap(), 1);
+    assert_eq!(memo_dao.delete_all().await.unwrap(), 1);
+    assert_eq!(memo_dao.count().await.unwrap(), 0);
+    assert_eq!(memo_dao.find_with_deleted().await.unwrap().len(), 3);
+    // truncate 不受软删除影响
+    memo_dao.truncate().await.unwrap();
+    assert!(memo_dao.find_with_deleted().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_exists_by_id() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    let product = create_test_product();
+    product_dao.create(&product).await.unwrap();
+
+    assert!(product_dao
+        .exists_by_id(Value::Bigint(product.id))
+        .await
+        .unwrap());
+    assert!(!product_dao.exists_by_id(Value::Bigint(999)).await.unwrap());
+}
+
+// 列顺序与字段顺序不同时按列名映射
+#[tokio::test]
+async fn test_row_to_entity_by_name() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    let product = create_test_product();
+    product_dao.create(&product).await.unwrap();
+
+    let row = db
+        .query_one(
+            "SELECT created_at, stock, price, description, name, id FROM products",
+            vec![],
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        row.get_by_name("name"),
+        Some(&Value::Text(product.name.clone()))
+    );
+    let mapped = DataAccessory::<Product, SqliteDatabase>::row_to_entity(row).unwrap();
+    let stored = product_dao.find_by_id(Value::Bigint(product.id)).await;
+    assert_eq!(Some(mapped), stored.unwrap());
+}
+
+#[tokio::test]
+async fn test_delete_by_ids() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = DataAccessory::new(db.clone());
+
+    for id in 1..=1200 {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment_dao.create(&payment).await.unwrap();
+    }
+
+    // 600 个主键, 分两条语句删除
+    let ids: Vec<Value> = (1..=1200)
+        .filter(|id| id % 2 == 0)
+        .map(Value::Bigint)
+        .collect();
+    let deleted = payment_dao.delete_by_ids(ids).await.unwrap();
+    assert_eq!(deleted, 600);
+
+    let remaining: Vec<Payment> = payment_dao.find_all().await.unwrap();
+    assert_eq!(remaining.len(), 600);
+    assert!(remaining.iter().all(|p| p.id % 2 == 1));
+
+    assert_eq!(payment_dao.delete_by_ids(vec![]).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_delete_by_ids_rejects_composite_key() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE favorites (
+            user_id INT8 NOT NULL,
+            product_id INT8 NOT NULL,
+            note TEXT NOT NULL,
+            PRIMARY KEY (user_id, product_id)
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let favorite_dao = ECommerceDo::new(db.clone());
+    for (user_id, product_id) in [(1, 1), (1, 2), (2, 1)] {
+        let favorite = Favorite {
+            user_id,
+            product_id,
+            note: String::new(),
+        };
+        favorite_dao.create(&favorite).await.unwrap();
+    }
+
+    // 只给出第一列的值, 不能据此删除
+    let result = favorite_dao.delete_by_ids(vec![Value::Bigint(1)]).await;
+    assert!(matches!(result, Err(DbError::QueryError(_))));
+    let remaining: Vec<Favorite> = favorite_dao.find_all().await.unwrap();
+    assert_eq!(remaining.len(), 3);
+}
+
+#[tokio::test]
+async fn test_delete_and_update_by_condition() {
+    let db = setup_ecommerce_test_db().await;
+    let cart_dao = DataAccessory::new(db.clone());
+    for (id, user_id) in [(1, 1), (2, 1), (3, 2), (4, 1)] {
+        let mut item = create_test_cart_item();
+        item.id = id;
+        item.user_id = user_id;
+        cart_dao.create(&item).await.unwrap();
+    }
+
+    let updated = cart_dao
+        .update_by_condition(
+            vec![("quantity", Value::Bigint(5))],
+            "user_id = ? AND id > ?",
+            vec![Value::Bigint(1), Value::Bigint(1)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated, 2);
+    let item: CartItem = cart_dao
+        .find_by_id(Value::Bigint(4))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(item.quantity, 5);
+
+    // 一条语句删除该用户的全部购物车项
+    let deleted = cart_dao
+        .delete_by_condition("user_id = $1", vec![Value::Bigint(1)])
+        .await
+        .unwrap();
+    assert_eq!(deleted, 3);
+    let remaining: Vec<CartItem> = cart_dao.find_all().await.unwrap();
+    assert_eq!(remaining.iter().map(|i| i.id).collect::<Vec<_>>(), vec![3]);
+}
+
+#[tokio::test]
+async fn test_refresh() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TRIGGER payments_tx AFTER INSERT ON payments BEGIN
+            UPDATE payments SET transaction_id = 'tx-' || NEW.id WHERE id = NEW.id;
+         END",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let payment_dao = DataAccessory::new(db.clone());
+
+    let payment = create_test_payment();
+    payment_dao.create(&payment).await.unwrap();
+    // 内存中的实体仍是插入前的值
+    assert_eq!(payment.transaction_id, "tx12345");
+    let refreshed = payment_dao.refresh(&payment).await.unwrap().unwrap();
+    assert_eq!(refreshed.transaction_id, format!("tx-{}", payment.id));
+
+    payment_dao.delete(Value::Bigint(payment.id)).await.unwrap();
+    assert!(payment_dao.refresh(&payment).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_find_by_multiple_conditions() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = DataAccessory::new(db.clone());
+    for id in 1..=5 {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.order_id = id % 2;
+        payment.amount = 100.0 * id as f64;
+        payment_dao.create(&payment).await.unwrap();
+    }
+
+    let payments = payment_dao
+        .find_by_condition(
+            vec!["order_id =", "amount <", "id >="],
+            vec![Value::Bigint(1), Value::Double(500.0), Value::Bigint(2)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(payments.iter().map(|p| p.id).collect::<Vec<_>>(), vec![3]);
+
+    let result = payment_dao
+        .find_by_condition(vec!["order_id =", "amount <"], vec![Value::Bigint(1)])
+        .await;
+    assert!(matches!(
+        result,
+        Err(DbError::ParameterCountMismatch {
+            expected: 2,
+            actual: 1
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_delete_all_and_truncate() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = DataAccessory::new(db.clone());
+    for id in 1..=5 {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment_dao.create(&payment).await.unwrap();
+    }
+    assert_eq!(payment_dao.count().await.unwrap(), 5);
+
+    assert_eq!(payment_dao.delete_all().await.unwrap(), 5);
+    assert_eq!(payment_dao.count().await.unwrap(), 0);
+    assert_eq!(payment_dao.delete_all().await.unwrap(), 0);
+
+    for id in 1..=3 {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment_dao.create(&payment).await.unwrap();
+    }
+    payment_dao.truncate().await.unwrap();
+    assert_eq!(payment_dao.count().await.unwrap(), 0);
+
+    payment_dao.drop_table(false).await.unwrap();
+    assert!(payment_dao.count().await.is_err());
+    assert!(payment_dao.drop_table(false).await.is_err());
+    payment_dao.drop_table(true).await.unwrap();
+}
+
+// 只读取库存的视图
+#[derive(Debug, Serialize, Deserialize)]
+struct StockView {
+    id: i64,
+    x_stock: i64,
+}
+
+#[tokio::test]
+async fn test_builder_identifier_check() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    let mut product = create_test_product();
+    product_dao.create(&product).await.unwrap();
+    product.id = 2;
+    product.stock = 5;
+    product_dao.create(&product).await.unwrap();
+
+    let result: Result<Vec<Product>, DbError> = product_dao
+        .prepare()
+        .find()
+        .order_by(vec!["name; DROP TABLE products"])
+        .query()
+        .await;
+    assert!(matches!(result, Err(DbError::InvalidIdentifier(_))));
+    assert_eq!(product_dao.find_all().await.unwrap().len(), 2);
+
+    let stocks: Vec<StockView> = SqlExecutor::new(&db, "products".to_string())
+        .select(&["id", "stock as x_stock"])
+        .order_by(vec!["stock desc"])
+        .query()
+        .await
+        .unwrap();
+    let stocks: Vec<(i64, i64)> = stocks.iter().map(|s| (s.id, s.x_stock)).collect();
+    assert_eq!(stocks, vec![(1, 100), (2, 5)]);
+
+    // raw_unchecked 之后可以使用表达式
+    let products = product_dao
+        .prepare()
+        .find()
+        .raw_unchecked()
+        .order_by(vec!["LENGTH(name) DESC, id DESC"])
+        .query()
+        .await
+        .unwrap();
+    assert_eq!(products[0].id, 2);
+}
+
+#[tokio::test]
+async fn test_where_in_subquery() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    let cart_dao = DataAccessory::new(db.clone());
+
+    // (id, price, stock)
+    for (id, price, stock) in [(1, 10.0, 5), (2, 20.0, 50), (3, 30.0, 50), (4, 90.0, 50)] {
+        let mut product = create_test_product();
+        product.id = id;
+        product.price = price;
+        product.stock = stock;
+        product_dao.create(&product).await.unwrap();
+    }
+    // (id, user_id, product_id, quantity)
+    for (id, user_id, product_id, quantity) in [
+        (1, 1, 1, 3),
+        (2, 1, 2, 3),
+        (3, 1, 4, 3),
+        (4, 2, 3, 3),
+        (5, 1, 3, 1),
+    ] {
+        let mut item = create_test_cart_item();
+        item.id = id;
+        item.user_id = user_id;
+        item.product_id = product_id;
+        item.quantity = quantity;
+        cart_dao.create(&item).await.unwrap();
+    }
+
+    // 用户 1 购物车中数量大于 2 的商品
+    let sub = SqlExecutor::<_, CartItem>::new(&db, "cart_items".to_string())
+        .select(&["product_id"])
+        .where_clauses(vec!["quantity >", "user_id ="])
+        .values(vec![Value::Bigint(2), Value::Bigint(1)]);
+    let products: Vec<Product> = product_dao
+        .prepare()
+        .find()
+        .where_clauses(vec!["price <"])
+        .where_in_subquery("id", sub)
+        .group_by(vec!["id"])
+        .having(vec!["stock >="])
+        .order_by(vec!["id"])
+        .values(vec![Value::Double(50.0), Value::Bigint(10)])
+        .query()
+        .await
+        .unwrap();
+    let ids: Vec<i64> = products.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![2]);
+}
+
+#[tokio::test]
+async fn test_insert_on_conflict() {
+    let db = setup_ecommerce_test_db().await;
+    let insert = |stock: i64| {
+        SqlExecutor::<_, Product>::new(&db, "products".to_string())
+            .insert(&["id", "name", "price", "stock"])
+            .values(vec![
+                Value::Bigint(1),
+                "pen".into(),
+                Value::Double(2.5),
+                Value::Bigint(stock),
+            ])
+    };
+    insert(10).execute().await.unwrap();
+    assert!(insert(20).execute().await.is_err());
+
+    // 冲突时保留原记录
+    let affected = insert(20)
+        .on_conflict(&["id"], ConflictAction::DoNothing)
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(affected, 0);
+
+    // 没有冲突列时不执行
+    let result = insert(20)
+        .on_conflict(&[], ConflictAction::DoNothing)
+        .execute()
+        .await;
+    assert!(matches!(result, Err(DbError::QueryError(_))));
+
+    // 冲突时更新库存
+    insert(30)
+        .on_conflict(
+            &["id"],
+            ConflictAction::DoUpdate(vec![("stock", "excluded.stock")]),
+        )
+        .execute()
+        .await
+        .unwrap();
+    let rows = db
+        .query("SELECT stock FROM products", vec![])
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<i64>("stock").unwrap(), 30);
+}
+
+// 退款金额 (分) 可能为空
+#[derive(Debug, Serialize, Deserialize)]
+struct RefundView {
+    id: i64,
+    amount: i64,
+}
+
+#[tokio::test]
+async fn test_raw_fragments() {
+    let db = setup_ecommerce_test_db().await;
+    db.execute(
+        "CREATE TABLE refunds (id INTEGER PRIMARY KEY, amount INTEGER)",
+        vec![],
+    )
+    .await
+    .unwrap();
+    for (id, amount) in [(1, None), (2, Some(500)), (3, None), (4, Some(5000))] {
+        db.execute(
+            "INSERT INTO refunds (id, amount) VALUES ($1, $2)",
+            vec![Value::Bigint(id), amount.map_or(Value::Null, Value::Bigint)],
+        )
+        .await
+        .unwrap();
+    }
+
+    let refunds: Vec<RefundView> = SqlExecutor::new(&db, "refunds".to_string())
+        .select(&["id"])
+        .raw_select("COALESCE(amount, 0) AS amount")
+        .where_clauses(vec!["id >"])
+        .raw_where("COALESCE(amount, 0) < ?", vec![Value::Bigint(1000)])
+        .order_by(vec!["id"])
+        .values(vec![Value::Bigint(1)])
+        .query()
+        .await
+        .unwrap();
+    let refunds: Vec<(i64, i64)> = refunds.iter().map(|r| (r.id, r.amount)).collect();
+    assert_eq!(refunds, vec![(2, 500), (3, 0)]);
+}
+
+#[tokio::test]
+async fn test_where_between_and_like() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    for (id, name, price) in [
+        (1, "Pencil", 5.0),
+        (2, "pen holder", 15.0),
+        (3, "Pen", 25.0),
+        (4, "Notebook", 15.0),
+    ] {
+        let mut product = create_test_product();
+        product.id = id;
+        product.name = name.to_string();
+        product.price = price;
+        product_dao.create(&product).await.unwrap();
+    }
+
+    let products: Vec<Product> = product_dao
+        .prepare()
+        .find()
+        .where_clauses(vec!["stock >"])
+        .where_between("price", Value::Double(10.0), Value::Double(20.0))
+        .order_by(vec!["id"])
+        .values(vec![Value::Bigint(0)])
+        .query()
+        .await
+        .unwrap();
+    let ids: Vec<i64> = products.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![2, 4]);
+
+    // SQLite 的 LIKE 对 ASCII 本身不区分大小写
+    let products: Vec<Product> = product_dao
+        .prepare()
+        .find()
+        .where_like("name", "Pen%".into())
+        .where_between("price", Value::Double(0.0), Value::Double(20.0))
+        .order_by(vec!["id"])
+        .query()
+        .await
+        .unwrap();
+    let ids: Vec<i64> = products.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 2]);
+
+    let products: Vec<Product> = product_dao
+        .prepare()
+        .find()
+        .where_ilike("name", "PEN%".into())
+        .order_by(vec!["id"])
+        .query()
+        .await
+        .unwrap();
+    let ids: Vec<i64> = products.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn test_fold_and_for_each() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    product_dao.begin_transaction().await.unwrap();
+    for id in 1..=10_000 {
+        let mut product = create_test_product();
+        product.id = id;
+        product.stock = id % 7;
+        product_dao.create(&product).await.unwrap();
+    }
+    product_dao.commit().await.unwrap();
+
+    let total = product_dao
+        .fold(0i64, |sum, product: Product| sum + product.stock)
+        .await
+        .unwrap();
+    let row = db
+        .query_one("SELECT SUM(stock) FROM products", vec![])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(total, row.get_by_index::<i64>(0).unwrap());
+
+    // 按主键顺序, 每条恰好处理一次
+    let mut last_id = 0;
+    let mut count = 0;
+    product_dao
+        .for_each(|product: Product| {
+            assert!(product.id > last_id);
+            last_id = product.id;
+            count += 1;
+        })
+        .await
+        .unwrap();
+    assert_eq!(count, 10_000);
+}
+
+#[tokio::test]
+async fn test_find_by_like() {
+    let db = setup_ecommerce_test_db().await;
+    let product_dao = DataAccessory::new(db.clone());
+    for (id, name) in [
+        (1, "test pen"),
+        (2, "Test book"),
+        (3, "test_100%"),
+        (4, "testX100"),
+        (5, "notebook"),
+    ] {
+        let mut product = create_test_product();
+        product.id = id;
+        product.name = name.to_string();
+        product_dao.create(&product).await.unwrap();
+    }
+    let ids = |products: Vec<Product>| products.iter().map(|p| p.id).collect::<Vec<i64>>();
+
+    let products = product_dao.find_by_like("name", "test%").await.unwrap();
+    assert_eq!(ids(products), vec![1, 2, 3, 4]);
+
+    // 调用方的文本按字面匹配, `_` 不再匹配任意字符
+    let products = product_dao
+        .find_by_like("name", &like_starts_with("test_"))
+        .await
+        .unwrap();
+    assert_eq!(ids(products), vec![3]);
+    let products = product_dao
+        .find_by_like("name", &like_contains("100%"))
+        .await
+        .unwrap();
+    assert_eq!(ids(products), vec![3]);
+
+    let products = product_dao.find_by_ilike("name", "TEST B%").await.unwrap();
+    assert_eq!(ids(products), vec![2]);
+
+    assert!(matches!(
+        product_dao.find_by_like("name; --", "test%").await,
+        Err(DbError::InvalidIdentifier(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_order_by_col() {
+    let db = setup_ecommerce_test_db().await;
+    let payment_dao = DataAccessory::new(db.clone());
+
+    for (id, amount) in [(1, 50.0), (2, 100.0), (3, 100.0), (4, 75.0)] {
+        let mut payment = create_test_payment();
+        payment.id = id;
+        payment.amount = amount;
+        payment_dao.create(&payment).await.unwrap();
+    }
+
+    let result = payment_dao
+        .prepare()
+        .find()
+        .order_by_col("amount", SortDir::Desc)
+        .order_by_col("id", SortDir::Asc)
+        .query()
+        .await
+        .unwrap();
+    let ids: Vec<i64> = result.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![2, 3, 4, 1]);
+}
+
+// 记录最后一条语句的观察者
+#[derive(Default)]
+struct LastQuery(Mutex<Option<(String, usize)>>);
+
+impl QueryObserver for LastQuery {
+    fn on_query(&self, sql: &str, param_count: usize, _elapsed: Duration) {
+        *self.0.lock().unwrap() = Some((sql.to_string(), param_count));
+    }
+}
+
+// 测试查询观察者只拿到 SQL 和参数个数
+#[tokio::test]
+async fn test_query_observer() {
+    let observer = Arc::new(LastQuery::default());
+    let config = DatabaseConfig {
+        database_name: ":memory:".to_string(),
+        observer: Some(observer.clone()),
+        ..Default::default()
+    };
+    let db = SqliteDatabase::connect(config).await.unwrap();
+    db.execute(
+        "CREATE TABLE products (
+            id INTEGER PRIMARY KEY AUTOINCREMENT ,
+            name TEXT NOT NULL,
+            description TEXT,
+            price FLOAT8 NOT NULL,
+            stock INT8 NOT NULL,
+            created_at TIMESTAMPTZ
+        )",
+        vec![],
+    )
+    .await
+    .unwrap();
+    let product_dao: DataAccessory<Product, _> = DataAccessory::new(db);
+
+    product_dao.find_all().await.unwrap();
+    assert_eq!(
+        observer.0.lock().unwrap().clone(),
+        Some(("SELECT * FROM products".to_string(), 0))
+    );
+
+    product_dao.find_by_id(Value::Bigint(1)).await.unwrap();
+    let (sql, param_count) = observer.0.lock().unwrap().clone().unwrap();
+    assert!(sql.starts_with("SELECT * FROM products WHERE id"));
+    assert_eq!(param_count, 1);
+}
@@ -0,0 +1,151 @@
+use bootrust::asyncdatabase::{
+    replicated::ReplicatedDatabase, sqlite::SqliteDatabase, DatabaseConfig, ReadPolicy,
+    RelationalDatabase, Value,
+};
+use std::time::Duration;
+use tempfile::tempdir;
+
+async fn count_rows(db: &impl RelationalDatabase) -> i64 {
+    let row = db
+        .query_one("SELECT COUNT(*) AS total FROM users", vec![])
+        .await
+        .unwrap()
+        .unwrap();
+    match row.values[0] {
+        Value::Bigint(n) => n,
+        ref other => panic!("unexpected count value: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_replica_routing() {
+    let dir = tempdir().unwrap();
+    let primary_path = dir.path().join("primary.db");
+    let replica_path = dir.path().join("replica.db");
+
+    let config = DatabaseConfig {
+        database_name: primary_path.to_str().unwrap().to_string(),
+        replica_config: Some(Box::new(DatabaseConfig {
+            database_name: replica_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    let db: ReplicatedDatabase<SqliteDatabase> = ReplicatedDatabase::connect(config).await.unwrap();
+
+    // 两个库各自建表, 从库预先放一条数据
+    let create_table = "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)";
+    db.primary().execute(create_table, vec![]).await.unwrap();
+    let replica = db.replica().unwrap();
+    replica.execute(create_table, vec![]).await.unwrap();
+    replica
+        .execute(
+            "INSERT INTO users (id, name) VALUES ($1, $2)",
+            vec![Value::Bigint(1), Value::Text("replica".to_string())],
+        )
+        .await
+        .unwrap();
+
+    // 写入走主库
+    db.execute(
+        "INSERT INTO users (id, name) VALUES ($1, $2)",
+        vec![Value::Bigint(2), Value::Text("primary".to_string())],
+    )
+    .await
+    .unwrap();
+    db.execute(
+        "INSERT INTO users (id, name) VALUES ($1, $2)",
+        vec![Value::Bigint(3), Value::Text("primary".to_string())],
+    )
+    .await
+    .unwrap();
+    assert_eq!(count_rows(db.primary()).await, 2);
+
+    // 读取走从库
+    assert_eq!(count_rows(&db).await, 1);
+    let rows = db.query("SELECT name FROM users", vec![]).await.unwrap();
+    assert_eq!(rows[0].values[0], Value::Text("replica".to_string()));
+
+    // 事务中读取固定在主库
+    db.begin_transaction().await.unwrap();
+    assert_eq!(count_rows(&db).await, 2);
+    db.rollback().await.unwrap();
+    assert_eq!(count_rows(&db).await, 1);
+}
+
+#[tokio::test]
+async fn test_ping_with_closed_replica() {
+    let db = setup_replicas(ReadPolicy::RoundRobinReplicas).await;
+    db.replicas()[0].close().await.unwrap();
+    assert!(db.ping().await.is_ok());
+    assert!(db.ping_timeout(Duration::from_secs(5)).await.is_ok());
+
+    // 主库不可用时失败
+    db.primary().close().await.unwrap();
+    assert!(db.ping().await.is_err());
+}
+
+// 每个 :memory: 连接都是独立的库, 各从库写入自己的名字以便区分
+async fn setup_replicas(read_policy: ReadPolicy) -> ReplicatedDatabase<SqliteDatabase> {
+    let config = DatabaseConfig::builder()
+        .database_name(":memory:")
+        .replica_host("replica-a", 5432)
+        .replica_host("replica-b", 5432)
+        .read_policy(read_policy)
+        .build();
+    let db: ReplicatedDatabase<SqliteDatabase> = ReplicatedDatabase::connect(config).await.unwrap();
+    assert_eq!(db.replicas().len(), 2);
+
+    let create_table = "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)";
+    let insert = "INSERT INTO users (id, name) VALUES ($1, $2)";
+    db.execute(create_table, vec![]).await.unwrap();
+    db.execute(insert, vec![Value::Bigint(1), "primary".into()])
+        .await
+        .unwrap();
+    for (replica, name) in db.replicas().iter().zip(["replica-a", "replica-b"]) {
+        replica.execute(create_table, vec![]).await.unwrap();
+        replica
+            .execute(insert, vec![Value::Bigint(1), name.into()])
+            .await
+            .unwrap();
+    }
+    db
+}
+
+async fn read_name(db: &ReplicatedDatabase<SqliteDatabase>) -> String {
+    let row = db
+        .query_one("SELECT name FROM users", vec![])
+        .await
+        .unwrap()
+        .unwrap();
+    row.get("name").unwrap()
+}
+
+#[tokio::test]
+async fn test_round_robin_replicas() {
+    let db = setup_replicas(ReadPolicy::RoundRobinReplicas).await;
+
+    let mut names = Vec::new();
+    for _ in 0..4 {
+        names.push(read_name(&db).await);
+    }
+    assert_eq!(
+        names,
+        vec!["replica-a", "replica-b", "replica-a", "replica-b"]
+    );
+}
+
+#[tokio::test]
+async fn test_primary_only_policy() {
+    let db = setup_replicas(ReadPolicy::PrimaryOnly).await;
+
+    assert_eq!(read_name(&db).await, "primary");
+    assert_eq!(read_name(&db).await, "primary");
+
+    // query_replica 明确指定从库
+    let rows = db
+        .query_replica("SELECT name FROM users", vec![])
+        .await
+        .unwrap();
+    assert_eq!(rows[0].get::<String>("name").unwrap(), "replica-a");
+}